@@ -0,0 +1,75 @@
+/*
+This file contains the activation of the RTResources
+declaring spec.schedule. The watchers only report spec and pod
+changes, so nothing happens when an active window opens or closes:
+this task periodically compares the replicas each scheduled
+RTResource desires now with the desired replicas of its status,
+and enqueues an event for the RTResources that must be scaled.
+It runs as a Tokio task, since it is not time critical
+(the windows have a one-minute granularity).
+*/
+
+use std::{
+    ffi::CString,
+    time::Duration
+};
+use kube::{
+    Api,
+    Client,
+    api::ListParams
+};
+
+use crate::utils::vars::{
+    QueueMessage,
+    EventKind
+};
+use crate::utils::rtresource::RTResource;
+use crate::utils::configuration::ControllerConfig;
+use crate::utils::event_queue::enqueue_once;
+use crate::utils::priorities::effective_criticality;
+
+
+
+/*
+Interval between two checks of the activation windows
+*/
+const ACTIVATION_CHECK_INTERVAL: Duration = Duration::from_secs(15);
+
+/*
+This function checks the activation windows once.
+It returns the number of RTResources enqueued.
+*/
+async fn check_windows(client: Client, config: &ControllerConfig, queue: &CString) -> Result<usize, kube::Error> {
+    let rtresources = Api::<RTResource>::all(client).list(&ListParams::default()).await?.items;
+    let mut enqueued: usize = 0;
+    for r in rtresources.iter().filter(|r| r.spec.schedule.is_some() && r.metadata.deletion_timestamp.is_none()) {
+        let (Some(name), Some(uid), Some(namespace)) = (r.metadata.name.clone(), r.metadata.uid.clone(), r.metadata.namespace.clone()) else {
+            continue;
+        };
+        let desired = r.spec.desired_replicas();
+        if r.status.as_ref().and_then(|s| s.desired_replicas) == Some(desired) {
+            continue;
+        }
+        println!("Activation Windows - RTResource {}, {} in namespace {} now desires {} replicas!", name, uid, namespace, desired);
+        let criticality = effective_criticality(config, &namespace, r.spec.criticality);
+        if enqueue_once("Activation Windows", queue, &QueueMessage {name, uid, namespace, enqueued_at: 0, kind: EventKind::Resync, trace_id: String::new()}, criticality) {
+            enqueued += 1;
+        }
+    }
+    Ok(enqueued)
+}
+
+/*
+This function periodically enqueues the RTResources
+whose activation window opened or closed.
+*/
+pub async fn activation_windows(client: Client, config: ControllerConfig) {
+    let queue = CString::new(config.event_queue_path.clone()).unwrap();
+    let mut interval = tokio::time::interval(ACTIVATION_CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(e) = check_windows(client.clone(), &config, &queue).await {
+            eprintln!("Activation Windows - An error occurred while checking the activation windows: {}", e);
+        }
+    }
+}
@@ -0,0 +1,212 @@
+/*
+This file contains the security layer of the admin API.
+When a TLS directory is configured, the admin API is served
+with mutual TLS: the server certificate ("tls.crt", "tls.key")
+and the CA the client certificates must be signed by ("ca.crt")
+are read from the directory (e.g. a mounted Secret).
+Each call (but the health check) must then carry the service account
+token of the caller as a bearer token: the token is authenticated
+through a TokenReview and the call is authorized through a
+SubjectAccessReview of the path and verb, e.g. with the rule
+    - nonResourceURLs: ["/metrics", "/queue"]
+      verbs: ["get"]
+so that access to each endpoint follows the Kubernetes RBAC.
+Without a TLS directory, only GET /metrics and /healthz are served
+to other hosts, the other endpoints to loopback callers only.
+Every admin call is written to the audit log.
+*/
+
+use std::{
+    error::Error,
+    fs::File,
+    io::BufReader,
+    net::SocketAddr,
+    path::Path,
+    sync::Arc
+};
+use hyper::{
+    Body,
+    Request,
+    StatusCode,
+    header::AUTHORIZATION
+};
+use kube::{
+    Api,
+    Client,
+    api::PostParams
+};
+use k8s_openapi::api::{
+    authentication::v1::{
+        TokenReview,
+        TokenReviewSpec
+    },
+    authorization::v1::{
+        NonResourceAttributes,
+        SubjectAccessReview,
+        SubjectAccessReviewSpec
+    }
+};
+use tokio_rustls::{
+    TlsAcceptor,
+    rustls::{
+        Certificate,
+        PrivateKey,
+        RootCertStore,
+        ServerConfig,
+        server::AllowAnyAuthenticatedClient
+    }
+};
+
+
+
+/*
+Paths exempt from the authorization (kubelet probes)
+*/
+const UNAUTHENTICATED_PATHS: [&str; 1] = ["/healthz"];
+
+/*
+Identity of the caller of an admin call
+*/
+pub enum Caller {
+    /*
+    Loopback or health check call, not authenticated
+    */
+    Anonymous,
+    /*
+    Authenticated user (e.g. system:serviceaccount:monitoring:prometheus)
+    */
+    User(String),
+}
+
+impl std::fmt::Display for Caller {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Caller::Anonymous => write!(f, "anonymous"),
+            Caller::User(user) => write!(f, "{}", user),
+        }
+    }
+}
+
+/*
+This function reads the PEM certificates of a file.
+*/
+fn read_certificates(path: &Path) -> Result<Vec<Certificate>, Box<dyn Error>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let certificates: Vec<Certificate> = rustls_pemfile::certs(&mut reader)?
+        .into_iter()
+        .map(Certificate)
+        .collect();
+    if certificates.is_empty() {
+        return Err(format!("no certificate in {}", path.display()).into());
+    }
+    Ok(certificates)
+}
+
+/*
+This function reads the first PEM private key of a file.
+*/
+fn read_private_key(path: &Path) -> Result<PrivateKey, Box<dyn Error>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    while let Some(item) = rustls_pemfile::read_one(&mut reader)? {
+        match item {
+            rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::ECKey(key) => return Ok(PrivateKey(key)),
+            _ => {}
+        }
+    }
+    Err(format!("no private key in {}", path.display()).into())
+}
+
+/*
+This function builds the mutual TLS acceptor
+from the certificates of the TLS directory.
+*/
+pub fn tls_acceptor(tls_dir: &str) -> Result<TlsAcceptor, Box<dyn Error>> {
+    let dir = Path::new(tls_dir);
+    let mut roots = RootCertStore::empty();
+    for ca in read_certificates(&dir.join("ca.crt"))? {
+        roots.add(&ca)?;
+    }
+    let config = ServerConfig::builder()
+        .with_safe_defaults()
+        .with_client_cert_verifier(AllowAnyAuthenticatedClient::new(roots).boxed())
+        .with_single_cert(read_certificates(&dir.join("tls.crt"))?, read_private_key(&dir.join("tls.key"))?)?;
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+/*
+This function authenticates and authorizes an admin call
+against the Kubernetes RBAC. It returns the caller, or the
+status to answer with when the call is refused.
+*/
+pub async fn authorize(client: Client, request: &Request<Body>) -> Result<Caller, StatusCode> {
+    let path = request.uri().path();
+    if UNAUTHENTICATED_PATHS.contains(&path) {
+        return Ok(Caller::Anonymous);
+    }
+    let token = request.headers().get(AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    let review = TokenReview {
+        spec: TokenReviewSpec {
+            token: Some(token.to_string()),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let review = Api::<TokenReview>::all(client.clone())
+        .create(&PostParams::default(), &review)
+        .await
+        .map_err(|e| {
+            eprintln!("Admin Server - An error occurred while reviewing a token: {}", e);
+            StatusCode::SERVICE_UNAVAILABLE
+        })?;
+    let status = review.status.unwrap_or_default();
+    let user = status.user.filter(|_| status.authenticated == Some(true))
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    let username = user.username.clone().unwrap_or_default();
+
+    let access = SubjectAccessReview {
+        spec: SubjectAccessReviewSpec {
+            user: user.username,
+            groups: user.groups,
+            uid: user.uid,
+            extra: user.extra,
+            non_resource_attributes: Some(NonResourceAttributes {
+                path: Some(path.to_string()),
+                verb: Some(request.method().as_str().to_lowercase()),
+            }),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let access = Api::<SubjectAccessReview>::all(client)
+        .create(&PostParams::default(), &access)
+        .await
+        .map_err(|e| {
+            eprintln!("Admin Server - An error occurred while reviewing the access of {}: {}", username, e);
+            StatusCode::SERVICE_UNAVAILABLE
+        })?;
+    if access.status.is_some_and(|s| s.allowed) {
+        Ok(Caller::User(username))
+    } else {
+        Err(StatusCode::FORBIDDEN)
+    }
+}
+
+/*
+This function writes an admin call to the audit log.
+*/
+pub fn audit(peer: SocketAddr, caller: &Caller, method: &str, path: &str, status: StatusCode) {
+    println!(
+        "Admin Server - Audit: {} {} {} from {} -> {}",
+        caller,
+        method,
+        path,
+        peer,
+        status.as_u16()
+    );
+}
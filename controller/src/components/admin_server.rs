@@ -0,0 +1,428 @@
+/*
+This file contains the controller admin API.
+It is a small HTTP server, kept off the real-time threads,
+exposing:
+    - GET /metrics: the controller and RTResource SLO metrics in the Prometheus text format;
+    - GET /queue: the event queue statistics as JSON;
+    - GET /priorities: the real-time priorities of the controller threads;
+    - PUT /priorities/{watchers,podWatcher,server,watchdogs}: changes the priority
+      of a role at runtime (body: {"priority": <1-99>});
+    - GET /threads: a dump of the controller threads (priorities,
+      event handled by each watchdog and for how long);
+    - GET /healthz: whether watchdogs are running;
+    - GET /headroom?criticality=N: how many pods of the standard shape
+      (or of ?cpu=&memory=) could still be admitted at a criticality
+      without preemption;
+    - GET /simulate/node-loss?node=<name>: the RTResources that would drop
+      below their desired replicas if the node were lost, and whether
+      (and with which preemptions) the other nodes absorb the replacements;
+    - POST /oracle: the criticality, PriorityClass and RT node verdict
+      of a non-managed pod (body: the pod, with its namespace);
+    - GET /snapshot: the versioned snapshot of the controller policy
+      and reservations (configuration, priorities, reservation ledger
+      and placement map);
+    - PUT /snapshot: imports the snapshot of another controller
+      (body: the snapshot), e.g. on a standby before a handover;
+    - GET /schemas: the schemas of the custom resources
+      and of the admin API payloads;
+    - GET /events/stream: the live pipeline events (enqueued, dispatched,
+      placed, preempted) as Server-Sent Events, each with the criticality
+      band and a display color (from red for the most critical
+      level to green for the least critical one), e.g. for a dashboard.
+The access to the endpoints is secured by the admin_auth layer.
+Without TLS, only /metrics and /healthz are served to other hosts
+(e.g. Prometheus and the kubelet probes), the other endpoints
+to loopback callers only.
+*/
+
+use std::{
+    convert::Infallible,
+    net::SocketAddr,
+    time::Duration
+};
+use hyper::{
+    Body,
+    Method,
+    Request,
+    Response,
+    StatusCode,
+    header::{
+        CACHE_CONTROL,
+        CONTENT_TYPE
+    },
+    server::conn::Http,
+    service::service_fn
+};
+use kube::Client;
+use k8s_openapi::api::core::v1::Pod;
+use serde::Deserialize;
+use schemars::JsonSchema;
+use tokio::{
+    io::{
+        AsyncRead,
+        AsyncWrite
+    },
+    net::TcpListener,
+    sync::broadcast::error::RecvError
+};
+
+use crate::utils::vars::SharedStatePtr;
+use crate::utils::configuration::ControllerConfig;
+use crate::utils::event_bus::{
+    PipelineEvent,
+    subscribe_events
+};
+use crate::utils::priorities::{
+    ThreadRole,
+    priority_of,
+    set_priority,
+    realtime_scheduling
+};
+use crate::utils::metrics::{
+    METRICS,
+    enqueued_by_priority,
+    render_metrics
+};
+use crate::components::slo_metrics::render_slo_metrics;
+use crate::components::thread_dump::thread_dump;
+use crate::components::headroom::capacity_headroom;
+use crate::components::node_loss::simulate_node_loss;
+use crate::components::priority_oracle::oracle;
+use crate::components::handover::{
+    Snapshot,
+    export_snapshot,
+    import_snapshot
+};
+use crate::components::admin_auth::{
+    Caller,
+    audit,
+    authorize,
+    tls_acceptor
+};
+use crate::components::schemas::schemas;
+
+
+
+/*
+Body of a priority change request
+*/
+#[derive(Deserialize, JsonSchema)]
+pub struct PriorityUpdate {
+    pub priority: i32,
+}
+
+fn respond(status: StatusCode, content_type: &str, body: String) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, content_type)
+        .body(Body::from(body))
+        .unwrap()
+}
+
+/*
+This function builds the event queue statistics.
+*/
+fn queue_stats() -> serde_json::Value {
+    use std::sync::atomic::Ordering;
+
+    let enqueued: serde_json::Map<String, serde_json::Value> = enqueued_by_priority()
+        .into_iter()
+        .map(|(priority, count)| (priority.to_string(), serde_json::Value::from(count)))
+        .collect();
+    serde_json::json!({
+        "depth": METRICS.queue_depth.load(Ordering::Relaxed),
+        "depthMax": METRICS.queue_depth_max.load(Ordering::Relaxed),
+        "capacity": METRICS.queue_capacity.load(Ordering::Relaxed),
+        "readyDepth": METRICS.ready_depth.load(Ordering::Relaxed),
+        "enqueuedByPriority": enqueued,
+        "enqueueFailures": METRICS.enqueue_failures.load(Ordering::Relaxed)
+    })
+}
+
+/*
+This function returns the current thread priorities.
+*/
+fn priorities() -> serde_json::Value {
+    serde_json::json!({
+        "watchers": priority_of(ThreadRole::Watchers),
+        "podWatcher": priority_of(ThreadRole::PodWatcher),
+        "server": priority_of(ThreadRole::Server),
+        "watchdogBase": priority_of(ThreadRole::Watchdogs)
+    })
+}
+
+/*
+Interval of the keep-alive comments of the event stream
+*/
+const EVENT_STREAM_KEEPALIVE: Duration = Duration::from_secs(15);
+
+/*
+This function renders a pipeline event as a Server-Sent Event,
+with the band and the display color of its criticality.
+*/
+fn render_event(config: &ControllerConfig, event: &PipelineEvent) -> String {
+    let band = if event.criticality <= config.critical_band_max { "critical" } else { "general" };
+    let levels = config.criticality_max.saturating_sub(1).max(1);
+    let hue = 120 * (event.criticality.clamp(1, config.criticality_max) - 1) / levels;
+    let mut data = serde_json::json!(event);
+    data["band"] = serde_json::json!(band);
+    data["color"] = serde_json::json!(format!("hsl({}, 75%, 45%)", hue));
+    format!("event: {:?}\ndata: {}\n\n", event.type_, data)
+}
+
+/*
+This function streams the pipeline events
+until the client disconnects.
+*/
+fn stream_events(state: SharedStatePtr) -> Response<Body> {
+    let config = unsafe { (*state.0).config.clone() };
+    let mut events = subscribe_events();
+    let (mut sender, body) = Body::channel();
+    tokio::spawn(async move {
+        loop {
+            let chunk = tokio::select! {
+                received = events.recv() => match received {
+                    Ok(event) => render_event(&config, &event),
+                    Err(RecvError::Lagged(missed)) => format!(": {} events missed\n\n", missed),
+                    Err(RecvError::Closed) => break,
+                },
+                _ = tokio::time::sleep(EVENT_STREAM_KEEPALIVE) => ": keep-alive\n\n".to_string(),
+            };
+            if sender.send_data(chunk.into()).await.is_err() {
+                break;
+            }
+        }
+    });
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(CONTENT_TYPE, "text/event-stream")
+        .header(CACHE_CONTROL, "no-cache")
+        .body(body)
+        .unwrap()
+}
+
+/*
+This function handles a preemption oracle request.
+*/
+async fn evaluate_pod(state: SharedStatePtr, request: Request<Body>) -> Response<Body> {
+    let body = match hyper::body::to_bytes(request.into_body()).await {
+        Ok(body) => body,
+        Err(e) => return respond(StatusCode::BAD_REQUEST, "text/plain", format!("{}
+", e)),
+    };
+    let pod = match serde_json::from_slice::<Pod>(&body) {
+        Ok(pod) => pod,
+        Err(e) => return respond(StatusCode::BAD_REQUEST, "text/plain", format!("Expected a Pod: {}\n", e)),
+    };
+    let shared_state = unsafe { &*state.0 };
+    match oracle(shared_state.context.client.clone(), &shared_state.config, &pod).await {
+        Ok(verdict) => respond(StatusCode::OK, "application/json", serde_json::json!(verdict).to_string()),
+        Err(e) => respond(StatusCode::BAD_REQUEST, "text/plain", format!("{}\n", e)),
+    }
+}
+
+/*
+This function handles a snapshot import request.
+*/
+async fn import_state(state: SharedStatePtr, request: Request<Body>) -> Response<Body> {
+    let body = match hyper::body::to_bytes(request.into_body()).await {
+        Ok(body) => body,
+        Err(e) => return respond(StatusCode::BAD_REQUEST, "text/plain", format!("{}\n", e)),
+    };
+    let snapshot = match serde_json::from_slice::<Snapshot>(&body) {
+        Ok(snapshot) => snapshot,
+        Err(e) => return respond(StatusCode::BAD_REQUEST, "text/plain", format!("Expected a snapshot: {}\n", e)),
+    };
+    match import_snapshot(state, &snapshot) {
+        Ok(report) => respond(StatusCode::OK, "application/json", serde_json::json!(report).to_string()),
+        Err(e) => respond(StatusCode::UNPROCESSABLE_ENTITY, "text/plain", format!("{}\n", e)),
+    }
+}
+
+/*
+This function handles a priority change request.
+*/
+async fn update_priority(state: SharedStatePtr, role: &str, request: Request<Body>) -> Response<Body> {
+    let Some(role) = ThreadRole::parse(role) else {
+        return respond(StatusCode::NOT_FOUND, "text/plain", format!("Unknown role {}\n", role));
+    };
+    let body = match hyper::body::to_bytes(request.into_body()).await {
+        Ok(body) => body,
+        Err(e) => return respond(StatusCode::BAD_REQUEST, "text/plain", format!("{}\n", e)),
+    };
+    let priority = serde_json::from_slice::<PriorityUpdate>(&body)
+        .ok()
+        .map(|update| update.priority);
+    let Some(priority) = priority else {
+        return respond(StatusCode::BAD_REQUEST, "text/plain", "Expected {\"priority\": <integer>}\n".to_string());
+    };
+    let shared_state = unsafe { &mut *state.0 };
+    match set_priority(shared_state, role, priority) {
+        Ok(_) => respond(StatusCode::OK, "application/json", priorities().to_string()),
+        Err(e) => respond(StatusCode::UNPROCESSABLE_ENTITY, "text/plain", format!("{}\n", e)),
+    }
+}
+
+async fn route(state: SharedStatePtr, request: Request<Body>) -> Response<Body> {
+    let path = request.uri().path().to_string();
+    if request.method() == Method::PUT
+        && let Some(role) = path.strip_prefix("/priorities/") {
+        return update_priority(state, role, request).await;
+    }
+    if request.method() == Method::POST && path == "/oracle" {
+        return evaluate_pod(state, request).await;
+    }
+    if request.method() == Method::PUT && path == "/snapshot" {
+        return import_state(state, request).await;
+    }
+    match (request.method(), path.as_str()) {
+        (&Method::GET, "/metrics") => respond(
+            StatusCode::OK,
+            "text/plain; version=0.0.4",
+            render_metrics() + &render_slo_metrics(state)
+        ),
+        (&Method::GET, "/queue") => respond(
+            StatusCode::OK,
+            "application/json",
+            queue_stats().to_string()
+        ),
+        (&Method::GET, "/priorities") => respond(
+            StatusCode::OK,
+            "application/json",
+            priorities().to_string()
+        ),
+        (&Method::GET, "/threads") => respond(
+            StatusCode::OK,
+            "application/json",
+            thread_dump(state).to_string()
+        ),
+        (&Method::GET, "/events/stream") => stream_events(state),
+        (&Method::GET, "/snapshot") => respond(
+            StatusCode::OK,
+            "application/json",
+            serde_json::json!(export_snapshot(state)).to_string()
+        ),
+        (&Method::GET, "/schemas") => respond(
+            StatusCode::OK,
+            "application/json",
+            schemas().to_string()
+        ),
+        (&Method::GET, "/headroom") => match capacity_headroom(state, request.uri().query()) {
+            Ok(headroom) => respond(StatusCode::OK, "application/json", headroom.to_string()),
+            Err(e) => respond(StatusCode::BAD_REQUEST, "text/plain", format!("{}\n", e)),
+        },
+        (&Method::GET, "/simulate/node-loss") => match simulate_node_loss(state, request.uri().query()).await {
+            Ok(report) => respond(StatusCode::OK, "application/json", report.to_string()),
+            Err(e) => respond(StatusCode::BAD_REQUEST, "text/plain", format!("{}\n", e)),
+        },
+        (&Method::GET, "/healthz") => {
+            let dump = thread_dump(state);
+            let healthy = dump["activeWatchdogs"].as_u64().unwrap_or(0) > 0;
+            respond(
+                if healthy { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE },
+                "text/plain",
+                match (healthy, realtime_scheduling()) {
+                    (true, true) => "ok\n".to_string(),
+                    (true, false) => "ok (degraded: no real-time scheduling)\n".to_string(),
+                    (false, _) => "no active watchdogs\n".to_string(),
+                }
+            )
+        }
+        _ => respond(StatusCode::NOT_FOUND, "text/plain", "Not Found\n".to_string()),
+    }
+}
+
+/*
+Endpoints served to other hosts without TLS
+*/
+const PUBLIC_ENDPOINTS: [&str; 2] = ["/metrics", "/healthz"];
+
+/*
+This function authorizes an admin call (with mutual TLS through
+RBAC, without it by the endpoint and the peer address),
+routes it and writes it to the audit log.
+*/
+async fn handle(state: SharedStatePtr, client: Option<Client>, peer: SocketAddr, request: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let method = request.method().to_string();
+    let path = request.uri().path().to_string();
+    let caller = match client {
+        Some(client) => authorize(client, &request).await,
+        None if peer.ip().is_loopback() => Ok(Caller::Anonymous),
+        None if request.method() == Method::GET && PUBLIC_ENDPOINTS.contains(&path.as_str()) => Ok(Caller::Anonymous),
+        None => Err(StatusCode::FORBIDDEN),
+    };
+    let (caller, response) = match caller {
+        Ok(caller) => (caller, route(state, request).await),
+        Err(status) => (
+            Caller::Anonymous,
+            respond(status, "text/plain", format!("{}\n", status.canonical_reason().unwrap_or_default()))
+        ),
+    };
+    audit(peer, &caller, &method, &path, response.status());
+    Ok(response)
+}
+
+/*
+This function serves the admin API on a connection.
+*/
+async fn serve_connection<S>(state: SharedStatePtr, client: Option<Client>, peer: SocketAddr, stream: S)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let service = service_fn(move |request| handle(state, client.clone(), peer, request));
+    if let Err(e) = Http::new().http1_only(true).serve_connection(stream, service).await {
+        eprintln!("Admin Server - An error occurred while serving {}: {}", peer, e);
+    }
+}
+
+/*
+This function serves the admin API on the given port.
+With a TLS directory, the API is exposed with mutual TLS and
+RBAC-backed authorization, otherwise only the public endpoints
+are served to other hosts.
+*/
+pub async fn admin_server(client: Client, port: u16, tls_dir: String, state: SharedStatePtr) {
+    let acceptor = if tls_dir.is_empty() {
+        None
+    } else {
+        match tls_acceptor(&tls_dir) {
+            Ok(acceptor) => Some(acceptor),
+            Err(e) => {
+                eprintln!("Admin Server - An error occurred while loading the TLS certificates from {}: {}", tls_dir, e);
+                return;
+            }
+        }
+    };
+    let address = SocketAddr::from(([0, 0, 0, 0], port));
+    let listener = match TcpListener::bind(address).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("Admin Server - An error occurred while binding port {}: {}", port, e);
+            return;
+        }
+    };
+    println!("Admin Server - Listening on {} ({})!", address, if acceptor.is_some() { "mTLS" } else { "metrics and health check only off loopback" });
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                eprintln!("Admin Server - An error occurred while accepting a connection: {}", e);
+                continue;
+            }
+        };
+        match acceptor.clone() {
+            Some(acceptor) => {
+                let client = client.clone();
+                tokio::spawn(async move {
+                    match acceptor.accept(stream).await {
+                        Ok(stream) => serve_connection(state, Some(client), peer, stream).await,
+                        Err(e) => eprintln!("Admin Server - TLS handshake with {} failed: {}", peer, e),
+                    }
+                });
+            }
+            None => {
+                tokio::spawn(serve_connection(state, None, peer, stream));
+            }
+        }
+    }
+}
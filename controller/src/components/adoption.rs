@@ -0,0 +1,121 @@
+/*
+This file contains the adoption of pre-existing pods.
+When an RTResource sets spec.adoptExisting, unmanaged pods in its
+namespace matching its selector are labeled as its replicas instead
+of creating new ones, so that existing workloads (e.g. pods orphaned
+from a Deployment) can be moved under Preempt-K8s management
+without downtime.
+Pods owned by another controller are never adopted.
+*/
+
+use kube::{
+    Api,
+    Client,
+    api::{
+        ListParams,
+        Patch,
+        PatchParams
+    }
+};
+use k8s_openapi::api::core::v1::Pod;
+
+use crate::utils::rtresource::{
+    RTResource,
+    Selector
+};
+use crate::components::label_guard::{
+    OWNER_ANNOTATION,
+    owner_annotation
+};
+use crate::utils::owner_labels::owner_labels;
+
+
+
+/*
+This function converts an RTResource selector
+into a Kubernetes label selector string.
+*/
+pub fn label_selector(selector: &Selector) -> String {
+    let mut terms: Vec<String> = Vec::new();
+    for (key, value) in selector.match_labels.iter().flatten() {
+        terms.push(format!("{}={}", key, value));
+    }
+    for expression in selector.match_expressions.iter().flatten() {
+        let values = expression.values.clone().unwrap_or_default().join(",");
+        match expression.operator.as_str() {
+            "In" => terms.push(format!("{} in ({})", expression.key, values)),
+            "NotIn" => terms.push(format!("{} notin ({})", expression.key, values)),
+            "Exists" => terms.push(expression.key.clone()),
+            "DoesNotExist" => terms.push(format!("!{}", expression.key)),
+            other => eprintln!("Adoption - Unsupported selector operator {}, ignoring it!", other),
+        }
+    }
+    terms.join(",")
+}
+
+/*
+This function returns whether a pod can be adopted:
+it is not managed by an RTResource, not owned by another
+controller, not being deleted and not terminated.
+*/
+fn is_adoptable(pod: &Pod) -> bool {
+    let managed = pod.metadata.labels.as_ref().is_some_and(|l| l.contains_key(&owner_labels().uid));
+    let owned = pod.metadata.owner_references.iter().flatten().any(|o| o.controller == Some(true));
+    let phase = pod.status.as_ref().and_then(|s| s.phase.as_deref());
+    !managed && !owned
+        && pod.metadata.deletion_timestamp.is_none()
+        && !matches!(phase, Some("Succeeded") | Some("Failed"))
+}
+
+/*
+This function adopts up to wanted unmanaged pods matching the selector
+of the RTResource, running pods first, by adding the RTResource labels.
+It returns the adopted pods, which the planner then assigns ordinals to.
+*/
+pub async fn adopt_pods(thread_name: &str, client: Client, rtresource: &RTResource, wanted: usize) -> Vec<Pod> {
+    let mut adopted: Vec<Pod> = Vec::new();
+    let Some(selector) = rtresource.spec.selector.as_ref().map(label_selector).filter(|s| !s.is_empty()) else {
+        eprintln!(
+            "{} - RTResource {} sets adoptExisting without a selector, no pod will be adopted!",
+            thread_name,
+            rtresource.metadata.name.clone().unwrap_or_default()
+        );
+        return adopted;
+    };
+    if wanted == 0 {
+        return adopted;
+    }
+
+    let pod_api: Api<Pod> = Api::namespaced(client, &rtresource.spec.namespace);
+    let mut candidates: Vec<Pod> = match pod_api.list(&ListParams::default().labels(&selector)).await {
+        Ok(list) => list.items.into_iter().filter(is_adoptable).collect(),
+        Err(e) => {
+            eprintln!("{} - An error occurred while listing the pods to adopt with selector {}: {}", thread_name, selector, e);
+            return adopted;
+        }
+    };
+    candidates.sort_by_key(|p| p.status.as_ref().and_then(|s| s.phase.as_deref()) != Some("Running"));
+
+    let patch = serde_json::json!({
+        "metadata": {
+            "labels": rtresource.owner_labels(),
+            "annotations": {
+                OWNER_ANNOTATION: owner_annotation(rtresource)
+            }
+        }
+    });
+    for pod in candidates.into_iter().take(wanted) {
+        let pod_name = pod.metadata.name.clone().unwrap_or_default();
+        match pod_api.patch(&pod_name, &PatchParams::default(), &Patch::Merge(&patch)).await {
+            Ok(pod) => {
+                println!("{} - Pod {} adopted in namespace {}!", thread_name, pod_name, rtresource.spec.namespace);
+                adopted.push(pod);
+            }
+            Err(e) => {
+                eprintln!("{} - An error occurred while adopting Pod {}: {}", thread_name, pod_name, e);
+            }
+        }
+    }
+
+    adopted
+}
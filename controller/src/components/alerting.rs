@@ -0,0 +1,186 @@
+/*
+This file contains the alerting hooks raised
+when an RTResource keeps failing to reconcile.
+After a configurable number of consecutive failures the
+RTResource gets a "ReconcileStalled" condition and an
+Alertmanager-compatible alert is sent to the configured webhook.
+The alert is resolved on the first successful reconcile.
+*/
+
+use std::time::Duration;
+use libc::{
+    pthread_mutex_lock,
+    pthread_mutex_unlock
+};
+use kube::{
+    Api,
+    Client
+};
+
+use crate::utils::vars::SharedState;
+use crate::utils::configuration::ControllerConfig;
+use crate::utils::rtresource::RTResource;
+use crate::utils::http::post_json;
+
+
+
+/*
+Condition set on RTResources whose reconcile is stalled
+*/
+pub const STALLED_CONDITION: &str = "ReconcileStalled";
+
+/*
+Timeout for the alert webhook requests
+*/
+const ALERT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/*
+Transition of the consecutive failures streak of an RTResource
+*/
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReconcileStreak {
+    /*
+    Nothing to notify
+    */
+    Unchanged,
+    /*
+    The failure threshold has just been reached
+    */
+    Stalled(u32),
+    /*
+    A stalled RTResource reconciled successfully
+    */
+    Recovered,
+}
+
+/*
+This function records the outcome of a reconcile and
+returns the resulting streak transition.
+It must be called without holding the shared mutex.
+*/
+pub fn track_reconcile_outcome(shared_state: &mut SharedState, uid: &str, failed: bool) -> ReconcileStreak {
+    unsafe {
+        pthread_mutex_lock(&mut shared_state.mutex);
+        let threshold = shared_state.config.alert_failure_threshold;
+        let streak = if failed {
+            let count = shared_state.reconcile_failures.entry(uid.to_string()).or_insert(0);
+            *count += 1;
+            if *count == threshold {
+                ReconcileStreak::Stalled(*count)
+            } else {
+                ReconcileStreak::Unchanged
+            }
+        } else {
+            match shared_state.reconcile_failures.remove(uid) {
+                Some(count) if count >= threshold => ReconcileStreak::Recovered,
+                _ => ReconcileStreak::Unchanged,
+            }
+        };
+        pthread_mutex_unlock(&mut shared_state.mutex);
+
+        streak
+    }
+}
+
+/*
+This function forgets the failures streak of a deleted RTResource.
+It must be called without holding the shared mutex.
+*/
+pub fn forget_reconcile_outcome(shared_state: &mut SharedState, uid: &str) {
+    unsafe {
+        pthread_mutex_lock(&mut shared_state.mutex);
+        shared_state.reconcile_failures.remove(uid);
+        pthread_mutex_unlock(&mut shared_state.mutex);
+    }
+}
+
+/*
+This function builds an Alertmanager-compatible alert (API v2)
+for the given RTResource. Resolved alerts carry an end time.
+*/
+fn build_alert(rtresource: &RTResource, failures: u32, resolved: bool) -> serde_json::Value {
+    let now = chrono::Utc::now().to_rfc3339();
+    let name = rtresource.metadata.name.clone().unwrap_or_default();
+    let namespace = rtresource.metadata.namespace.clone().unwrap_or_default();
+    let mut alert = serde_json::json!({
+        "labels": {
+            "alertname": "RTResourceReconcileStalled",
+            "severity": "critical",
+            "rtresource": name,
+            "namespace": namespace,
+            "uid": rtresource.metadata.uid.clone().unwrap_or_default(),
+            "criticality": rtresource.spec.criticality.to_string()
+        },
+        "annotations": {
+            "summary": format!("RTResource {}/{} reconcile is stalled", namespace, name),
+            "description": format!("The reconcile of RTResource {}/{} failed {} consecutive times", namespace, name, failures)
+        },
+        "startsAt": now
+    });
+    if resolved {
+        alert["endsAt"] = serde_json::Value::String(now);
+    }
+    serde_json::Value::Array(vec![alert])
+}
+
+/*
+This function notifies a streak transition:
+    1. it sets the "ReconcileStalled" condition accordingly;
+    2. it sends (or resolves) the alert through the webhook, if configured.
+*/
+pub async fn notify_reconcile_streak(thread_name: &str, config: &ControllerConfig, client: Client, rtresource: &RTResource, streak: ReconcileStreak) {
+    let (status, reason, message, failures) = match streak {
+        ReconcileStreak::Unchanged => return,
+        ReconcileStreak::Stalled(failures) => (
+            "True",
+            "ConsecutiveFailures",
+            format!("Reconcile failed {} consecutive times", failures),
+            failures
+        ),
+        ReconcileStreak::Recovered => (
+            "False",
+            "ReconcileSucceeded",
+            "Reconcile succeeded".to_string(),
+            0
+        ),
+    };
+    let name = rtresource.metadata.name.clone().unwrap_or_default();
+    let namespace = rtresource.metadata.namespace.clone().unwrap_or_default();
+
+    let rtresource_api = Api::<RTResource>::namespaced(client, &namespace);
+    match rtresource_api.get(&name).await {
+        Ok(current) => {
+            let mut updated_resource = current.clone();
+            let mut new_status = current.status.clone().unwrap_or_default();
+            if new_status.set_condition(STALLED_CONDITION, status, reason, &message) {
+                updated_resource.status = Some(new_status);
+                if let Err(e) = rtresource_api.replace_status(
+                    &name,
+                    &Default::default(),
+                    serde_json::to_vec(&updated_resource).unwrap()
+                ).await {
+                    eprintln!("{} - An error occurred while setting the {} condition on RTResource {}/{}: {}", thread_name, STALLED_CONDITION, namespace, name, e);
+                }
+            }
+        }
+        Err(e) => {
+            eprintln!("{} - An error occurred while retrieving RTResource {}/{}: {}", thread_name, namespace, name, e);
+        }
+    }
+
+    if config.alert_webhook_url.is_empty() {
+        return;
+    }
+    let alert = build_alert(rtresource, failures, streak == ReconcileStreak::Recovered);
+    match post_json(&config.alert_webhook_url, &alert, ALERT_TIMEOUT).await {
+        Ok((code, _)) if (200..300).contains(&code) => {
+            println!("{} - Alert webhook notified for RTResource {}/{} ({})!", thread_name, namespace, name, message);
+        }
+        Ok((code, _)) => {
+            eprintln!("{} - The alert webhook answered with status {} for RTResource {}/{}!", thread_name, code, namespace, name);
+        }
+        Err(e) => {
+            eprintln!("{} - An error occurred while calling the alert webhook for RTResource {}/{}: {}", thread_name, namespace, name, e);
+        }
+    }
+}
@@ -0,0 +1,295 @@
+/*
+This file contains the static schedulability report (--analyze).
+The RTResources and the nodes are read from the cluster or from
+an exported YAML file (documents or Lists of RTResources and Nodes),
+then the offline schedulability analysis reports:
+    - the feasibility: the node of each replica, or the reasons
+      each node was discarded for;
+    - the utilization of the nodes (requested over allocatable
+      CPU and memory) once every placed replica runs;
+    - the critical-path latencies: the estimated time to recover
+      each RTResource after all of them are lost at once, when the
+      watchdogs handle the RTResources in criticality order and
+      create their replicas one after the other;
+    - the single-node failure tolerance: for each node, the replicas
+      that cannot be placed anymore once the node is lost.
+The report is printed as text, or as JSON with --json, and the
+command fails when some replica cannot be placed.
+*/
+
+use std::{
+    cmp::Reverse,
+    collections::{
+        BinaryHeap,
+        HashMap
+    },
+    error::Error,
+    fs,
+    time::Duration
+};
+use kube::{
+    Api,
+    Client
+};
+use k8s_openapi::api::core::v1::Node;
+use serde::Deserialize;
+
+use crate::utils::configuration::ControllerConfig;
+use crate::utils::rtresource::RTResource;
+use crate::utils::priorities::effective_criticality;
+use crate::utils::quantity::{
+    pod_requests,
+    resource_amount
+};
+use crate::components::scheduling_policy::SchedulingPolicy;
+use crate::components::schedulability::{
+    SchedulabilityReport,
+    analyze
+};
+
+
+
+/*
+Default estimate of the creation of a replica
+*/
+pub const DEFAULT_CREATE_LATENCY: Duration = Duration::from_millis(100);
+
+/*
+This function reads the RTResources and the nodes
+from an exported YAML file.
+*/
+fn read_export(path: &str) -> Result<(Vec<RTResource>, Vec<Node>), Box<dyn Error + Send + Sync + 'static>> {
+    let content = fs::read_to_string(path)?;
+    let mut rtresources = Vec::new();
+    let mut nodes = Vec::new();
+    let mut objects: Vec<serde_yaml::Value> = Vec::new();
+    for document in serde_yaml::Deserializer::from_str(&content) {
+        let value = serde_yaml::Value::deserialize(document)?;
+        match value.get("items").and_then(|i| i.as_sequence()) {
+            Some(items) => objects.extend(items.iter().cloned()),
+            None => objects.push(value),
+        }
+    }
+    for object in objects {
+        match object.get("kind").and_then(|k| k.as_str()) {
+            Some("RTResource") => rtresources.push(serde_yaml::from_value(object)?),
+            Some("Node") => nodes.push(serde_yaml::from_value(object)?),
+            _ => {}
+        }
+    }
+    Ok((rtresources, nodes))
+}
+
+/*
+This function reads the RTResources and the nodes from the cluster.
+*/
+async fn read_cluster() -> Result<(Vec<RTResource>, Vec<Node>), Box<dyn Error + Send + Sync + 'static>> {
+    let client = Client::try_default().await?;
+    let rtresources = Api::<RTResource>::all(client.clone()).list(&Default::default()).await?.items;
+    let nodes = Api::<Node>::all(client).list(&Default::default()).await?.items;
+    Ok((rtresources, nodes))
+}
+
+/*
+This function returns the requested over allocatable CPU and
+memory of each node, once the placed replicas run on it.
+*/
+fn utilization(report: &SchedulabilityReport, rtresources: &[RTResource], nodes: &[Node]) -> Vec<serde_json::Value> {
+    let requests: HashMap<(String, String), (f64, f64)> = rtresources.iter()
+        .map(|r| {
+            let requests = pod_requests(&r.spec.template.spec.clone().unwrap_or_default());
+            (
+                (r.spec.namespace.clone(), r.metadata.name.clone().unwrap_or_default()),
+                (requests.get("cpu").copied().unwrap_or(0.0), requests.get("memory").copied().unwrap_or(0.0))
+            )
+        })
+        .collect();
+    let mut requested: HashMap<&str, (f64, f64)> = HashMap::new();
+    for placement in report.placements.iter() {
+        if let Some(node) = placement.node.as_deref()
+            && let Some((cpu, memory)) = requests.get(&(placement.namespace.clone(), placement.name.clone())) {
+            let entry = requested.entry(node).or_default();
+            entry.0 += cpu;
+            entry.1 += memory;
+        }
+    }
+    nodes.iter()
+        .filter_map(|n| {
+            let name = n.metadata.name.as_deref()?;
+            let allocatable = n.status.as_ref().and_then(|s| s.allocatable.as_ref());
+            let (cpu, memory) = requested.get(name).copied().unwrap_or_default();
+            let ratio = |used: f64, total: f64| if total > 0.0 { used / total } else { 0.0 };
+            Some(serde_json::json!({
+                "node": name,
+                "cpu": ratio(cpu, resource_amount(allocatable, "cpu")),
+                "memory": ratio(memory, resource_amount(allocatable, "memory"))
+            }))
+        })
+        .collect()
+}
+
+/*
+This function estimates the recovery time of each RTResource after all
+of them are lost at once: the RTResources are handed to the watchdogs
+in criticality order, and each watchdog creates the replicas of its
+RTResource one after the other.
+*/
+fn critical_path_latencies(config: &ControllerConfig, rtresources: &[RTResource], create_latency: Duration) -> Vec<serde_json::Value> {
+    let mut ordered: Vec<(u32, &RTResource)> = rtresources.iter()
+        .map(|r| (effective_criticality(config, r.metadata.namespace.as_deref().unwrap_or_default(), r.spec.criticality), r))
+        .collect();
+    ordered.sort_by_key(|(criticality, _)| *criticality);
+    let mut watchdogs: BinaryHeap<Reverse<Duration>> = (0..config.max_watchdogs.max(1))
+        .map(|_| Reverse(Duration::ZERO))
+        .collect();
+    ordered.into_iter()
+        .map(|(criticality, r)| {
+            let Reverse(start) = watchdogs.pop().unwrap();
+            let finish = start + create_latency * r.spec.replicas.unwrap_or(1).max(0) as u32;
+            watchdogs.push(Reverse(finish));
+            serde_json::json!({
+                "name": r.metadata.name.clone().unwrap_or_default(),
+                "namespace": r.spec.namespace,
+                "criticality": criticality,
+                "recoveryMs": finish.as_millis() as u64
+            })
+        })
+        .collect()
+}
+
+/*
+This function runs the analysis without each node,
+reporting the replicas that cannot be placed anymore.
+*/
+fn node_failure_tolerance(policy: &SchedulingPolicy, rtresources: &[RTResource], nodes: &[Node]) -> Vec<serde_json::Value> {
+    nodes.iter()
+        .filter_map(|failed| {
+            let name = failed.metadata.name.as_deref()?;
+            let remaining: Vec<Node> = nodes.iter()
+                .filter(|n| n.metadata.name.as_deref() != Some(name))
+                .cloned()
+                .collect();
+            let report = analyze(policy, rtresources, &remaining);
+            let lost: Vec<String> = report.unschedulable().iter()
+                .map(|p| format!("{}/{}-{}", p.namespace, p.name, p.ordinal))
+                .collect();
+            Some(serde_json::json!({
+                "node": name,
+                "tolerated": lost.is_empty(),
+                "unschedulable": lost
+            }))
+        })
+        .collect()
+}
+
+/*
+This function prints the report as text.
+*/
+fn print_text(report: &serde_json::Value) {
+    let feasible = report["feasible"].as_bool().unwrap_or(false);
+    println!("Schedulability report ({} RTResources, {} nodes)", report["rtresources"], report["nodes"]);
+    println!();
+    println!("Feasibility: {}", if feasible { "every Hard replica can be placed" } else { "some Hard replicas cannot be placed" });
+    for placement in report["placements"].as_array().into_iter().flatten() {
+        match placement["node"].as_str() {
+            Some(node) => println!(
+                "    {}/{}-{} (criticality {}): {}",
+                placement["namespace"].as_str().unwrap_or_default(),
+                placement["name"].as_str().unwrap_or_default(),
+                placement["ordinal"],
+                placement["criticality"],
+                node
+            ),
+            None => {
+                println!(
+                    "    {}/{}-{} (criticality {}, {}): UNSCHEDULABLE",
+                    placement["namespace"].as_str().unwrap_or_default(),
+                    placement["name"].as_str().unwrap_or_default(),
+                    placement["ordinal"],
+                    placement["criticality"],
+                    placement["criticalityClass"].as_str().unwrap_or_default()
+                );
+                for failure in placement["failures"].as_array().into_iter().flatten() {
+                    println!("        {}: {}", failure["node"].as_str().unwrap_or_default(), failure["reason"].as_str().unwrap_or_default());
+                }
+            }
+        }
+    }
+    println!();
+    println!("Utilization:");
+    for node in report["utilization"].as_array().into_iter().flatten() {
+        println!(
+            "    {}: CPU {:.0}%, memory {:.0}%",
+            node["node"].as_str().unwrap_or_default(),
+            node["cpu"].as_f64().unwrap_or(0.0) * 100.0,
+            node["memory"].as_f64().unwrap_or(0.0) * 100.0
+        );
+    }
+    println!();
+    println!("Critical-path latencies (estimated recovery after losing every RTResource):");
+    for latency in report["criticalPath"].as_array().into_iter().flatten() {
+        println!(
+            "    {}/{} (criticality {}): {} ms",
+            latency["namespace"].as_str().unwrap_or_default(),
+            latency["name"].as_str().unwrap_or_default(),
+            latency["criticality"],
+            latency["recoveryMs"]
+        );
+    }
+    println!();
+    println!("Single-node failure tolerance:");
+    for failure in report["nodeFailures"].as_array().into_iter().flatten() {
+        let lost: Vec<&str> = failure["unschedulable"].as_array().into_iter().flatten().filter_map(|l| l.as_str()).collect();
+        if lost.is_empty() {
+            println!("    {}: tolerated", failure["node"].as_str().unwrap_or_default());
+        } else {
+            println!("    {}: loses {}", failure["node"].as_str().unwrap_or_default(), lost.join(", "));
+        }
+    }
+}
+
+/*
+This function runs the static schedulability analysis
+and prints its report.
+*/
+pub async fn run_analysis(config: &ControllerConfig, export: Option<&str>, json: bool, create_latency: Duration) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    let (rtresources, nodes) = match export {
+        Some(path) => read_export(path)?,
+        None => read_cluster().await?,
+    };
+    let policy = SchedulingPolicy::default_policy();
+    let analysis = analyze(&policy, &rtresources, &nodes);
+    let placements: Vec<serde_json::Value> = analysis.placements.iter()
+        .map(|p| serde_json::json!({
+            "name": p.name,
+            "namespace": p.namespace,
+            "criticality": p.criticality,
+            "ordinal": p.ordinal,
+            "criticalityClass": if p.soft { "Soft" } else { "Hard" },
+            "node": p.node,
+            "failures": p.failures.iter()
+                .map(|f| serde_json::json!({"node": f.node, "plugin": f.plugin, "reason": f.reason}))
+                .collect::<Vec<_>>()
+        }))
+        .collect();
+    let report = serde_json::json!({
+        "rtresources": rtresources.len(),
+        "nodes": nodes.len(),
+        "feasible": analysis.is_schedulable(),
+        "placements": placements,
+        "utilization": utilization(&analysis, &rtresources, &nodes),
+        "criticalPath": critical_path_latencies(config, &rtresources, create_latency),
+        "nodeFailures": node_failure_tolerance(&policy, &rtresources, &nodes)
+    });
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print_text(&report);
+    }
+
+    if !analysis.is_schedulable() {
+        let hard = analysis.unschedulable().iter().filter(|p| !p.soft).count();
+        return Err(format!("{} replicas of Hard RTResources cannot be placed", hard).into());
+    }
+    Ok(())
+}
@@ -0,0 +1,250 @@
+/*
+This file contains the archival of deleted RTResources.
+When an archive store is configured, the watchdogs add a finalizer
+to the RTResources they reconcile, so that on deletion the final
+status is still readable. Before removing the finalizer, a compact
+record is written to the store:
+    - the final spec summary and status;
+    - the placements (pod, ordinal, node, phase) of the replicas;
+    - the SLO samples and the pending preemptions kept by the controller.
+The supported stores are a ConfigMap per RTResource ("configmap:<namespace>"),
+a JSON file per RTResource ("file:<directory>") and an HTTP endpoint
+receiving a PUT per RTResource (e.g. a pre-authorized S3-compatible bucket).
+Archival is best effort: a failed write is reported, but it
+does not block the deletion of the RTResource.
+*/
+
+use std::{
+    path::PathBuf,
+    time::Duration
+};
+use libc::{
+    pthread_mutex_lock,
+    pthread_mutex_unlock
+};
+use kube::{
+    Api,
+    Client,
+    api::{
+        Patch,
+        PatchParams
+    }
+};
+use k8s_openapi::api::core::v1::{
+    ConfigMap,
+    Pod
+};
+
+use crate::utils::vars::SharedStatePtr;
+use crate::utils::rtresource::RTResource;
+use crate::utils::http::put_json;
+use crate::components::planner::ORDINAL_LABEL;
+use crate::components::slo_metrics::slo_summary;
+use crate::utils::owner_labels::owner_labels;
+
+
+
+/*
+Finalizer holding deleted RTResources until they are archived
+*/
+pub const ARCHIVE_FINALIZER: &str = "rtgroup.critical.com/archive";
+
+/*
+Field manager used for the archive ConfigMap server-side applies
+*/
+const ARCHIVE_FIELD_MANAGER: &str = "preempt-k8s";
+
+/*
+Timeout of the writes to an HTTP archive store
+*/
+const ARCHIVE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/*
+Archive store
+*/
+enum ArchiveStore {
+    ConfigMap(String),
+    File(PathBuf),
+    Http(String),
+}
+
+impl ArchiveStore {
+    fn parse(store: &str) -> Option<ArchiveStore> {
+        if let Some(namespace) = store.strip_prefix("configmap:") {
+            Some(ArchiveStore::ConfigMap(namespace.to_string()))
+        } else if let Some(directory) = store.strip_prefix("file:") {
+            Some(ArchiveStore::File(PathBuf::from(directory)))
+        } else if store.starts_with("http://") || store.starts_with("https://") {
+            Some(ArchiveStore::Http(store.trim_end_matches('/').to_string()))
+        } else {
+            None
+        }
+    }
+}
+
+/*
+This function returns whether an RTResource holds the archive finalizer.
+*/
+pub fn has_archive_finalizer(rtresource: &RTResource) -> bool {
+    rtresource.metadata.finalizers.iter().flatten().any(|f| f == ARCHIVE_FINALIZER)
+}
+
+/*
+This function sets the finalizers of an RTResource.
+The patch carries the resource version, so that finalizers
+changed concurrently by other controllers are not overwritten.
+*/
+async fn patch_finalizers(thread_name: &str, client: Client, rtresource: &RTResource, finalizers: Vec<String>) -> bool {
+    let name = rtresource.metadata.name.clone().unwrap_or_default();
+    let namespace = rtresource.metadata.namespace.clone().unwrap_or_default();
+    let patch = serde_json::json!({
+        "metadata": {
+            "resourceVersion": rtresource.metadata.resource_version,
+            "finalizers": finalizers
+        }
+    });
+    let rtresource_api = Api::<RTResource>::namespaced(client, &namespace);
+    match rtresource_api.patch(&name, &PatchParams::default(), &Patch::Merge(&patch)).await {
+        Ok(_) => true,
+        Err(e) => {
+            eprintln!("{} - An error occurred while updating the finalizers of RTResource {}/{}: {}", thread_name, namespace, name, e);
+            false
+        }
+    }
+}
+
+/*
+This function adds the archive finalizer to an RTResource.
+The RTResource is retrieved again, since its status
+may have just been written by the watchdog.
+*/
+pub async fn add_archive_finalizer(thread_name: &str, client: Client, namespace: &str, name: &str) -> bool {
+    let current = match Api::<RTResource>::namespaced(client.clone(), namespace).get(name).await {
+        Ok(current) => current,
+        Err(e) => {
+            eprintln!("{} - An error occurred while retrieving RTResource {}/{}: {}", thread_name, namespace, name, e);
+            return false;
+        }
+    };
+    if has_archive_finalizer(&current) || current.metadata.deletion_timestamp.is_some() {
+        return true;
+    }
+    let mut finalizers = current.metadata.finalizers.clone().unwrap_or_default();
+    finalizers.push(ARCHIVE_FINALIZER.to_string());
+    patch_finalizers(thread_name, client, &current, finalizers).await
+}
+
+/*
+This function removes the archive finalizer from an RTResource,
+letting the apiserver complete its deletion.
+*/
+pub async fn remove_archive_finalizer(thread_name: &str, client: Client, rtresource: &RTResource) -> bool {
+    let finalizers: Vec<String> = rtresource.metadata.finalizers.iter().flatten()
+        .filter(|f| *f != ARCHIVE_FINALIZER)
+        .cloned()
+        .collect();
+    patch_finalizers(thread_name, client, rtresource, finalizers).await
+}
+
+/*
+This function builds the archival record of a deleted RTResource.
+It must be called without holding the shared mutex.
+*/
+pub fn archive_record(state: SharedStatePtr, rtresource: &RTResource, pods: &[Pod]) -> serde_json::Value {
+    let uid = rtresource.metadata.uid.clone().unwrap_or_default();
+    let shared_state = unsafe { &mut *state.0 };
+    let (slo, preempted) = unsafe {
+        pthread_mutex_lock(&mut shared_state.mutex);
+        let slo = slo_summary(shared_state, &uid);
+        let preempted = shared_state.preempted.get(&uid).map(|v| v.replicas);
+        pthread_mutex_unlock(&mut shared_state.mutex);
+        (slo, preempted)
+    };
+
+    let placements: Vec<serde_json::Value> = pods.iter()
+        .map(|p| serde_json::json!({
+            "pod": p.metadata.name,
+            "ordinal": p.metadata.labels.as_ref().and_then(|l| l.get(ORDINAL_LABEL)),
+            "node": p.spec.as_ref().and_then(|s| s.node_name.clone()),
+            "phase": p.status.as_ref().and_then(|s| s.phase.clone()),
+        }))
+        .collect();
+    serde_json::json!({
+        "name": rtresource.metadata.name,
+        "namespace": rtresource.metadata.namespace,
+        "uid": uid,
+        "generation": rtresource.metadata.generation,
+        "createdAt": rtresource.metadata.creation_timestamp.as_ref().map(|t| t.0.to_rfc3339()),
+        "deletedAt": rtresource.metadata.deletion_timestamp.as_ref().map(|t| t.0.to_rfc3339()),
+        "criticality": rtresource.spec.criticality,
+        "replicas": rtresource.spec.replicas,
+        "finalStatus": rtresource.status,
+        "placements": placements,
+        "slo": slo,
+        "pendingPreemptedReplicas": preempted,
+    })
+}
+
+/*
+This function writes the archival record of an RTResource to the store.
+It returns whether the record was written.
+*/
+pub async fn archive_rtresource(thread_name: &str, client: Client, store: &str, record: &serde_json::Value) -> bool {
+    let Some(store) = ArchiveStore::parse(store) else {
+        eprintln!("{} - Invalid archive store {}, the RTResource is not archived!", thread_name, store);
+        return false;
+    };
+    let key = format!(
+        "rtresource-{}-{}-{}",
+        record["namespace"].as_str().unwrap_or_default(),
+        record["name"].as_str().unwrap_or_default(),
+        record["uid"].as_str().unwrap_or_default().split('-').next().unwrap_or_default()
+    );
+
+    let result: Result<(), String> = match store {
+        ArchiveStore::ConfigMap(namespace) => {
+            let configmap = serde_json::json!({
+                "apiVersion": "v1",
+                "kind": "ConfigMap",
+                "metadata": {
+                    "name": key,
+                    "namespace": namespace,
+                    "labels": {
+                        "app": "preempt-k8s",
+                        owner_labels().uid.as_str(): record["uid"]
+                    }
+                },
+                "data": {
+                    "record.json": record.to_string()
+                }
+            });
+            Api::<ConfigMap>::namespaced(client, &namespace)
+                .patch(&key, &PatchParams::apply(ARCHIVE_FIELD_MANAGER).force(), &Patch::Apply(&configmap))
+                .await
+                .map(|_| ())
+                .map_err(|e| e.to_string())
+        }
+        ArchiveStore::File(directory) => {
+            tokio::fs::write(directory.join(format!("{}.json", key)), record.to_string())
+                .await
+                .map_err(|e| e.to_string())
+        }
+        ArchiveStore::Http(url) => {
+            match put_json(&format!("{}/{}.json", url, key), record, ARCHIVE_TIMEOUT).await {
+                Ok(status) if (200..300).contains(&status) => Ok(()),
+                Ok(status) => Err(format!("status {}", status)),
+                Err(e) => Err(e.to_string()),
+            }
+        }
+    };
+    match result {
+        Ok(()) => {
+            println!("{} - Archived RTResource {}!", thread_name, key);
+            true
+        }
+        Err(e) => {
+            eprintln!("{} - An error occurred while archiving RTResource {}: {}", thread_name, key, e);
+            false
+        }
+    }
+}
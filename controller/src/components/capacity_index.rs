@@ -0,0 +1,744 @@
+/*
+This file contains the node capacity index used by the built-in scheduler.
+Instead of listing and scoring every node on each placement, the index
+keeps the nodes ordered by free CPU, updated incrementally from node
+events and from the pod add/delete events collected by the pod watcher.
+A placement only scores the few nodes with the most free capacity
+that fit the pod, which is a logarithmic lookup on large clusters.
+The index also keeps a reservation ledger: replicas pinned to a node
+by spec.placementOverrides hold their resources on that node until
+their pod is bound, so that other placements do not take them.
+A more critical placement that does not fit may cancel the pending
+reservations of less critical RTResources (which disrupts no running
+pod): a cancelled replica is not reserved again until its node has
+room for it.
+When metrics-server is available, the recent CPU usage of the nodes
+is polled as well, so that the least loaded nodes are preferred.
+*/
+
+use std::{
+    time::Duration,
+    collections::{
+        BTreeSet,
+        HashMap,
+        HashSet
+    }
+};
+use libc::{
+    pthread_mutex_lock,
+    pthread_mutex_unlock
+};
+use kube::{
+    Api,
+    Client,
+    api::{
+        ApiResource,
+        DynamicObject,
+        GroupVersionKind,
+        ListParams
+    },
+    runtime::watcher::{
+        watcher,
+        Config,
+        Event
+    }
+};
+use k8s_openapi::api::core::v1::{
+    Node,
+    Pod,
+    PodSpec
+};
+use futures::StreamExt;
+use serde::{
+    Deserialize,
+    Serialize
+};
+use schemars::JsonSchema;
+
+use crate::utils::vars::{
+    SharedState,
+    SharedStatePtr,
+    QueueMessage,
+    EventKind
+};
+use crate::utils::quantity::{
+    parse_quantity,
+    pod_requests,
+    resource_amount
+};
+use crate::utils::rtresource::RTResource;
+use crate::components::planner::ORDINAL_LABEL;
+use crate::components::scheduling_policy::node_os;
+use crate::utils::owner_labels::owner_labels;
+
+
+
+/*
+Interval between two polls of the node metrics
+*/
+const NODE_METRICS_INTERVAL: Duration = Duration::from_secs(15);
+
+/*
+Cached node with its capacity
+(CPU in millicores, memory in bytes)
+*/
+struct NodeEntry {
+    node: Node,
+    schedulable: bool,
+    allocatable_cpu: i64,
+    allocatable_memory: i64,
+    requested_cpu: i64,
+    requested_memory: i64,
+}
+
+impl NodeEntry {
+    fn free_cpu(&self) -> i64 {
+        self.allocatable_cpu - self.requested_cpu
+    }
+
+    fn free_memory(&self) -> i64 {
+        self.allocatable_memory - self.requested_memory
+    }
+}
+
+/*
+Resources requested by a pod bound to a node
+(or reserved for a pinned replica)
+*/
+struct PodEntry {
+    node: String,
+    cpu: i64,
+    memory: i64,
+    /*
+    The RTResource UID and replica ordinal of a managed pod
+    */
+    owner: Option<(String, u32)>,
+}
+
+/*
+Ledger entry of the index, exported for the handover
+between controllers (CPU in millicores, memory in bytes)
+*/
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LedgerEntry {
+    #[serde(rename = "rtresourceUid")]
+    pub rtresource_uid: Option<String>,
+    pub ordinal: Option<u32>,
+    #[serde(rename = "podUid")]
+    pub pod_uid: Option<String>,
+    pub node: String,
+    #[serde(rename = "cpuMillis")]
+    pub cpu_millis: i64,
+    #[serde(rename = "memoryBytes")]
+    pub memory_bytes: i64,
+}
+
+impl PodEntry {
+    fn ledger_entry(&self, pod_uid: Option<&String>) -> LedgerEntry {
+        LedgerEntry {
+            rtresource_uid: self.owner.as_ref().map(|(uid, _)| uid.clone()),
+            ordinal: self.owner.as_ref().map(|(_, ordinal)| *ordinal),
+            pod_uid: pod_uid.cloned(),
+            node: self.node.clone(),
+            cpu_millis: self.cpu,
+            memory_bytes: self.memory,
+        }
+    }
+}
+
+/*
+Node capacity index
+*/
+#[derive(Default)]
+pub struct CapacityIndex {
+    nodes: HashMap<String, NodeEntry>,
+    pods: HashMap<String, PodEntry>,
+    reservations: HashMap<(String, u32), PodEntry>,
+    /*
+    Event message and criticality of the RTResources
+    holding reservations, and the pinned replicas whose
+    reservation was cancelled by a more critical placement
+    */
+    reservation_owners: HashMap<String, (QueueMessage, u32)>,
+    cancelled: HashSet<(String, u32)>,
+    by_free_cpu: BTreeSet<(i64, String)>,
+    cpu_usage: HashMap<String, f64>,
+}
+
+/*
+This function returns the CPU (millicores) and memory (bytes) requested by a pod spec.
+*/
+pub fn requested(spec: &PodSpec) -> (i64, i64) {
+    let requests = pod_requests(spec);
+    (
+        (requests.get("cpu").copied().unwrap_or(0.0) * 1000.0) as i64,
+        requests.get("memory").copied().unwrap_or(0.0) as i64
+    )
+}
+
+/*
+This function returns the RTResource UID and replica ordinal of a managed pod.
+*/
+fn pod_owner(pod: &Pod) -> Option<(String, u32)> {
+    let labels = pod.metadata.labels.as_ref()?;
+    let uid = labels.get(&owner_labels().uid)?;
+    let ordinal = labels.get(ORDINAL_LABEL)?.parse().ok()?;
+    Some((uid.clone(), ordinal))
+}
+
+/*
+This function returns whether a pod holds resources on its node.
+*/
+fn holds_resources(pod: &Pod) -> bool {
+    let phase = pod.status.as_ref().and_then(|s| s.phase.as_deref());
+    pod.spec.as_ref().is_some_and(|s| s.node_name.is_some())
+        && !matches!(phase, Some("Succeeded") | Some("Failed"))
+}
+
+impl CapacityIndex {
+    /*
+    This function moves a node in the index
+    after its free capacity changed.
+    */
+    fn reindex(&mut self, name: &str, old_free_cpu: Option<i64>) {
+        if let Some(free) = old_free_cpu {
+            self.by_free_cpu.remove(&(free, name.to_string()));
+        }
+        if let Some(entry) = self.nodes.get(name) {
+            self.by_free_cpu.insert((entry.free_cpu(), name.to_string()));
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /*
+    This function adds or updates a node.
+    */
+    pub fn update_node(&mut self, node: &Node) {
+        let Some(name) = node.metadata.name.clone() else {
+            return;
+        };
+        let allocatable = node.status.as_ref().and_then(|s| s.allocatable.as_ref());
+        let schedulable = !node.spec.as_ref().and_then(|s| s.unschedulable).unwrap_or(false);
+        let old_free_cpu = self.nodes.get(&name).map(|e| e.free_cpu());
+        /*
+        A new node accounts for the pods already known to be bound to it
+        and for the replicas pinned to it.
+        */
+        let (requested_cpu, requested_memory) = match self.nodes.get(&name) {
+            Some(e) => (e.requested_cpu, e.requested_memory),
+            None => self.pods.values()
+                .chain(self.reservations.values())
+                .filter(|p| p.node == name)
+                .fold((0, 0), |(cpu, memory), p| (cpu + p.cpu, memory + p.memory)),
+        };
+        self.nodes.insert(name.clone(), NodeEntry {
+            node: node.clone(),
+            schedulable,
+            allocatable_cpu: (resource_amount(allocatable, "cpu") * 1000.0) as i64,
+            allocatable_memory: resource_amount(allocatable, "memory") as i64,
+            requested_cpu,
+            requested_memory,
+        });
+        self.reindex(&name, old_free_cpu);
+    }
+
+    /*
+    This function removes a node.
+    */
+    pub fn remove_node(&mut self, name: &str) {
+        self.cpu_usage.remove(name);
+        if let Some(entry) = self.nodes.remove(name) {
+            self.by_free_cpu.remove(&(entry.free_cpu(), name.to_string()));
+        }
+    }
+
+    /*
+    This function replaces all the nodes (after a relist).
+    */
+    pub fn reset_nodes(&mut self, nodes: &[Node]) {
+        let names: Vec<String> = self.nodes.keys()
+            .filter(|n| !nodes.iter().any(|node| node.metadata.name.as_ref() == Some(*n)))
+            .cloned()
+            .collect();
+        for name in names {
+            self.remove_node(&name);
+        }
+        for node in nodes {
+            self.update_node(node);
+        }
+    }
+
+    /*
+    This function accounts for an added or modified pod.
+    Pods that are not bound or have completed release their resources.
+    */
+    pub fn update_pod(&mut self, pod: &Pod) {
+        let Some(uid) = pod.metadata.uid.clone() else {
+            return;
+        };
+        if !holds_resources(pod) {
+            self.remove_pod(&uid);
+            return;
+        }
+        if self.pods.contains_key(&uid) {
+            return;
+        }
+        let owner = pod_owner(pod);
+        if let Some(owner) = owner.as_ref() {
+            self.release(owner);
+        }
+        let node = pod.spec.as_ref().and_then(|s| s.node_name.clone()).unwrap_or_default();
+        let (cpu, memory) = pod.spec.as_ref().map(requested).unwrap_or((0, 0));
+        self.charge(&node, cpu, memory);
+        self.pods.insert(uid, PodEntry {node, cpu, memory, owner});
+    }
+
+    /*
+    This function adds resources to the requests of a node.
+    */
+    fn charge(&mut self, node: &str, cpu: i64, memory: i64) {
+        if let Some(entry) = self.nodes.get_mut(node) {
+            let old_free_cpu = entry.free_cpu();
+            entry.requested_cpu += cpu;
+            entry.requested_memory += memory;
+            self.reindex(node, Some(old_free_cpu));
+        }
+    }
+
+    /*
+    This function accounts for a deleted pod.
+    */
+    pub fn remove_pod(&mut self, uid: &str) {
+        let Some(pod) = self.pods.remove(uid) else {
+            return;
+        };
+        self.charge(&pod.node, -pod.cpu, -pod.memory);
+    }
+
+    /*
+    This function releases the reservation of a pinned replica.
+    */
+    fn release(&mut self, owner: &(String, u32)) {
+        if let Some(reservation) = self.reservations.remove(owner) {
+            self.charge(&reservation.node, -reservation.cpu, -reservation.memory);
+        }
+    }
+
+    /*
+    This function replaces the reservations of an RTResource
+    (with its event message and criticality) with the given
+    pinned replicas (ordinal and node).
+    Replicas whose pod is already bound hold no reservation, cancelled
+    ones are only reserved again once their node has room for them.
+    */
+    pub fn reserve(&mut self, owner: (QueueMessage, u32), pinned: &[(u32, String)], spec: &PodSpec) {
+        let uid = owner.0.uid.clone();
+        self.release_all(&uid);
+        self.reservation_owners.insert(uid.clone(), owner);
+        let (cpu, memory) = requested(spec);
+        for (ordinal, node) in pinned {
+            let owner = (uid.clone(), *ordinal);
+            if self.pods.values().any(|p| p.owner.as_ref() == Some(&owner)) {
+                self.cancelled.remove(&owner);
+                continue;
+            }
+            if self.cancelled.contains(&owner) {
+                let fits = self.nodes.get(node).is_some_and(|e| e.free_cpu() >= cpu && e.free_memory() >= memory);
+                if !fits {
+                    continue;
+                }
+                self.cancelled.remove(&owner);
+            }
+            self.charge(node, cpu, memory);
+            self.reservations.insert(owner.clone(), PodEntry {
+                node: node.clone(),
+                cpu,
+                memory,
+                owner: Some(owner),
+            });
+        }
+    }
+
+    /*
+    This function releases all the reservations of an RTResource.
+    */
+    pub fn release_all(&mut self, uid: &str) {
+        let owners: Vec<(String, u32)> = self.reservations.keys()
+            .filter(|(u, _)| u == uid)
+            .cloned()
+            .collect();
+        for owner in owners {
+            self.release(&owner);
+        }
+    }
+
+    /*
+    This function forgets the reservations of a deleted RTResource.
+    */
+    pub fn forget_reservations(&mut self, uid: &str) {
+        self.release_all(uid);
+        self.reservation_owners.remove(uid);
+        self.cancelled.retain(|(u, _)| u != uid);
+    }
+
+    /*
+    This function returns the pinned replicas of an RTResource
+    whose reservation is cancelled.
+    */
+    pub fn cancelled_replicas(&self, uid: &str) -> Vec<u32> {
+        let mut ordinals: Vec<u32> = self.cancelled.iter()
+            .filter(|(u, _)| u == uid)
+            .map(|(_, ordinal)| *ordinal)
+            .collect();
+        ordinals.sort();
+        ordinals
+    }
+
+    /*
+    This function makes room for a pod of the given criticality on one
+    of the given nodes by cancelling the fewest reservations of less
+    critical RTResources, least critical first. The capacity already
+    claimed by the previous pods of the placement is accounted for
+    and updated. It returns the owners (event message, criticality)
+    and ordinals of the cancelled reservations.
+    */
+    pub fn cancel_reservations(
+        &mut self,
+        criticality: u32,
+        (cpu, memory): (i64, i64),
+        nodes: &[String],
+        claimed: &mut HashMap<String, (i64, i64)>
+    ) -> Vec<(QueueMessage, u32, u32)> {
+        let mut best: Option<(String, Vec<(String, u32)>)> = None;
+        for node in nodes {
+            let Some(entry) = self.nodes.get(node).filter(|e| e.schedulable) else {
+                continue;
+            };
+            let (claimed_cpu, claimed_memory) = claimed.get(node).copied().unwrap_or((0, 0));
+            let mut free_cpu = entry.free_cpu() - claimed_cpu;
+            let mut free_memory = entry.free_memory() - claimed_memory;
+            let mut victims: Vec<(&(String, u32), &PodEntry, u32)> = self.reservations.iter()
+                .filter(|(_, r)| r.node == *node)
+                .filter_map(|(owner, r)| {
+                    let (_, owner_criticality) = self.reservation_owners.get(&owner.0)?;
+                    (*owner_criticality > criticality).then_some((owner, r, *owner_criticality))
+                })
+                .collect();
+            victims.sort_by(|a, b| b.2.cmp(&a.2).then_with(|| a.0.cmp(b.0)));
+            let mut cancelled: Vec<(String, u32)> = Vec::new();
+            for (owner, reservation, _) in victims {
+                if free_cpu >= cpu && free_memory >= memory {
+                    break;
+                }
+                free_cpu += reservation.cpu;
+                free_memory += reservation.memory;
+                cancelled.push(owner.clone());
+            }
+            if cancelled.is_empty() || free_cpu < cpu || free_memory < memory {
+                continue;
+            }
+            if best.as_ref().is_none_or(|(_, b)| cancelled.len() < b.len()) {
+                best = Some((node.clone(), cancelled));
+            }
+        }
+
+        let Some((node, cancelled)) = best else {
+            return Vec::new();
+        };
+        let claim = claimed.entry(node).or_insert((0, 0));
+        claim.0 += cpu;
+        claim.1 += memory;
+        cancelled.into_iter()
+            .filter_map(|owner| {
+                self.release(&owner);
+                self.cancelled.insert(owner.clone());
+                let (msg, owner_criticality) = self.reservation_owners.get(&owner.0)?.clone();
+                Some((msg, owner_criticality, owner.1))
+            })
+            .collect()
+    }
+
+    /*
+    This function returns the reservations of the pinned replicas.
+    */
+    pub fn reservation_entries(&self) -> Vec<LedgerEntry> {
+        self.reservations.values().map(|r| r.ledger_entry(None)).collect()
+    }
+
+    /*
+    This function returns the pods bound to the nodes.
+    */
+    pub fn placement_entries(&self) -> Vec<LedgerEntry> {
+        self.pods.iter().map(|(uid, p)| p.ledger_entry(Some(uid))).collect()
+    }
+
+    /*
+    This function imports the reservation of a pinned replica.
+    It returns false when the replica is already reserved or bound.
+    */
+    pub fn import_reservation(&mut self, entry: &LedgerEntry) -> bool {
+        let (Some(uid), Some(ordinal)) = (entry.rtresource_uid.clone(), entry.ordinal) else {
+            return false;
+        };
+        let owner = (uid, ordinal);
+        if self.reservations.contains_key(&owner) || self.pods.values().any(|p| p.owner.as_ref() == Some(&owner)) {
+            return false;
+        }
+        self.charge(&entry.node, entry.cpu_millis, entry.memory_bytes);
+        self.reservations.insert(owner.clone(), PodEntry {
+            node: entry.node.clone(),
+            cpu: entry.cpu_millis,
+            memory: entry.memory_bytes,
+            owner: Some(owner),
+        });
+        true
+    }
+
+    /*
+    This function replaces all the pods (after a relist).
+    */
+    pub fn reset_pods(&mut self, pods: &[Pod]) {
+        let uids: Vec<String> = self.pods.keys().cloned().collect();
+        for uid in uids {
+            self.remove_pod(&uid);
+        }
+        for pod in pods {
+            self.update_pod(pod);
+        }
+    }
+
+    /*
+    This function records the CPU usage (millicores) of the nodes,
+    as a fraction of their allocatable CPU.
+    */
+    pub fn update_cpu_usage(&mut self, usage: &HashMap<String, f64>) {
+        self.cpu_usage = usage.iter()
+            .filter_map(|(name, cpu)| {
+                let entry = self.nodes.get(name).filter(|e| e.allocatable_cpu > 0)?;
+                Some((name.clone(), cpu / entry.allocatable_cpu as f64))
+            })
+            .collect();
+    }
+
+    /*
+    This function returns the free CPU and memory of
+    the schedulable nodes running the given operating system.
+    */
+    pub fn free_capacity(&self, os: &str) -> HashMap<String, (i64, i64)> {
+        self.nodes.iter()
+            .filter(|(_, e)| e.schedulable && node_os(&e.node) == os)
+            .map(|(name, e)| (name.clone(), (e.free_cpu(), e.free_memory())))
+            .collect()
+    }
+
+    /*
+    This function returns the free CPU and memory
+    of the schedulable nodes passing the filter.
+    */
+    pub fn headroom<F: Fn(&Node) -> bool>(&self, eligible: F) -> Vec<(String, i64, i64)> {
+        self.nodes.iter()
+            .filter(|(_, e)| e.schedulable && eligible(&e.node))
+            .map(|(name, e)| (name.clone(), e.free_cpu(), e.free_memory()))
+            .collect()
+    }
+
+    /*
+    This function returns up to limit schedulable nodes running the
+    given operating system and fitting the pod, starting from the one
+    with the most free CPU (a limit of 0 returns all the fitting nodes).
+    */
+    pub fn candidates(&self, spec: &PodSpec, os: &str, limit: usize) -> Vec<Node> {
+        let (cpu, memory) = requested(spec);
+        self.by_free_cpu.iter()
+            .rev()
+            .take_while(|(free_cpu, _)| *free_cpu >= cpu)
+            .filter_map(|(_, name)| self.nodes.get(name))
+            .filter(|e| e.schedulable && e.free_memory() >= memory && node_os(&e.node) == os)
+            .take(if limit == 0 { usize::MAX } else { limit })
+            .map(|e| e.node.clone())
+            .collect()
+    }
+}
+
+/*
+This function keeps the nodes of the capacity index up to date.
+It runs as a Tokio task, since it is not time critical.
+*/
+pub async fn node_capacity_watcher(client: Client, state: SharedStatePtr) {
+    let mut watcher = watcher(Api::<Node>::all(client), Config::default()).boxed();
+    while let Some(event) = watcher.next().await {
+        let shared_state = unsafe { &mut *state.0 };
+        unsafe {
+            pthread_mutex_lock(&mut shared_state.mutex);
+            match &event {
+                Ok(Event::Applied(node)) => shared_state.capacity.update_node(node),
+                Ok(Event::Deleted(node)) => shared_state.capacity.remove_node(node.metadata.name.as_deref().unwrap_or_default()),
+                Ok(Event::Restarted(nodes)) => shared_state.capacity.reset_nodes(nodes),
+                Err(_) => {}
+            }
+            pthread_mutex_unlock(&mut shared_state.mutex);
+        }
+        if let Err(e) = event {
+            eprintln!("Node Capacity Watcher - {}", e);
+        }
+    }
+}
+
+/*
+This function polls the CPU usage of the nodes from metrics-server.
+It stops when the metrics API is not served by the cluster.
+It runs as a Tokio task, since it is not time critical.
+*/
+pub async fn node_metrics_watcher(client: Client, state: SharedStatePtr) {
+    let resource = ApiResource::from_gvk_with_plural(&GroupVersionKind::gvk("metrics.k8s.io", "v1beta1", "NodeMetrics"), "nodes");
+    let api: Api<DynamicObject> = Api::all_with(client, &resource);
+    let mut interval = tokio::time::interval(NODE_METRICS_INTERVAL);
+    loop {
+        interval.tick().await;
+        let metrics = match api.list(&ListParams::default()).await {
+            Ok(list) => list.items,
+            Err(kube::Error::Api(e)) if e.code == 404 => {
+                println!("Node Metrics Watcher - metrics-server is not available, nodes will not be ranked by CPU usage!");
+                return;
+            }
+            Err(e) => {
+                eprintln!("Node Metrics Watcher - An error occurred while listing the node metrics: {}", e);
+                continue;
+            }
+        };
+        let usage: HashMap<String, f64> = metrics.iter()
+            .filter_map(|m| {
+                let name = m.metadata.name.clone()?;
+                let cpu = m.data.get("usage")?.get("cpu")?.as_str()?;
+                Some((name, parse_quantity(cpu)? * 1000.0))
+            })
+            .collect();
+        let shared_state = unsafe { &mut *state.0 };
+        unsafe {
+            pthread_mutex_lock(&mut shared_state.mutex);
+            shared_state.capacity.update_cpu_usage(&usage);
+            pthread_mutex_unlock(&mut shared_state.mutex);
+        }
+    }
+}
+
+/*
+This function returns the recent CPU usage of the nodes.
+It must be called without holding the shared mutex.
+*/
+pub fn node_cpu_usage(state: SharedStatePtr) -> HashMap<String, f64> {
+    let shared_state = unsafe { &mut *state.0 };
+    unsafe {
+        pthread_mutex_lock(&mut shared_state.mutex);
+        let usage = shared_state.capacity.cpu_usage.clone();
+        pthread_mutex_unlock(&mut shared_state.mutex);
+
+        usage
+    }
+}
+
+/*
+This function feeds a pod event to the capacity index.
+It must be called without holding the shared mutex.
+*/
+pub fn track_pod_event(shared_state: &mut SharedState, event: &Event<Pod>) {
+    unsafe {
+        pthread_mutex_lock(&mut shared_state.mutex);
+        match event {
+            Event::Applied(pod) => shared_state.capacity.update_pod(pod),
+            Event::Deleted(pod) => shared_state.capacity.remove_pod(pod.metadata.uid.as_deref().unwrap_or_default()),
+            Event::Restarted(pods) => shared_state.capacity.reset_pods(pods),
+        }
+        pthread_mutex_unlock(&mut shared_state.mutex);
+    }
+}
+
+/*
+This function returns the candidate nodes for a pod spec
+targeting the given operating system, or None if the index has not been populated yet.
+It must be called without holding the shared mutex.
+*/
+pub fn placement_candidates(state: SharedStatePtr, spec: &PodSpec, os: &str) -> Option<Vec<Node>> {
+    let shared_state = unsafe { &mut *state.0 };
+    unsafe {
+        pthread_mutex_lock(&mut shared_state.mutex);
+        let candidates = if shared_state.capacity.is_empty() {
+            None
+        } else {
+            Some(shared_state.capacity.candidates(spec, os, shared_state.config.scoring_candidates))
+        };
+        pthread_mutex_unlock(&mut shared_state.mutex);
+
+        candidates
+    }
+}
+
+/*
+This function records the reservations of the replicas
+of an RTResource pinned by spec.placementOverrides, at the
+criticality of the event being handled.
+Only the overrides of the desired replicas are reserved.
+It returns the pinned replicas whose reservation is cancelled
+by a more critical placement (they must not be created yet).
+It must be called without holding the shared mutex.
+*/
+pub fn reserve_placement_overrides(state: SharedStatePtr, rtresource: &RTResource, criticality: u32) -> Vec<u32> {
+    let uid = rtresource.metadata.uid.clone().unwrap_or_default();
+    let desired = rtresource.spec.desired_replicas() as u32;
+    let pinned: Vec<(u32, String)> = rtresource.spec.placement_overrides.iter().flatten()
+        .filter(|o| o.ordinal < desired)
+        .map(|o| (o.ordinal, o.node_name.clone()))
+        .collect();
+    let Some(spec) = rtresource.spec.template.spec.as_ref() else {
+        return Vec::new();
+    };
+    let msg = QueueMessage {
+        name: rtresource.metadata.name.clone().unwrap_or_default(),
+        uid: uid.clone(),
+        namespace: rtresource.metadata.namespace.clone().unwrap_or_default(),
+        enqueued_at: 0,
+        kind: EventKind::PodChanged,
+        trace_id: String::new(),
+    };
+    let shared_state = unsafe { &mut *state.0 };
+    unsafe {
+        pthread_mutex_lock(&mut shared_state.mutex);
+        shared_state.capacity.reserve((msg, criticality), &pinned, spec);
+        let cancelled = shared_state.capacity.cancelled_replicas(&uid);
+        pthread_mutex_unlock(&mut shared_state.mutex);
+
+        cancelled
+    }
+}
+
+/*
+This function releases the reservations of a deleted RTResource.
+It must be called without holding the shared mutex.
+*/
+pub fn release_placement_overrides(shared_state: &mut SharedState, uid: &str) {
+    unsafe {
+        pthread_mutex_lock(&mut shared_state.mutex);
+        shared_state.capacity.forget_reservations(uid);
+        pthread_mutex_unlock(&mut shared_state.mutex);
+    }
+}
+
+/*
+This function returns the free CPU and memory of the schedulable
+nodes running the given operating system, or None until the index
+is populated.
+It must be called without holding the shared mutex.
+*/
+pub fn node_free_capacity(state: SharedStatePtr, os: &str) -> Option<HashMap<String, (i64, i64)>> {
+    let shared_state = unsafe { &mut *state.0 };
+    unsafe {
+        pthread_mutex_lock(&mut shared_state.mutex);
+        let free = (!shared_state.capacity.is_empty()).then(|| shared_state.capacity.free_capacity(os));
+        pthread_mutex_unlock(&mut shared_state.mutex);
+
+        free
+    }
+}
@@ -0,0 +1,163 @@
+/*
+This file contains the chaos injection used for resilience testing.
+It is only built with the "chaos" feature, and injects at
+configurable rates:
+    - random delays in the delivery of queued events by the dispatcher;
+    - watchdog terminations, right after a watchdog releases its event;
+    - apiserver error responses, through a client wrapping the real one.
+The rates (probabilities between 0 and 1) are read from the
+environment variables CHAOS_DELAY_RATE, CHAOS_KILL_RATE and
+CHAOS_API_ERROR_RATE; CHAOS_DELAY_MAX_MS bounds the injected delays.
+*/
+
+use std::{
+    env,
+    future::Future,
+    pin::Pin,
+    sync::OnceLock,
+    task::{
+        Context,
+        Poll
+    },
+    time::Duration
+};
+use hyper::{
+    Body,
+    Request,
+    Response,
+    StatusCode
+};
+use kube::Client;
+use rand::Rng;
+use tower_service::Service;
+
+
+
+/*
+Chaos injection rates
+*/
+pub struct ChaosConfig {
+    pub delay_rate: f64,        // Probability of delaying an event delivery
+    pub delay_max_ms: u64,      // Highest injected delivery delay
+    pub kill_rate: f64,         // Probability of terminating a watchdog after an event
+    pub api_error_rate: f64,    // Probability of failing an apiserver request
+}
+
+static CHAOS: OnceLock<ChaosConfig> = OnceLock::new();
+
+fn get_rate(name: &str) -> f64 {
+    env::var(name)
+        .ok()
+        .and_then(|v| v.parse::<f64>().ok())
+        .map(|v| v.clamp(0.0, 1.0))
+        .unwrap_or(0.0) // 0 is the Default Value
+}
+
+/*
+This function reads the chaos injection rates.
+It must be called before the controller threads are started.
+*/
+pub fn init_chaos() {
+    let config = ChaosConfig {
+        delay_rate: get_rate("CHAOS_DELAY_RATE"),
+        delay_max_ms: env::var("CHAOS_DELAY_MAX_MS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(100), // 100 is the Default Value
+        kill_rate: get_rate("CHAOS_KILL_RATE"),
+        api_error_rate: get_rate("CHAOS_API_ERROR_RATE"),
+    };
+    println!(
+        "Chaos - Injecting delivery delays (rate {}, up to {} ms), watchdog terminations (rate {}) and apiserver errors (rate {})!",
+        config.delay_rate,
+        config.delay_max_ms,
+        config.kill_rate,
+        config.api_error_rate
+    );
+    let _ = CHAOS.set(config);
+}
+
+fn chaos() -> Option<&'static ChaosConfig> {
+    CHAOS.get()
+}
+
+fn roll(rate: f64) -> bool {
+    rate > 0.0 && rand::thread_rng().gen_bool(rate)
+}
+
+/*
+This function randomly delays the delivery of an event.
+*/
+pub fn delay_delivery() {
+    let Some(config) = chaos() else {
+        return;
+    };
+    if roll(config.delay_rate) {
+        let delay = Duration::from_millis(rand::thread_rng().gen_range(0..=config.delay_max_ms));
+        println!("Chaos - Delaying the event delivery by {:?}!", delay);
+        std::thread::sleep(delay);
+    }
+}
+
+/*
+This function returns whether a watchdog must be terminated.
+*/
+pub fn kill_watchdog() -> bool {
+    chaos().is_some_and(|config| roll(config.kill_rate))
+}
+
+/*
+Apiserver client failing requests at random
+*/
+#[derive(Clone)]
+pub struct ChaosService {
+    inner: Client,
+    error_rate: f64,
+}
+
+impl Service<Request<Body>> for ChaosService {
+    type Response = Response<Body>;
+    type Error = kube::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Response<Body>, kube::Error>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        let inner = self.inner.clone();
+        let fail = roll(self.error_rate);
+        Box::pin(async move {
+            if fail {
+                println!("Chaos - Failing apiserver request {} {}!", request.method(), request.uri().path());
+                let status = serde_json::json!({
+                    "kind": "Status",
+                    "apiVersion": "v1",
+                    "status": "Failure",
+                    "message": "injected by the chaos module",
+                    "reason": "ServiceUnavailable",
+                    "code": 503
+                });
+                return Ok(Response::builder()
+                    .status(StatusCode::SERVICE_UNAVAILABLE)
+                    .header("content-type", "application/json")
+                    .body(Body::from(status.to_string()))
+                    .unwrap());
+            }
+            inner.send(request).await
+        })
+    }
+}
+
+/*
+This function wraps a client so that its requests
+fail at the configured rate.
+*/
+pub fn chaos_client(client: Client) -> Client {
+    let error_rate = chaos().map_or(0.0, |config| config.api_error_rate);
+    if error_rate == 0.0 {
+        return client;
+    }
+    let default_namespace = client.default_namespace().to_string();
+    Client::new(ChaosService {inner: client, error_rate}, default_namespace)
+}
@@ -0,0 +1,169 @@
+/*
+This file contains the per-RTResource reconcile circuit breaker.
+An RTResource failing to reconcile (e.g. image pull errors) would
+otherwise consume watchdog time at its criticality on every event.
+After BACKOFF_THRESHOLD consecutive failures the circuit opens:
+the RTResource gets a "Backoff" condition and the dispatcher drops its
+events until a retry, scheduled after an exponentially growing delay
+(capped by BACKOFF_MAX_SECONDS). The circuit closes on the first
+successful reconcile.
+*/
+
+use std::{
+    ffi::CString,
+    time::{
+        Duration,
+        Instant
+    }
+};
+use libc::{
+    pthread_mutex_lock,
+    pthread_mutex_unlock
+};
+use kube::{
+    Api,
+    Client
+};
+
+use crate::utils::vars::{
+    SharedState,
+    QueueMessage,
+    EventKind
+};
+use crate::utils::rtresource::RTResource;
+use crate::utils::event_queue::enqueue_once;
+
+
+
+/*
+Condition set on RTResources whose circuit is open
+*/
+pub const BACKOFF_CONDITION: &str = "Backoff";
+
+/*
+Transition of the circuit of an RTResource
+*/
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CircuitTransition {
+    Unchanged,
+    /*
+    The circuit is open until the retry (failures, delay)
+    */
+    Opened(u32, Duration),
+    /*
+    The RTResource reconciled successfully after a backoff
+    */
+    Closed,
+}
+
+/*
+This function returns the backoff delay after the given failures.
+*/
+fn backoff_delay(failures: u32, threshold: u32, max: Duration) -> Duration {
+    let exponent = failures.saturating_sub(threshold).min(16);
+    Duration::from_secs(1u64 << exponent).min(max)
+}
+
+/*
+This function updates the circuit of an RTResource after a reconcile.
+It must be called after the reconcile outcome is tracked,
+and without holding the shared mutex.
+*/
+pub fn update_circuit(shared_state: &mut SharedState, uid: &str, failed: bool) -> CircuitTransition {
+    unsafe {
+        pthread_mutex_lock(&mut shared_state.mutex);
+        let threshold = shared_state.config.backoff_threshold;
+        let failures = shared_state.reconcile_failures.get(uid).copied().unwrap_or(0);
+        let transition = if !failed {
+            match shared_state.backoff.remove(uid) {
+                Some(_) => CircuitTransition::Closed,
+                None => CircuitTransition::Unchanged,
+            }
+        } else if threshold > 0 && failures >= threshold {
+            let delay = backoff_delay(failures, threshold, Duration::from_secs(shared_state.config.backoff_max_seconds));
+            shared_state.backoff.insert(uid.to_string(), Instant::now() + delay);
+            CircuitTransition::Opened(failures, delay)
+        } else {
+            CircuitTransition::Unchanged
+        };
+        pthread_mutex_unlock(&mut shared_state.mutex);
+
+        transition
+    }
+}
+
+/*
+This function returns whether the events of an RTResource
+must be dropped because its circuit is open.
+It must be called holding the shared mutex.
+*/
+pub fn circuit_open(shared_state: &SharedState, uid: &str) -> bool {
+    shared_state.backoff.get(uid).is_some_and(|until| Instant::now() < *until)
+}
+
+/*
+This function forgets the circuit of a deleted RTResource.
+It must be called without holding the shared mutex.
+*/
+pub fn forget_circuit(shared_state: &mut SharedState, uid: &str) {
+    unsafe {
+        pthread_mutex_lock(&mut shared_state.mutex);
+        shared_state.backoff.remove(uid);
+        pthread_mutex_unlock(&mut shared_state.mutex);
+    }
+}
+
+/*
+This function sends the retry event of an RTResource
+to the event priority queue once its backoff expires.
+*/
+pub async fn schedule_retry(queue: CString, mut msg: QueueMessage, criticality: u32, delay: Duration) {
+    tokio::time::sleep(delay).await;
+    msg.kind = EventKind::Resync;
+    if enqueue_once("Circuit Breaker", &queue, &msg, criticality) {
+        println!("Circuit Breaker - Retrying RTResource {}, {} in namespace {}!", msg.name, msg.uid, msg.namespace);
+    }
+}
+
+/*
+This function sets the "Backoff" condition of an RTResource
+according to a circuit transition.
+*/
+pub async fn notify_circuit(thread_name: &str, client: Client, rtresource: &RTResource, transition: CircuitTransition) {
+    let (status, reason, message) = match transition {
+        CircuitTransition::Unchanged => return,
+        CircuitTransition::Opened(failures, delay) => (
+            "True",
+            "ConsecutiveFailures",
+            format!("Reconcile failed {} consecutive times, next retry in {}s", failures, delay.as_secs())
+        ),
+        CircuitTransition::Closed => (
+            "False",
+            "ReconcileSucceeded",
+            "Reconcile succeeded".to_string()
+        ),
+    };
+    let name = rtresource.metadata.name.clone().unwrap_or_default();
+    let namespace = rtresource.metadata.namespace.clone().unwrap_or_default();
+
+    let rtresource_api = Api::<RTResource>::namespaced(client, &namespace);
+    match rtresource_api.get(&name).await {
+        Ok(current) => {
+            let mut updated_resource = current.clone();
+            let mut new_status = current.status.clone().unwrap_or_default();
+            if new_status.set_condition(BACKOFF_CONDITION, status, reason, &message) {
+                updated_resource.status = Some(new_status);
+                if let Err(e) = rtresource_api.replace_status(
+                    &name,
+                    &Default::default(),
+                    serde_json::to_vec(&updated_resource).unwrap()
+                ).await {
+                    eprintln!("{} - An error occurred while setting the {} condition on RTResource {}/{}: {}", thread_name, BACKOFF_CONDITION, namespace, name, e);
+                }
+            }
+        }
+        Err(e) => {
+            eprintln!("{} - An error occurred while retrieving RTResource {}/{}: {}", thread_name, namespace, name, e);
+        }
+    }
+}
@@ -0,0 +1,159 @@
+/*
+This file contains the publisher of the RTClusterStats resources.
+It periodically aggregates, per namespace with RTResources, the
+events waiting in the ready queue or being handled, the recovery
+latencies of the SLO samples and the preemptions suffered and caused,
+and applies them to the RTClusterStats of the namespace. The
+RTClusterStats of the namespaces left without RTResources are deleted.
+It runs as a Tokio task, since it is not time critical.
+*/
+
+use std::{
+    collections::HashMap,
+    time::Duration
+};
+use libc::{
+    pthread_mutex_lock,
+    pthread_mutex_unlock
+};
+use kube::{
+    Api,
+    Client,
+    api::{
+        DeleteParams,
+        ListParams,
+        Patch,
+        PatchParams
+    }
+};
+
+use crate::utils::vars::{
+    SharedState,
+    SharedStatePtr
+};
+use crate::utils::rtresource::RTResource;
+use crate::utils::rtclusterstats::{
+    RTClusterStats,
+    RTClusterStatsSpec
+};
+use crate::components::slo_metrics::namespace_recovery;
+
+
+
+/*
+Name of the RTClusterStats of each namespace
+*/
+pub const CLUSTER_STATS_NAME: &str = "preempt-k8s";
+
+/*
+Field manager used for RTClusterStats server-side applies
+*/
+const STATS_FIELD_MANAGER: &str = "preempt-k8s-stats";
+
+/*
+This function records a preemption for the namespace of the
+victim (suffered) or of the preemptor (caused).
+It must be called without holding the shared mutex.
+*/
+pub fn record_namespace_preemption(shared_state: &mut SharedState, namespace: &str, caused: bool) {
+    unsafe {
+        pthread_mutex_lock(&mut shared_state.mutex);
+        let counts = shared_state.namespace_preemptions.entry(namespace.to_string()).or_default();
+        if caused {
+            counts.1 += 1;
+        } else {
+            counts.0 += 1;
+        }
+        pthread_mutex_unlock(&mut shared_state.mutex);
+    }
+}
+
+/*
+This function aggregates the statistics of the given namespaces.
+It must be called without holding the shared mutex.
+*/
+fn aggregate(state: SharedStatePtr, rtresources: &HashMap<String, u32>) -> HashMap<String, RTClusterStatsSpec> {
+    let shared_state = unsafe { &mut *state.0 };
+    let updated_at = chrono::Utc::now().to_rfc3339();
+    let mut stats: HashMap<String, RTClusterStatsSpec> = rtresources.iter()
+        .map(|(namespace, count)| (namespace.clone(), RTClusterStatsSpec {
+            updated_at: updated_at.clone(),
+            rtresources: *count,
+            ..Default::default()
+        }))
+        .collect();
+    unsafe {
+        pthread_mutex_lock(&mut shared_state.mutex);
+        for event in shared_state.ready.iter() {
+            if let Some(s) = stats.get_mut(&event.msg.namespace) {
+                s.pending_events += 1;
+            }
+        }
+        for (msg, _) in shared_state.handling.values() {
+            if let Some(s) = stats.get_mut(&msg.namespace) {
+                s.in_flight_events += 1;
+            }
+        }
+        for (namespace, (events, sum, max)) in namespace_recovery(shared_state) {
+            if let Some(s) = stats.get_mut(&namespace) && events > 0 {
+                s.handled_events = events;
+                s.mean_recovery_ms = Some((sum / events as f64 * 1000.0) as u64);
+                s.max_recovery_ms = Some((max * 1000.0) as u64);
+            }
+        }
+        for (namespace, (suffered, caused)) in shared_state.namespace_preemptions.iter() {
+            if let Some(s) = stats.get_mut(namespace) {
+                s.preemptions_suffered = *suffered;
+                s.preemptions_caused = *caused;
+            }
+        }
+        pthread_mutex_unlock(&mut shared_state.mutex);
+    }
+    stats
+}
+
+/*
+This function publishes the RTClusterStats once.
+*/
+async fn publish_stats(client: Client, state: SharedStatePtr) -> Result<(), kube::Error> {
+    let mut rtresources: HashMap<String, u32> = HashMap::new();
+    for r in Api::<RTResource>::all(client.clone()).list(&ListParams::default()).await?.items {
+        if let Some(namespace) = r.metadata.namespace {
+            *rtresources.entry(namespace).or_default() += 1;
+        }
+    }
+    for (namespace, spec) in aggregate(state, &rtresources) {
+        let stats = RTClusterStats::new(CLUSTER_STATS_NAME, spec);
+        if let Err(e) = Api::<RTClusterStats>::namespaced(client.clone(), &namespace).patch(
+            CLUSTER_STATS_NAME,
+            &PatchParams::apply(STATS_FIELD_MANAGER).force(),
+            &Patch::Apply(&stats)
+        ).await {
+            eprintln!("Cluster Stats - An error occurred while publishing the statistics of namespace {}: {}", namespace, e);
+        }
+    }
+    for stale in Api::<RTClusterStats>::all(client.clone()).list(&ListParams::default()).await?.items {
+        let Some(namespace) = stale.metadata.namespace.as_deref() else {
+            continue;
+        };
+        if stale.metadata.name.as_deref() == Some(CLUSTER_STATS_NAME) && !rtresources.contains_key(namespace) {
+            Api::<RTClusterStats>::namespaced(client.clone(), namespace).delete(CLUSTER_STATS_NAME, &DeleteParams::default()).await?;
+            println!("Cluster Stats - Deleted the statistics of namespace {}, which has no RTResources left!", namespace);
+        }
+    }
+    Ok(())
+}
+
+/*
+This function periodically publishes the RTClusterStats.
+*/
+pub async fn cluster_stats_publisher(client: Client, state: SharedStatePtr) {
+    let interval_ms = unsafe { (*state.0).config.cluster_stats_interval_ms };
+    let mut interval = tokio::time::interval(Duration::from_millis(interval_ms));
+    loop {
+        interval.tick().await;
+        if let Err(e) = publish_stats(client.clone(), state).await {
+            eprintln!("Cluster Stats - An error occurred while publishing the statistics: {}", e);
+        }
+    }
+}
@@ -0,0 +1,188 @@
+/*
+This file contains the scraping of the concurrency metrics exposed
+by a sidecar of the replicas (CONCURRENCY_SCRAPE_INTERVAL_MS), such
+as the Knative queue-proxy.
+Each cycle, the metrics endpoint of the sidecar of every running
+replica is scraped, and the sum of the concurrency of the replicas
+is reflected into status.observedConcurrency of the RTResource, for
+the operator dashboards and any external autoscaler.
+The endpoint and the metric default to the controller configuration
+(CONCURRENCY_SIDECAR, CONCURRENCY_METRICS_PORT, CONCURRENCY_METRICS_PATH,
+CONCURRENCY_METRIC) and can be overridden per RTResource with the
+rtgroup.critical.com/concurrency-metrics-port, -metrics-path and
+rtgroup.critical.com/concurrency-metric annotations; with the port
+annotation set, the replicas are scraped even without the sidecar.
+It runs as a Tokio task, since it is not time critical.
+*/
+
+use std::time::Duration;
+use kube::{
+    Api,
+    Client,
+    api::{
+        ListParams,
+        Patch,
+        PatchParams
+    }
+};
+use k8s_openapi::api::core::v1::Pod;
+
+use crate::utils::rtresource::RTResource;
+use crate::utils::configuration::ControllerConfig;
+use crate::utils::http::get_text;
+use crate::utils::owner_labels::owner_labels;
+
+
+
+/*
+Annotations overriding the metrics endpoint of an RTResource
+*/
+pub const METRICS_PORT_ANNOTATION: &str = "rtgroup.critical.com/concurrency-metrics-port";
+pub const METRICS_PATH_ANNOTATION: &str = "rtgroup.critical.com/concurrency-metrics-path";
+pub const METRIC_ANNOTATION: &str = "rtgroup.critical.com/concurrency-metric";
+
+/*
+Timeout of a scrape
+*/
+const SCRAPE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/*
+Metrics endpoint of the replicas of an RTResource
+*/
+struct MetricsEndpoint {
+    port: u16,
+    path: String,
+    metric: String,
+    /*
+    Whether the replicas without the sidecar are scraped too
+    */
+    any_pod: bool,
+}
+
+/*
+This function returns the metrics endpoint of the replicas
+of an RTResource, from its annotations or the configuration.
+*/
+fn metrics_endpoint(config: &ControllerConfig, rtresource: &RTResource) -> MetricsEndpoint {
+    let annotation = |key: &str| rtresource.metadata.annotations.as_ref().and_then(|a| a.get(key)).cloned();
+    let port = annotation(METRICS_PORT_ANNOTATION).and_then(|p| p.parse().ok());
+    MetricsEndpoint {
+        port: port.unwrap_or(config.concurrency_metrics_port),
+        path: annotation(METRICS_PATH_ANNOTATION).unwrap_or_else(|| config.concurrency_metrics_path.clone()),
+        metric: annotation(METRIC_ANNOTATION).unwrap_or_else(|| config.concurrency_metric.clone()),
+        any_pod: port.is_some(),
+    }
+}
+
+/*
+This function returns the sum of the samples of a metric
+in a Prometheus text exposition (None if absent).
+*/
+fn metric_value(exposition: &str, metric: &str) -> Option<f64> {
+    let mut value: Option<f64> = None;
+    for line in exposition.lines().filter(|l| !l.starts_with('#')) {
+        let Some(rest) = line.strip_prefix(metric) else {
+            continue;
+        };
+        let rest = match rest.chars().next() {
+            Some('{') => rest.split_once('}').map_or("", |(_, r)| r),
+            Some(' ') => rest,
+            _ => continue,
+        };
+        if let Some(sample) = rest.split_whitespace().next().and_then(|v| v.parse::<f64>().ok()) {
+            *value.get_or_insert(0.0) += sample;
+        }
+    }
+    value
+}
+
+/*
+This function scrapes the concurrency of a replica.
+*/
+async fn scrape_pod(pod: &Pod, endpoint: &MetricsEndpoint) -> Result<Option<f64>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let Some(ip) = pod.status.as_ref().and_then(|s| s.pod_ip.as_ref()) else {
+        return Ok(None);
+    };
+    let url = format!("http://{}:{}{}", ip, endpoint.port, endpoint.path);
+    let (status, body) = get_text(&url, SCRAPE_TIMEOUT).await?;
+    if status != 200 {
+        return Err(format!("{} answered {}", url, status).into());
+    }
+    Ok(metric_value(&body, &endpoint.metric))
+}
+
+/*
+This function scrapes the replicas of an RTResource and returns
+their total concurrency (None if no replica reported it).
+*/
+async fn observed_concurrency(client: Client, config: &ControllerConfig, rtresource: &RTResource) -> Result<Option<f64>, kube::Error> {
+    let endpoint = metrics_endpoint(config, rtresource);
+    let uid = rtresource.metadata.uid.clone().unwrap_or_default();
+    let lp = ListParams::default().labels(&owner_labels().uid_selector(&uid));
+    let pods = Api::<Pod>::namespaced(client, &rtresource.spec.namespace).list(&lp).await?.items;
+    let mut total: Option<f64> = None;
+    for pod in pods.iter() {
+        let running = pod.status.as_ref().and_then(|s| s.phase.as_deref()) == Some("Running");
+        let has_sidecar = pod.spec.as_ref()
+            .is_some_and(|s| s.containers.iter().any(|c| c.name == config.concurrency_sidecar));
+        if !running || pod.metadata.deletion_timestamp.is_some() || !(has_sidecar || endpoint.any_pod) {
+            continue;
+        }
+        match scrape_pod(pod, &endpoint).await {
+            Ok(Some(value)) => *total.get_or_insert(0.0) += value,
+            Ok(None) => {}
+            Err(e) => eprintln!(
+                "Concurrency Scraper - An error occurred while scraping pod {}: {}",
+                pod.metadata.name.clone().unwrap_or_default(),
+                e
+            ),
+        }
+    }
+    Ok(total)
+}
+
+/*
+This function periodically reflects the concurrency reported by
+the sidecars of the replicas into the RTResource statuses.
+*/
+pub async fn concurrency_scraper(client: Client, config: ControllerConfig) {
+    let mut interval = tokio::time::interval(Duration::from_millis(config.concurrency_scrape_interval_ms));
+    loop {
+        interval.tick().await;
+        let rtresources = match Api::<RTResource>::all(client.clone()).list(&ListParams::default()).await {
+            Ok(list) => list.items,
+            Err(e) => {
+                eprintln!("Concurrency Scraper - An error occurred while listing the RTResources: {}", e);
+                continue;
+            }
+        };
+        for r in rtresources.iter().filter(|r| r.metadata.deletion_timestamp.is_none()) {
+            let concurrency = match observed_concurrency(client.clone(), &config, r).await {
+                Ok(Some(concurrency)) => concurrency,
+                Ok(None) => continue,
+                Err(e) => {
+                    eprintln!(
+                        "Concurrency Scraper - An error occurred while listing the pods of RTResource {}: {}",
+                        r.metadata.uid.clone().unwrap_or_default(),
+                        e
+                    );
+                    continue;
+                }
+            };
+            if r.status.as_ref().and_then(|s| s.observed_concurrency) == Some(concurrency) {
+                continue;
+            }
+            let patch = serde_json::json!({"status": {"observedConcurrency": concurrency}});
+            if let Err(e) = Api::<RTResource>::namespaced(client.clone(), r.metadata.namespace.as_deref().unwrap_or_default())
+                .patch_status(r.metadata.name.as_deref().unwrap_or_default(), &PatchParams::default(), &Patch::Merge(&patch))
+                .await
+            {
+                eprintln!(
+                    "Concurrency Scraper - An error occurred while updating the status of RTResource {}: {}",
+                    r.metadata.uid.clone().unwrap_or_default(),
+                    e
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,337 @@
+/*
+This file contains the watcher of the ConfigMaps and Secrets
+referenced by spec.restartOn. The RT applications read their
+configuration at startup only, so a change of a referenced object
+is applied by replacing the pods. The watcher keeps the hash of the
+data of each ConfigMap and Secret (never the data itself) and, when
+one referenced by an RTResource changes, enqueues the RTResources
+referencing it; the changes of the other objects (e.g. the ConfigMaps
+written by the controller itself) are dropped without any apiserver
+call. The versions and the referenced keys are kept behind their own
+mutex, not the shared one the real-time threads wait on. The pods are
+stamped at creation with the hash of the objects their RTResource
+references (rtgroup.critical.com/config-hash), and the watchdog
+replaces the pods with another hash one at a time, make-before-break,
+like the pods migrated off the nodes under maintenance.
+It runs as a Tokio task, since it is not time critical.
+*/
+
+use std::{
+    collections::HashMap,
+    ffi::CString
+};
+use kube::{
+    Api,
+    Client,
+    api::ListParams,
+    runtime::watcher::{
+        watcher,
+        Config,
+        Event
+    }
+};
+use k8s_openapi::api::core::v1::{
+    ConfigMap,
+    Pod,
+    Secret
+};
+use futures::{
+    stream,
+    StreamExt
+};
+
+use crate::utils::vars::{
+    QueueMessage,
+    EventKind,
+    SharedState,
+    SharedStatePtr
+};
+use crate::utils::rtresource::RTResource;
+use crate::utils::configuration::ControllerConfig;
+use crate::utils::event_queue::enqueue_once;
+use crate::utils::priorities::effective_criticality;
+
+
+
+/*
+Annotation carrying the hash of the ConfigMaps and
+Secrets a pod was started with
+*/
+pub const CONFIG_HASH_ANNOTATION: &str = "rtgroup.critical.com/config-hash";
+
+/*
+Kinds of the watched objects
+*/
+const CONFIG_MAP: &str = "ConfigMap";
+const SECRET: &str = "Secret";
+const WATCHED_KINDS: [&str; 2] = [CONFIG_MAP, SECRET];
+
+/*
+Version of a watched object: its key and the hash of its data
+*/
+type Version = (String, String);
+
+/*
+Versions of the ConfigMaps and Secrets and the keys referenced by
+the RTResources. They are kept behind their own mutex, so that the
+merge of a relist of the whole cluster never holds the shared mutex.
+*/
+#[derive(Default)]
+pub struct ConfigVersions {
+    /*
+    Hash of the data of each ConfigMap and Secret,
+    per "<kind>/<namespace>/<name>" key
+    */
+    pub hashes: HashMap<String, String>,
+    /*
+    Whether the ConfigMaps and Secrets were listed
+    since the controller started
+    */
+    pub synced: bool,
+    /*
+    Keys referenced by spec.restartOn, per RTResource UID
+    */
+    pub references: HashMap<String, Vec<String>>,
+}
+
+/*
+This function returns the key of an object in the config versions.
+*/
+fn version_key(kind: &str, namespace: &str, name: &str) -> String {
+    format!("{}/{}/{}", kind, namespace, name)
+}
+
+/*
+This function returns a stable hash (FNV-1a, 64 bits)
+of the given entries.
+*/
+fn data_hash<'a>(entries: impl Iterator<Item = (&'a str, &'a [u8])>) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for (key, value) in entries {
+        for byte in key.as_bytes().iter().chain(b"=").chain(value).chain(b";") {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    format!("{:016x}", hash)
+}
+
+/*
+This function returns the version of a ConfigMap.
+*/
+fn config_map_version(config_map: &ConfigMap) -> Version {
+    let key = version_key(
+        CONFIG_MAP,
+        config_map.metadata.namespace.as_deref().unwrap_or_default(),
+        config_map.metadata.name.as_deref().unwrap_or_default()
+    );
+    let data = config_map.data.iter().flatten().map(|(k, v)| (k.as_str(), v.as_bytes()));
+    let binary_data = config_map.binary_data.iter().flatten().map(|(k, v)| (k.as_str(), v.0.as_slice()));
+    (key, data_hash(data.chain(binary_data)))
+}
+
+/*
+This function returns the version of a Secret.
+*/
+fn secret_version(secret: &Secret) -> Version {
+    let key = version_key(
+        SECRET,
+        secret.metadata.namespace.as_deref().unwrap_or_default(),
+        secret.metadata.name.as_deref().unwrap_or_default()
+    );
+    let data = secret.data.iter().flatten().map(|(k, v)| (k.as_str(), v.0.as_slice()));
+    (key, data_hash(data))
+}
+
+/*
+This function maps a watch event to the versions of its objects.
+*/
+fn event_versions<K>(event: Event<K>, version: fn(&K) -> Version) -> Event<Version> {
+    match event {
+        Event::Applied(object) => Event::Applied(version(&object)),
+        Event::Deleted(object) => Event::Deleted(version(&object)),
+        Event::Restarted(objects) => Event::Restarted(objects.iter().map(version).collect()),
+    }
+}
+
+/*
+This function returns the keys of the ConfigMaps and Secrets
+an RTResource references (in the namespace of its pods).
+*/
+fn restart_on_keys(rtresource: &RTResource) -> Vec<String> {
+    let Some(restart_on) = rtresource.spec.restart_on.as_ref() else {
+        return Vec::new();
+    };
+    let namespace = &rtresource.spec.namespace;
+    let config_maps = restart_on.config_map_refs.iter().flatten().map(|name| version_key(CONFIG_MAP, namespace, name));
+    let secrets = restart_on.secret_refs.iter().flatten().map(|name| version_key(SECRET, namespace, name));
+    config_maps.chain(secrets).collect()
+}
+
+/*
+This function records the versions of a watch event. It returns
+the keys of the referenced objects that changed, none while the
+objects of the kind are listed for the first time.
+*/
+fn record_versions(state: SharedStatePtr, kind: &'static str, event: Event<Version>, synced: &mut Vec<&'static str>) -> Vec<String> {
+    let shared_state = unsafe { &*state.0 };
+    let was_synced = synced.contains(&kind);
+    let mut changed = Vec::new();
+    match event {
+        Event::Applied((key, hash)) => {
+            let mut versions = shared_state.config_versions.lock().unwrap();
+            if versions.hashes.insert(key.clone(), hash.clone()) != Some(hash) {
+                changed.push(key);
+            }
+        }
+        Event::Deleted((key, _)) => {
+            let mut versions = shared_state.config_versions.lock().unwrap();
+            if versions.hashes.remove(&key).is_some() {
+                changed.push(key);
+            }
+        }
+        Event::Restarted(listed) => {
+            let listed: HashMap<String, String> = listed.into_iter().collect();
+            let prefix = format!("{}/", kind);
+            if !synced.contains(&kind) {
+                synced.push(kind);
+            }
+            let mut versions = shared_state.config_versions.lock().unwrap();
+            versions.hashes.retain(|key, _| {
+                let kept = !key.starts_with(&prefix) || listed.contains_key(key);
+                if !kept {
+                    changed.push(key.clone());
+                }
+                kept
+            });
+            for (key, hash) in listed {
+                if versions.hashes.insert(key.clone(), hash.clone()) != Some(hash) {
+                    changed.push(key);
+                }
+            }
+            versions.synced = WATCHED_KINDS.iter().all(|k| synced.contains(k));
+        }
+    }
+    if !was_synced {
+        return Vec::new();
+    }
+    let versions = shared_state.config_versions.lock().unwrap();
+    changed.retain(|key| versions.references.values().any(|keys| keys.contains(key)));
+    changed
+}
+
+/*
+This function records the keys an RTResource references.
+*/
+pub fn track_references(shared_state: &SharedState, rtresource: &RTResource) {
+    let Some(uid) = rtresource.metadata.uid.clone() else {
+        return;
+    };
+    let keys = restart_on_keys(rtresource);
+    let mut versions = shared_state.config_versions.lock().unwrap();
+    if keys.is_empty() {
+        versions.references.remove(&uid);
+    } else {
+        versions.references.insert(uid, keys);
+    }
+}
+
+/*
+This function forgets the keys a deleted RTResource referenced.
+*/
+pub fn forget_references(shared_state: &SharedState, uid: &str) {
+    shared_state.config_versions.lock().unwrap().references.remove(uid);
+}
+
+/*
+This function rebuilds the referenced keys from the
+listed RTResources, when the CRD watcher relists them.
+*/
+pub fn reset_references(shared_state: &SharedState, rtresources: &[RTResource]) {
+    let references: HashMap<String, Vec<String>> = rtresources.iter()
+        .filter_map(|r| Some((r.metadata.uid.clone()?, restart_on_keys(r))))
+        .filter(|(_, keys)| !keys.is_empty())
+        .collect();
+    shared_state.config_versions.lock().unwrap().references = references;
+}
+
+/*
+This function enqueues the RTResources referencing
+the changed ConfigMaps and Secrets.
+*/
+async fn enqueue_referencing(client: Client, config: &ControllerConfig, queue: &CString, changed: &[String]) -> Result<(), kube::Error> {
+    let rtresources = Api::<RTResource>::all(client).list(&ListParams::default()).await?.items;
+    for r in rtresources.iter().filter(|r| r.metadata.deletion_timestamp.is_none()) {
+        let Some(key) = restart_on_keys(r).into_iter().find(|k| changed.contains(k)) else {
+            continue;
+        };
+        let (Some(name), Some(uid), Some(namespace)) = (r.metadata.name.clone(), r.metadata.uid.clone(), r.metadata.namespace.clone()) else {
+            continue;
+        };
+        println!("Config Watcher - {} changed, rolling the pods of RTResource {}, {} in namespace {}!", key, name, uid, namespace);
+        let criticality = effective_criticality(config, &namespace, r.spec.criticality);
+        enqueue_once("Config Watcher", queue, &QueueMessage {name, uid, namespace, enqueued_at: 0, kind: EventKind::Resync, trace_id: String::new()}, criticality);
+    }
+    Ok(())
+}
+
+/*
+This function keeps the versions of the ConfigMaps and Secrets
+up to date and enqueues the RTResources referencing the changed ones.
+*/
+pub async fn config_watcher(client: Client, state: SharedStatePtr) {
+    let config = unsafe { (*state.0).config.clone() };
+    let queue = CString::new(config.event_queue_path.clone()).unwrap();
+    let config_maps = watcher(Api::<ConfigMap>::all(client.clone()), Config::default())
+        .map(|event| (CONFIG_MAP, event.map(|e| event_versions(e, config_map_version))));
+    let secrets = watcher(Api::<Secret>::all(client.clone()), Config::default())
+        .map(|event| (SECRET, event.map(|e| event_versions(e, secret_version))));
+    let mut events = stream::select(config_maps.boxed(), secrets.boxed());
+    let mut synced: Vec<&'static str> = Vec::new();
+    while let Some((kind, event)) = events.next().await {
+        let event = match event {
+            Ok(event) => event,
+            Err(e) => {
+                eprintln!("Config Watcher - {}", e);
+                continue;
+            }
+        };
+        let changed = record_versions(state, kind, event, &mut synced);
+        if changed.is_empty() {
+            continue;
+        }
+        if let Err(e) = enqueue_referencing(client.clone(), &config, &queue, &changed).await {
+            eprintln!("Config Watcher - An error occurred while listing the RTResources: {}", e);
+        }
+    }
+}
+
+/*
+This function returns the hash of the ConfigMaps and Secrets
+referenced by an RTResource, None without spec.restartOn or
+before the objects were listed.
+*/
+pub fn restart_hash(state: SharedStatePtr, rtresource: &RTResource) -> Option<String> {
+    let keys = restart_on_keys(rtresource);
+    if keys.is_empty() {
+        return None;
+    }
+    let shared_state = unsafe { &*state.0 };
+    let versions = shared_state.config_versions.lock().unwrap();
+    versions.synced.then(|| {
+        let hashes: Vec<&str> = keys.iter()
+            .map(|k| versions.hashes.get(k).map_or("missing", String::as_str))
+            .collect();
+        data_hash(keys.iter().map(String::as_str).zip(hashes.iter().map(|v| v.as_bytes())))
+    })
+}
+
+/*
+This function returns whether a pod was started with
+other versions of the referenced ConfigMaps and Secrets.
+*/
+pub fn has_stale_config(pod: &Pod, hash: &str) -> bool {
+    pod.metadata.annotations.as_ref()
+        .and_then(|a| a.get(CONFIG_HASH_ANNOTATION))
+        .map(String::as_str) != Some(hash)
+}
@@ -0,0 +1,125 @@
+/*
+This file contains the mutual exclusion constraints between
+RTResources (spec.conflictsWith), e.g. between redundant control
+channels that must survive the loss of any single node.
+Two RTResources conflict when either one lists the other: their
+pods must never be co-located on the same node. The constraint is
+enforced:
+    - with kube-scheduler, through a required pod anti-affinity term
+      injected into the pods (also honoured for the pods of the
+      conflicting RTResource, since it is checked on existing pods too);
+    - with the built-in scheduler, by the ConflictExclusion filter plugin,
+      fed with the nodes running pods of the conflicting RTResources.
+*/
+
+use kube::{
+    Api,
+    Client,
+    api::ListParams
+};
+use k8s_openapi::{
+    api::core::v1::{
+        Affinity,
+        Pod,
+        PodAffinityTerm,
+        PodAntiAffinity,
+        PodSpec
+    },
+    apimachinery::pkg::apis::meta::v1::{
+        LabelSelector,
+        LabelSelectorRequirement
+    }
+};
+
+use crate::utils::rtresource::RTResource;
+use crate::utils::owner_labels::owner_labels;
+
+
+
+/*
+Topology of the mutual exclusion constraints
+*/
+const CONFLICT_TOPOLOGY_KEY: &str = "kubernetes.io/hostname";
+
+/*
+This function adds the anti-affinity term keeping the pods of an
+RTResource away from the pods of the RTResources it conflicts with.
+*/
+pub fn add_conflict_anti_affinity(spec: &mut PodSpec, rtresource: &RTResource) {
+    let names: Vec<String> = rtresource.spec.conflicts_with.iter().flatten()
+        .filter(|name| Some(*name) != rtresource.metadata.name.as_ref())
+        .cloned()
+        .collect();
+    if names.is_empty() {
+        return;
+    }
+    let term = PodAffinityTerm {
+        label_selector: Some(LabelSelector {
+            match_expressions: Some(vec![
+                LabelSelectorRequirement {
+                    key: owner_labels().name.clone(),
+                    operator: "In".to_string(),
+                    values: Some(names),
+                },
+                LabelSelectorRequirement {
+                    key: owner_labels().namespace.clone(),
+                    operator: "In".to_string(),
+                    values: Some(vec![rtresource.metadata.namespace.clone().unwrap_or_default()]),
+                },
+            ]),
+            ..Default::default()
+        }),
+        /*
+        The pods of the conflicting RTResources
+        may be deployed in any namespace.
+        */
+        namespace_selector: Some(LabelSelector::default()),
+        topology_key: CONFLICT_TOPOLOGY_KEY.to_string(),
+        ..Default::default()
+    };
+    spec.affinity
+        .get_or_insert_with(Affinity::default)
+        .pod_anti_affinity
+        .get_or_insert_with(PodAntiAffinity::default)
+        .required_during_scheduling_ignored_during_execution
+        .get_or_insert_with(Vec::new)
+        .push(term);
+}
+
+/*
+This function returns the RTResources conflicting with an RTResource.
+*/
+pub async fn conflicting_rtresources(client: Client, rtresource: &RTResource) -> Result<Vec<RTResource>, kube::Error> {
+    let namespace = rtresource.metadata.namespace.clone().unwrap_or_default();
+    let rtresources = Api::<RTResource>::namespaced(client, &namespace).list(&ListParams::default()).await?.items;
+    Ok(rtresources.into_iter().filter(|r| rtresource.conflicts(r)).collect())
+}
+
+/*
+This function returns the nodes running pods of
+the RTResources conflicting with an RTResource.
+*/
+pub async fn conflicting_nodes(client: Client, rtresource: &RTResource) -> Result<Vec<String>, kube::Error> {
+    let names: Vec<String> = conflicting_rtresources(client.clone(), rtresource).await?
+        .into_iter()
+        .filter_map(|r| r.metadata.name)
+        .collect();
+    if names.is_empty() {
+        return Ok(Vec::new());
+    }
+    let selector = format!(
+        "{}={},{} in ({})",
+        owner_labels().namespace,
+        rtresource.metadata.namespace.clone().unwrap_or_default(),
+        owner_labels().name,
+        names.join(",")
+    );
+    let pods = Api::<Pod>::all(client).list(&ListParams::default().labels(&selector)).await?.items;
+    let mut nodes: Vec<String> = pods.into_iter()
+        .filter(|p| p.metadata.deletion_timestamp.is_none())
+        .filter_map(|p| p.spec.and_then(|s| s.node_name))
+        .collect();
+    nodes.sort();
+    nodes.dedup();
+    Ok(nodes)
+}
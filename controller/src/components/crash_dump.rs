@@ -0,0 +1,270 @@
+/*
+This file contains the crash dump of the controller.
+On a panic, or on a fatal error of a controller thread, the state
+of the dispatcher is written to a crash file (CRASH_DUMP_DIR) before
+the process exits, so that a crash can be diagnosed without
+reconstructing the state from the API server history:
+    - the events being handled by the watchdogs, which also act as
+      the per-RTResource locks (one watchdog per RTResource at a time);
+    - the events waiting in the ready queue;
+    - the watchdog slots;
+    - the reservation ledger and the placement map of the capacity index.
+The shared mutex may be held by the panicking thread itself: if it
+cannot be taken within CRASH_LOCK_WAIT, the state is read anyway and
+the dump is marked as possibly inconsistent.
+The crash files are inspected with --inspect-crash=<file> (--json
+prints the file as is).
+*/
+
+use std::{
+    error::Error,
+    fs,
+    panic,
+    path::Path,
+    process::exit,
+    ptr,
+    sync::atomic::{
+        AtomicPtr,
+        Ordering
+    },
+    thread,
+    time::Duration
+};
+use libc::{
+    pthread_mutex_trylock,
+    pthread_mutex_unlock
+};
+use chrono::Utc;
+use serde::{
+    Deserialize,
+    Serialize
+};
+
+use crate::utils::vars::{
+    SharedState,
+    SharedStatePtr
+};
+use crate::components::capacity_index::LedgerEntry;
+use crate::components::info_publisher::instance_identity;
+
+
+
+/*
+Version of the crash dump format
+*/
+const CRASH_DUMP_VERSION: u32 = 1;
+
+/*
+Longest wait for the shared mutex before reading the state anyway
+*/
+const CRASH_LOCK_WAIT: Duration = Duration::from_millis(100);
+
+/*
+Shared state dumped on a crash
+*/
+static CRASH_STATE: AtomicPtr<SharedState> = AtomicPtr::new(ptr::null_mut());
+
+/*
+Event being handled by a watchdog
+*/
+#[derive(Serialize, Deserialize, Debug)]
+pub struct InFlightEvent {
+    pub thread: u64,
+    pub name: String,
+    pub uid: String,
+    pub namespace: String,
+    pub criticality: Option<u32>,
+    pub borrowed: bool,
+    #[serde(rename = "handlingForMs")]
+    pub handling_for_ms: u64,
+}
+
+/*
+Event waiting in the ready queue
+*/
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WaitingEvent {
+    pub name: String,
+    pub uid: String,
+    pub namespace: String,
+    pub criticality: u32,
+    #[serde(rename = "waitingForMs")]
+    pub waiting_for_ms: u64,
+}
+
+/*
+Crash dump
+*/
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CrashDump {
+    pub version: u32,
+    pub time: String,
+    pub controller: String,
+    #[serde(rename = "controllerVersion")]
+    pub controller_version: String,
+    pub reason: String,
+    /*
+    Whether the state was read holding the shared mutex
+    */
+    pub consistent: bool,
+    #[serde(rename = "activeWatchdogs")]
+    pub active_watchdogs: usize,
+    #[serde(rename = "workingWatchdogs")]
+    pub working_watchdogs: usize,
+    #[serde(rename = "inFlight")]
+    pub in_flight: Vec<InFlightEvent>,
+    pub waiting: Vec<WaitingEvent>,
+    pub reservations: Vec<LedgerEntry>,
+    pub placements: Vec<LedgerEntry>,
+}
+
+/*
+This function captures the crash dump of the shared state.
+*/
+fn capture(shared_state: &mut SharedState, reason: &str) -> CrashDump {
+    let mut locked = false;
+    for _ in 0..10 {
+        if unsafe { pthread_mutex_trylock(&mut shared_state.mutex) } == 0 {
+            locked = true;
+            break;
+        }
+        thread::sleep(CRASH_LOCK_WAIT / 10);
+    }
+    let in_flight = shared_state.handling.iter()
+        .map(|(thread, (msg, since))| {
+            let worker = shared_state.workers.iter().find(|w| w.id == *thread);
+            InFlightEvent {
+                thread: *thread,
+                name: msg.name.clone(),
+                uid: msg.uid.clone(),
+                namespace: msg.namespace.clone(),
+                criticality: worker.and_then(|w| w.criticality),
+                borrowed: worker.is_some_and(|w| w.borrowed),
+                handling_for_ms: since.elapsed().as_millis() as u64,
+            }
+        })
+        .collect();
+    let waiting = shared_state.ready.iter()
+        .map(|e| WaitingEvent {
+            name: e.msg.name.clone(),
+            uid: e.msg.uid.clone(),
+            namespace: e.msg.namespace.clone(),
+            criticality: e.criticality,
+            waiting_for_ms: e.received_at.elapsed().as_millis() as u64,
+        })
+        .collect();
+    let dump = CrashDump {
+        version: CRASH_DUMP_VERSION,
+        time: Utc::now().to_rfc3339(),
+        controller: instance_identity(),
+        controller_version: env!("CARGO_PKG_VERSION").to_string(),
+        reason: reason.to_string(),
+        consistent: locked,
+        active_watchdogs: shared_state.active_threads,
+        working_watchdogs: shared_state.working_threads,
+        in_flight,
+        waiting,
+        reservations: shared_state.capacity.reservation_entries(),
+        placements: shared_state.capacity.placement_entries(),
+    };
+    if locked {
+        unsafe { pthread_mutex_unlock(&mut shared_state.mutex) };
+    }
+    dump
+}
+
+/*
+This function writes the crash dump of the shared state, if enabled.
+*/
+fn write_crash_dump(shared_state: &mut SharedState, reason: &str) {
+    let dir = shared_state.config.crash_dump_dir.clone();
+    if dir.is_empty() {
+        return;
+    }
+    let dump = capture(shared_state, reason);
+    let path = Path::new(&dir).join(format!("preempt-k8s-crash-{}.json", Utc::now().timestamp_millis()));
+    let written = serde_json::to_vec_pretty(&dump)
+        .map_err(|e| e.to_string())
+        .and_then(|content| fs::write(&path, content).map_err(|e| e.to_string()));
+    match written {
+        Ok(()) => eprintln!("Crash Dump - The controller state was written to {}!", path.display()),
+        Err(e) => eprintln!("Crash Dump - An error occurred while writing {}: {}", path.display(), e),
+    }
+}
+
+/*
+This function installs the panic hook writing the crash dump,
+before the default hook reports the panic.
+*/
+pub fn install_crash_handler(state: SharedStatePtr) {
+    CRASH_STATE.store(state.0, Ordering::SeqCst);
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let shared_state = CRASH_STATE.load(Ordering::SeqCst);
+        if !shared_state.is_null() {
+            let location = info.location().map(|l| format!(" at {}:{}", l.file(), l.line())).unwrap_or_default();
+            let message = info.payload().downcast_ref::<&str>().map(|s| s.to_string())
+                .or_else(|| info.payload().downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            let thread = thread::current().name().unwrap_or("unnamed").to_string();
+            write_crash_dump(unsafe { &mut *shared_state }, &format!("panic in thread {}{}: {}", thread, location, message));
+        }
+        default_hook(info);
+    }));
+}
+
+/*
+This function writes the crash dump on a fatal error
+of a controller thread, then exits.
+*/
+pub fn crash_exit(shared_state: &mut SharedState, reason: &str) -> ! {
+    write_crash_dump(shared_state, reason);
+    exit(-1);
+}
+
+/*
+This function prints a crash file.
+*/
+pub fn inspect_crash(path: &str, json: bool) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    let dump: CrashDump = serde_json::from_str(&fs::read_to_string(path)?)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&dump)?);
+        return Ok(());
+    }
+    println!("Crash of {} (version {}) at {}", dump.controller, dump.controller_version, dump.time);
+    println!("Reason: {}", dump.reason);
+    if !dump.consistent {
+        println!("WARNING: the state was read without the shared mutex and may be inconsistent");
+    }
+    println!("Watchdogs: {} active, {} working", dump.active_watchdogs, dump.working_watchdogs);
+    println!("\nIn-flight events (RTResources locked by a watchdog):");
+    for e in dump.in_flight.iter() {
+        println!(
+            "  {}/{} ({}) criticality {} on thread {}{} for {} ms",
+            e.namespace,
+            e.name,
+            e.uid,
+            e.criticality.map_or("-".to_string(), |c| c.to_string()),
+            e.thread,
+            if e.borrowed { " (borrowed)" } else { "" },
+            e.handling_for_ms
+        );
+    }
+    println!("\nWaiting events:");
+    for e in dump.waiting.iter() {
+        println!("  {}/{} ({}) criticality {} for {} ms", e.namespace, e.name, e.uid, e.criticality, e.waiting_for_ms);
+    }
+    println!("\nReservations:");
+    for r in dump.reservations.iter() {
+        println!(
+            "  {} replica {} on {} ({}m CPU, {} bytes)",
+            r.rtresource_uid.as_deref().unwrap_or("-"),
+            r.ordinal.map_or("-".to_string(), |o| o.to_string()),
+            r.node,
+            r.cpu_millis,
+            r.memory_bytes
+        );
+    }
+    println!("\nPlacements: {} pods bound", dump.placements.len());
+    Ok(())
+}
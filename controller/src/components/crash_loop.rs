@@ -0,0 +1,207 @@
+/*
+This file contains the stability gate of the RTResources
+(spec.failureThreshold).
+The pod watcher records each crash of a managed pod (a container
+restart or the pod failing). Once the pods of an RTResource crashed
+failureThreshold times within CRASH_LOOP_WINDOW_MS, the watchdogs
+stop recreating its replicas and set the "CrashLooping" condition,
+so that a broken critical image does not burn RT capacity forever.
+The gate stays closed until an operator annotates the RTResource
+with the resume annotation: the crash history is then forgotten and
+the annotation removed by the controller.
+*/
+
+use std::{
+    collections::HashMap,
+    time::{
+        Duration,
+        Instant
+    }
+};
+use libc::{
+    pthread_mutex_lock,
+    pthread_mutex_unlock
+};
+use kube::{
+    Api,
+    Client,
+    api::{
+        Patch,
+        PatchParams
+    }
+};
+use k8s_openapi::api::core::v1::Pod;
+
+use crate::utils::vars::{
+    SharedState,
+    SharedStatePtr
+};
+use crate::utils::rtresource::{
+    RTResource,
+    RTResourceStatus
+};
+use crate::utils::owner_labels::owner_labels;
+
+
+
+/*
+Condition set on the RTResources whose replicas are no longer recreated
+*/
+pub const CRASH_LOOP_CONDITION: &str = "CrashLooping";
+
+/*
+Annotation resuming the recreation of the replicas of an RTResource
+*/
+pub const RESUME_ANNOTATION: &str = "rtgroup.critical.com/resume-after-crash-loop";
+
+/*
+This function returns the crashes of a pod so far:
+the restarts of its containers, plus one if it failed.
+*/
+fn pod_crashes(pod: &Pod) -> u32 {
+    let Some(status) = pod.status.as_ref() else {
+        return 0;
+    };
+    let restarts: i32 = status.container_statuses.iter().flatten().map(|c| c.restart_count).sum();
+    restarts.max(0) as u32 + u32::from(status.phase.as_deref() == Some("Failed"))
+}
+
+/*
+This function records the new crashes of a managed pod, comparing
+with the crashes seen for the pod so far (the first time a pod
+is seen its crashes are only recorded).
+It must be called without holding the shared mutex.
+*/
+pub fn record_pod_crashes(shared_state: &mut SharedState, seen: &mut HashMap<String, u32>, pod: &Pod) {
+    let (Some(pod_uid), Some(uid)) = (
+        pod.metadata.uid.as_ref(),
+        pod.metadata.labels.as_ref().and_then(|l| l.get(&owner_labels().uid))
+    ) else {
+        return;
+    };
+    let crashes = pod_crashes(pod);
+    let new = match seen.insert(pod_uid.clone(), crashes) {
+        Some(previous) => crashes.saturating_sub(previous),
+        None => 0,
+    };
+    if new == 0 {
+        return;
+    }
+    println!(
+        "Pod Watcher - Pod {} of RTResource {} crashed {} more times.",
+        pod.metadata.name.clone().unwrap_or_default(),
+        uid,
+        new
+    );
+    unsafe {
+        pthread_mutex_lock(&mut shared_state.mutex);
+        let window = Duration::from_millis(shared_state.config.crash_loop_window_ms);
+        let history = shared_state.pod_crashes.entry(uid.clone()).or_default();
+        history.retain(|at| at.elapsed() < window);
+        history.extend((0..new).map(|_| Instant::now()));
+        pthread_mutex_unlock(&mut shared_state.mutex);
+    }
+}
+
+/*
+This function returns the crashes of the pods of an RTResource
+within the window, forgetting older ones.
+It must be called without holding the shared mutex.
+*/
+pub fn recent_pod_crashes(shared_state: &mut SharedState, uid: &str) -> usize {
+    unsafe {
+        pthread_mutex_lock(&mut shared_state.mutex);
+        let window = Duration::from_millis(shared_state.config.crash_loop_window_ms);
+        let mut crashes = 0;
+        if let Some(history) = shared_state.pod_crashes.get_mut(uid) {
+            history.retain(|at| at.elapsed() < window);
+            crashes = history.len();
+            if history.is_empty() {
+                shared_state.pod_crashes.remove(uid);
+            }
+        }
+        pthread_mutex_unlock(&mut shared_state.mutex);
+
+        crashes
+    }
+}
+
+/*
+This function forgets the crashes of the pods of an RTResource.
+It must be called without holding the shared mutex.
+*/
+pub fn forget_pod_crashes(state: SharedStatePtr, uid: &str) {
+    let shared_state = unsafe { &mut *state.0 };
+    unsafe {
+        pthread_mutex_lock(&mut shared_state.mutex);
+        shared_state.pod_crashes.remove(uid);
+        pthread_mutex_unlock(&mut shared_state.mutex);
+    }
+}
+
+/*
+This function returns whether an operator asked
+to resume the recreation of the replicas.
+*/
+pub fn resume_requested(rtresource: &RTResource) -> bool {
+    rtresource.metadata.annotations.as_ref().is_some_and(|a| a.contains_key(RESUME_ANNOTATION))
+}
+
+/*
+Outcome of the stability gate
+*/
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CrashLoopGate {
+    Open,       // Replicas are recreated
+    Closed,     // Replicas are no longer recreated
+    Resumed,    // An operator reopened the gate
+}
+
+/*
+This function evaluates the stability gate of an RTResource from its
+recent crashes, updating the CrashLooping condition of its status.
+*/
+pub fn crash_loop_gate(rtresource: &RTResource, recent_crashes: usize, status: &mut RTResourceStatus) -> CrashLoopGate {
+    let looping = status.conditions.iter().flatten()
+        .any(|c| c.condition_type == CRASH_LOOP_CONDITION && c.status == "True");
+    if resume_requested(rtresource) {
+        if looping {
+            status.set_condition(CRASH_LOOP_CONDITION, "False", "Resumed", "The recreation of the replicas was resumed by an operator");
+        }
+        return CrashLoopGate::Resumed;
+    }
+    if looping {
+        return CrashLoopGate::Closed;
+    }
+    match rtresource.spec.failure_threshold {
+        Some(threshold) if threshold > 0 && recent_crashes >= threshold as usize => {
+            status.set_condition(
+                CRASH_LOOP_CONDITION,
+                "True",
+                "FailureThresholdExceeded",
+                &format!(
+                    "The pods crashed {} times (failureThreshold {}), annotate with {} to resume",
+                    recent_crashes,
+                    threshold,
+                    RESUME_ANNOTATION
+                )
+            );
+            CrashLoopGate::Closed
+        }
+        _ => CrashLoopGate::Open,
+    }
+}
+
+/*
+This function removes the resume annotation of an RTResource.
+*/
+pub async fn clear_resume_annotation(client: Client, rtresource: &RTResource) -> Result<(), kube::Error> {
+    let api: Api<RTResource> = Api::namespaced(client, rtresource.metadata.namespace.as_deref().unwrap_or_default());
+    let patch = serde_json::json!({"metadata": {"annotations": {RESUME_ANNOTATION: null}}});
+    api.patch(
+        rtresource.metadata.name.as_deref().unwrap_or_default(),
+        &PatchParams::default(),
+        &Patch::Merge(&patch)
+    ).await?;
+    Ok(())
+}
@@ -0,0 +1,82 @@
+/*
+This file contains the tracking of the criticality classes
+(spec.criticalityClass) of the RTResources. The events only carry
+the UID of their RTResource, so the RTResource watcher records the
+Soft RTResources for the dispatcher and the watchdogs:
+    - the events of Soft RTResources are ordered by criticality
+      in the queue, but are handled on the general watchdog pool
+      and never take the slots reserved to the top band;
+    - the Soft pods never tolerate the dedicated RT nodes, and get
+      the non-preempting PriorityClasses of the Soft class, below
+      the Hard ones, so that they never preempt and are the first
+      victims of kube-scheduler.
+*/
+
+use libc::{
+    pthread_mutex_lock,
+    pthread_mutex_unlock
+};
+
+use crate::utils::vars::SharedState;
+use crate::utils::rtresource::RTResource;
+
+
+
+/*
+This function records the criticality class of an RTResource.
+It must be called without holding the shared mutex.
+*/
+pub fn track_criticality_class(shared_state: &mut SharedState, rtresource: &RTResource) {
+    let Some(uid) = rtresource.metadata.uid.clone() else {
+        return;
+    };
+    unsafe {
+        pthread_mutex_lock(&mut shared_state.mutex);
+        if rtresource.spec.is_soft() {
+            shared_state.soft_resources.insert(uid);
+        } else {
+            shared_state.soft_resources.remove(&uid);
+        }
+        pthread_mutex_unlock(&mut shared_state.mutex);
+    }
+}
+
+/*
+This function replaces the criticality classes after a relist.
+It must be called without holding the shared mutex.
+*/
+pub fn reset_criticality_classes(shared_state: &mut SharedState, rtresources: &[RTResource]) {
+    unsafe {
+        pthread_mutex_lock(&mut shared_state.mutex);
+        shared_state.soft_resources = rtresources.iter()
+            .filter(|r| r.spec.is_soft())
+            .filter_map(|r| r.metadata.uid.clone())
+            .collect();
+        pthread_mutex_unlock(&mut shared_state.mutex);
+    }
+}
+
+/*
+This function forgets the criticality class of a deleted RTResource.
+It must be called without holding the shared mutex.
+*/
+pub fn forget_criticality_class(shared_state: &mut SharedState, uid: &str) {
+    unsafe {
+        pthread_mutex_lock(&mut shared_state.mutex);
+        shared_state.soft_resources.remove(uid);
+        pthread_mutex_unlock(&mut shared_state.mutex);
+    }
+}
+
+/*
+This function returns whether an RTResource is Soft.
+It must be called without holding the shared mutex.
+*/
+pub fn is_soft_resource(shared_state: &mut SharedState, uid: &str) -> bool {
+    unsafe {
+        pthread_mutex_lock(&mut shared_state.mutex);
+        let soft = shared_state.soft_resources.contains(uid);
+        pthread_mutex_unlock(&mut shared_state.mutex);
+        soft
+    }
+}
@@ -0,0 +1,90 @@
+/*
+This file contains the per-namespace criticality policy.
+RTResources created without spec.criticality inherit the default
+criticality of their namespace, from the namespace annotation
+"rtgroup.critical.com/default-criticality" (or the least critical
+level of the scale when the annotation is missing): the controller
+writes it into the spec, then handles the resulting event.
+The most critical level each namespace may use is capped by the
+NAMESPACE_CRITICALITY_CAPS policy (see effective_criticality),
+so that tenants cannot outrank each other.
+*/
+
+use kube::{
+    Api,
+    Client,
+    api::{
+        Patch,
+        PatchParams
+    }
+};
+use k8s_openapi::api::core::v1::Namespace;
+
+use crate::utils::rtresource::RTResource;
+use crate::utils::configuration::ControllerConfig;
+
+
+
+/*
+Namespace annotation holding the default criticality
+of the RTResources of the namespace
+*/
+pub const DEFAULT_CRITICALITY_ANNOTATION: &str = "rtgroup.critical.com/default-criticality";
+
+/*
+This function returns whether the criticality
+of an RTResource was left unset.
+*/
+pub fn criticality_unset(rtresource: &RTResource) -> bool {
+    rtresource.spec.criticality == 0
+}
+
+/*
+This function returns the default criticality
+of the RTResources of a namespace.
+*/
+pub async fn namespace_default(client: Client, config: &ControllerConfig, namespace: &str) -> u32 {
+    let annotation = match Api::<Namespace>::all(client).get(namespace).await {
+        Ok(ns) => ns.metadata.annotations.and_then(|a| a.get(DEFAULT_CRITICALITY_ANNOTATION).cloned()),
+        Err(e) => {
+            eprintln!("CRD Watcher - An error occurred while retrieving namespace {}: {}", namespace, e);
+            None
+        }
+    };
+    match annotation.map(|a| a.trim().parse::<u32>()) {
+        Some(Ok(criticality)) if (1..=config.criticality_max).contains(&criticality) => criticality,
+        Some(_) => {
+            eprintln!("CRD Watcher - Invalid {} annotation on namespace {}, using the least critical level!", DEFAULT_CRITICALITY_ANNOTATION, namespace);
+            least_critical(config)
+        }
+        None => least_critical(config),
+    }
+}
+
+/*
+This function returns the least critical level
+of the spec criticality scale.
+*/
+fn least_critical(config: &ControllerConfig) -> u32 {
+    if config.criticality_inverted { 1 } else { config.criticality_max }
+}
+
+/*
+This function writes the default criticality of its namespace
+into the spec of an RTResource created without one.
+*/
+pub async fn apply_default_criticality(client: Client, config: &ControllerConfig, rtresource: &RTResource) {
+    let (Some(name), Some(namespace)) = (rtresource.metadata.name.as_ref(), rtresource.metadata.namespace.as_ref()) else {
+        return;
+    };
+    let criticality = namespace_default(client.clone(), config, namespace).await;
+    let patch = serde_json::json!({
+        "spec": {
+            "criticality": criticality
+        }
+    });
+    match Api::<RTResource>::namespaced(client, namespace).patch(name, &PatchParams::default(), &Patch::Merge(&patch)).await {
+        Ok(_) => println!("CRD Watcher - RTResource {} in namespace {} defaulted to criticality {}!", name, namespace, criticality),
+        Err(e) => eprintln!("CRD Watcher - An error occurred while defaulting the criticality of RTResource {} in namespace {}: {}", name, namespace, e),
+    }
+}
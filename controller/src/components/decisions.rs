@@ -0,0 +1,79 @@
+/*
+This file contains the functions used in observe mode
+to record the decisions the controller would have taken
+into RTDecision diagnostic resources, instead of acting on pods.
+This allows running a new controller version in shadow
+next to the active one and diffing their decisions.
+*/
+
+use kube::{
+    Api,
+    Client,
+    api::{
+        Patch,
+        PatchParams
+    }
+};
+
+use crate::utils::vars::QueueMessage;
+use crate::utils::rtdecision::{
+    RTDecision,
+    RTDecisionSpec,
+    DecisionLabelUpdate
+};
+use crate::components::planner::Plan;
+
+
+
+/*
+Field manager used for RTDecision server-side applies
+*/
+const DECISION_FIELD_MANAGER: &str = "preempt-k8s-observer";
+
+/*
+This function records the plan computed for an RTResource
+into the RTDecision with the same name and namespace,
+creating or replacing it through a server-side apply.
+*/
+pub async fn record_decision(thread_name: &str, client: Client, rtresource_data: &QueueMessage, generation: Option<i64>, deleted: bool, plan: &Plan) {
+    let decision = RTDecision::new(&rtresource_data.name, RTDecisionSpec {
+        rtresource_name: rtresource_data.name.clone(),
+        rtresource_uid: rtresource_data.uid.clone(),
+        observed_generation: generation,
+        controller_version: env!("CARGO_PKG_VERSION").to_string(),
+        decided_at: chrono::Utc::now().to_rfc3339(),
+        deleted,
+        creates: plan.creates.clone(),
+        deletes: plan.deletes.iter()
+            .filter_map(|p| p.metadata.name.clone())
+            .collect(),
+        updates: plan.updates.iter()
+            .map(|u| DecisionLabelUpdate {
+                pod: u.name.clone(),
+                labels: u.labels.clone(),
+            })
+            .collect(),
+    });
+    let decision_api = Api::<RTDecision>::namespaced(client, &rtresource_data.namespace);
+    match decision_api.patch(
+        &rtresource_data.name,
+        &PatchParams::apply(DECISION_FIELD_MANAGER).force(),
+        &Patch::Apply(&decision)
+    ).await {
+        Ok(_) => println!(
+            "{} - Observe mode: recorded decision for RTResource {}, {} in namespace {}!",
+            thread_name,
+            rtresource_data.name,
+            rtresource_data.uid,
+            rtresource_data.namespace
+        ),
+        Err(e) => eprintln!(
+            "{} - Observe mode: an error occurred while recording the decision for RTResource {}, {} in namespace {}: {}",
+            thread_name,
+            rtresource_data.name,
+            rtresource_data.uid,
+            rtresource_data.namespace,
+            e
+        ),
+    }
+}
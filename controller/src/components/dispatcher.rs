@@ -0,0 +1,411 @@
+/*
+This file contains the dispatcher.
+The dispatcher drains the event priority queue into the ready queue,
+and hands events to the watchdogs enforcing the watchdog slots
+reserved to the top criticality band: at most
+(max_watchdogs - reserved_watchdogs) watchdogs may be handling
+events outside the top band at any time, so that a critical event
+arriving during a burst of less critical ones finds a free watchdog.
+The events of Soft RTResources (spec.criticalityClass) are always
+handled on the general pool, whatever their criticality.
+When the general pool is idle, a bounded number of its watchdogs
+may help drain the top band backlog (work stealing).
+During the startup sequencing, only the events admitted
+by the startup gate are handed to the watchdogs.
+With DISPATCH_MODE=wfq, the events are handed in proportion
+to the weights of their criticality bands rather than most
+critical first (see the ready queue), within the same slots.
+*/
+
+use std::{
+    ptr,
+    time::Instant,
+    os::raw::c_char,
+    ffi::c_void
+};
+use libc::{
+    SCHED_FIFO,
+    pthread_setschedparam,
+    pthread_t,
+    pthread_cond_signal,
+    pthread_cond_broadcast,
+    pthread_mutex_lock,
+    pthread_mutex_unlock,
+    mqd_t,
+    O_RDONLY,
+    mq_close,
+    mq_receive
+};
+
+use crate::utils::vars::{
+    SharedState,
+    SharedStatePtr,
+    QueueMessage
+};
+use crate::utils::ready_queue::{
+    ReadyEvent,
+    band_of
+};
+use crate::utils::configuration::ControllerConfig;
+use crate::utils::priorities::watchdog_priority;
+use crate::utils::metrics::{
+    record_ready_depth,
+    record_dispatch
+};
+use crate::utils::flight_recorder::record_dequeue_event;
+use crate::utils::event_queue::{
+    QUEUE_MESSAGE_SIZE,
+    open_queue
+};
+use crate::utils::event_bus::{
+    PipelineEventType,
+    publish_event
+};
+use crate::utils::lock_metrics::{
+    LockSite,
+    lock_shared,
+    unlock_shared,
+    wait_shared
+};
+use crate::components::circuit_breaker::circuit_open;
+use crate::components::startup_sequencing::admitted;
+use crate::components::crash_dump::crash_exit;
+#[cfg(feature = "chaos")]
+use crate::components::chaos::delay_delivery;
+
+
+
+/*
+This function returns whether a criticality
+belongs to the top criticality band.
+*/
+pub fn in_top_band(shared_state: &SharedState, criticality: u32) -> bool {
+    criticality <= shared_state.config.critical_band_max
+}
+
+/*
+This function returns whether an event more critical than the
+given criticality is waiting for a watchdog.
+It must be called without holding the shared mutex.
+*/
+pub fn more_critical_waiting(state: SharedStatePtr, criticality: u32) -> bool {
+    let shared_state = unsafe { &mut *state.0 };
+    unsafe {
+        pthread_mutex_lock(&mut shared_state.mutex);
+        let waiting = shared_state.ready.any(|c, _| c < criticality);
+        pthread_mutex_unlock(&mut shared_state.mutex);
+        waiting
+    }
+}
+
+/*
+Watchdog slot an event is handled on
+*/
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Slot {
+    General,    // Event outside the top band (or of a Soft RTResource)
+    Critical,   // Top band event on a reserved slot
+    Borrowed,   // Top band event on a slot of the idle general pool
+}
+
+/*
+Busy watchdog slots per pool
+*/
+struct Occupancy {
+    general: usize,
+    critical: usize,
+    borrowed: usize,
+    general_backlog: bool,
+}
+
+/*
+This function counts the busy watchdog slots.
+It must be called holding the shared mutex.
+*/
+fn occupancy(shared_state: &SharedState) -> Occupancy {
+    let mut occupancy = Occupancy {
+        general: 0,
+        critical: 0,
+        borrowed: 0,
+        general_backlog: shared_state.ready.any(|criticality, soft| {
+            admitted(shared_state, criticality) && (!in_top_band(shared_state, criticality) || soft)
+        }),
+    };
+    for worker in shared_state.workers.iter().filter(|w| w.active) {
+        match worker.criticality {
+            Some(_) if worker.borrowed => occupancy.borrowed += 1,
+            Some(_) if worker.soft => occupancy.general += 1,
+            Some(c) if in_top_band(shared_state, c) => occupancy.critical += 1,
+            Some(_) => occupancy.general += 1,
+            None => {}
+        }
+    }
+    occupancy
+}
+
+/*
+This function returns the slot an event can be handled on, if any.
+The general pool (max_watchdogs - reserved_watchdogs slots) handles
+the events outside the top band, the reserved slots the top band ones.
+While no event outside the top band is waiting, up to steal_limit
+free general slots may be borrowed to drain the top band backlog,
+never the reverse.
+Without reserved slots, the top band may use any slot.
+The Soft events only use the general pool.
+*/
+fn dispatch_slot(config: &ControllerConfig, occupancy: &Occupancy, criticality: u32, soft: bool) -> Option<Slot> {
+    let general_slots = config.max_watchdogs
+        .saturating_sub(config.reserved_watchdogs)
+        .max(1);
+    let general_free = occupancy.general + occupancy.borrowed < general_slots;
+    if criticality > config.critical_band_max || soft {
+        return general_free.then_some(Slot::General);
+    }
+    if config.reserved_watchdogs == 0 || occupancy.critical < config.reserved_watchdogs {
+        return Some(Slot::Critical);
+    }
+    (general_free && !occupancy.general_backlog && occupancy.borrowed < config.steal_limit)
+        .then_some(Slot::Borrowed)
+}
+
+/*
+This function returns whether an event can be handed out:
+it is within the startup gate, if any, and has a slot.
+*/
+fn dispatchable(config: &ControllerConfig, occupancy: &Occupancy, gate: Option<u32>, criticality: u32, soft: bool) -> bool {
+    gate.is_none_or(|g| criticality <= g) && dispatch_slot(config, occupancy, criticality, soft).is_some()
+}
+
+/*
+This function blocks the calling watchdog until it is allowed
+to handle an event, then marks it as working on it.
+The watchdog gets the most critical event it can handle
+(the next one of the weighted fair share with DISPATCH_MODE=wfq),
+and is raised to the priority of that event before the mutex is
+released, so that a watchdog borrowed from the general pool does not
+run the critical event at the base priority.
+It must be called without holding the shared mutex.
+*/
+pub fn take_event(shared_state: &mut SharedState, thread: pthread_t) -> ReadyEvent {
+    unsafe {
+        lock_shared(&mut shared_state.mutex, shared_state.config.lock_wait_warn_us, LockSite::WatchdogTake);
+        let (event, slot, soft) = loop {
+            let occupancy = occupancy(shared_state);
+            let config = &shared_state.config;
+            let gate = shared_state.startup_gate;
+            let event = shared_state.ready.pop_first(|criticality, soft| dispatchable(config, &occupancy, gate, criticality, soft));
+            if let Some(event) = event {
+                let soft = event.soft;
+                let slot = dispatch_slot(&shared_state.config, &occupancy, event.criticality, soft).unwrap();
+                break (event, slot, soft);
+            }
+            wait_shared(&mut shared_state.dispatch_cond, &mut shared_state.mutex, LockSite::WatchdogTake);
+        };
+        record_ready_depth(shared_state.ready.len());
+        record_dispatch(band_of(&shared_state.config.wfq_bands, event.criticality));
+        if slot == Slot::Borrowed {
+            println!("Dispatcher - A general watchdog is helping with an event of criticality {}!", event.criticality);
+        }
+        let param = watchdog_priority(event.criticality).sched_param();
+        pthread_setschedparam(thread, SCHED_FIFO, &param);
+
+        /*
+        The event server must be aware that the watchdog
+        is now working on an event, so that it can decide
+        whether to spawn new watchdogs or not.
+        */
+        shared_state.working_threads += 1;
+        if let Some(worker) = shared_state.workers.iter_mut().find(|w| w.id == thread) {
+            worker.criticality = Some(event.criticality);
+            worker.borrowed = slot == Slot::Borrowed;
+            worker.soft = soft;
+            worker.decay = 0;
+        }
+        shared_state.handling.insert(thread, (event.msg.clone(), Instant::now()));
+        publish_event(
+            PipelineEventType::Dispatched,
+            &event.msg,
+            event.criticality,
+            if slot == Slot::Borrowed { "borrowed watchdog".to_string() } else { "watchdog".to_string() }
+        );
+        pthread_cond_signal(&mut shared_state.cond);
+        unlock_shared(&mut shared_state.mutex);
+
+        event
+    }
+}
+
+/*
+This function marks the calling watchdog as no longer working
+on an event, waking up the watchdogs waiting for a slot.
+It must be called holding the shared mutex.
+*/
+pub fn release_event(shared_state: &mut SharedState, thread: pthread_t) {
+    unsafe {
+        shared_state.working_threads -= 1;
+        if let Some(worker) = shared_state.workers.iter_mut().find(|w| w.id == thread) {
+            worker.criticality = None;
+            worker.borrowed = false;
+            worker.soft = false;
+            worker.decay = 0;
+        }
+        shared_state.handling.remove(&thread);
+        pthread_cond_broadcast(&mut shared_state.dispatch_cond);
+    }
+}
+
+pub extern "C" fn dispatcher(thread_data: *mut c_void) -> *mut c_void {
+    unsafe {
+        let shared_state = &mut *(thread_data as *mut SharedState);
+
+        /*
+        We open the priority queue in read-only mode,
+        since this thread only retrieves events from it.
+        */
+        let queue_des: mqd_t = open_queue(&shared_state.queue, O_RDONLY);
+        if queue_des == -1 {
+            eprintln!("Dispatcher - An error occurred while opening the queue!");
+            crash_exit(shared_state, "Dispatcher - The queue could not be opened");
+        }
+
+        /*
+        Each event retrieved from the queue is moved to the
+        ready queue and the waiting watchdogs are woken up.
+        */
+        let mut error_count: usize = 0;
+        loop {
+            let mut msg: [u8; QUEUE_MESSAGE_SIZE as usize] = [0; QUEUE_MESSAGE_SIZE as usize];
+            let mut criticality: u32 = 0;
+            let result = mq_receive(
+                queue_des,
+                msg.as_mut_ptr() as *mut c_char,
+                msg.len(),
+                &mut criticality as *mut u32
+            );
+            if result == -1 {
+                eprintln!("Dispatcher - An error occurred while retrieving a message from the queue!");
+                error_count += 1;
+                if error_count >= 10 {
+                    eprintln!("Dispatcher - Too many errors occurred while retrieving messages! Exiting...");
+                    break;
+                }
+                continue;
+            }
+            error_count = 0;
+            record_dequeue_event(criticality, &msg[..result as usize]);
+            let rtresource_data = match QueueMessage::from_bytes(&msg[..result as usize]) {
+                Ok(data) => data,
+                Err(e) => {
+                    eprintln!("Dispatcher - An error occurred while deserializing the message from the queue: {}", e);
+                    continue;
+                }
+            };
+
+            #[cfg(feature = "chaos")]
+            delay_delivery();
+
+            pthread_mutex_lock(&mut shared_state.mutex);
+            /*
+            The events of an RTResource whose reconcile circuit is open
+            are dropped: the circuit breaker enqueues its retry.
+            */
+            if circuit_open(shared_state, &rtresource_data.uid) {
+                pthread_mutex_unlock(&mut shared_state.mutex);
+                println!("Dispatcher - Dropping the event {} of RTResource {} in backoff!", rtresource_data.trace_id, rtresource_data.uid);
+                continue;
+            }
+            publish_event(PipelineEventType::Enqueued, &rtresource_data, criticality, rtresource_data.kind.to_string());
+            let soft = shared_state.soft_resources.contains(&rtresource_data.uid);
+            shared_state.ready.push(rtresource_data, criticality, soft);
+            record_ready_depth(shared_state.ready.len());
+            pthread_cond_broadcast(&mut shared_state.dispatch_cond);
+            pthread_mutex_unlock(&mut shared_state.mutex);
+        }
+
+        println!("Dispatcher - Something went wrong, no new events will be dispatched! Restart the controller to recover!");
+
+        /*
+        Cleanup phase.
+        */
+        mq_close(queue_des);
+    }
+
+    ptr::null_mut()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::configuration::get_controller_configuration;
+
+    /*
+    4 watchdogs, 2 of them reserved to the top band (criticality <= 10),
+    and at most 1 general slot borrowed by the top band.
+    */
+    fn config() -> ControllerConfig {
+        let mut config = get_controller_configuration();
+        config.max_watchdogs = 4;
+        config.reserved_watchdogs = 2;
+        config.critical_band_max = 10;
+        config.steal_limit = 1;
+        config
+    }
+
+    fn occupancy(general: usize, critical: usize, borrowed: usize, general_backlog: bool) -> Occupancy {
+        Occupancy {
+            general,
+            critical,
+            borrowed,
+            general_backlog,
+        }
+    }
+
+    #[test]
+    fn keeps_general_events_to_the_general_pool() {
+        let config = config();
+        assert_eq!(dispatch_slot(&config, &occupancy(0, 0, 0, false), 20, false), Some(Slot::General));
+        assert_eq!(dispatch_slot(&config, &occupancy(1, 2, 0, false), 20, false), Some(Slot::General));
+        assert_eq!(dispatch_slot(&config, &occupancy(2, 0, 0, false), 20, false), None);
+        assert_eq!(dispatch_slot(&config, &occupancy(1, 0, 1, false), 20, false), None);
+    }
+
+    #[test]
+    fn keeps_soft_events_to_the_general_pool() {
+        let config = config();
+        assert_eq!(dispatch_slot(&config, &occupancy(0, 0, 0, false), 1, true), Some(Slot::General));
+        assert_eq!(dispatch_slot(&config, &occupancy(2, 0, 0, false), 1, true), None);
+    }
+
+    #[test]
+    fn handles_top_band_events_on_the_reserved_slots() {
+        let config = config();
+        assert_eq!(dispatch_slot(&config, &occupancy(2, 0, 0, true), 1, false), Some(Slot::Critical));
+        assert_eq!(dispatch_slot(&config, &occupancy(2, 1, 0, true), 10, false), Some(Slot::Critical));
+    }
+
+    #[test]
+    fn borrows_idle_general_slots_up_to_the_steal_limit() {
+        let config = config();
+        assert_eq!(dispatch_slot(&config, &occupancy(0, 2, 0, false), 1, false), Some(Slot::Borrowed));
+        assert_eq!(dispatch_slot(&config, &occupancy(0, 2, 1, false), 1, false), None);
+        assert_eq!(dispatch_slot(&config, &occupancy(0, 2, 0, true), 1, false), None);
+        assert_eq!(dispatch_slot(&config, &occupancy(2, 2, 0, false), 1, false), None);
+    }
+
+    #[test]
+    fn lets_the_top_band_use_any_slot_without_reservation() {
+        let mut config = config();
+        config.reserved_watchdogs = 0;
+        assert_eq!(dispatch_slot(&config, &occupancy(3, 1, 0, true), 1, false), Some(Slot::Critical));
+    }
+
+    #[test]
+    fn holds_events_beyond_the_startup_gate() {
+        let config = config();
+        let idle = occupancy(0, 0, 0, false);
+        assert!(dispatchable(&config, &idle, None, 20, false));
+        assert!(dispatchable(&config, &idle, Some(5), 5, false));
+        assert!(!dispatchable(&config, &idle, Some(5), 6, false));
+        assert!(!dispatchable(&config, &occupancy(2, 0, 0, false), Some(30), 20, false));
+    }
+}
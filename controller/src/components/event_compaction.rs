@@ -0,0 +1,222 @@
+/*
+This file contains the compaction of the RTResource events at the
+CRD watcher (EVENT_COMPACTION_QUIET_MS).
+A user scaling an RTResource step by step (e.g. 3 -> 4 -> 5 replicas)
+produces one spec event per step, each of them running a full
+reconcile. A change of the replica count only is therefore held for
+a quiet period, restarted by each further change of the replica count
+(the updates leaving it unchanged, e.g. of the status, do not restart
+it), and a single event is enqueued for the final state once it
+elapses. An event is never held longer than MAX_HOLD_PERIODS quiet
+periods in total, so that a user scaling on and on still gets served.
+Any other change (or deletion) is enqueued at once and supersedes
+the held event, and the top criticality band is never held.
+*/
+
+use std::{
+    collections::HashMap,
+    time::{
+        Duration,
+        Instant
+    }
+};
+
+use crate::utils::vars::QueueMessage;
+use crate::utils::rtresource::RTResource;
+use crate::utils::metrics::record_compaction;
+
+
+
+/*
+Maximum number of quiet periods an event is held for in total
+*/
+const MAX_HOLD_PERIODS: u32 = 4;
+
+/*
+Event held until the end of its quiet period
+*/
+struct HeldEvent {
+    msg: QueueMessage,
+    criticality: u32,
+    /*
+    Replica count the event was last held for
+    */
+    replicas: Option<i32>,
+    held_since: Instant,
+    due: Instant,
+}
+
+/*
+Compaction state of the CRD watcher
+*/
+pub struct EventCompactor {
+    quiet: Duration,
+    /*
+    Last seen spec of each RTResource, without the replica count
+    */
+    specs: HashMap<String, serde_json::Value>,
+    held: HashMap<String, HeldEvent>,
+}
+
+impl EventCompactor {
+    pub fn new(quiet_ms: u64) -> Self {
+        EventCompactor {
+            quiet: Duration::from_millis(quiet_ms),
+            specs: HashMap::new(),
+            held: HashMap::new(),
+        }
+    }
+
+    /*
+    This function records the spec of an applied RTResource and
+    returns whether only its replica count changed since the last one.
+    */
+    pub fn replicas_only(&mut self, uid: &str, object: &RTResource) -> bool {
+        let mut spec = serde_json::to_value(&object.spec).unwrap_or_default();
+        if let Some(fields) = spec.as_object_mut() {
+            fields.remove("replicas");
+        }
+        self.specs.insert(uid.to_string(), spec.clone()).is_some_and(|previous| previous == spec)
+    }
+
+    /*
+    This function holds an event for the quiet period, merging it
+    with the event already held for the same RTResource: the quiet
+    period restarts only if the replica count changed, and ends
+    MAX_HOLD_PERIODS quiet periods after the first event at the latest.
+    It returns false when compaction is disabled (the event
+    must then be enqueued at once).
+    */
+    pub fn hold(&mut self, msg: &QueueMessage, criticality: u32, replicas: Option<i32>) -> bool {
+        if self.quiet.is_zero() {
+            return false;
+        }
+        let now = Instant::now();
+        match self.held.get_mut(&msg.uid) {
+            Some(held) if held.replicas == replicas => {}
+            Some(held) => {
+                held.msg = msg.clone();
+                held.criticality = criticality;
+                held.replicas = replicas;
+                held.due = (now + self.quiet).min(held.held_since + self.quiet * MAX_HOLD_PERIODS);
+                record_compaction();
+            }
+            None => {
+                self.held.insert(msg.uid.clone(), HeldEvent {
+                    msg: msg.clone(),
+                    criticality,
+                    replicas,
+                    held_since: now,
+                    due: now + self.quiet,
+                });
+            }
+        }
+        true
+    }
+
+    /*
+    This function drops the event held for an RTResource,
+    superseded by an event enqueued at once.
+    */
+    pub fn supersede(&mut self, uid: &str) {
+        if self.held.remove(uid).is_some() {
+            record_compaction();
+        }
+    }
+
+    /*
+    This function forgets a deleted RTResource.
+    */
+    pub fn forget(&mut self, uid: &str) {
+        self.supersede(uid);
+        self.specs.remove(uid);
+    }
+
+    /*
+    This function rebuilds the last seen specs after a relist,
+    so that the RTResources deleted meanwhile are forgotten and
+    a change missed meanwhile is not taken for a replica-count-only one.
+    */
+    pub fn reset(&mut self, objects: &[RTResource]) {
+        self.specs.clear();
+        for object in objects {
+            if let Some(uid) = object.metadata.uid.as_deref() {
+                self.replicas_only(uid, object);
+            }
+        }
+        let specs = &self.specs;
+        self.held.retain(|uid, _| specs.contains_key(uid));
+    }
+
+    /*
+    This function returns when the next held event is due.
+    */
+    pub fn next_due(&self) -> Option<Instant> {
+        self.held.values().map(|e| e.due).min()
+    }
+
+    /*
+    This function returns the held events whose quiet period
+    elapsed, with their criticality.
+    */
+    pub fn take_due(&mut self) -> Vec<(QueueMessage, u32)> {
+        let now = Instant::now();
+        let due: Vec<String> = self.held.iter()
+            .filter(|(_, e)| e.due <= now)
+            .map(|(uid, _)| uid.clone())
+            .collect();
+        due.iter()
+            .filter_map(|uid| self.held.remove(uid))
+            .map(|e| (e.msg, e.criticality))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::vars::EventKind;
+
+    fn msg(uid: &str) -> QueueMessage {
+        QueueMessage {
+            name: "app".to_string(),
+            uid: uid.to_string(),
+            namespace: "default".to_string(),
+            enqueued_at: 0,
+            kind: EventKind::ResourceApplied,
+            trace_id: String::new(),
+        }
+    }
+
+    #[test]
+    fn keeps_the_quiet_period_on_unchanged_replicas() {
+        let mut compactor = EventCompactor::new(60_000);
+        assert!(compactor.hold(&msg("a"), 20, Some(3)));
+        let due = compactor.next_due().unwrap();
+        std::thread::sleep(Duration::from_millis(5));
+        assert!(compactor.hold(&msg("a"), 20, Some(3)));
+        assert_eq!(compactor.next_due(), Some(due));
+    }
+
+    #[test]
+    fn restarts_the_quiet_period_on_a_replica_change_up_to_the_cap() {
+        let mut compactor = EventCompactor::new(60_000);
+        let start = Instant::now();
+        assert!(compactor.hold(&msg("a"), 20, Some(3)));
+        let first_due = compactor.next_due().unwrap();
+        std::thread::sleep(Duration::from_millis(5));
+        assert!(compactor.hold(&msg("a"), 20, Some(4)));
+        assert!(compactor.next_due().unwrap() > first_due);
+        for replicas in 5..50 {
+            compactor.hold(&msg("a"), 20, Some(replicas));
+        }
+        assert!(compactor.next_due().unwrap() <= start + Duration::from_millis(60_000) * MAX_HOLD_PERIODS + Duration::from_millis(5));
+    }
+
+    #[test]
+    fn does_not_hold_without_a_quiet_period() {
+        let mut compactor = EventCompactor::new(0);
+        assert!(!compactor.hold(&msg("a"), 20, Some(3)));
+        assert_eq!(compactor.next_due(), None);
+    }
+}
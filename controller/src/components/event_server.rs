@@ -2,31 +2,44 @@
 This file contains the component in charge
 of spawning watchdog threads when free ones
 are under a certain threshold.
+The watchdogs terminate themselves when free ones are
+above a higher (scale-down) threshold and the pool has not been
+scaled for a cooldown period, so that bursty load does not make
+the pool oscillate between creating and killing threads.
 */
 
 use std::{
     mem,
     ptr,
-    ffi::c_void
+    ffi::c_void,
+    time::Instant
 };
 use libc::{
+    cpu_set_t,
+    CPU_SET,
+    CPU_ZERO,
+    pthread_attr_setaffinity_np,
     pthread_create,
     pthread_join,
     pthread_attr_t,
     pthread_attr_init,
-    pthread_attr_setschedpolicy,
     pthread_attr_setschedparam,
-    pthread_attr_setinheritsched,
     pthread_attr_destroy,
-    sched_param,
-    SCHED_FIFO,
-    PTHREAD_EXPLICIT_SCHED,
-    pthread_cond_wait,
-    pthread_mutex_lock,
-    pthread_mutex_unlock
+    sched_param
 };
 
+use crate::utils::priorities::{
+    ThreadRole,
+    band_of,
+    set_realtime_policy
+};
 use crate::utils::vars::SharedState;
+use crate::utils::lock_metrics::{
+    LockSite,
+    lock_shared,
+    unlock_shared,
+    wait_shared
+};
 use crate::components::watchdog::watchdog;
 
 
@@ -47,28 +60,50 @@ pub extern "C" fn server(thread_data: *mut c_void) -> *mut c_void {
 		for i in 0..shared_state.config.max_watchdogs {
 			shared_state.workers[i].id = 0;
 			shared_state.workers[i].active = false;
+			shared_state.workers[i].criticality = None;
+			shared_state.workers[i].borrowed = false;
+			shared_state.workers[i].soft = false;
+			shared_state.workers[i].decay = 0;
 		}
         let mut last_working: usize = 0;
         
         /*
         Now we can create the initial watchdog threads  
         (the minimum number).
-        Each watchdog thread is created with SCHED_FIFO policy
-        and a priority level of "94".
+        Each watchdog thread is created with SCHED_FIFO policy (unless degraded)
+        and the watchdog base priority (PriorityBand::WATCHDOG_BASE unless changed at runtime).
         */
         let mut attr: pthread_attr_t = mem::zeroed();
-		let mut param: sched_param = sched_param{sched_priority: 0};
+		let mut param: sched_param;
         let mut result: i32;
 		pthread_attr_init(&mut attr);
-		pthread_attr_setschedpolicy(&mut attr, SCHED_FIFO);
-		pthread_attr_setinheritsched(&mut attr, PTHREAD_EXPLICIT_SCHED);
+		set_realtime_policy(&mut attr);
 
-		param.sched_priority = 94;
+		param = band_of(ThreadRole::Watchdogs).sched_param();
 		pthread_attr_setschedparam(&mut attr, &param);
+
+        /*
+        If a watchdog CPU set is configured, watchdogs are pinned
+        to those housekeeping cores, keeping controller activity
+        off the isolated cores reserved for RT pods.
+        */
+        if !shared_state.config.watchdog_cpuset.is_empty() {
+            let mut cpuset: cpu_set_t = mem::zeroed();
+            CPU_ZERO(&mut cpuset);
+            for cpu in shared_state.config.watchdog_cpuset.iter() {
+                CPU_SET(*cpu, &mut cpuset);
+            }
+            result = pthread_attr_setaffinity_np(&mut attr, mem::size_of::<cpu_set_t>(), &cpuset);
+            if result != 0 {
+                eprintln!("Server - An error occurred while setting the watchdog CPU affinity! {}", result);
+            } else {
+                println!("Server - Watchdogs will be pinned to CPUs {:?}!", shared_state.config.watchdog_cpuset);
+            }
+        }
 		for i in 0..shared_state.config.min_watchdogs {
 		    result = pthread_create(
                 &mut shared_state.workers[i].id,
-                &attr as *const _ as *const pthread_attr_t,
+                &attr,
                 watchdog,
                 thread_data);
 		    if result != 0 {
@@ -85,12 +120,12 @@ pub extern "C" fn server(thread_data: *mut c_void) -> *mut c_void {
         */
 		'outer: loop {
             let mut error_count: usize = 0;
-			pthread_mutex_lock(&mut shared_state.mutex);
+			lock_shared(&mut shared_state.mutex, shared_state.config.lock_wait_warn_us, LockSite::ServerScale);
 			while shared_state.working_threads == last_working {
-                pthread_cond_wait(&mut shared_state.cond, &mut shared_state.mutex);
+                wait_shared(&mut shared_state.cond, &mut shared_state.mutex, LockSite::ServerScale);
             }
     		last_working = shared_state.working_threads;
-            let difference = shared_state.active_threads - shared_state.working_threads as usize;
+            let difference = shared_state.active_threads - shared_state.working_threads;
             let currently_active = shared_state.active_threads;
             if difference < shared_state.config.threshold {
                 let needed = shared_state.config.threshold - difference;
@@ -101,7 +136,8 @@ pub extern "C" fn server(thread_data: *mut c_void) -> *mut c_void {
                 } else {
                     shared_state.active_threads = new_active;
                 }
-                pthread_mutex_unlock(&mut shared_state.mutex);
+                shared_state.last_scaled = Some(Instant::now());
+                unlock_shared(&mut shared_state.mutex);
                 let mut i: usize = 0;
                 while i < needed {
                     println!("Server - There will be a total of {} Active Threads!", new_active);
@@ -110,19 +146,21 @@ pub extern "C" fn server(thread_data: *mut c_void) -> *mut c_void {
                         break;
                     }
                     let mut free = 0;
-                    while shared_state.workers[free].active == true {
-                        free = free + 1;
+                    while shared_state.workers[free].active {
+                        free += 1;
                     }
+                    param = band_of(ThreadRole::Watchdogs).sched_param();
+                    pthread_attr_setschedparam(&mut attr, &param);
                     result = pthread_create(
                         &mut shared_state.workers[free].id,
-                        &attr as *const _ as *const pthread_attr_t,
+                        &attr,
                         watchdog,
                         thread_data
                     );
                     if result != 0 {
-                        i = i - 1;
+                        i -= 1;
                         eprintln!("Server - An error occurred while creating a Watchdog thread!");
-                        error_count = error_count + 1;
+                        error_count += 1;
                         if error_count > 5 {
                             eprintln!("Server - Too many errors occurred while creating watchdog threads! Exiting...");
                             break 'outer;
@@ -130,12 +168,12 @@ pub extern "C" fn server(thread_data: *mut c_void) -> *mut c_void {
                     } else {
                         shared_state.workers[free].active = true;
                         println!("Server - Thread Created in position {}!", free);
-                        i = i + 1;
+                        i += 1;
                         error_count = 0;
                     }
                 }
             } else {
-                pthread_mutex_unlock(&mut shared_state.mutex);
+                unlock_shared(&mut shared_state.mutex);
             }
 		}
 
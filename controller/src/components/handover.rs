@@ -0,0 +1,211 @@
+/*
+This file contains the handover of the controller state between
+a controller and its standby. The exported snapshot is a versioned
+JSON document holding:
+    - the controller configuration (rendered) and its hash;
+    - the real-time priorities of the controller threads;
+    - the reservation ledger of the capacity index (the replicas
+      pinned by spec.placementOverrides);
+    - the placement map (the pods bound to each node).
+On import, the priorities are applied and the reservations missing
+from the local ledger are recorded, so that the standby does not
+place other pods on the capacity reserved for the pinned replicas
+before their pods are created. The configuration is not applied
+(it comes from the ConfigMap of each controller), a hash mismatch
+is only reported. The placement map is rebuilt by the pod watcher
+and only compared with the local one.
+*/
+
+use std::collections::HashSet;
+use libc::{
+    pthread_mutex_lock,
+    pthread_mutex_unlock
+};
+use chrono::Utc;
+use serde::{
+    Deserialize,
+    Serialize
+};
+use schemars::JsonSchema;
+
+use crate::utils::vars::SharedStatePtr;
+use crate::utils::priorities::{
+    ThreadRole,
+    priority_of,
+    set_priority
+};
+use crate::components::capacity_index::LedgerEntry;
+use crate::components::info_publisher::{
+    config_hash,
+    instance_identity
+};
+
+
+
+/*
+Version of the snapshot document
+*/
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/*
+Real-time priorities of the controller threads
+*/
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SnapshotPriorities {
+    pub watchers: i32,
+    /*
+    Missing from the snapshots of older controllers,
+    whose pod watcher ran at the watchers priority
+    (the local pod watcher priority is then kept)
+    */
+    #[serde(rename = "podWatcher", default)]
+    pub pod_watcher: Option<i32>,
+    pub server: i32,
+    pub watchdogs: i32,
+}
+
+/*
+Snapshot of the controller policy and reservations
+*/
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
+pub struct Snapshot {
+    pub version: u32,
+    #[serde(rename = "exportedAt")]
+    pub exported_at: String,
+    pub controller: String,
+    #[serde(rename = "configHash")]
+    pub config_hash: String,
+    pub config: String,
+    pub priorities: SnapshotPriorities,
+    pub reservations: Vec<LedgerEntry>,
+    pub placements: Vec<LedgerEntry>,
+}
+
+/*
+Outcome of a snapshot import
+*/
+#[derive(Serialize, Clone, Debug, Default, JsonSchema)]
+pub struct ImportReport {
+    #[serde(rename = "configMatches")]
+    pub config_matches: bool,
+    #[serde(rename = "prioritiesApplied")]
+    pub priorities_applied: Vec<String>,
+    #[serde(rename = "priorityErrors")]
+    pub priority_errors: Vec<String>,
+    #[serde(rename = "reservationsImported")]
+    pub reservations_imported: usize,
+    #[serde(rename = "reservationsSkipped")]
+    pub reservations_skipped: usize,
+    #[serde(rename = "unknownPlacements")]
+    pub unknown_placements: usize,
+}
+
+/*
+This function exports the snapshot of the controller state.
+It must be called without holding the shared mutex.
+*/
+pub fn export_snapshot(state: SharedStatePtr) -> Snapshot {
+    let shared_state = unsafe { &mut *state.0 };
+    let (reservations, placements) = unsafe {
+        pthread_mutex_lock(&mut shared_state.mutex);
+        let ledger = (shared_state.capacity.reservation_entries(), shared_state.capacity.placement_entries());
+        pthread_mutex_unlock(&mut shared_state.mutex);
+
+        ledger
+    };
+    Snapshot {
+        version: SNAPSHOT_VERSION,
+        exported_at: Utc::now().to_rfc3339(),
+        controller: instance_identity(),
+        config_hash: config_hash(&shared_state.config),
+        config: shared_state.config.to_string(),
+        priorities: SnapshotPriorities {
+            watchers: priority_of(ThreadRole::Watchers),
+            pod_watcher: Some(priority_of(ThreadRole::PodWatcher)),
+            server: priority_of(ThreadRole::Server),
+            watchdogs: priority_of(ThreadRole::Watchdogs),
+        },
+        reservations,
+        placements,
+    }
+}
+
+/*
+This function imports the snapshot of another controller.
+It must be called without holding the shared mutex.
+*/
+pub fn import_snapshot(state: SharedStatePtr, snapshot: &Snapshot) -> Result<ImportReport, String> {
+    if snapshot.version != SNAPSHOT_VERSION {
+        return Err(format!("unsupported snapshot version {} (expected {})", snapshot.version, SNAPSHOT_VERSION));
+    }
+    let shared_state = unsafe { &mut *state.0 };
+    let mut report = ImportReport {
+        config_matches: snapshot.config_hash == config_hash(&shared_state.config),
+        ..Default::default()
+    };
+    if !report.config_matches {
+        eprintln!(
+            "Handover - The configuration of controller {} ({}) differs from the local one ({})!",
+            snapshot.controller,
+            snapshot.config_hash,
+            config_hash(&shared_state.config)
+        );
+    }
+
+    /*
+    Each priority change must keep the roles ordered, so a change
+    rejected only because of the priorities still to apply
+    (e.g. when every role is lowered) is retried after them.
+    */
+    let mut pending: Vec<(ThreadRole, i32)> = [
+        (ThreadRole::Watchers, snapshot.priorities.watchers),
+        (ThreadRole::PodWatcher, snapshot.priorities.pod_watcher.unwrap_or(priority_of(ThreadRole::PodWatcher))),
+        (ThreadRole::Server, snapshot.priorities.server),
+        (ThreadRole::Watchdogs, snapshot.priorities.watchdogs),
+    ].into_iter().filter(|(role, priority)| priority_of(*role) != *priority).collect();
+    loop {
+        let mut rejected = Vec::new();
+        let mut errors = Vec::new();
+        for (role, priority) in pending.iter().copied() {
+            match set_priority(shared_state, role, priority) {
+                Ok(_) => report.priorities_applied.push(role.to_string()),
+                Err(e) => {
+                    rejected.push((role, priority));
+                    errors.push(format!("{}: {}", role, e));
+                }
+            }
+        }
+        if rejected.is_empty() || rejected.len() == pending.len() {
+            report.priority_errors.extend(errors);
+            break;
+        }
+        pending = rejected;
+    }
+
+    unsafe {
+        pthread_mutex_lock(&mut shared_state.mutex);
+        for entry in snapshot.reservations.iter() {
+            if shared_state.capacity.import_reservation(entry) {
+                report.reservations_imported += 1;
+            } else {
+                report.reservations_skipped += 1;
+            }
+        }
+        let local: HashSet<String> = shared_state.capacity.placement_entries().into_iter()
+            .filter_map(|p| p.pod_uid)
+            .collect();
+        report.unknown_placements = snapshot.placements.iter()
+            .filter(|p| p.pod_uid.as_ref().is_none_or(|uid| !local.contains(uid)))
+            .count();
+        pthread_mutex_unlock(&mut shared_state.mutex);
+    }
+
+    println!(
+        "Handover - Snapshot of controller {} imported: {} reservations imported, {} skipped, {} placements unknown locally.",
+        snapshot.controller,
+        report.reservations_imported,
+        report.reservations_skipped,
+        report.unknown_placements
+    );
+    Ok(report)
+}
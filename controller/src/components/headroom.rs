@@ -0,0 +1,112 @@
+/*
+This file contains the capacity headroom query of the admin API
+(GET /headroom?criticality=N). It returns how many pods of a standard
+shape (HEADROOM_CPU and HEADROOM_MEMORY, or the cpu and memory query
+parameters) could still be admitted at a criticality without preempting
+anything, from the free capacity of the capacity index, which already
+holds the reservation ledger of the pinned replicas.
+The nodes a pod of the criticality cannot use are left out: the nodes
+under maintenance, the nodes running another operating system than the
+os query parameter (linux by default) and, outside the top band, the
+dedicated nodes.
+It needs the built-in scheduler, which maintains the capacity index.
+*/
+
+use std::collections::HashMap;
+use libc::{
+    pthread_mutex_lock,
+    pthread_mutex_unlock
+};
+
+use crate::utils::vars::SharedStatePtr;
+use crate::utils::quantity::parse_quantity;
+use crate::utils::priorities::effective_criticality;
+use crate::utils::rtresource::DEFAULT_OS_TARGET;
+use crate::components::scheduling_policy::node_os;
+use crate::components::node_taints::{
+    dedicated_nodes_enabled,
+    is_dedicated
+};
+
+
+
+/*
+This function parses a query string into its parameters.
+*/
+pub fn query_parameters(query: Option<&str>) -> HashMap<String, String> {
+    query.unwrap_or_default()
+        .split('&')
+        .filter_map(|p| p.split_once('='))
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+/*
+This function returns the CPU (millicores) and memory (bytes) of a shape.
+*/
+fn shape(cpu: &str, memory: &str) -> Option<(i64, i64)> {
+    let cpu = (parse_quantity(cpu)? * 1000.0) as i64;
+    let memory = parse_quantity(memory)? as i64;
+    (cpu > 0 || memory > 0).then_some((cpu, memory))
+}
+
+/*
+This function computes the capacity headroom of a criticality.
+It returns the headroom, or the error to answer with.
+It must be called without holding the shared mutex.
+*/
+pub fn capacity_headroom(state: SharedStatePtr, query: Option<&str>) -> Result<serde_json::Value, String> {
+    let shared_state = unsafe { &mut *state.0 };
+    let config = &shared_state.config;
+    let parameters = query_parameters(query);
+    let criticality: u32 = parameters.get("criticality")
+        .ok_or("Expected ?criticality=<level>")?
+        .parse()
+        .map_err(|_| "The criticality must be an integer")?;
+    let criticality = effective_criticality(config, "", criticality);
+    let cpu = parameters.get("cpu").unwrap_or(&config.headroom_cpu);
+    let memory = parameters.get("memory").unwrap_or(&config.headroom_memory);
+    let os = parameters.get("os").map(String::as_str).unwrap_or(DEFAULT_OS_TARGET);
+    let (shape_cpu, shape_memory) = shape(cpu, memory).ok_or("The pod shape must be valid, non-zero quantities")?;
+    let top_band = criticality <= config.critical_band_max;
+    let dedicated_enabled = dedicated_nodes_enabled(config);
+
+    let nodes = unsafe {
+        pthread_mutex_lock(&mut shared_state.mutex);
+        let maintenance = &shared_state.maintenance_nodes;
+        let nodes = (!shared_state.capacity.is_empty()).then(|| shared_state.capacity.headroom(|node| {
+            let name = node.metadata.name.clone().unwrap_or_default();
+            !maintenance.contains(&name)
+                && node_os(node) == os
+                && (top_band || !dedicated_enabled || !is_dedicated(config, node))
+        }));
+        pthread_mutex_unlock(&mut shared_state.mutex);
+
+        nodes
+    };
+    let Some(mut nodes) = nodes else {
+        return Err("The capacity index is not available (built-in scheduler only)".to_string());
+    };
+    nodes.sort();
+    let fits = |free_cpu: i64, free_memory: i64| {
+        let by_cpu = if shape_cpu > 0 { free_cpu.max(0) / shape_cpu } else { i64::MAX };
+        let by_memory = if shape_memory > 0 { free_memory.max(0) / shape_memory } else { i64::MAX };
+        by_cpu.min(by_memory)
+    };
+    let per_node: Vec<serde_json::Value> = nodes.iter()
+        .map(|(name, free_cpu, free_memory)| serde_json::json!({
+            "node": name,
+            "freeCpuMillis": free_cpu,
+            "freeMemoryBytes": free_memory,
+            "pods": fits(*free_cpu, *free_memory)
+        }))
+        .collect();
+    Ok(serde_json::json!({
+        "criticality": criticality,
+        "band": if top_band { "critical" } else { "general" },
+        "os": os,
+        "shape": {"cpuMillis": shape_cpu, "memoryBytes": shape_memory},
+        "pods": nodes.iter().map(|(_, c, m)| fits(*c, *m)).sum::<i64>(),
+        "nodes": per_node
+    }))
+}
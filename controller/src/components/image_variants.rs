@@ -0,0 +1,99 @@
+/*
+This file contains the selection of the image variants
+declared in spec.imageVariants. When a replica is bound to a node
+by the controller (built-in scheduler), the first variant matching
+the node labels (kubernetes.io/arch and the variant nodeSelector,
+e.g. NFD labels) replaces the image of its container, and the chosen
+image is recorded on the pod, then in status.imageVariants.
+The pods left to kube-scheduler keep the template images.
+*/
+
+use k8s_openapi::api::core::v1::{
+    Node,
+    Pod
+};
+
+use crate::utils::rtresource::{
+    RTResource,
+    ImageVariant,
+    ImageVariantPlacement
+};
+use crate::components::planner::ORDINAL_LABEL;
+
+
+
+/*
+Pod annotation storing the image variant chosen for the replica
+*/
+pub const IMAGE_VARIANT_ANNOTATION: &str = "rtgroup.critical.com/image-variant";
+
+/*
+Label storing the architecture of a node
+*/
+const ARCH_LABEL: &str = "kubernetes.io/arch";
+
+/*
+This function returns whether a variant matches the labels of a node.
+*/
+fn matches(variant: &ImageVariant, node: &Node) -> bool {
+    let labels = node.metadata.labels.clone().unwrap_or_default();
+    variant.arch.as_ref().is_none_or(|arch| labels.get(ARCH_LABEL) == Some(arch))
+        && variant.node_selector.iter().flatten().all(|(key, value)| labels.get(key) == Some(value))
+}
+
+/*
+This function sets the image of the first variant matching
+the node a pod is bound to, and records it on the pod.
+*/
+pub fn apply_image_variant(thread_name: &str, pod: &mut Pod, rtresource: &RTResource, node: &Node) {
+    let Some(variant) = rtresource.spec.image_variants.iter().flatten().find(|v| matches(v, node)) else {
+        return;
+    };
+    let Some(spec) = pod.spec.as_mut() else {
+        return;
+    };
+    let container = match variant.container.as_ref() {
+        Some(name) => spec.containers.iter_mut().find(|c| &c.name == name),
+        None => spec.containers.first_mut(),
+    };
+    let Some(container) = container else {
+        eprintln!(
+            "{} - The image variant {} names a container missing from the template of RTResource {}!",
+            thread_name,
+            variant.image,
+            rtresource.metadata.name.clone().unwrap_or_default()
+        );
+        return;
+    };
+    container.image = Some(variant.image.clone());
+    pod.metadata.annotations.get_or_insert_with(Default::default)
+        .insert(IMAGE_VARIANT_ANNOTATION.to_string(), variant.image.clone());
+    println!(
+        "{} - Pod {} uses image variant {} on node {}!",
+        thread_name,
+        pod.metadata.name.clone().unwrap_or_default(),
+        variant.image,
+        node.metadata.name.clone().unwrap_or_default()
+    );
+}
+
+/*
+This function returns the image variants chosen
+for the pods of an RTResource, by ordinal.
+*/
+pub fn image_variant_placements(pods: &[Pod]) -> Option<Vec<ImageVariantPlacement>> {
+    let mut placements: Vec<ImageVariantPlacement> = pods.iter()
+        .filter(|p| p.metadata.deletion_timestamp.is_none())
+        .filter_map(|p| {
+            let image = p.metadata.annotations.as_ref()?.get(IMAGE_VARIANT_ANNOTATION)?;
+            let ordinal = p.metadata.labels.as_ref()?.get(ORDINAL_LABEL)?.parse().ok()?;
+            Some(ImageVariantPlacement {
+                ordinal,
+                node: p.spec.as_ref().and_then(|s| s.node_name.clone()).unwrap_or_default(),
+                image: image.clone(),
+            })
+        })
+        .collect();
+    placements.sort_by_key(|p| p.ordinal);
+    (!placements.is_empty()).then_some(placements)
+}
@@ -0,0 +1,164 @@
+/*
+This file contains the publication of the controller
+information into the "preempt-k8s-info" ConfigMap, so that
+fleet tooling can audit which version and policy each
+cluster runs. The ConfigMap holds:
+    - the controller version;
+    - the enabled feature flags;
+    - a hash of the configuration and the configuration itself;
+    - the identity of the controller instance (pod name);
+    - whether the controller threads run under SCHED_FIFO.
+It is updated on every controller (re)start, which is when
+the configuration is (re)loaded.
+*/
+
+use std::{
+    env,
+    collections::BTreeMap
+};
+use kube::{
+    Api,
+    Client,
+    api::{
+        Patch,
+        PatchParams
+    }
+};
+use k8s_openapi::api::core::v1::ConfigMap;
+
+use crate::utils::priorities::realtime_scheduling;
+use crate::utils::configuration::{
+    ControllerConfig,
+    ControllerMode,
+    SchedulerKind,
+    OrphanPolicy
+};
+
+
+
+/*
+Name of the controller information ConfigMap
+*/
+pub const INFO_CONFIGMAP: &str = "preempt-k8s-info";
+
+/*
+Field manager used for the ConfigMap server-side applies
+*/
+const INFO_FIELD_MANAGER: &str = "preempt-k8s";
+
+/*
+This function returns the feature flags
+enabled by the configuration.
+*/
+pub fn feature_flags(config: &ControllerConfig) -> Vec<&'static str> {
+    let mut features: Vec<&'static str> = Vec::new();
+    if config.mode == ControllerMode::Observe {
+        features.push("observe-mode");
+    }
+    if config.scheduler == SchedulerKind::Builtin {
+        features.push("builtin-scheduler");
+    }
+    if config.scheduler == SchedulerKind::Builtin && !config.scheduler_webhook_url.is_empty() {
+        features.push("scheduler-webhook");
+    }
+    if !config.critical_service_account.is_empty() {
+        features.push("apf-critical-path");
+    }
+    if !config.watchdog_cpuset.is_empty() {
+        features.push("watchdog-cpuset");
+    }
+    if !config.alert_webhook_url.is_empty() {
+        features.push("alert-webhook");
+    }
+    if config.admin_port != 0 {
+        features.push("admin-api");
+    }
+    if config.admin_port != 0 && !config.admin_tls_dir.is_empty() {
+        features.push("admin-mtls");
+    }
+    if config.reserved_watchdogs > 0 {
+        features.push("reserved-watchdogs");
+    }
+    if !config.dedicated_nodes.is_empty() || !config.dedicated_node_label.is_empty() {
+        features.push("dedicated-nodes");
+    }
+    if config.orphan_policy != OrphanPolicy::Off {
+        features.push("orphan-sweeper");
+    }
+    if !config.archive_store.is_empty() {
+        features.push("archival");
+    }
+    if !config.provisioning_class.is_empty() {
+        features.push("provisioning-requests");
+    }
+    if !realtime_scheduling() {
+        features.push("non-rt-degraded");
+    }
+    if cfg!(feature = "chaos") {
+        features.push("chaos");
+    }
+    if cfg!(feature = "alloc-tracker") {
+        features.push("alloc-tracker");
+    }
+    features
+}
+
+/*
+This function returns a stable hash (FNV-1a, 64 bits)
+of the configuration.
+*/
+pub fn config_hash(config: &ControllerConfig) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in config.to_string().bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)
+}
+
+/*
+This function returns the identity of the controller instance.
+*/
+pub fn instance_identity() -> String {
+    env::var("POD_NAME")
+        .or_else(|_| env::var("HOSTNAME"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+/*
+This function publishes the controller information ConfigMap
+in the controller namespace.
+*/
+pub async fn publish_info(client: Client, config: &ControllerConfig) {
+    let namespace = client.default_namespace().to_string();
+    let mut data: BTreeMap<String, String> = BTreeMap::new();
+    data.insert("version".to_string(), env!("CARGO_PKG_VERSION").to_string());
+    data.insert("features".to_string(), feature_flags(config).join(","));
+    data.insert("configHash".to_string(), config_hash(config));
+    data.insert("config".to_string(), config.to_string());
+    data.insert("leader".to_string(), instance_identity());
+    data.insert("realtimeScheduling".to_string(), realtime_scheduling().to_string());
+    data.insert("updatedAt".to_string(), chrono::Utc::now().to_rfc3339());
+
+    let configmap = serde_json::json!({
+        "apiVersion": "v1",
+        "kind": "ConfigMap",
+        "metadata": {
+            "name": INFO_CONFIGMAP,
+            "namespace": namespace,
+            "labels": {
+                "app": "preempt-k8s"
+            }
+        },
+        "data": data
+    });
+    let configmap_api = Api::<ConfigMap>::namespaced(client, &namespace);
+    match configmap_api.patch(
+        INFO_CONFIGMAP,
+        &PatchParams::apply(INFO_FIELD_MANAGER).force(),
+        &Patch::Apply(&configmap)
+    ).await {
+        Ok(_) => println!("Info Publisher - Published ConfigMap {}/{}!", namespace, INFO_CONFIGMAP),
+        Err(e) => eprintln!("Info Publisher - An error occurred while publishing ConfigMap {}/{}: {}", namespace, INFO_CONFIGMAP, e),
+    }
+}
@@ -0,0 +1,177 @@
+/*
+This file contains the guard of the controller-owned pod labels.
+The watchdogs list the replicas of an RTResource through the
+UID owner label: a user editing or stripping the labels of a
+managed pod hides it from its RTResource, which then replaces it
+while the hidden pod keeps running. The managed pods therefore also
+carry their owner labels in the owner annotation, and:
+    - the planner restores the edited owner labels of the listed pods;
+    - the pod watcher records the pods whose owner labels were
+      stripped (the pods are watched cluster-wide) and enqueues
+      their RTResource;
+    - the watchdog re-adopts them by restoring their labels before
+      planning (the planner then keeps or replaces them as usual),
+      or deletes them with their RTResource.
+The generated pod protection policy (POD_PROTECTION) rejects
+such edits at admission in the first place.
+*/
+
+use std::collections::BTreeMap;
+use libc::{
+    pthread_mutex_lock,
+    pthread_mutex_unlock
+};
+use kube::{
+    Api,
+    Client,
+    api::{
+        ListParams,
+        Patch,
+        PatchParams
+    }
+};
+use k8s_openapi::api::core::v1::Pod;
+
+use crate::utils::vars::{
+    SharedState,
+    SharedStatePtr,
+    QueueMessage,
+    EventKind
+};
+use crate::utils::rtresource::{
+    RTResource,
+    CRITICALITY_CLASS_LABEL
+};
+use crate::utils::owner_labels::owner_labels;
+
+
+
+/*
+Annotation carrying the owner labels of a managed pod
+*/
+pub const OWNER_ANNOTATION: &str = "rtgroup.critical.com/owner-labels";
+
+/*
+This function returns the owner annotation of the pods of an RTResource.
+*/
+pub fn owner_annotation(rtresource: &RTResource) -> String {
+    serde_json::to_string(&rtresource.owner_labels()).unwrap_or_default()
+}
+
+/*
+This function returns the owner labels recorded on a pod.
+*/
+fn recorded_owner_labels(pod: &Pod) -> Option<BTreeMap<String, String>> {
+    let annotation = pod.metadata.annotations.as_ref()?.get(OWNER_ANNOTATION)?;
+    serde_json::from_str(annotation).ok()
+}
+
+/*
+This function returns the namespace/name of the managed pods (the pods
+carrying the criticality class label) stamped with other owner label
+keys than the configured ones, e.g. after POD_WATCHER_*_LABEL were
+changed on a running cluster: the selectors would not match them, so
+that their RTResources would get a second set of replicas.
+*/
+pub async fn foreign_owner_labels(client: Client) -> Result<Vec<String>, kube::Error> {
+    let pods = Api::<Pod>::all(client).list(&ListParams::default().labels(CRITICALITY_CLASS_LABEL)).await?.items;
+    let uid = &owner_labels().uid;
+    Ok(pods.iter()
+        .filter(|pod| {
+            let labelled = pod.metadata.labels.as_ref().is_some_and(|l| l.contains_key(uid));
+            let recorded = recorded_owner_labels(pod).is_some_and(|o| o.contains_key(uid));
+            !labelled && !recorded
+        })
+        .map(|pod| format!(
+            "{}/{}",
+            pod.metadata.namespace.as_deref().unwrap_or_default(),
+            pod.metadata.name.as_deref().unwrap_or_default()
+        ))
+        .collect())
+}
+
+/*
+This function returns the event of the RTResource owning a pod whose
+UID owner label was stripped or changed, with the criticality
+recorded on the pod, or None for the other pods.
+*/
+pub fn stripped_pod_owner(pod: &Pod) -> Option<(QueueMessage, u32)> {
+    let owner = recorded_owner_labels(pod)?;
+    let (name, uid, namespace) = owner_labels().owner(&owner)?;
+    let labels = pod.metadata.labels.clone().unwrap_or_default();
+    if labels.get(&owner_labels().uid) == Some(uid) || pod.metadata.deletion_timestamp.is_some() {
+        return None;
+    }
+    Some((
+        QueueMessage {
+            name: name.clone(),
+            uid: uid.clone(),
+            namespace: namespace.clone(),
+            enqueued_at: 0,
+            kind: EventKind::PodChanged,
+            trace_id: String::new(),
+        },
+        owner_labels().criticality(&owner)?
+    ))
+}
+
+/*
+This function records a pod whose owner labels were stripped.
+It must be called without holding the shared mutex.
+*/
+pub fn record_stripped_pod(shared_state: &mut SharedState, uid: &str, pod: &Pod) {
+    let name = pod.metadata.name.clone().unwrap_or_default();
+    let namespace = pod.metadata.namespace.clone().unwrap_or_default();
+    unsafe {
+        pthread_mutex_lock(&mut shared_state.mutex);
+        let pods = shared_state.stripped_pods.entry(uid.to_string()).or_default();
+        if !pods.contains(&(namespace.clone(), name.clone())) {
+            pods.push((namespace, name));
+        }
+        pthread_mutex_unlock(&mut shared_state.mutex);
+    }
+}
+
+/*
+This function returns and forgets the pods of an RTResource
+whose owner labels were stripped (namespace and name).
+It must be called without holding the shared mutex.
+*/
+pub fn take_stripped_pods(state: SharedStatePtr, uid: &str) -> Vec<(String, String)> {
+    let shared_state = unsafe { &mut *state.0 };
+    unsafe {
+        pthread_mutex_lock(&mut shared_state.mutex);
+        let pods = shared_state.stripped_pods.remove(uid).unwrap_or_default();
+        pthread_mutex_unlock(&mut shared_state.mutex);
+
+        pods
+    }
+}
+
+/*
+This function restores the owner labels of a pod from its owner
+annotation, returning the updated pod (None if it is gone
+or was changed to another owner meanwhile).
+*/
+pub async fn restore_owner_labels(thread_name: &str, client: Client, uid: &str, namespace: &str, name: &str) -> Option<Pod> {
+    let pod_api: Api<Pod> = Api::namespaced(client, namespace);
+    let pod = match pod_api.get_opt(name).await {
+        Ok(pod) => pod?,
+        Err(e) => {
+            eprintln!("{} - An error occurred while retrieving the stripped Pod {}/{}: {}", thread_name, namespace, name, e);
+            return None;
+        }
+    };
+    let owner = recorded_owner_labels(&pod).filter(|o| o.get(&owner_labels().uid).is_some_and(|u| u == uid))?;
+    let patch = serde_json::json!({"metadata": {"labels": owner}});
+    match pod_api.patch(name, &PatchParams::default(), &Patch::Merge(&patch)).await {
+        Ok(pod) => {
+            println!("{} - Restored the owner labels of Pod {}/{}, re-adopting it!", thread_name, namespace, name);
+            Some(pod)
+        }
+        Err(e) => {
+            eprintln!("{} - An error occurred while restoring the owner labels of Pod {}/{}: {}", thread_name, namespace, name, e);
+            None
+        }
+    }
+}
@@ -0,0 +1,244 @@
+/*
+This file contains the offline linter of RTResource manifests
+(lint <file>, or --lint=<file>).
+It evaluates the RTResources of a YAML file (documents or Lists)
+before they reach the cluster, reusing the controller decisions:
+    - the spec validation: the manifest must deserialize as an
+      RTResource, and its criticality, replicas, pod template and
+      placement overrides must be usable;
+    - the template defaulting: the platform defaults (POD_DEFAULTS)
+      of the criticality band are applied as the watchdogs do;
+    - the template validation against the Nodes of the file, if any
+      (the checks needing the cluster, e.g. the runtime class, are skipped);
+    - the static schedulability of the RTResources of the file
+      on the Nodes of the file, if any.
+The findings are printed as JSON, and the command fails
+when an error is found, so that CI pipelines can gate on it.
+*/
+
+use std::{
+    error::Error,
+    fs
+};
+use k8s_openapi::api::core::v1::Node;
+use serde::Deserialize;
+
+use crate::utils::configuration::ControllerConfig;
+use crate::utils::rtresource::RTResource;
+use crate::utils::priorities::effective_criticality;
+use crate::components::pod_defaults::{
+    band_defaults,
+    apply_pod_defaults
+};
+use crate::components::template_validation::check_template;
+use crate::components::scheduling_policy::SchedulingPolicy;
+use crate::components::schedulability::analyze;
+
+
+
+/*
+Severity of a finding
+*/
+#[derive(Clone, Copy, PartialEq)]
+enum Severity {
+    Error,
+    Warning,
+    Info,
+}
+
+impl Severity {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Info => "info",
+        }
+    }
+}
+
+/*
+Finding of the linter
+*/
+struct Finding {
+    severity: Severity,
+    rule: &'static str,
+    object: String,
+    message: String,
+}
+
+impl Finding {
+    fn new(severity: Severity, rule: &'static str, object: &str, message: String) -> Self {
+        Finding {severity, rule, object: object.to_string(), message}
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "severity": self.severity.as_str(),
+            "rule": self.rule,
+            "object": self.object,
+            "message": self.message
+        })
+    }
+}
+
+/*
+This function returns the namespace/name of an RTResource.
+*/
+fn object_name(rtresource: &RTResource) -> String {
+    format!(
+        "{}/{}",
+        rtresource.metadata.namespace.as_deref().unwrap_or("default"),
+        rtresource.metadata.name.as_deref().unwrap_or_default()
+    )
+}
+
+/*
+This function validates the spec of an RTResource.
+*/
+fn lint_spec(config: &ControllerConfig, rtresource: &RTResource, findings: &mut Vec<Finding>) {
+    let object = object_name(rtresource);
+    let spec = &rtresource.spec;
+    if rtresource.metadata.name.as_deref().is_none_or(str::is_empty) {
+        findings.push(Finding::new(Severity::Error, "MissingName", &object, "metadata.name is not set".to_string()));
+    }
+    if spec.criticality == 0 || spec.criticality > config.criticality_max {
+        let effective = effective_criticality(config, rtresource.metadata.namespace.as_deref().unwrap_or("default"), spec.criticality);
+        findings.push(Finding::new(
+            Severity::Warning,
+            "CriticalityOutOfRange",
+            &object,
+            format!("criticality {} is outside [1, {}] and is handled as {}", spec.criticality, config.criticality_max, effective)
+        ));
+    }
+    let replicas = spec.replicas.unwrap_or(1);
+    if replicas < 0 {
+        findings.push(Finding::new(Severity::Error, "NegativeReplicas", &object, format!("replicas is {}", replicas)));
+    }
+    match spec.template.spec.as_ref() {
+        None => findings.push(Finding::new(Severity::Error, "MissingPodSpec", &object, "template.spec is not set".to_string())),
+        Some(pod_spec) if pod_spec.containers.is_empty() => {
+            findings.push(Finding::new(Severity::Error, "NoContainers", &object, "template.spec has no containers".to_string()));
+        }
+        Some(_) => {}
+    }
+    for o in spec.placement_overrides.iter().flatten().filter(|o| o.ordinal as i32 >= replicas) {
+        findings.push(Finding::new(
+            Severity::Warning,
+            "UnusedPlacementOverride",
+            &object,
+            format!("replica {} is pinned to node {}, but only {} replicas are desired", o.ordinal, o.node_name, replicas)
+        ));
+    }
+}
+
+/*
+This function applies the platform defaults to the pod template
+of an RTResource, then validates it against the nodes, if any.
+*/
+fn lint_template(config: &ControllerConfig, rtresource: &RTResource, rtresources: &[RTResource], nodes: &[Node], findings: &mut Vec<Finding>) {
+    let object = object_name(rtresource);
+    let Some(mut pod_spec) = rtresource.spec.template.spec.clone() else {
+        return;
+    };
+    let criticality = effective_criticality(config, rtresource.metadata.namespace.as_deref().unwrap_or("default"), rtresource.spec.criticality);
+    if let Some(defaults) = band_defaults(&config.pod_defaults, criticality) {
+        let before = pod_spec.clone();
+        apply_pod_defaults(&mut pod_spec, defaults);
+        let defaulted: Vec<&str> = [
+            ("tolerations", before.tolerations != pod_spec.tolerations),
+            ("nodeSelector", before.node_selector != pod_spec.node_selector),
+            ("runtimeClassName", before.runtime_class_name != pod_spec.runtime_class_name),
+        ].into_iter().filter(|(_, changed)| *changed).map(|(field, _)| field).collect();
+        if !defaulted.is_empty() {
+            findings.push(Finding::new(
+                Severity::Info,
+                "DefaultsApplied",
+                &object,
+                format!("the platform defaults set {}", defaulted.join(", "))
+            ));
+        }
+    }
+    if nodes.is_empty() {
+        return;
+    }
+    let conflicting = rtresources.iter()
+        .filter(|r| r.metadata.namespace == rtresource.metadata.namespace && rtresource.conflicts(r))
+        .count();
+    for issue in check_template(rtresource, &pod_spec, nodes, conflicting) {
+        findings.push(Finding::new(Severity::Error, issue.reason(), &object, issue.message()));
+    }
+}
+
+/*
+This function lints the RTResources of a YAML file
+and prints the findings.
+*/
+pub fn run_lint(config: &ControllerConfig, path: &str) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    let content = fs::read_to_string(path)?;
+    let mut findings: Vec<Finding> = Vec::new();
+    let mut objects: Vec<serde_yaml::Value> = Vec::new();
+    for document in serde_yaml::Deserializer::from_str(&content) {
+        let value = serde_yaml::Value::deserialize(document)?;
+        match value.get("items").and_then(|i| i.as_sequence()) {
+            Some(items) => objects.extend(items.iter().cloned()),
+            None => objects.push(value),
+        }
+    }
+    let mut rtresources: Vec<RTResource> = Vec::new();
+    let mut nodes: Vec<Node> = Vec::new();
+    for (index, object) in objects.into_iter().enumerate() {
+        let name = object.get("metadata").and_then(|m| m.get("name")).and_then(|n| n.as_str())
+            .map_or_else(|| format!("document {}", index), str::to_string);
+        match object.get("kind").and_then(|k| k.as_str()) {
+            Some("RTResource") => match serde_yaml::from_value::<RTResource>(object) {
+                Ok(rtresource) => rtresources.push(rtresource),
+                Err(e) => findings.push(Finding::new(Severity::Error, "InvalidSpec", &name, e.to_string())),
+            },
+            Some("Node") => nodes.extend(serde_yaml::from_value::<Node>(object).ok()),
+            _ => {}
+        }
+    }
+
+    for rtresource in rtresources.iter() {
+        lint_spec(config, rtresource, &mut findings);
+        lint_template(config, rtresource, &rtresources, &nodes, &mut findings);
+    }
+    if nodes.is_empty() {
+        findings.push(Finding::new(
+            Severity::Info,
+            "NoNodes",
+            path,
+            "the file has no Nodes, the node checks and the schedulability analysis are skipped".to_string()
+        ));
+    } else {
+        let analysis = analyze(&SchedulingPolicy::default_policy(), &rtresources, &nodes);
+        for placement in analysis.unschedulable() {
+            let reasons: Vec<String> = placement.failures.iter()
+                .map(|f| format!("{} ({}: {})", f.node, f.plugin, f.reason))
+                .collect();
+            findings.push(Finding::new(
+                if placement.soft { Severity::Warning } else { Severity::Error },
+                "Unschedulable",
+                &format!("{}/{}", placement.namespace, placement.name),
+                format!("replica {} cannot be placed: {}", placement.ordinal, reasons.join(", "))
+            ));
+        }
+    }
+
+    let errors = findings.iter().filter(|f| f.severity == Severity::Error).count();
+    let warnings = findings.iter().filter(|f| f.severity == Severity::Warning).count();
+    let report = serde_json::json!({
+        "file": path,
+        "rtresources": rtresources.len(),
+        "nodes": nodes.len(),
+        "errors": errors,
+        "warnings": warnings,
+        "findings": findings.iter().map(Finding::to_json).collect::<Vec<_>>()
+    });
+    println!("{}", serde_json::to_string_pretty(&report)?);
+
+    if errors > 0 {
+        return Err(format!("{} errors found in {}", errors, path).into());
+    }
+    Ok(())
+}
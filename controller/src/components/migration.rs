@@ -0,0 +1,189 @@
+/*
+This file contains the migration of a Deployment to an RTResource
+(migrate deployment/<name> --criticality=<level>).
+The Deployment (in --namespace=<namespace>, default by default) is
+read and an equivalent RTResource is generated from it: same pod
+template (labels and annotations only in the metadata), selector
+and replicas, with the given criticality (--name=<name> overrides
+the name of the RTResource, the Deployment one by default).
+Without --cutover the RTResource is only printed as YAML, e.g. for
+review or a GitOps repository. With --cutover the migration is
+performed make-before-break: the RTResource is created with no
+replica, then, one replica at a time, the RTResource is scaled up
+and, once the new replica is Ready, the Deployment is scaled down,
+so that the serving capacity never drops. The progress is printed
+at each step; a replica not Ready within --step-timeout-s=<s>
+(300 by default) stops the migration, leaving both at their
+current scale for an operator to inspect.
+*/
+
+use std::{
+    env,
+    error::Error,
+    time::{
+        Duration,
+        Instant
+    }
+};
+use kube::{
+    Api,
+    Client,
+    api::{
+        Patch,
+        PatchParams,
+        PostParams
+    }
+};
+use k8s_openapi::{
+    api::apps::v1::Deployment,
+    apimachinery::pkg::apis::meta::v1::{
+        LabelSelector,
+        ObjectMeta
+    }
+};
+
+use crate::utils::configuration::ControllerConfig;
+use crate::utils::rtresource::{
+    RTResource,
+    RTResourceSpec,
+    Template,
+    Selector,
+    MatchExpression
+};
+
+
+
+/*
+Interval between two checks of the migrated replicas
+*/
+const PROGRESS_POLL: Duration = Duration::from_secs(2);
+
+/*
+Default bound on the readiness of a migrated replica
+*/
+const DEFAULT_STEP_TIMEOUT: Duration = Duration::from_secs(300);
+
+/*
+This function returns the value of a --key=<value> argument.
+*/
+fn argument(key: &str) -> Option<String> {
+    env::args().find_map(|arg| arg.strip_prefix(&format!("--{}=", key)).map(str::to_string))
+}
+
+/*
+This function converts the selector of a Deployment.
+*/
+fn selector(selector: &LabelSelector) -> Selector {
+    Selector {
+        match_labels: selector.match_labels.clone(),
+        match_expressions: selector.match_expressions.as_ref().map(|expressions| expressions.iter()
+            .map(|e| MatchExpression {
+                key: e.key.clone(),
+                operator: e.operator.clone(),
+                values: e.values.clone(),
+            })
+            .collect()),
+    }
+}
+
+/*
+This function generates the RTResource equivalent to a Deployment.
+*/
+fn rtresource_of(deployment: &Deployment, name: &str, criticality: u32) -> Result<RTResource, String> {
+    let namespace = deployment.metadata.namespace.clone().unwrap_or_default();
+    let spec = deployment.spec.as_ref().ok_or("The Deployment has no spec")?;
+    let template_metadata = spec.template.metadata.as_ref().map(|m| ObjectMeta {
+        labels: m.labels.clone(),
+        annotations: m.annotations.clone(),
+        ..ObjectMeta::default()
+    });
+    let mut rtresource = RTResource::new(name, RTResourceSpec {
+        namespace: namespace.clone(),
+        replicas: Some(spec.replicas.unwrap_or(1)),
+        selector: Some(selector(&spec.selector)),
+        criticality,
+        template: Template {
+            metadata: template_metadata,
+            spec: spec.template.spec.clone(),
+        },
+        ..RTResourceSpec::default()
+    });
+    rtresource.metadata.namespace = Some(namespace);
+    Ok(rtresource)
+}
+
+/*
+This function scales a Deployment.
+*/
+async fn scale_deployment(api: &Api<Deployment>, name: &str, replicas: i32) -> Result<(), kube::Error> {
+    let patch = serde_json::json!({"spec": {"replicas": replicas}});
+    api.patch(name, &PatchParams::default(), &Patch::Merge(&patch)).await?;
+    Ok(())
+}
+
+/*
+This function scales an RTResource and waits for its
+replicas to be Ready, up to the given timeout.
+*/
+async fn scale_rtresource(api: &Api<RTResource>, name: &str, replicas: i32, timeout: Duration) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    let patch = serde_json::json!({"spec": {"replicas": replicas}});
+    api.patch(name, &PatchParams::default(), &Patch::Merge(&patch)).await?;
+    let start = Instant::now();
+    loop {
+        let ready = api.get(name).await?.status.and_then(|s| s.ready_replicas).unwrap_or(0);
+        if ready >= replicas {
+            return Ok(());
+        }
+        if start.elapsed() >= timeout {
+            return Err(format!("RTResource {} has {} of {} Ready replicas after {:?}", name, ready, replicas, timeout).into());
+        }
+        tokio::time::sleep(PROGRESS_POLL).await;
+    }
+}
+
+/*
+This function migrates a Deployment (deployment/<name>) to
+an RTResource, printing it or performing the cutover.
+*/
+pub async fn run_migration(config: &ControllerConfig, target: &str) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    let deployment_name = target.strip_prefix("deployment/")
+        .or_else(|| target.strip_prefix("deployments/"))
+        .ok_or("usage: migrate deployment/<name> --criticality=<level>")?;
+    let criticality: u32 = argument("criticality")
+        .ok_or("usage: migrate deployment/<name> --criticality=<level>")?
+        .parse()
+        .map_err(|_| "The criticality must be an integer")?;
+    if criticality == 0 || criticality > config.criticality_max {
+        return Err(format!("The criticality must be between 1 and {}", config.criticality_max).into());
+    }
+    let namespace = argument("namespace").unwrap_or_else(|| "default".to_string());
+    let name = argument("name").unwrap_or_else(|| deployment_name.to_string());
+    let step_timeout = argument("step-timeout-s")
+        .and_then(|t| t.parse().ok())
+        .map_or(DEFAULT_STEP_TIMEOUT, Duration::from_secs);
+
+    let client = Client::try_default().await?;
+    let deployments = Api::<Deployment>::namespaced(client.clone(), &namespace);
+    let deployment = deployments.get(deployment_name).await?;
+    let mut rtresource = rtresource_of(&deployment, &name, criticality)?;
+    if !env::args().any(|arg| arg == "--cutover") {
+        print!("{}", serde_yaml::to_string(&rtresource)?);
+        return Ok(());
+    }
+
+    /*
+    Make-before-break cutover, one replica at a time.
+    */
+    let replicas = rtresource.spec.replicas.unwrap_or(1);
+    let rtresources = Api::<RTResource>::namespaced(client, &namespace);
+    rtresource.spec.replicas = Some(0);
+    rtresources.create(&PostParams::default(), &rtresource).await?;
+    println!("Migration - Created RTResource {}/{} (criticality {}) for Deployment {} with {} replicas.", namespace, name, criticality, deployment_name, replicas);
+    for migrated in 1..=replicas {
+        scale_rtresource(&rtresources, &name, migrated, step_timeout).await?;
+        scale_deployment(&deployments, deployment_name, replicas - migrated).await?;
+        println!("Migration - {} of {} replicas migrated.", migrated, replicas);
+    }
+    println!("Migration - Deployment {} is scaled to 0 and can be deleted once RTResource {} is verified.", deployment_name, name);
+    Ok(())
+}
@@ -3,4 +3,70 @@ pub mod pod_watcher;
 pub mod event_server;
 pub mod watchdog;
 pub mod resource_state_updater;
-pub mod scheduling;
\ No newline at end of file
+pub mod scheduling;
+pub mod planner;
+pub mod alerting;
+pub mod decisions;
+pub mod scheduling_policy;
+pub mod scheduling_plugins;
+pub mod queue_stats;
+pub mod admin_server;
+pub mod resurrection;
+pub mod dispatcher;
+pub mod template_validation;
+pub mod capacity_index;
+pub mod info_publisher;
+pub mod adoption;
+pub mod scheduler_webhook;
+pub mod startup_recovery;
+pub mod slo_metrics;
+#[cfg(feature = "chaos")]
+pub mod chaos;
+pub mod node_taints;
+pub mod thread_dump;
+pub mod pod_defaults;
+pub mod circuit_breaker;
+pub mod node_failures;
+pub mod archival;
+pub mod conflicts;
+pub mod orphan_sweeper;
+pub mod provisioning;
+pub mod admin_auth;
+pub mod criticality_defaults;
+pub mod activation_windows;
+pub mod services;
+pub mod preemption_history;
+pub mod priority_oracle;
+pub mod image_variants;
+pub mod handover;
+pub mod ready_wait;
+pub mod schedulability;
+pub mod analysis;
+pub mod partial_placement;
+pub mod node_maintenance;
+pub mod security_hardening;
+pub mod status_verifier;
+pub mod criticality_class;
+pub mod cluster_stats;
+pub mod volume_topology;
+pub mod startup_sequencing;
+pub mod lint;
+pub mod pod_protection;
+pub mod crash_dump;
+pub mod headroom;
+pub mod namespace_policy;
+pub mod event_compaction;
+pub mod priority_decay;
+pub mod crash_loop;
+pub mod reservation_preemption;
+pub mod node_heartbeats;
+pub mod schemas;
+pub mod label_guard;
+pub mod concurrency_metrics;
+pub mod node_loss;
+pub mod pod_janitor;
+pub mod migration;
+pub mod replicaset_backend;
+pub mod recovery_probe;
+pub mod rbac;
+pub mod config_watcher;
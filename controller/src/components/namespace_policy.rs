@@ -0,0 +1,155 @@
+/*
+This file contains the namespace placement policy (NAMESPACE_POLICY).
+The pods of an RTResource are created in spec.namespace, which a
+tenant could otherwise point at the namespaces of other teams.
+With the policy enabled, an RTResource may only deploy to:
+    - same: its own namespace;
+    - allowlist: its own namespace and the namespaces listed for it
+      in NAMESPACE_ALLOWLIST.
+The policy is enforced:
+    - at admission, by the generated Kyverno policy (--print-policy);
+    - by the watchdogs, which create no pod for a violating RTResource
+      and report it in the NamespaceAllowed condition;
+    - in create_pod, as a last line of defence.
+*/
+
+use std::{
+    fmt,
+    error::Error
+};
+
+use crate::utils::configuration::{
+    ControllerConfig,
+    NamespacePolicy
+};
+use crate::utils::rtresource::RTResource;
+
+
+
+/*
+Condition reporting whether spec.namespace is allowed
+*/
+pub const NAMESPACE_CONDITION: &str = "NamespaceAllowed";
+
+/*
+Name of the generated Kyverno policy
+*/
+const POLICY_NAME: &str = "preempt-k8s-namespace-policy";
+
+/*
+Error returned when an RTResource targets
+a namespace it is not allowed to deploy to
+*/
+#[derive(Debug)]
+pub struct NamespaceNotAllowed {
+    pub rtresource: String,
+    pub namespace: String,
+    pub target: String,
+}
+
+impl fmt::Display for NamespaceNotAllowed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "RTResource {} in namespace {} may not deploy its pods to namespace {}!",
+            self.rtresource,
+            self.namespace,
+            self.target
+        )
+    }
+}
+
+impl Error for NamespaceNotAllowed {}
+
+/*
+This function returns the namespaces the RTResources of
+the given namespace may deploy to (None = any namespace).
+*/
+pub fn allowed_namespaces(config: &ControllerConfig, namespace: &str) -> Option<Vec<String>> {
+    match config.namespace_policy {
+        NamespacePolicy::Any => None,
+        NamespacePolicy::Same => Some(vec![namespace.to_string()]),
+        NamespacePolicy::Allowlist => {
+            let mut allowed = vec![namespace.to_string()];
+            allowed.extend(config.namespace_allowlist.get(namespace).into_iter().flatten().cloned());
+            Some(allowed)
+        }
+    }
+}
+
+/*
+This function checks the spec.namespace of an RTResource
+against the namespaces it may deploy to.
+*/
+pub fn check_target_namespace(allowed: Option<&[String]>, rtresource: &RTResource) -> Result<(), NamespaceNotAllowed> {
+    let target = &rtresource.spec.namespace;
+    match allowed {
+        Some(allowed) if !allowed.contains(target) => Err(NamespaceNotAllowed {
+            rtresource: rtresource.metadata.name.clone().unwrap_or_default(),
+            namespace: rtresource.metadata.namespace.clone().unwrap_or_default(),
+            target: target.clone(),
+        }),
+        _ => Ok(()),
+    }
+}
+
+/*
+This function returns the Kyverno policy rejecting the RTResources
+whose spec.namespace is not allowed, if NAMESPACE_POLICY is enabled.
+The namespaces with an allowlist entry get a rule of their own,
+the others may only deploy to themselves.
+*/
+pub fn namespace_policy(config: &ControllerConfig) -> Option<serde_json::Value> {
+    let rule = |name: String, namespaces: Option<Vec<String>>, excluded: &[String], allowed: serde_json::Value| {
+        let mut resources = serde_json::json!({"kinds": ["rtgroup.critical.com/v1/RTResource"], "operations": ["CREATE", "UPDATE"]});
+        if let Some(namespaces) = namespaces {
+            resources["namespaces"] = serde_json::json!(namespaces);
+        }
+        let mut rule = serde_json::json!({
+            "name": name,
+            "match": {"any": [{"resources": resources}]},
+            "validate": {
+                "message": "spec.namespace is not allowed for the RTResources of this namespace (NAMESPACE_POLICY).",
+                "deny": {"conditions": {"all": [{
+                    "key": "{{ request.object.spec.namespace }}",
+                    "operator": "AnyNotIn",
+                    "value": allowed
+                }]}}
+            }
+        });
+        if !excluded.is_empty() {
+            rule["exclude"] = serde_json::json!({"any": [{"resources": {"namespaces": excluded}}]});
+        }
+        rule
+    };
+
+    let listed: Vec<String> = match config.namespace_policy {
+        NamespacePolicy::Any => return None,
+        NamespacePolicy::Same => Vec::new(),
+        NamespacePolicy::Allowlist => config.namespace_allowlist.keys().cloned().collect(),
+    };
+    let mut rules = vec![rule("same-namespace".to_string(), None, &listed, serde_json::json!(["{{ request.namespace }}"]))];
+    for namespace in listed.iter() {
+        rules.push(rule(
+            format!("allowlist-{}", namespace),
+            Some(vec![namespace.clone()]),
+            &[],
+            serde_json::json!(allowed_namespaces(config, namespace))
+        ));
+    }
+    Some(serde_json::json!({
+        "apiVersion": "kyverno.io/v1",
+        "kind": "ClusterPolicy",
+        "metadata": {
+            "name": POLICY_NAME,
+            "annotations": {
+                "policies.kyverno.io/description": "Generated by Preempt-K8s (--print-policy) from the controller configuration."
+            }
+        },
+        "spec": {
+            "validationFailureAction": "Enforce",
+            "background": false,
+            "rules": rules
+        }
+    }))
+}
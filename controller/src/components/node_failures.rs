@@ -0,0 +1,80 @@
+/*
+This file contains the tracking of the replicas lost to node
+failures, used by the ZoneFailover scheduling plugin to keep
+recreated replicas away from the failed zones.
+*/
+
+use std::time::{
+    Duration,
+    Instant
+};
+use libc::{
+    pthread_mutex_lock,
+    pthread_mutex_unlock
+};
+use k8s_openapi::api::core::v1::Pod;
+
+use crate::utils::vars::SharedState;
+
+
+
+/*
+Time a node failure is remembered for an RTResource
+*/
+const NODE_FAILURE_MEMORY: Duration = Duration::from_secs(600);
+
+/*
+This function returns whether a deleted pod was lost
+because of a failure of its node (node lost, node shutdown
+or eviction by the taint manager of a not ready node).
+*/
+pub fn is_node_failure(pod: &Pod) -> bool {
+    let Some(status) = pod.status.as_ref() else {
+        return false;
+    };
+    if matches!(status.reason.as_deref(), Some("NodeLost") | Some("NodeShutdown")) {
+        return true;
+    }
+    status.conditions.as_ref()
+        .map(|c| c.iter().any(|c| {
+            c.type_ == "DisruptionTarget" && c.status == "True" && c.reason.as_deref() == Some("DeletionByTaintManager")
+        }))
+        .unwrap_or(false)
+}
+
+/*
+This function records that a replica of an RTResource
+was lost to the failure of the given node.
+It must be called without holding the shared mutex.
+*/
+pub fn record_node_failure(shared_state: &mut SharedState, uid: &str, node: &str) {
+    unsafe {
+        pthread_mutex_lock(&mut shared_state.mutex);
+        let failures = shared_state.node_failures.entry(uid.to_string()).or_default();
+        failures.retain(|(n, at)| n != node && at.elapsed() < NODE_FAILURE_MEMORY);
+        failures.push((node.to_string(), Instant::now()));
+        pthread_mutex_unlock(&mut shared_state.mutex);
+    }
+}
+
+/*
+This function returns the nodes where replicas of an
+RTResource were recently lost, forgetting older failures.
+It must be called without holding the shared mutex.
+*/
+pub fn recent_node_failures(shared_state: &mut SharedState, uid: &str) -> Vec<String> {
+    unsafe {
+        pthread_mutex_lock(&mut shared_state.mutex);
+        let mut nodes: Vec<String> = Vec::new();
+        if let Some(failures) = shared_state.node_failures.get_mut(uid) {
+            failures.retain(|(_, at)| at.elapsed() < NODE_FAILURE_MEMORY);
+            nodes = failures.iter().map(|(n, _)| n.clone()).collect();
+            if failures.is_empty() {
+                shared_state.node_failures.remove(uid);
+            }
+        }
+        pthread_mutex_unlock(&mut shared_state.mutex);
+
+        nodes
+    }
+}
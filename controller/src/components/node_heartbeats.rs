@@ -0,0 +1,146 @@
+/*
+This file contains the heartbeat freshness check of the nodes
+(NODE_HEARTBEAT_FRESHNESS_MS).
+The kubelet renews the Lease of its node (kube-node-lease namespace)
+every few seconds, and the node is declared dead by the node lifecycle
+controller only after the grace period. A replacement replica of the
+top criticality band bound to a node that stopped renewing its Lease
+would be lost again, wasting the recovery deadline: the built-in
+scheduler therefore discards the nodes whose Lease was not renewed
+within the freshness window when placing top-band pods.
+The renewals are timed on the controller clock, when observed, so
+that the check does not depend on the clock skew of the nodes (only
+the first observation of a Lease is dated from its renewTime).
+Nodes whose Lease was not observed yet are not discarded.
+*/
+
+use std::{
+    collections::HashMap,
+    time::{
+        Duration,
+        Instant
+    }
+};
+use libc::{
+    pthread_mutex_lock,
+    pthread_mutex_unlock
+};
+use kube::{
+    Api,
+    Client,
+    runtime::watcher::{
+        watcher,
+        Config,
+        Event
+    }
+};
+use k8s_openapi::{
+    api::coordination::v1::Lease,
+    chrono::{
+        DateTime,
+        Utc
+    }
+};
+use futures::StreamExt;
+
+use crate::utils::vars::{
+    SharedState,
+    SharedStatePtr
+};
+
+
+
+/*
+Namespace of the node Leases
+*/
+pub const NODE_LEASE_NAMESPACE: &str = "kube-node-lease";
+
+/*
+This function returns the renewal time of a Lease.
+*/
+fn renew_time(lease: &Lease) -> Option<DateTime<Utc>> {
+    lease.spec.as_ref()?.renew_time.as_ref().map(|t| t.0)
+}
+
+/*
+This function records the renewal of a node Lease, if it was renewed
+since the last observation (the first observation is dated back by
+the age of the renewal).
+It must be called while holding the shared mutex.
+*/
+fn observe_lease(shared_state: &mut SharedState, seen: &mut HashMap<String, DateTime<Utc>>, lease: &Lease) {
+    let (Some(node), Some(renewed)) = (lease.metadata.name.as_ref(), renew_time(lease)) else {
+        return;
+    };
+    match seen.insert(node.clone(), renewed) {
+        Some(previous) if previous == renewed => {}
+        Some(_) => {
+            shared_state.node_heartbeats.insert(node.clone(), Instant::now());
+        }
+        None => {
+            let age = (Utc::now() - renewed).to_std().unwrap_or_default();
+            let heartbeat = Instant::now().checked_sub(age).unwrap_or_else(Instant::now);
+            shared_state.node_heartbeats.insert(node.clone(), heartbeat);
+        }
+    }
+}
+
+/*
+This function keeps the node heartbeats up to date
+from the node Leases.
+It runs as a Tokio task, since it is not time critical.
+*/
+pub async fn node_lease_watcher(client: Client, state: SharedStatePtr) {
+    let mut seen: HashMap<String, DateTime<Utc>> = HashMap::new();
+    let mut watcher = watcher(Api::<Lease>::namespaced(client, NODE_LEASE_NAMESPACE), Config::default()).boxed();
+    while let Some(event) = watcher.next().await {
+        let shared_state = unsafe { &mut *state.0 };
+        unsafe {
+            pthread_mutex_lock(&mut shared_state.mutex);
+            match &event {
+                Ok(Event::Applied(lease)) => observe_lease(shared_state, &mut seen, lease),
+                Ok(Event::Deleted(lease)) => {
+                    let node = lease.metadata.name.clone().unwrap_or_default();
+                    seen.remove(&node);
+                    shared_state.node_heartbeats.remove(&node);
+                }
+                Ok(Event::Restarted(leases)) => {
+                    let nodes: Vec<&String> = leases.iter().filter_map(|l| l.metadata.name.as_ref()).collect();
+                    seen.retain(|node, _| nodes.contains(&node));
+                    shared_state.node_heartbeats.retain(|node, _| nodes.contains(&node));
+                    for lease in leases {
+                        observe_lease(shared_state, &mut seen, lease);
+                    }
+                }
+                Err(_) => {}
+            }
+            pthread_mutex_unlock(&mut shared_state.mutex);
+        }
+        if let Err(e) = event {
+            eprintln!("Node Lease Watcher - {}", e);
+        }
+    }
+}
+
+/*
+This function returns the nodes whose Lease was not
+renewed within the freshness window (none when the
+check is disabled).
+It must be called without holding the shared mutex.
+*/
+pub fn stale_nodes(shared_state: &mut SharedState) -> Vec<String> {
+    let freshness = Duration::from_millis(shared_state.config.node_heartbeat_freshness_ms);
+    if freshness.is_zero() {
+        return Vec::new();
+    }
+    unsafe {
+        pthread_mutex_lock(&mut shared_state.mutex);
+        let stale = shared_state.node_heartbeats.iter()
+            .filter(|(_, heartbeat)| heartbeat.elapsed() > freshness)
+            .map(|(node, _)| node.clone())
+            .collect();
+        pthread_mutex_unlock(&mut shared_state.mutex);
+
+        stale
+    }
+}
@@ -0,0 +1,280 @@
+/*
+This file contains the node loss simulation of the admin API
+(GET /simulate/node-loss?node=<name>), for the single-failure
+tolerance analysis. From the live pods of the cluster, it reports:
+    - the RTResources that would drop below their desired replicas
+      once the replicas bound to the node are lost;
+    - whether the remaining nodes can absorb the replacements, placed
+      most critical first on the node with the most free CPU, within
+      the criticality constraints (the dedicated nodes only take the
+      top band, the nodes under maintenance take nothing);
+    - the preemptions the replacements would need when they do not fit:
+      like the scheduler does, only pods of a lower priority are evicted
+      (fewest victims first), and never by the Soft RTResources, whose
+      pods do not preempt.
+Nothing is changed in the cluster.
+*/
+
+use std::collections::{
+    BTreeMap,
+    HashMap
+};
+use libc::{
+    pthread_mutex_lock,
+    pthread_mutex_unlock
+};
+use kube::{
+    Api,
+    api::ListParams
+};
+use k8s_openapi::api::core::v1::{
+    Node,
+    Pod
+};
+
+use crate::utils::vars::SharedStatePtr;
+use crate::utils::rtresource::RTResource;
+use crate::utils::quantity::resource_amount;
+use crate::utils::priorities::effective_criticality;
+use crate::components::capacity_index::requested;
+use crate::components::headroom::query_parameters;
+use crate::components::node_taints::{
+    dedicated_nodes_enabled,
+    is_dedicated
+};
+use crate::utils::owner_labels::owner_labels;
+
+
+
+/*
+Pod holding resources on a node
+*/
+#[derive(Clone)]
+struct BoundPod {
+    name: String,
+    namespace: String,
+    node: String,
+    priority: i32,
+    preempts: bool,
+    cpu: i64,
+    memory: i64,
+    node_selector: BTreeMap<String, String>,
+    /*
+    The RTResource UID of a managed pod
+    */
+    owner: Option<String>,
+}
+
+/*
+Node left after the loss, with its free capacity
+(CPU in millicores, memory in bytes)
+*/
+struct RemainingNode {
+    node: Node,
+    free_cpu: i64,
+    free_memory: i64,
+    pods: Vec<BoundPod>,
+}
+
+/*
+This function returns the pod bound to a node, if it holds resources.
+*/
+fn bound_pod(pod: &Pod) -> Option<BoundPod> {
+    let spec = pod.spec.as_ref()?;
+    let phase = pod.status.as_ref().and_then(|s| s.phase.as_deref());
+    if matches!(phase, Some("Succeeded") | Some("Failed")) || pod.metadata.deletion_timestamp.is_some() {
+        return None;
+    }
+    let (cpu, memory) = requested(spec);
+    Some(BoundPod {
+        name: pod.metadata.name.clone().unwrap_or_default(),
+        namespace: pod.metadata.namespace.clone().unwrap_or_default(),
+        node: spec.node_name.clone()?,
+        priority: spec.priority.unwrap_or(0),
+        preempts: spec.preemption_policy.as_deref() != Some("Never"),
+        cpu,
+        memory,
+        node_selector: spec.node_selector.clone().unwrap_or_default(),
+        owner: pod.metadata.labels.as_ref().and_then(|l| l.get(&owner_labels().uid)).cloned(),
+    })
+}
+
+/*
+This function returns the pods to evict from a node for a replacement
+to fit (lowest priorities first), None if it cannot fit.
+*/
+fn victims(node: &RemainingNode, replacement: &BoundPod) -> Option<Vec<BoundPod>> {
+    let mut candidates: Vec<&BoundPod> = node.pods.iter().filter(|p| p.priority < replacement.priority).collect();
+    candidates.sort_by_key(|p| (p.priority, -p.cpu));
+    let (mut free_cpu, mut free_memory) = (node.free_cpu, node.free_memory);
+    let mut evicted = Vec::new();
+    for victim in candidates {
+        if free_cpu >= replacement.cpu && free_memory >= replacement.memory {
+            break;
+        }
+        free_cpu += victim.cpu;
+        free_memory += victim.memory;
+        evicted.push(victim.clone());
+    }
+    (free_cpu >= replacement.cpu && free_memory >= replacement.memory).then_some(evicted)
+}
+
+/*
+This function simulates the loss of a node.
+It returns the report, or the error to answer with.
+It must be called without holding the shared mutex.
+*/
+pub async fn simulate_node_loss(state: SharedStatePtr, query: Option<&str>) -> Result<serde_json::Value, String> {
+    let shared_state = unsafe { &mut *state.0 };
+    let config = shared_state.config.clone();
+    let client = shared_state.client.clone();
+    let parameters = query_parameters(query);
+    let failed = parameters.get("node").ok_or("Expected ?node=<name>")?.clone();
+    let maintenance = unsafe {
+        pthread_mutex_lock(&mut shared_state.mutex);
+        let maintenance = shared_state.maintenance_nodes.clone();
+        pthread_mutex_unlock(&mut shared_state.mutex);
+
+        maintenance
+    };
+
+    let lp = ListParams::default();
+    let nodes = Api::<Node>::all(client.clone()).list(&lp).await.map_err(|e| e.to_string())?.items;
+    if !nodes.iter().any(|n| n.metadata.name.as_deref() == Some(failed.as_str())) {
+        return Err(format!("Node {} not found", failed));
+    }
+    let rtresources = Api::<RTResource>::all(client.clone()).list(&lp).await.map_err(|e| e.to_string())?.items;
+    let pods: Vec<BoundPod> = Api::<Pod>::all(client).list(&lp).await.map_err(|e| e.to_string())?.items
+        .iter()
+        .filter_map(bound_pod)
+        .collect();
+    let criticality: HashMap<String, u32> = rtresources.iter()
+        .map(|r| (
+            r.metadata.uid.clone().unwrap_or_default(),
+            effective_criticality(&config, r.metadata.namespace.as_deref().unwrap_or_default(), r.spec.criticality)
+        ))
+        .collect();
+
+    /*
+    The RTResources losing replicas on the node.
+    */
+    let (lost, survivors): (Vec<BoundPod>, Vec<BoundPod>) = pods.into_iter().partition(|p| p.node == failed);
+    let affected: Vec<serde_json::Value> = rtresources.iter()
+        .filter_map(|r| {
+            let uid = r.metadata.uid.clone().unwrap_or_default();
+            let lost_replicas = lost.iter().filter(|p| p.owner.as_ref() == Some(&uid)).count() as i32;
+            if lost_replicas == 0 {
+                return None;
+            }
+            let desired = r.status.as_ref().and_then(|s| s.desired_replicas).unwrap_or(r.spec.replicas.unwrap_or(1));
+            let remaining = survivors.iter().filter(|p| p.owner.as_ref() == Some(&uid)).count() as i32;
+            Some(serde_json::json!({
+                "name": r.metadata.name.clone().unwrap_or_default(),
+                "namespace": r.spec.namespace,
+                "uid": uid,
+                "criticality": criticality.get(&uid),
+                "desiredReplicas": desired,
+                "lostReplicas": lost_replicas,
+                "remainingReplicas": remaining,
+                "belowDesired": remaining < desired
+            }))
+        })
+        .collect();
+
+    /*
+    The nodes left, with the pods bound to them.
+    */
+    let mut remaining: Vec<RemainingNode> = nodes.into_iter()
+        .filter(|n| {
+            let name = n.metadata.name.clone().unwrap_or_default();
+            name != failed
+                && !maintenance.contains(&name)
+                && !n.spec.as_ref().and_then(|s| s.unschedulable).unwrap_or(false)
+        })
+        .map(|n| {
+            let name = n.metadata.name.clone().unwrap_or_default();
+            let allocatable = n.status.as_ref().and_then(|s| s.allocatable.as_ref());
+            let pods: Vec<BoundPod> = survivors.iter().filter(|p| p.node == name).cloned().collect();
+            RemainingNode {
+                free_cpu: (resource_amount(allocatable, "cpu") * 1000.0) as i64 - pods.iter().map(|p| p.cpu).sum::<i64>(),
+                free_memory: resource_amount(allocatable, "memory") as i64 - pods.iter().map(|p| p.memory).sum::<i64>(),
+                node: n,
+                pods,
+            }
+        })
+        .collect();
+
+    /*
+    The replacements of the managed replicas, most critical first.
+    */
+    let mut replacements: Vec<BoundPod> = lost.into_iter().filter(|p| p.owner.is_some()).collect();
+    replacements.sort_by_key(|p| (p.owner.as_ref().and_then(|o| criticality.get(o)).copied().unwrap_or(u32::MAX), -p.priority));
+    let dedicated_enabled = dedicated_nodes_enabled(&config);
+    let mut absorbed = true;
+    let mut placements: Vec<serde_json::Value> = Vec::new();
+    for replacement in replacements {
+        let level = replacement.owner.as_ref().and_then(|o| criticality.get(o)).copied().unwrap_or(u32::MAX);
+        let top_band = level <= config.critical_band_max;
+        let eligible = |n: &RemainingNode| {
+            let labels = n.node.metadata.labels.clone().unwrap_or_default();
+            replacement.node_selector.iter().all(|(k, v)| labels.get(k) == Some(v))
+                && (top_band || !dedicated_enabled || !is_dedicated(&config, &n.node))
+        };
+        let fitting = remaining.iter()
+            .enumerate()
+            .filter(|(_, n)| eligible(*n) && n.free_cpu >= replacement.cpu && n.free_memory >= replacement.memory)
+            .max_by_key(|(_, n)| n.free_cpu)
+            .map(|(i, _)| (i, Vec::new()));
+        let choice = fitting.or_else(|| {
+            if !replacement.preempts {
+                return None;
+            }
+            remaining.iter()
+                .enumerate()
+                .filter(|(_, n)| eligible(*n))
+                .filter_map(|(i, n)| victims(n, &replacement).map(|v| (i, v)))
+                .min_by_key(|(_, v)| (v.len(), v.iter().map(|p| p.priority).max()))
+        });
+
+        let Some((index, evicted)) = choice else {
+            absorbed = false;
+            placements.push(serde_json::json!({
+                "pod": format!("{}/{}", replacement.namespace, replacement.name),
+                "criticality": level,
+                "node": null,
+                "preemptions": []
+            }));
+            continue;
+        };
+        let node = &mut remaining[index];
+        for victim in evicted.iter() {
+            node.pods.retain(|p| !(p.name == victim.name && p.namespace == victim.namespace));
+            node.free_cpu += victim.cpu;
+            node.free_memory += victim.memory;
+        }
+        node.free_cpu -= replacement.cpu;
+        node.free_memory -= replacement.memory;
+        let node_name = node.node.metadata.name.clone().unwrap_or_default();
+        node.pods.push(BoundPod {node: node_name.clone(), ..replacement.clone()});
+        placements.push(serde_json::json!({
+            "pod": format!("{}/{}", replacement.namespace, replacement.name),
+            "criticality": level,
+            "node": node_name,
+            "preemptions": evicted.iter()
+                .map(|v| serde_json::json!({
+                    "pod": format!("{}/{}", v.namespace, v.name),
+                    "priority": v.priority,
+                    "rtresourceUid": v.owner
+                }))
+                .collect::<Vec<_>>()
+        }));
+    }
+
+    Ok(serde_json::json!({
+        "node": failed,
+        "affected": affected,
+        "absorbed": absorbed,
+        "preemptions": placements.iter().map(|p| p["preemptions"].as_array().map_or(0, Vec::len)).sum::<usize>(),
+        "replacements": placements
+    }))
+}
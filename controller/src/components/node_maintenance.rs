@@ -0,0 +1,342 @@
+/*
+This file contains the migration of the managed pods off the nodes
+under maintenance: the nodes labelled rtgroup.critical.com/maintenance
+(cordoned by the controller until the label is removed) and, with
+MAINTENANCE_ON_CORDON, the nodes cordoned by the administrators.
+When a node enters maintenance, the RTResources with pods on it are
+enqueued at their criticality, so that the most critical ones are
+migrated first. The watchdogs plan the replicas of the pods on the
+node as missing, create their replacements elsewhere, wait for them
+to be Ready (until the recovery deadline) and only then evict the old
+pods through the Eviction API, which respects the PodDisruptionBudgets
+(make-before-break). The RTResources still having pods on a node under
+maintenance are enqueued again periodically (e.g. after an eviction
+refused by a PodDisruptionBudget).
+The MaintenanceSafe condition of the node reports whether every managed
+pod left it, i.e. whether the maintenance can start.
+*/
+
+use std::{
+    collections::{
+        HashMap,
+        HashSet
+    },
+    ffi::CString,
+    time::Duration
+};
+use libc::{
+    pthread_mutex_lock,
+    pthread_mutex_unlock
+};
+use kube::{
+    Api,
+    Client,
+    api::{
+        EvictParams,
+        ListParams,
+        Patch,
+        PatchParams
+    },
+    runtime::watcher::{
+        watcher,
+        Config,
+        Event
+    }
+};
+use k8s_openapi::api::core::v1::{
+    Node,
+    Pod
+};
+use futures::StreamExt;
+
+use crate::utils::vars::{
+    SharedState,
+    SharedStatePtr,
+    QueueMessage,
+    EventKind
+};
+use crate::utils::configuration::ControllerConfig;
+use crate::utils::event_queue::enqueue_once;
+use crate::utils::priorities::effective_criticality;
+use crate::utils::owner_labels::owner_labels;
+
+
+
+/*
+Label putting a node under maintenance
+(any value but "false")
+*/
+pub const MAINTENANCE_LABEL: &str = "rtgroup.critical.com/maintenance";
+
+/*
+Annotation marking the nodes cordoned by the controller
+*/
+const CORDONED_ANNOTATION: &str = "rtgroup.critical.com/maintenance-cordoned";
+
+/*
+Node condition reporting whether the managed pods left the node
+*/
+pub const MAINTENANCE_SAFE_CONDITION: &str = "MaintenanceSafe";
+
+/*
+Interval between two checks of the nodes under maintenance
+*/
+const MAINTENANCE_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/*
+This function returns whether a node carries the maintenance label.
+*/
+fn is_labelled(node: &Node) -> bool {
+    node.metadata.labels.as_ref()
+        .and_then(|l| l.get(MAINTENANCE_LABEL))
+        .is_some_and(|v| v != "false")
+}
+
+/*
+This function returns whether a node is under maintenance.
+*/
+fn in_maintenance(config: &ControllerConfig, node: &Node) -> bool {
+    let cordoned = node.spec.as_ref().and_then(|s| s.unschedulable).unwrap_or(false);
+    let cordoned_by_us = node.metadata.annotations.as_ref().is_some_and(|a| a.contains_key(CORDONED_ANNOTATION));
+    is_labelled(node) || (config.maintenance_on_cordon && cordoned && !cordoned_by_us)
+}
+
+/*
+This function returns the nodes under maintenance.
+It must be called without holding the shared mutex.
+*/
+pub fn maintenance_nodes(shared_state: &mut SharedState) -> HashSet<String> {
+    unsafe {
+        pthread_mutex_lock(&mut shared_state.mutex);
+        let nodes = shared_state.maintenance_nodes.clone();
+        pthread_mutex_unlock(&mut shared_state.mutex);
+
+        nodes
+    }
+}
+
+/*
+This function replaces the nodes under maintenance.
+It must be called without holding the shared mutex.
+*/
+fn set_maintenance_nodes(state: SharedStatePtr, nodes: HashSet<String>) {
+    let shared_state = unsafe { &mut *state.0 };
+    unsafe {
+        pthread_mutex_lock(&mut shared_state.mutex);
+        shared_state.maintenance_nodes = nodes;
+        pthread_mutex_unlock(&mut shared_state.mutex);
+    }
+}
+
+/*
+This function returns the managed pods still running on a node.
+*/
+async fn managed_pods_on(client: Client, node: &str) -> Result<Vec<Pod>, kube::Error> {
+    let lp = ListParams::default()
+        .labels(&owner_labels().uid)
+        .fields(&format!("spec.nodeName={}", node));
+    let pods = Api::<Pod>::all(client).list(&lp).await?.items;
+    Ok(pods.into_iter()
+        .filter(|p| p.metadata.deletion_timestamp.is_none())
+        .filter(|p| !matches!(p.status.as_ref().and_then(|s| s.phase.as_deref()), Some("Succeeded") | Some("Failed")))
+        .collect())
+}
+
+/*
+This function enqueues the RTResources of the given pods,
+most critical first.
+*/
+fn enqueue_owners(config: &ControllerConfig, queue: &CString, node: &str, pods: &[Pod]) {
+    let mut owners: HashMap<String, (QueueMessage, u32)> = HashMap::new();
+    for pod in pods {
+        let labels = pod.metadata.labels.clone().unwrap_or_default();
+        let Some((name, uid, namespace)) = owner_labels().owner(&labels) else {
+            continue;
+        };
+        let raw = owner_labels().criticality(&labels).unwrap_or(config.criticality_max);
+        owners.entry(uid.clone()).or_insert_with(|| (
+            QueueMessage {
+                name: name.clone(),
+                uid: uid.clone(),
+                namespace: namespace.clone(),
+                enqueued_at: 0,
+                kind: EventKind::Resync,
+                trace_id: String::new(),
+            },
+            effective_criticality(config, namespace, raw)
+        ));
+    }
+    let mut owners: Vec<(QueueMessage, u32)> = owners.into_values().collect();
+    owners.sort_by_key(|(_, criticality)| *criticality);
+    for (msg, criticality) in owners {
+        println!(
+            "Node Maintenance - Migrating the pods of RTResource {}, {} in namespace {} off node {}.",
+            msg.name,
+            msg.uid,
+            msg.namespace,
+            node
+        );
+        enqueue_once("Node Maintenance", queue, &msg, criticality);
+    }
+}
+
+/*
+This function cordons a labelled node, or uncordons a node
+cordoned by the controller once its label is removed.
+*/
+async fn update_cordon(client: Client, node: &Node) {
+    let Some(name) = node.metadata.name.as_deref() else {
+        return;
+    };
+    let cordoned = node.spec.as_ref().and_then(|s| s.unschedulable).unwrap_or(false);
+    let cordoned_by_us = node.metadata.annotations.as_ref().is_some_and(|a| a.contains_key(CORDONED_ANNOTATION));
+    let patch = match (is_labelled(node), cordoned, cordoned_by_us) {
+        (true, false, _) => serde_json::json!({
+            "metadata": {"annotations": {CORDONED_ANNOTATION: "true"}},
+            "spec": {"unschedulable": true}
+        }),
+        (false, _, true) => serde_json::json!({
+            "metadata": {"annotations": {CORDONED_ANNOTATION: null}},
+            "spec": {"unschedulable": null}
+        }),
+        _ => return,
+    };
+    match Api::<Node>::all(client).patch(name, &PatchParams::default(), &Patch::Merge(&patch)).await {
+        Ok(_) => println!("Node Maintenance - Node {} {}!", name, if is_labelled(node) { "cordoned for maintenance" } else { "uncordoned after maintenance" }),
+        Err(e) => eprintln!("Node Maintenance - An error occurred while cordoning node {}: {}", name, e),
+    }
+}
+
+/*
+This function sets the MaintenanceSafe condition of a node
+(None removes it).
+*/
+async fn set_maintenance_safe(client: Client, node: &str, safe: Option<(bool, String)>) {
+    let now = chrono::Utc::now().to_rfc3339();
+    let condition = match safe {
+        Some((safe, message)) => serde_json::json!({
+            "type": MAINTENANCE_SAFE_CONDITION,
+            "status": if safe { "True" } else { "False" },
+            "reason": if safe { "ManagedPodsMigrated" } else { "MigrationInProgress" },
+            "message": message,
+            "lastHeartbeatTime": now,
+            "lastTransitionTime": now
+        }),
+        None => serde_json::json!({"type": MAINTENANCE_SAFE_CONDITION, "$patch": "delete"}),
+    };
+    let patch = serde_json::json!({"status": {"conditions": [condition]}});
+    if let Err(e) = Api::<Node>::all(client).patch_status(node, &PatchParams::default(), &Patch::Strategic(&patch)).await {
+        eprintln!("Node Maintenance - An error occurred while updating the {} condition of node {}: {}", MAINTENANCE_SAFE_CONDITION, node, e);
+    }
+}
+
+/*
+This function evicts the pods migrated off the nodes under maintenance,
+once their replacements are Ready. The evictions refused by a
+PodDisruptionBudget are retried on the next check of the node.
+*/
+pub async fn evict_migrated_pods(thread_name: &str, client: Client, pods: &[Pod]) {
+    for pod in pods {
+        let (Some(name), Some(namespace)) = (pod.metadata.name.as_deref(), pod.metadata.namespace.as_deref()) else {
+            continue;
+        };
+        match Api::<Pod>::namespaced(client.clone(), namespace).evict(name, &EvictParams::default()).await {
+            Ok(_) => println!("{} - Pod {} evicted from the node under maintenance!", thread_name, name),
+            Err(kube::Error::Api(e)) if e.code == 429 => {
+                println!("{} - The eviction of Pod {} is blocked by a PodDisruptionBudget, retrying later!", thread_name, name);
+            }
+            Err(kube::Error::Api(e)) if e.code == 404 => {}
+            Err(e) => eprintln!("{} - An error occurred while evicting Pod {}: {}", thread_name, name, e),
+        }
+    }
+}
+
+/*
+This function keeps track of the nodes under maintenance, enqueues
+the RTResources with pods on them and reports the MaintenanceSafe
+condition. It runs as a Tokio task, since it is not time critical.
+*/
+pub async fn node_maintenance_manager(client: Client, state: SharedStatePtr) {
+    let config = unsafe { (*state.0).config.clone() };
+    let queue = CString::new(config.event_queue_path.clone()).unwrap();
+    let mut nodes: HashMap<String, Node> = HashMap::new();
+    let mut reported: HashMap<String, bool> = HashMap::new();
+    let mut watcher = watcher(Api::<Node>::all(client.clone()), Config::default()).boxed();
+    let mut interval = tokio::time::interval(MAINTENANCE_CHECK_INTERVAL);
+    loop {
+        tokio::select! {
+            event = watcher.next() => {
+                let previous: HashSet<String> = nodes.keys().cloned().collect();
+                match event {
+                    Some(Ok(Event::Applied(node))) => {
+                        update_cordon(client.clone(), &node).await;
+                        let name = node.metadata.name.clone().unwrap_or_default();
+                        if in_maintenance(&config, &node) {
+                            nodes.insert(name, node);
+                        } else {
+                            nodes.remove(&name);
+                        }
+                    }
+                    Some(Ok(Event::Deleted(node))) => {
+                        nodes.remove(&node.metadata.name.clone().unwrap_or_default());
+                    }
+                    Some(Ok(Event::Restarted(all))) => {
+                        nodes.clear();
+                        for node in all {
+                            update_cordon(client.clone(), &node).await;
+                            if in_maintenance(&config, &node) {
+                                nodes.insert(node.metadata.name.clone().unwrap_or_default(), node);
+                            }
+                        }
+                    }
+                    Some(Err(e)) => {
+                        eprintln!("Node Maintenance - An error occurred while watching the nodes: {}", e);
+                        continue;
+                    }
+                    None => break,
+                }
+                let current: HashSet<String> = nodes.keys().cloned().collect();
+                set_maintenance_nodes(state, current.clone());
+                for name in current.difference(&previous) {
+                    println!("Node Maintenance - Node {} entered maintenance!", name);
+                    match managed_pods_on(client.clone(), name).await {
+                        Ok(pods) => enqueue_owners(&config, &queue, name, &pods),
+                        Err(e) => eprintln!("Node Maintenance - An error occurred while listing the pods of node {}: {}", name, e),
+                    }
+                }
+                for name in previous.difference(&current) {
+                    println!("Node Maintenance - Node {} left maintenance!", name);
+                    if reported.remove(name).is_some() {
+                        set_maintenance_safe(client.clone(), name, None).await;
+                    }
+                }
+            }
+            _ = interval.tick() => {
+                for name in nodes.keys() {
+                    let pods = match managed_pods_on(client.clone(), name).await {
+                        Ok(pods) => pods,
+                        Err(e) => {
+                            eprintln!("Node Maintenance - An error occurred while listing the pods of node {}: {}", name, e);
+                            continue;
+                        }
+                    };
+                    let safe = pods.is_empty();
+                    if reported.get(name) != Some(&safe) {
+                        let message = if safe {
+                            "Every managed pod left the node".to_string()
+                        } else {
+                            format!("{} managed pods are still running on the node", pods.len())
+                        };
+                        set_maintenance_safe(client.clone(), name, Some((safe, message))).await;
+                        reported.insert(name.clone(), safe);
+                        if safe {
+                            println!("Node Maintenance - Node {} is safe for maintenance!", name);
+                        }
+                    }
+                    enqueue_owners(&config, &queue, name, &pods);
+                }
+            }
+        }
+    }
+    eprintln!("Node Maintenance - The node watch ended!");
+}
@@ -0,0 +1,143 @@
+/*
+This file contains the management of the nodes dedicated
+to the top criticality band.
+The dedicated nodes (listed in the configuration or carrying the
+configured label) are kept tainted with rt-criticality=<band>:NoSchedule,
+and the pods created for RTResources in the top band get the matching
+toleration, so that best-effort workloads stay off those nodes
+even when they are scheduled by other controllers.
+The taint is removed from the nodes that are no longer dedicated.
+*/
+
+use kube::{
+    Api,
+    Client,
+    api::{
+        Patch,
+        PatchParams
+    },
+    runtime::watcher::{
+        watcher,
+        Config,
+        Event
+    }
+};
+use k8s_openapi::api::core::v1::{
+    Node,
+    Taint,
+    Toleration
+};
+use futures::StreamExt;
+
+use crate::utils::configuration::ControllerConfig;
+
+
+
+/*
+Key of the taint set on the dedicated nodes
+*/
+pub const DEDICATED_TAINT_KEY: &str = "rt-criticality";
+
+/*
+This function returns whether the dedicated nodes are configured.
+*/
+pub fn dedicated_nodes_enabled(config: &ControllerConfig) -> bool {
+    !config.dedicated_nodes.is_empty() || !config.dedicated_node_label.is_empty()
+}
+
+/*
+This function returns whether a node is dedicated to the top criticality band.
+*/
+pub fn is_dedicated(config: &ControllerConfig, node: &Node) -> bool {
+    let name = node.metadata.name.clone().unwrap_or_default();
+    if config.dedicated_nodes.contains(&name) {
+        return true;
+    }
+    if config.dedicated_node_label.is_empty() {
+        return false;
+    }
+    let labels = node.metadata.labels.as_ref();
+    match config.dedicated_node_label.split_once('=') {
+        Some((key, value)) => labels.and_then(|l| l.get(key)).is_some_and(|v| v == value),
+        None => labels.is_some_and(|l| l.contains_key(&config.dedicated_node_label)),
+    }
+}
+
+/*
+This function returns the toleration injected into the pods
+of the RTResources in the top criticality band.
+*/
+pub fn dedicated_toleration() -> Toleration {
+    Toleration {
+        key: Some(DEDICATED_TAINT_KEY.to_string()),
+        operator: Some("Exists".to_string()),
+        effect: Some("NoSchedule".to_string()),
+        ..Default::default()
+    }
+}
+
+/*
+This function adds or removes the dedicated taint of a node,
+if needed. The patch carries the node resource version, so that
+taints changed concurrently by other controllers are not overwritten.
+*/
+async fn sync_node_taint(nodes: &Api<Node>, config: &ControllerConfig, node: &Node) {
+    let name = node.metadata.name.clone().unwrap_or_default();
+    let current: Vec<Taint> = node.spec.as_ref().and_then(|s| s.taints.clone()).unwrap_or_default();
+    let wanted = Taint {
+        key: DEDICATED_TAINT_KEY.to_string(),
+        value: Some(config.critical_band_max.to_string()),
+        effect: "NoSchedule".to_string(),
+        ..Default::default()
+    };
+    let mut taints: Vec<Taint> = current.iter()
+        .filter(|t| t.key != DEDICATED_TAINT_KEY)
+        .cloned()
+        .collect();
+    if is_dedicated(config, node) {
+        taints.push(wanted);
+    }
+    let has = |list: &[Taint]| list.iter()
+        .find(|t| t.key == DEDICATED_TAINT_KEY)
+        .map(|t| (t.value.clone(), t.effect.clone()));
+    if has(&current) == has(&taints) {
+        return;
+    }
+
+    let patch = serde_json::json!({
+        "metadata": {
+            "resourceVersion": node.metadata.resource_version
+        },
+        "spec": {
+            "taints": taints
+        }
+    });
+    match nodes.patch(&name, &PatchParams::default(), &Patch::Merge(&patch)).await {
+        Ok(_) => println!("Node Taints - Updated the {} taint of node {}!", DEDICATED_TAINT_KEY, name),
+        /*
+        On a conflict the node is synced again on its next event.
+        */
+        Err(e) => eprintln!("Node Taints - An error occurred while updating the taints of node {}: {}", name, e),
+    }
+}
+
+/*
+This function keeps the dedicated taint in sync on all the nodes.
+It runs as a Tokio task, since it is not time critical.
+*/
+pub async fn node_taint_manager(client: Client, config: ControllerConfig) {
+    let nodes = Api::<Node>::all(client);
+    let mut watcher = watcher(nodes.clone(), Config::default()).boxed();
+    while let Some(event) = watcher.next().await {
+        match event {
+            Ok(Event::Applied(node)) => sync_node_taint(&nodes, &config, &node).await,
+            Ok(Event::Restarted(list)) => {
+                for node in list.iter() {
+                    sync_node_taint(&nodes, &config, node).await;
+                }
+            }
+            Ok(Event::Deleted(_)) => {}
+            Err(e) => eprintln!("Node Taints - {}", e),
+        }
+    }
+}
@@ -0,0 +1,118 @@
+/*
+This file contains the periodic sweep of orphaned pods.
+Managed pods carry the UID of their RTResource in the
+UID owner label (rtresource_uid by default); when the controller crashes while deleting
+an RTResource, or misses its deletion event, its pods are left
+running with no RTResource to reconcile them. The sweeper
+cross-checks the labels against the existing RTResources and,
+according to ORPHAN_POLICY, deletes the orphaned pods or labels
+them with rtresource_orphaned=true for an operator to inspect.
+It runs as a Tokio task, since it is not time critical.
+*/
+
+use std::{
+    collections::HashSet,
+    time::Duration
+};
+use kube::{
+    Api,
+    Client,
+    api::{
+        ListParams,
+        Patch,
+        PatchParams
+    }
+};
+use k8s_openapi::api::core::v1::Pod;
+
+use crate::utils::configuration::{
+    ControllerConfig,
+    ControllerMode,
+    OrphanPolicy
+};
+use crate::utils::rtresource::RTResource;
+use crate::components::scheduling::delete_pod;
+use crate::utils::owner_labels::owner_labels;
+
+
+
+/*
+Label set on the orphaned pods by the "flag" policy
+*/
+pub const ORPHAN_LABEL: &str = "rtresource_orphaned";
+
+/*
+This function sweeps the orphaned pods once.
+It returns the number of orphaned pods found.
+*/
+async fn sweep_orphans(client: Client, config: &ControllerConfig) -> Result<usize, kube::Error> {
+    /*
+    The pods are listed before the RTResources, so that the pods
+    of an RTResource created in between are not taken as orphans.
+    */
+    let pods = Api::<Pod>::all(client.clone()).list(&ListParams::default().labels(&owner_labels().uid)).await?.items;
+    let uids: HashSet<String> = Api::<RTResource>::all(client.clone()).list(&ListParams::default()).await?.items
+        .into_iter()
+        .filter_map(|r| r.metadata.uid)
+        .collect();
+
+    let mut orphans: usize = 0;
+    for pod in pods {
+        let labels = pod.metadata.labels.clone().unwrap_or_default();
+        let Some(uid) = labels.get(&owner_labels().uid) else {
+            continue;
+        };
+        if uids.contains(uid) || pod.metadata.deletion_timestamp.is_some() {
+            continue;
+        }
+        orphans += 1;
+        let name = pod.metadata.name.clone().unwrap_or_default();
+        let namespace = pod.metadata.namespace.clone().unwrap_or_default();
+        if config.mode == ControllerMode::Observe {
+            println!("Orphan Sweeper - Observe mode: Pod {}/{} of the deleted RTResource {} is orphaned!", namespace, name, uid);
+            continue;
+        }
+        match config.orphan_policy {
+            OrphanPolicy::Delete => {
+                println!("Orphan Sweeper - Deleting Pod {}/{} of the deleted RTResource {}!", namespace, name, uid);
+                if let Err(e) = delete_pod("Orphan Sweeper".to_string(), client.clone(), pod).await {
+                    eprintln!("{}", e);
+                }
+            }
+            OrphanPolicy::Flag => {
+                if labels.get(ORPHAN_LABEL).is_some_and(|v| v == "true") {
+                    continue;
+                }
+                let patch = serde_json::json!({
+                    "metadata": {
+                        "labels": {
+                            ORPHAN_LABEL: "true"
+                        }
+                    }
+                });
+                let pods_api = Api::<Pod>::namespaced(client.clone(), &namespace);
+                match pods_api.patch(&name, &PatchParams::default(), &Patch::Merge(&patch)).await {
+                    Ok(_) => println!("Orphan Sweeper - Flagged Pod {}/{} of the deleted RTResource {}!", namespace, name, uid),
+                    Err(e) => eprintln!("Orphan Sweeper - An error occurred while flagging Pod {}/{}: {}", namespace, name, e),
+                }
+            }
+            OrphanPolicy::Off => {}
+        }
+    }
+    Ok(orphans)
+}
+
+/*
+This function sweeps the orphaned pods periodically.
+*/
+pub async fn orphan_sweeper(client: Client, config: ControllerConfig) {
+    let mut interval = tokio::time::interval(Duration::from_secs(config.orphan_sweep_interval_s));
+    loop {
+        interval.tick().await;
+        match sweep_orphans(client.clone(), &config).await {
+            Ok(0) => {}
+            Ok(orphans) => println!("Orphan Sweeper - {} orphaned pods found!", orphans),
+            Err(e) => eprintln!("Orphan Sweeper - An error occurred while sweeping orphaned pods: {}", e),
+        }
+    }
+}
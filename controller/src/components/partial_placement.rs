@@ -0,0 +1,199 @@
+/*
+This file contains the partial placement policy of the RTResources
+(spec.partialPlacementPolicy), applied with the built-in scheduler.
+Before creating the replicas of a reconcile, their placement is
+simulated on the free capacity of the nodes, charging each placed
+replica, so that the replicas beyond the node pool capacity are known
+before any of them is created:
+    - BestEffort: the replicas that fit are created, the others
+      are reported as pending placements;
+    - AllOrNothing: no replica is created, and all of them are
+      reported as pending placements.
+In both cases the Unschedulable condition reports the replicas
+left out, instead of a silent under-replication.
+*/
+
+use std::collections::HashMap;
+use k8s_openapi::api::core::v1::{
+    Node,
+    Toleration
+};
+
+use crate::utils::rtresource::{
+    RTResource,
+    RTResourceStatus,
+    PartialPlacementPolicy,
+    PendingPlacement
+};
+use crate::components::scheduling::{
+    NoFeasibleNode,
+    Placement
+};
+use crate::components::scheduling_policy::{
+    PlacementRequest,
+    FilterFailure
+};
+use crate::components::capacity_index::requested;
+
+
+
+/*
+Condition reporting the replicas left out by the placement
+*/
+pub const UNSCHEDULABLE_CONDITION: &str = "Unschedulable";
+
+/*
+This function simulates the placement of the replicas to create
+on the free capacity of the nodes, returning the replicas that
+do not fit. The replicas pinned to a node are not simulated, since
+their capacity is already reserved.
+*/
+pub fn unplaceable_replicas(
+    thread_name: &str,
+    placement: &Placement<'_>,
+    rtresource: &RTResource,
+    ordinals: &[u32],
+    tolerations: &[Toleration],
+    mut free: HashMap<String, (i64, i64)>
+) -> Vec<PendingPlacement> {
+    let spec = rtresource.spec.template.spec.clone().unwrap_or_default();
+    let (cpu, memory) = requested(&spec);
+    let mut all_tolerations = spec.tolerations.clone().unwrap_or_default();
+    all_tolerations.extend(tolerations.iter().cloned());
+    let volume_terms = placement.volumes.node_terms();
+    let mut unplaceable = Vec::new();
+    for ordinal in ordinals.iter().filter(|o| rtresource.spec.pinned_node(**o).is_none()) {
+        let mut failures: Vec<FilterFailure> = Vec::new();
+        let fitting: Vec<Node> = placement.nodes.iter()
+            .filter(|n| {
+                let name = n.metadata.name.clone().unwrap_or_default();
+                let fits = free.get(&name).is_some_and(|(free_cpu, free_memory)| *free_cpu >= cpu && *free_memory >= memory);
+                if !fits {
+                    failures.push(FilterFailure {
+                        node: name,
+                        plugin: "Capacity",
+                        reason: "insufficient capacity left by the other replicas".to_string(),
+                    });
+                }
+                fits
+            })
+            .cloned()
+            .collect();
+        let request = PlacementRequest {
+            rtresource,
+            nodes: &fitting,
+            failed_nodes: &placement.failed_nodes,
+            tolerations: &all_tolerations,
+            conflicting_nodes: &placement.conflicting_nodes,
+            node_cpu_usage: &placement.node_cpu_usage,
+            volume_terms: &volume_terms,
+            stale_nodes: &placement.stale_nodes,
+        };
+        match placement.policy.place(&request) {
+            Ok(node) => {
+                if let Some((free_cpu, free_memory)) = free.get_mut(&node) {
+                    *free_cpu -= cpu;
+                    *free_memory -= memory;
+                }
+            }
+            Err(filtered) => {
+                failures.extend(filtered);
+                unplaceable.push(NoFeasibleNode {
+                    thread_name: thread_name.to_string(),
+                    pod_name: format!("{}-{}", rtresource.metadata.name.clone().unwrap_or_default(), ordinal),
+                    failures,
+                }.pending_placement(*ordinal));
+            }
+        }
+    }
+    unplaceable
+}
+
+/*
+This function returns the nodes passing the filters of the
+policy for the replicas of an RTResource, regardless of their
+free capacity.
+*/
+pub fn feasible_nodes(placement: &Placement<'_>, rtresource: &RTResource, tolerations: &[Toleration]) -> Vec<String> {
+    let mut all_tolerations = rtresource.spec.template.spec.as_ref()
+        .and_then(|s| s.tolerations.clone())
+        .unwrap_or_default();
+    all_tolerations.extend(tolerations.iter().cloned());
+    let volume_terms = placement.volumes.node_terms();
+    let request = PlacementRequest {
+        rtresource,
+        nodes: &placement.nodes,
+        failed_nodes: &placement.failed_nodes,
+        tolerations: &all_tolerations,
+        conflicting_nodes: &placement.conflicting_nodes,
+        node_cpu_usage: &placement.node_cpu_usage,
+        volume_terms: &volume_terms,
+        stale_nodes: &placement.stale_nodes,
+    };
+    placement.policy.feasible(&request).0.iter()
+        .map(|n| n.metadata.name.clone().unwrap_or_default())
+        .collect()
+}
+
+/*
+This function applies the partial placement policy of an RTResource
+to the replicas to create, given the ones that do not fit.
+It returns the replicas left out as pending placements, removing them
+from the replicas to create, and updates the Unschedulable condition.
+*/
+pub fn apply_partial_placement(
+    rtresource: &RTResource,
+    creates: &mut Vec<u32>,
+    unplaceable: Vec<PendingPlacement>,
+    status: Option<&mut RTResourceStatus>
+) -> Vec<PendingPlacement> {
+    let policy = rtresource.spec.partial_placement_policy.unwrap_or_default();
+    let total = creates.len();
+    let unfit = unplaceable.len();
+    let left_out = match policy {
+        _ if unplaceable.is_empty() => Vec::new(),
+        PartialPlacementPolicy::BestEffort => {
+            creates.retain(|o| !unplaceable.iter().any(|p| p.ordinal == *o));
+            unplaceable
+        }
+        PartialPlacementPolicy::AllOrNothing => {
+            let mut left_out: Vec<PendingPlacement> = creates.drain(..)
+                .filter(|o| !unplaceable.iter().any(|p| p.ordinal == *o))
+                .map(|ordinal| PendingPlacement {
+                    ordinal,
+                    reason: "AllOrNothing".to_string(),
+                    message: "other replicas of the RTResource do not fit".to_string(),
+                    nodes: None,
+                })
+                .collect();
+            left_out.extend(unplaceable);
+            left_out
+        }
+    };
+    if let Some(status) = status {
+        match (left_out.is_empty(), policy) {
+            (true, _) => {
+                if status.conditions.iter().flatten().any(|c| c.condition_type == UNSCHEDULABLE_CONDITION) {
+                    status.set_condition(UNSCHEDULABLE_CONDITION, "False", "Placed", "Every replica fits on the nodes");
+                }
+            }
+            (false, PartialPlacementPolicy::BestEffort) => {
+                status.set_condition(
+                    UNSCHEDULABLE_CONDITION,
+                    "True",
+                    "PartiallyPlaced",
+                    &format!("{} of {} replicas do not fit on the nodes and are pending", unfit, total)
+                );
+            }
+            (false, PartialPlacementPolicy::AllOrNothing) => {
+                status.set_condition(
+                    UNSCHEDULABLE_CONDITION,
+                    "True",
+                    "AllOrNothing",
+                    &format!("{} of {} replicas do not fit on the nodes, none is placed", unfit, total)
+                );
+            }
+        }
+    }
+    left_out
+}
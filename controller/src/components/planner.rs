@@ -0,0 +1,447 @@
+/*
+This file contains the reconcile planner.
+Given a snapshot of the pods currently associated to an
+RTResource and its spec, it computes the full replica
+transition plan before any action is taken, so that the
+watchdog never interleaves decisions with live list results.
+*/
+
+use std::collections::BTreeMap;
+use chrono::{
+    DateTime,
+    Utc
+};
+use k8s_openapi::api::core::v1::Pod;
+
+use crate::utils::rtresource::RTResource;
+
+
+
+/*
+Label storing the replica ordinal of a managed pod
+*/
+pub const ORDINAL_LABEL: &str = "replica_ordinal";
+
+/*
+Label and annotation update for a pod that is kept by the plan
+*/
+#[derive(Clone, Debug, PartialEq)]
+pub struct LabelUpdate {
+    pub name: String,
+    pub namespace: String,
+    pub labels: BTreeMap<String, String>,
+    pub annotations: BTreeMap<String, String>,
+}
+
+/*
+Replica transition plan
+*/
+#[derive(Clone, Debug, Default)]
+pub struct Plan {
+    /*
+    Ordinals of the replicas to create
+    */
+    pub creates: Vec<u32>,
+    /*
+    Pods to delete
+    */
+    pub deletes: Vec<Pod>,
+    /*
+    Controller-owned labels and propagated metadata to restore on kept pods
+    */
+    pub updates: Vec<LabelUpdate>,
+}
+
+impl Plan {
+    pub fn is_empty(&self) -> bool {
+        self.creates.is_empty() && self.deletes.is_empty() && self.updates.is_empty()
+    }
+
+    /*
+    This function keeps at most max creations and deletions
+    (creations first), returning how many were deferred.
+    */
+    pub fn limit_operations(&mut self, max: usize) -> usize {
+        let deferred = (self.creates.len() + self.deletes.len()).saturating_sub(max);
+        self.creates.truncate(max);
+        self.deletes.truncate(max - self.creates.len());
+        deferred
+    }
+}
+
+/*
+This function returns whether a pod is already being deleted.
+Such pods are ignored by the planner since they are going away anyway.
+*/
+fn is_terminating(pod: &Pod) -> bool {
+    pod.metadata.deletion_timestamp.is_some()
+}
+
+/*
+This function returns whether a pod is running.
+Running pods are preferred when choosing which pods to keep.
+*/
+fn is_running(pod: &Pod) -> bool {
+    pod.status.as_ref().and_then(|s| s.phase.as_deref()) == Some("Running")
+}
+
+/*
+This function returns whether a replica is ready.
+A running pod is ready when all its required containers are ready:
+without exclusions the pod Ready condition is used (falling back to
+the container statuses if missing), otherwise the excluded containers
+(e.g. sidecars) are ignored and every other container must be ready.
+*/
+pub fn is_ready(pod: &Pod, exclusions: &[String]) -> bool {
+    if !is_running(pod) || is_terminating(pod) {
+        return false;
+    }
+    let Some(status) = pod.status.as_ref() else {
+        return false;
+    };
+    if exclusions.is_empty()
+        && let Some(condition) = status.conditions.iter().flatten().find(|c| c.type_ == "Ready") {
+        return condition.status == "True";
+    }
+    let statuses = status.container_statuses.as_deref().unwrap_or_default();
+    let required: Vec<&String> = match pod.spec.as_ref() {
+        Some(spec) => spec.containers.iter().map(|c| &c.name).collect(),
+        None => statuses.iter().map(|s| &s.name).collect(),
+    };
+    required.iter()
+        .filter(|name| !exclusions.contains(name))
+        .all(|name| statuses.iter().any(|s| &&s.name == name && s.ready))
+}
+
+fn pod_label<'a>(pod: &'a Pod, key: &str) -> Option<&'a String> {
+    pod.metadata.labels.as_ref().and_then(|l| l.get(key))
+}
+
+/*
+This function returns the replica ordinal of a pod.
+*/
+pub fn pod_ordinal(pod: &Pod) -> Option<u32> {
+    pod_label(pod, ORDINAL_LABEL).and_then(|o| o.parse().ok())
+}
+
+fn pod_annotation<'a>(pod: &'a Pod, key: &str) -> Option<&'a String> {
+    pod.metadata.annotations.as_ref().and_then(|a| a.get(key))
+}
+
+/*
+This function computes the replica transition plan
+for an RTResource at the current time.
+*/
+pub fn plan_reconcile(current_pods: &[Pod], rtresource: &RTResource) -> Plan {
+    plan_reconcile_at(current_pods, rtresource, Utc::now())
+}
+
+/*
+This function computes the replica transition plan for an RTResource.
+The steps are:
+    1. pods being deleted are ignored;
+    2. pods with a valid and unique ordinal lower than the desired
+       number of replicas keep their ordinal;
+    3. the remaining pods (no ordinal, duplicates or ordinals beyond
+       the desired replicas) fill the free ordinals, running pods first;
+    4. pods that could not be assigned an ordinal are deleted,
+       free ordinals left are created;
+    5. kept pods whose controller-owned labels (e.g. edited by a user)
+       or propagated labels and annotations are out of date are updated.
+The desired replicas follow the activation schedule at the given time.
+The function is pure: it only depends on its inputs.
+*/
+pub fn plan_reconcile_at(current_pods: &[Pod], rtresource: &RTResource, now: DateTime<Utc>) -> Plan {
+    let mut plan = Plan::default();
+    let desired = rtresource.spec.replicas_at(now) as u32;
+    let owner_labels = rtresource.owner_labels();
+    let propagated_labels = rtresource.propagated_labels();
+    let propagated_annotations = rtresource.propagated_annotations();
+
+    let mut live: Vec<&Pod> = current_pods.iter().filter(|p| !is_terminating(p)).collect();
+    live.sort_by(|a, b| a.metadata.name.cmp(&b.metadata.name));
+
+    let mut assigned: BTreeMap<u32, &Pod> = BTreeMap::new();
+    let mut unassigned: Vec<&Pod> = Vec::new();
+    for pod in live {
+        match pod_label(pod, ORDINAL_LABEL).and_then(|o| o.parse::<u32>().ok()) {
+            Some(ordinal) if ordinal < desired && !assigned.contains_key(&ordinal) => {
+                assigned.insert(ordinal, pod);
+            }
+            _ => unassigned.push(pod),
+        }
+    }
+
+    /*
+    Running pods fill the free ordinals first, so that
+    pending or failed pods are the first to be deleted.
+    */
+    unassigned.sort_by_key(|p| !is_running(p));
+    let mut unassigned = unassigned.into_iter();
+    let mut kept: Vec<(u32, &Pod)> = assigned.into_iter().collect();
+    for ordinal in 0..desired {
+        if kept.iter().any(|(o, _)| *o == ordinal) {
+            continue;
+        }
+        match unassigned.next() {
+            Some(pod) => kept.push((ordinal, pod)),
+            None => plan.creates.push(ordinal),
+        }
+    }
+    plan.deletes = unassigned.cloned().collect();
+
+    kept.sort_by_key(|(o, _)| *o);
+    for (ordinal, pod) in kept {
+        let ordinal = ordinal.to_string();
+        let mut labels: BTreeMap<String, String> = propagated_labels.iter()
+            .chain(owner_labels.iter())
+            .filter(|(key, value)| pod_label(pod, key) != Some(*value))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        let annotations: BTreeMap<String, String> = propagated_annotations.iter()
+            .filter(|(key, value)| pod_annotation(pod, key) != Some(*value))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        if pod_label(pod, ORDINAL_LABEL) != Some(&ordinal) {
+            labels.insert(ORDINAL_LABEL.to_string(), ordinal);
+        }
+        if !labels.is_empty() || !annotations.is_empty() {
+            plan.updates.push(LabelUpdate {
+                name: pod.metadata.name.clone().unwrap_or_default(),
+                namespace: pod.metadata.namespace.clone().unwrap_or_default(),
+                labels,
+                annotations,
+            });
+        }
+    }
+
+    plan
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use k8s_openapi::{
+        api::core::v1::{
+            Container,
+            ContainerStatus,
+            PodCondition,
+            PodSpec,
+            PodStatus
+        },
+        apimachinery::pkg::apis::meta::v1::Time
+    };
+    use kube::core::ObjectMeta;
+    use crate::utils::rtresource::{
+        RTResourceSpec,
+        Schedule,
+        ActiveWindow,
+        CRITICALITY_CLASS_LABEL
+    };
+
+    fn rtresource(replicas: i32, criticality: u32) -> RTResource {
+        RTResource::new("app", RTResourceSpec {
+            namespace: "realtime".to_string(),
+            replicas: Some(replicas),
+            criticality,
+            ..Default::default()
+        })
+    }
+
+    fn pod(name: &str, ordinal: Option<u32>, criticality: u32, phase: &str) -> Pod {
+        let mut labels = rtresource(1, criticality).owner_labels();
+        if let Some(ordinal) = ordinal {
+            labels.insert(ORDINAL_LABEL.to_string(), ordinal.to_string());
+        }
+        Pod {
+            metadata: ObjectMeta {
+                name: Some(name.to_string()),
+                namespace: Some("realtime".to_string()),
+                labels: Some(labels),
+                ..Default::default()
+            },
+            status: Some(PodStatus {
+                phase: Some(phase.to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn deleted_names(plan: &Plan) -> Vec<String> {
+        plan.deletes.iter().map(|p| p.metadata.name.clone().unwrap()).collect()
+    }
+
+    #[test]
+    fn creates_missing_ordinals() {
+        let pods = vec![pod("app-1", Some(1), 2, "Running")];
+        let plan = plan_reconcile(&pods, &rtresource(3, 2));
+        assert_eq!(plan.creates, vec![0, 2]);
+        assert!(plan.deletes.is_empty());
+        assert!(plan.updates.is_empty());
+    }
+
+    #[test]
+    fn deletes_pods_beyond_desired_replicas() {
+        let pods = vec![
+            pod("app-a", Some(0), 2, "Running"),
+            pod("app-b", Some(1), 2, "Running"),
+            pod("app-c", Some(2), 2, "Running"),
+        ];
+        let plan = plan_reconcile(&pods, &rtresource(1, 2));
+        assert!(plan.creates.is_empty());
+        assert_eq!(deleted_names(&plan), vec!["app-b", "app-c"]);
+    }
+
+    #[test]
+    fn ignores_terminating_pods() {
+        let mut terminating = pod("app-a", Some(0), 2, "Running");
+        terminating.metadata.deletion_timestamp = Some(Time(chrono::Utc::now()));
+        let plan = plan_reconcile(&[terminating], &rtresource(1, 2));
+        assert_eq!(plan.creates, vec![0]);
+        assert!(plan.deletes.is_empty());
+    }
+
+    #[test]
+    fn adopts_unlabeled_pods_preferring_running_ones() {
+        let pods = vec![
+            pod("app-a", None, 2, "Pending"),
+            pod("app-b", None, 2, "Running"),
+        ];
+        let plan = plan_reconcile(&pods, &rtresource(1, 2));
+        assert!(plan.creates.is_empty());
+        assert_eq!(deleted_names(&plan), vec!["app-a"]);
+        assert_eq!(plan.updates.len(), 1);
+        assert_eq!(plan.updates[0].name, "app-b");
+        assert_eq!(plan.updates[0].labels.get(ORDINAL_LABEL), Some(&"0".to_string()));
+    }
+
+    #[test]
+    fn resolves_duplicate_ordinals() {
+        let pods = vec![
+            pod("app-a", Some(0), 2, "Running"),
+            pod("app-b", Some(0), 2, "Running"),
+        ];
+        let plan = plan_reconcile(&pods, &rtresource(2, 2));
+        assert!(plan.creates.is_empty());
+        assert!(plan.deletes.is_empty());
+        assert_eq!(plan.updates.len(), 1);
+        assert_eq!(plan.updates[0].name, "app-b");
+        assert_eq!(plan.updates[0].labels.get(ORDINAL_LABEL), Some(&"1".to_string()));
+    }
+
+    #[test]
+    fn updates_stale_criticality_labels() {
+        let pods = vec![pod("app-a", Some(0), 5, "Running")];
+        let plan = plan_reconcile(&pods, &rtresource(1, 2));
+        assert_eq!(plan.updates.len(), 1);
+        assert_eq!(plan.updates[0].labels.get("criticality"), Some(&"2".to_string()));
+        assert!(!plan.updates[0].labels.contains_key(ORDINAL_LABEL));
+    }
+
+    #[test]
+    fn restores_edited_owner_labels() {
+        let mut edited = pod("app-a", Some(0), 2, "Running");
+        let labels = edited.metadata.labels.as_mut().unwrap();
+        labels.insert("rtresource_name".to_string(), "other".to_string());
+        labels.remove(CRITICALITY_CLASS_LABEL);
+        let plan = plan_reconcile(&[edited], &rtresource(1, 2));
+        assert_eq!(plan.updates.len(), 1);
+        assert_eq!(plan.updates[0].labels, BTreeMap::from([
+            ("rtresource_name".to_string(), "app".to_string()),
+            (CRITICALITY_CLASS_LABEL.to_string(), "Hard".to_string()),
+        ]));
+    }
+
+    #[test]
+    fn propagates_selected_metadata() {
+        let mut r = rtresource(1, 2);
+        r.metadata.labels = Some(BTreeMap::from([
+            ("team".to_string(), "control".to_string()),
+            ("internal".to_string(), "yes".to_string()),
+        ]));
+        r.metadata.annotations = Some(BTreeMap::from([
+            ("trace.io/id".to_string(), "42".to_string()),
+        ]));
+        r.spec.propagate_labels = Some(vec!["team".to_string()]);
+        r.spec.propagate_annotations = Some(vec!["trace.io/*".to_string()]);
+        let plan = plan_reconcile(&[pod("app-a", Some(0), 2, "Running")], &r);
+        assert_eq!(plan.updates.len(), 1);
+        assert_eq!(plan.updates[0].labels, BTreeMap::from([("team".to_string(), "control".to_string())]));
+        assert_eq!(plan.updates[0].annotations.get("trace.io/id"), Some(&"42".to_string()));
+    }
+
+    #[test]
+    fn limits_operations_creations_first() {
+        let mut plan = Plan {
+            creates: vec![1, 2],
+            deletes: vec![pod("app-b", Some(3), 2, "Running"), pod("app-c", Some(4), 2, "Running")],
+            ..Default::default()
+        };
+        assert_eq!(plan.limit_operations(3), 1);
+        assert_eq!(plan.creates, vec![1, 2]);
+        assert_eq!(deleted_names(&plan), vec!["app-b"]);
+        assert_eq!(plan.limit_operations(1), 2);
+        assert_eq!(plan.creates, vec![1]);
+        assert!(plan.deletes.is_empty());
+    }
+
+    #[test]
+    fn empty_plan_when_converged() {
+        let pods = vec![
+            pod("app-a", Some(0), 2, "Running"),
+            pod("app-b", Some(1), 2, "Running"),
+        ];
+        assert!(plan_reconcile(&pods, &rtresource(2, 2)).is_empty());
+    }
+
+    #[test]
+    fn readiness_ignores_excluded_containers() {
+        let mut p = pod("app-a", Some(0), 2, "Running");
+        p.spec = Some(PodSpec {
+            containers: ["app", "proxy"].iter()
+                .map(|name| Container {name: name.to_string(), ..Default::default()})
+                .collect(),
+            ..Default::default()
+        });
+        let status = p.status.as_mut().unwrap();
+        status.conditions = Some(vec![PodCondition {
+            type_: "Ready".to_string(),
+            status: "False".to_string(),
+            ..Default::default()
+        }]);
+        status.container_statuses = Some(vec![
+            ContainerStatus {name: "app".to_string(), ready: true, ..Default::default()},
+            ContainerStatus {name: "proxy".to_string(), ready: false, ..Default::default()},
+        ]);
+        assert!(!is_ready(&p, &[]));
+        assert!(is_ready(&p, &["proxy".to_string()]));
+        assert!(!is_ready(&p, &["app".to_string()]));
+        assert!(!is_ready(&pod("app-b", Some(1), 2, "Pending"), &[]));
+    }
+
+    #[test]
+    fn scales_to_standby_outside_active_windows() {
+        let mut r = rtresource(3, 2);
+        r.spec.schedule = Some(Schedule {
+            active_windows: vec![ActiveWindow {
+                days: Some("Mon-Fri".to_string()),
+                start: "22:00".to_string(),
+                end: "06:00".to_string(),
+            }],
+            standby_replicas: Some(1),
+        });
+        let pods = vec![
+            pod("app-a", Some(0), 2, "Running"),
+            pod("app-b", Some(1), 2, "Running"),
+            pod("app-c", Some(2), 2, "Running"),
+        ];
+        let at = |time: &str| DateTime::parse_from_rfc3339(time).unwrap().with_timezone(&Utc);
+        // Friday night, and the following Saturday morning (window started on Friday)
+        assert!(plan_reconcile_at(&pods, &r, at("2024-03-01T23:00:00Z")).is_empty());
+        assert!(plan_reconcile_at(&pods, &r, at("2024-03-02T05:59:00Z")).is_empty());
+        // Saturday night and Monday noon
+        assert_eq!(deleted_names(&plan_reconcile_at(&pods, &r, at("2024-03-02T23:00:00Z"))), vec!["app-b", "app-c"]);
+        assert_eq!(deleted_names(&plan_reconcile_at(&pods, &r, at("2024-03-04T12:00:00Z"))), vec!["app-b", "app-c"]);
+    }
+}
@@ -0,0 +1,67 @@
+/*
+This file contains the platform defaults injected into the pods
+created by the controller, per criticality range.
+The defaults are configured once for the controller (POD_DEFAULTS),
+instead of being repeated in the template of every RTResource, and
+each of them is only applied when the template does not set it.
+*/
+
+use std::collections::BTreeMap;
+use serde::{
+    Deserialize,
+    Serialize
+};
+use k8s_openapi::api::core::v1::{
+    PodSpec,
+    Toleration
+};
+
+
+
+/*
+Defaults of the pods whose criticality is within
+[minCriticality, maxCriticality] (both optional)
+*/
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct PodDefaults {
+    #[serde(rename = "minCriticality")]
+    pub min_criticality: Option<u32>,
+    #[serde(rename = "maxCriticality")]
+    pub max_criticality: Option<u32>,
+    pub tolerations: Option<Vec<Toleration>>,
+    #[serde(rename = "nodeSelector")]
+    pub node_selector: Option<BTreeMap<String, String>>,
+    #[serde(rename = "runtimeClassName")]
+    pub runtime_class_name: Option<String>,
+}
+
+impl PodDefaults {
+    fn matches(&self, criticality: u32) -> bool {
+        self.min_criticality.is_none_or(|min| criticality >= min)
+            && self.max_criticality.is_none_or(|max| criticality <= max)
+    }
+}
+
+/*
+This function returns the defaults of a criticality
+(the first matching range), if any.
+*/
+pub fn band_defaults(rules: &[PodDefaults], criticality: u32) -> Option<&PodDefaults> {
+    rules.iter().find(|r| r.matches(criticality))
+}
+
+/*
+This function applies the defaults to a pod spec,
+leaving the fields set by the template untouched.
+*/
+pub fn apply_pod_defaults(spec: &mut PodSpec, defaults: &PodDefaults) {
+    if spec.tolerations.as_ref().is_none_or(|t| t.is_empty()) && defaults.tolerations.is_some() {
+        spec.tolerations = defaults.tolerations.clone();
+    }
+    if spec.node_selector.as_ref().is_none_or(|s| s.is_empty()) && defaults.node_selector.is_some() {
+        spec.node_selector = defaults.node_selector.clone();
+    }
+    if spec.runtime_class_name.is_none() && defaults.runtime_class_name.is_some() {
+        spec.runtime_class_name = defaults.runtime_class_name.clone();
+    }
+}
@@ -0,0 +1,165 @@
+/*
+This file contains the periodic deletion of the terminated managed
+pods (POD_JANITOR_INTERVAL_S). Failed and Succeeded pods are never
+restarted, yet they keep their name (a replacement cannot reuse the
+replica name) and count against the object quota of the namespace.
+Each run, the janitor deletes the Succeeded pods and all but the
+POD_JANITOR_KEEP_FAILED latest Failed pods of each RTResource, kept
+for debugging. So that the reclamation never races with the
+diagnosis of a crash loop (spec.failureThreshold):
+    - a Failed pod is only deleted once its failure is older than
+      CRASH_LOOP_WINDOW_MS, so the pod watcher has counted it and it
+      no longer weighs in the stability gate;
+    - the Failed pods of a CrashLooping RTResource are all kept,
+      until an operator resumes it.
+The pods of the most critical RTResources are reclaimed first.
+It runs as a Tokio task, since it is not time critical.
+*/
+
+use std::{
+    collections::HashMap,
+    time::Duration
+};
+use kube::{
+    Api,
+    Client,
+    api::ListParams
+};
+use k8s_openapi::{
+    api::core::v1::Pod,
+    chrono::{
+        DateTime,
+        Duration as ChronoDuration,
+        Utc
+    }
+};
+
+use crate::utils::configuration::{
+    ControllerConfig,
+    ControllerMode
+};
+use crate::utils::rtresource::RTResource;
+use crate::utils::priorities::effective_criticality;
+use crate::components::scheduling::delete_pod;
+use crate::components::crash_loop::CRASH_LOOP_CONDITION;
+use crate::utils::owner_labels::owner_labels;
+
+
+
+/*
+This function returns the phase of a pod.
+*/
+fn phase(pod: &Pod) -> Option<&str> {
+    pod.status.as_ref().and_then(|s| s.phase.as_deref())
+}
+
+/*
+This function returns when a pod terminated: the latest termination
+of its containers, falling back to its start and creation times.
+*/
+fn terminated_at(pod: &Pod) -> Option<DateTime<Utc>> {
+    let status = pod.status.as_ref();
+    status.and_then(|s| s.container_statuses.as_ref())
+        .into_iter()
+        .flatten()
+        .filter_map(|c| c.state.as_ref()?.terminated.as_ref()?.finished_at.as_ref())
+        .map(|t| t.0)
+        .max()
+        .or_else(|| status.and_then(|s| s.start_time.as_ref()).map(|t| t.0))
+        .or_else(|| pod.metadata.creation_timestamp.as_ref().map(|t| t.0))
+}
+
+/*
+This function returns the terminated pods of an RTResource to delete:
+the Succeeded ones and the Failed ones beyond the kept ones, whose
+failure left the crash loop window.
+*/
+fn garbage(config: &ControllerConfig, rtresource: Option<&RTResource>, mut pods: Vec<Pod>) -> Vec<Pod> {
+    let crash_looping = rtresource
+        .and_then(|r| r.status.as_ref())
+        .and_then(|s| s.conditions.as_ref())
+        .is_some_and(|c| c.iter().any(|c| c.condition_type == CRASH_LOOP_CONDITION && c.status == "True"));
+    let window = ChronoDuration::milliseconds(config.crash_loop_window_ms as i64);
+    pods.sort_by_key(|p| std::cmp::Reverse(terminated_at(p)));
+
+    let mut failed: usize = 0;
+    pods.into_iter()
+        .filter(|p| match phase(p) {
+            Some("Succeeded") => true,
+            Some("Failed") => {
+                failed += 1;
+                !crash_looping
+                    && failed > config.pod_janitor_keep_failed
+                    && terminated_at(p).is_some_and(|t| Utc::now() - t > window)
+            }
+            _ => false,
+        })
+        .collect()
+}
+
+/*
+This function deletes the terminated managed pods once.
+It returns the number of pods deleted.
+*/
+async fn collect_garbage(client: Client, config: &ControllerConfig) -> Result<usize, kube::Error> {
+    let pods = Api::<Pod>::all(client.clone()).list(&ListParams::default().labels(&owner_labels().uid)).await?.items;
+    let rtresources: HashMap<String, RTResource> = Api::<RTResource>::all(client.clone()).list(&ListParams::default()).await?.items
+        .into_iter()
+        .filter_map(|r| Some((r.metadata.uid.clone()?, r)))
+        .collect();
+
+    let mut terminated: HashMap<String, Vec<Pod>> = HashMap::new();
+    for pod in pods {
+        if !matches!(phase(&pod), Some("Succeeded") | Some("Failed")) || pod.metadata.deletion_timestamp.is_some() {
+            continue;
+        }
+        if let Some(uid) = pod.metadata.labels.as_ref().and_then(|l| l.get(&owner_labels().uid)).cloned() {
+            terminated.entry(uid).or_default().push(pod);
+        }
+    }
+
+    /*
+    The pods of the most critical RTResources come first
+    (the orphaned pods are left to the orphan sweeper).
+    */
+    let mut owners: Vec<(u32, String)> = terminated.keys()
+        .filter_map(|uid| {
+            let r = rtresources.get(uid)?;
+            Some((effective_criticality(config, r.metadata.namespace.as_deref().unwrap_or_default(), r.spec.criticality), uid.clone()))
+        })
+        .collect();
+    owners.sort();
+
+    let mut deleted: usize = 0;
+    for (_, uid) in owners {
+        let pods = terminated.remove(&uid).unwrap_or_default();
+        for pod in garbage(config, rtresources.get(&uid), pods) {
+            let name = pod.metadata.name.clone().unwrap_or_default();
+            let namespace = pod.metadata.namespace.clone().unwrap_or_default();
+            if config.mode == ControllerMode::Observe {
+                println!("Pod Janitor - Observe mode: Pod {}/{} of RTResource {} would be deleted!", namespace, name, uid);
+                continue;
+            }
+            match delete_pod("Pod Janitor".to_string(), client.clone(), pod).await {
+                Ok(_) => deleted += 1,
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+    }
+    Ok(deleted)
+}
+
+/*
+This function deletes the terminated managed pods periodically.
+*/
+pub async fn pod_janitor(client: Client, config: ControllerConfig) {
+    let mut interval = tokio::time::interval(Duration::from_secs(config.pod_janitor_interval_s));
+    loop {
+        interval.tick().await;
+        match collect_garbage(client.clone(), &config).await {
+            Ok(0) => {}
+            Ok(deleted) => println!("Pod Janitor - {} terminated pods deleted!", deleted),
+            Err(e) => eprintln!("Pod Janitor - An error occurred while deleting the terminated pods: {}", e),
+        }
+    }
+}
@@ -0,0 +1,114 @@
+/*
+This file contains the protection of the managed pods against
+direct changes (POD_PROTECTION).
+The pods created by the controller carry the replica invariants of
+their RTResource (ordinal, labels, placement), which a direct edit or
+deletion by a user silently breaks. The generated Kyverno policy
+(--print-policy) rejects (enforce) or reports (audit) the updates
+and deletions of the managed pods, unless they are made:
+    - by the controller service accounts (POD_PROTECTION_EXEMPT and
+      CRITICAL_SERVICE_ACCOUNT) or by the cluster components
+      (kube-scheduler preemptions, kubelet and controller manager
+      evictions and garbage collection);
+    - on a pod annotated with the override annotation, e.g. by an
+      operator during an incident.
+*/
+
+use crate::utils::configuration::{
+    ControllerConfig,
+    PodProtection
+};
+use crate::utils::owner_labels::owner_labels;
+
+
+
+/*
+Annotation allowing the direct changes of a managed pod
+*/
+pub const OVERRIDE_ANNOTATION: &str = "rtgroup.critical.com/allow-direct-changes";
+
+/*
+Name of the generated Kyverno policy
+*/
+const POLICY_NAME: &str = "preempt-k8s-pod-protection";
+
+/*
+Users of the cluster components allowed to change the managed pods
+*/
+const SYSTEM_USERS: [&str; 4] = [
+    "system:serviceaccount:kube-system:*",
+    "system:node:*",
+    "system:kube-scheduler",
+    "system:kube-controller-manager",
+];
+
+/*
+This function returns the usernames
+allowed to change the managed pods.
+*/
+fn exempt_users(config: &ControllerConfig) -> Vec<String> {
+    let accounts = config.pod_protection_exempt.iter()
+        .chain(Some(&config.critical_service_account).filter(|a| !a.is_empty()));
+    accounts
+        .filter_map(|account| account.split_once('/'))
+        .map(|(namespace, name)| format!("system:serviceaccount:{}:{}", namespace, name))
+        .chain(SYSTEM_USERS.iter().map(|u| u.to_string()))
+        .collect()
+}
+
+/*
+This function returns the Kyverno policy protecting
+the managed pods, if POD_PROTECTION is enabled.
+*/
+pub fn pod_protection_policy(config: &ControllerConfig) -> Option<serde_json::Value> {
+    let action = match config.pod_protection {
+        PodProtection::Off => return None,
+        PodProtection::Audit => "Audit",
+        PodProtection::Enforce => "Enforce",
+    };
+    let annotation = |object: &str| format!("request.{}.metadata.annotations.\"{}\"", object, OVERRIDE_ANNOTATION);
+    Some(serde_json::json!({
+        "apiVersion": "kyverno.io/v1",
+        "kind": "ClusterPolicy",
+        "metadata": {
+            "name": POLICY_NAME,
+            "annotations": {
+                "policies.kyverno.io/description": "Generated by Preempt-K8s (--print-policy) from the controller configuration."
+            }
+        },
+        "spec": {
+            "validationFailureAction": action,
+            "background": false,
+            "rules": [{
+                "name": "managed-pods",
+                "match": {"any": [{"resources": {
+                    "kinds": ["Pod"],
+                    "operations": ["UPDATE", "DELETE"],
+                    "selector": {"matchExpressions": [{"key": owner_labels().uid, "operator": "Exists"}]}
+                }}]},
+                "validate": {
+                    "message": format!(
+                        "The pods managed by Preempt-K8s may only be changed by the controller (annotate the pod with {}=true to override).",
+                        OVERRIDE_ANNOTATION
+                    ),
+                    "deny": {
+                        "conditions": {
+                            "all": [
+                                {
+                                    "key": "{{ request.userInfo.username }}",
+                                    "operator": "AnyNotIn",
+                                    "value": exempt_users(config)
+                                },
+                                {
+                                    "key": format!("{{{{ {} || {} || '' }}}}", annotation("object"), annotation("oldObject")),
+                                    "operator": "NotEquals",
+                                    "value": "true"
+                                }
+                            ]
+                        }
+                    }
+                }
+            }]
+        }
+    }))
+}
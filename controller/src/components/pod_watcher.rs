@@ -5,20 +5,18 @@ related Pods.
 */
 
 use std::{
-    mem,
+    collections::{
+        HashMap,
+        HashSet
+    },
+    time::Instant,
     ptr,
-    process::exit,
-    os::raw::c_char,
     ffi::c_void
 };
 use libc::{
     mqd_t,
-    mq_open,
-    mq_send,
     mq_close,
     mq_unlink,
-    mq_attr,
-    O_CREAT,
     O_WRONLY
 };
 use kube::runtime::watcher::{
@@ -27,9 +25,49 @@ use kube::runtime::watcher::{
         Event
 };
 use futures::StreamExt;
+use k8s_openapi::api::core::v1::Pod;
 
-use crate::utils::vars::SharedState;
-use crate::utils::vars::QueueMessage;
+use crate::utils::vars::{
+    SharedState,
+    SharedStatePtr
+};
+use crate::utils::vars::{
+    QueueMessage,
+    EventKind
+};
+use crate::utils::event_queue::{
+    open_queue,
+    enqueue
+};
+use crate::components::node_failures::{
+    is_node_failure,
+    record_node_failure
+};
+use crate::utils::configuration::{
+    ControllerConfig,
+    PodWatcherEvent,
+    SchedulerKind
+};
+use crate::utils::priorities::effective_criticality;
+use crate::utils::event_bus::{
+    PipelineEventType,
+    publish_event
+};
+use crate::components::capacity_index::track_pod_event;
+use crate::components::resurrection::{
+    RESURRECTION_MIN_INTERVAL,
+    is_preempted,
+    record_preemption,
+    pending_victims
+};
+use crate::components::preemption_history::record_preemption_history;
+use crate::components::crash_dump::crash_exit;
+use crate::components::crash_loop::record_pod_crashes;
+use crate::components::label_guard::{
+    stripped_pod_owner,
+    record_stripped_pod
+};
+use crate::utils::owner_labels::owner_labels;
 
 
 
@@ -43,96 +81,150 @@ pub extern "C" fn pod_watcher(thread_data: *mut c_void) -> *mut c_void {
 		We open it in write-only mode, since
 		this thread only sends messages to it.
 		*/
-        let mut msg = QueueMessage {
-			name: "".to_string(),
-			uid: "".to_string(),
-			namespace: "".to_string(),
-		};
-        let mut queue_attr: mq_attr = { mem::zeroed() };
-        queue_attr.mq_flags = 0;
-        queue_attr.mq_maxmsg = 2000;
-        queue_attr.mq_msgsize = 256;
-        queue_attr.mq_curmsgs = 0;
-        let queue_des: mqd_t = mq_open(
-            shared_state.queue.as_ptr() as *const c_char,
-            O_CREAT | O_WRONLY,
-            0664,
-            &queue_attr
-        );
+        let queue_des: mqd_t = open_queue(&shared_state.queue, O_WRONLY);
         if queue_des == -1 {
             eprintln!("Pod Watcher - An error occurred while opening the queue!");
-            exit(-1);
+            crash_exit(shared_state, "Pod Watcher - The queue could not be opened");
         }
         
         /*
 		Now we can start the event watcher for RTResources related Pods.
-		Each time an event is captured, we send a message to the
+		Each time a pod is deleted or its availability changes (it becomes
+        unschedulable, not ready, gets OOMKilled, ...), we send a message to the
 		event priority queue with name, UID and namespace of the related
         RTResource. The message priority is set equal to the criticality
 		level of the resource.
-        Note: we use the Pods label "criticality" to filter RTResource related Pods
-        and retrieve the application criticality level.
+        Note: we use the owner labels of the Pods to filter RTResource related
+        Pods and retrieve the application criticality level.
+        The label keys (POD_WATCHER_*_LABEL) and the forwarded events
+        (POD_WATCHER_EVENTS) are configurable; every component stamping,
+        selecting or reading the owner labels uses the same keys (owner_labels.rs).
+        Pods lost to a node failure are also recorded, so that
+        the built-in scheduler can steer their replacements.
 		*/
-        shared_state.runtime_handle.block_on(async {
+        let runtime_handle = shared_state.runtime_handle.clone();
+        let pods = shared_state.context.pods.clone();
+        runtime_handle.block_on(async {
             let watcher_config = Config {
                 timeout: Some(100),
                 ..Config::default()
             };
             let mut watcher = watcher(
-                shared_state.context.pods.clone(),
+                pods,
                 watcher_config
             ).boxed();
+            let mut availability: HashMap<String, String> = HashMap::new();
+            let mut crashes: HashMap<String, u32> = HashMap::new();
+            let mut stripped: HashSet<String> = HashSet::new();
+            let mut last_resurrection = Instant::now();
+            let builtin_scheduler = shared_state.config.scheduler == SchedulerKind::Builtin;
+            let forwarded = shared_state.config.pod_watcher_events.clone();
             while let Some(event) = watcher.next().await {
+                /*
+                With the built-in scheduler every pod event
+                also updates the node capacity index.
+                */
+                if builtin_scheduler && let Ok(event) = event.as_ref() {
+                    track_pod_event(shared_state, event);
+                }
                 match event{
                     Ok(Event::Deleted(object)) => {
-                        if let Some(labels) = &object.metadata.labels {
-                            if let (Some(name), Some(uid), Some(namespace), Some(critcality_str)) = (
-                                labels.get("rtresource_name"),
-                                labels.get("rtresource_uid"),
-                                labels.get("rtresource_namespace"),
-                                labels.get("criticality")
-                            ) {
-                                if let Ok(criticality) = critcality_str.parse::<u32>() {
-                                    msg.name = name.clone();
-                                    msg.uid = uid.clone();
-                                    msg.namespace = namespace.clone();
-                                    println!(
-                                        "Pod Watcher - Detected deletion of Pod {} related to RTResource {}, {} in namespace {} with criticality {}.",
-                                        object.metadata.name.clone().unwrap(),
-                                        msg.name,
-                                        msg.uid,
-                                        msg.namespace,
-                                        criticality
-                                    );
-                                    let mut c_msg = msg.clone().into_bytes();
-                                    c_msg.push(0);
-                                    let result = mq_send(
-                                        queue_des,
-                                        c_msg.as_ptr() as *const i8,
-                                        c_msg.len(),
-                                        criticality
-                                    );
-                                    if result == -1 {
-                                        eprintln!("Pod Watcher - An error occurred while sending a message to the queue!");
-                                    }
-                                } else {
-                                    eprintln!("Pod Watcher - Error while parsing criticality!");
-                                    continue;
-                                }
-                            } else {
-                                continue;
+                        if let Some(pod_uid) = object.metadata.uid.as_ref() {
+                            availability.remove(pod_uid);
+                            crashes.remove(pod_uid);
+                            stripped.remove(pod_uid);
+                        }
+
+                        /*
+                        A pod leaving a node (other than a preemption victim)
+                        frees capacity: RTResources with preempted replicas
+                        are re-enqueued at their own criticality.
+                        */
+                        let freed_capacity = object.spec.as_ref().is_some_and(|s| s.node_name.is_some())
+                            && !is_preempted(&object);
+                        if freed_capacity
+                            && forwarded.contains(&PodWatcherEvent::CapacityFreed)
+                            && last_resurrection.elapsed() >= RESURRECTION_MIN_INTERVAL {
+                            let victims = pending_victims(shared_state);
+                            if !victims.is_empty() {
+                                last_resurrection = Instant::now();
+                            }
+                            for victim in victims {
+                                println!(
+                                    "Pod Watcher - Capacity freed, re-enqueuing RTResource {}, {} in namespace {} with {} preempted replicas.",
+                                    victim.msg.name,
+                                    victim.msg.uid,
+                                    victim.msg.namespace,
+                                    victim.replicas
+                                );
+                                enqueue("Pod Watcher", queue_des, &victim.msg, victim.criticality);
                             }
-                        } else {
-                            eprintln!("Pod Watcher - An error occurred while retrieving the Pod labels!");
+                        }
+
+                        let Some((msg, criticality)) = managed_pod_event(&shared_state.config, &object, EventKind::PodDeleted) else {
                             continue;
+                        };
+                        println!(
+                            "Pod Watcher - Detected deletion of Pod {} related to RTResource {}, {} in namespace {} with criticality {}.",
+                            object.metadata.name.clone().unwrap(),
+                            msg.name,
+                            msg.uid,
+                            msg.namespace,
+                            criticality
+                        );
+                        if is_node_failure(&object)
+                            && let Some(node) = object.spec.as_ref().and_then(|s| s.node_name.as_ref()) {
+                            println!("Pod Watcher - Pod {} was lost to a failure of node {}.", object.metadata.name.clone().unwrap(), node);
+                            record_node_failure(shared_state, &msg.uid, node);
+                        }
+                        if is_preempted(&object) {
+                            println!("Pod Watcher - Pod {} was preempted by a more critical pod.", object.metadata.name.clone().unwrap());
+                            record_preemption(shared_state, &msg, criticality);
+                            publish_event(
+                                PipelineEventType::Preempted,
+                                &msg,
+                                criticality,
+                                format!("pod {} on node {}", object.metadata.name.clone().unwrap(), object.spec.as_ref().and_then(|s| s.node_name.clone()).unwrap_or_default())
+                            );
+                            runtime_handle.spawn(record_preemption_history(
+                                SharedStatePtr(thread_data as *mut SharedState),
+                                shared_state.context.client.clone(),
+                                object.clone()
+                            ));
                         }
+                        if forwarded.contains(&PodWatcherEvent::Deleted) {
+                            enqueue("Pod Watcher", queue_des, &msg, criticality);
+                        }
+                    }
+                    Ok(Event::Applied(object)) => {
+                        record_pod_crashes(shared_state, &mut crashes, &object);
+                        handle_stripped(shared_state, queue_des, &mut stripped, &object);
+                        handle_applied(&shared_state.config, queue_des, &mut availability, &object);
+                    }
+                    Ok(Event::Restarted(objects)) => {
+                        /*
+                        After a relist the changes that happened while the watch
+                        was down are caught by comparing with the known state,
+                        then pods that no longer exist are forgotten.
+                        */
+                        for object in objects.iter() {
+                            record_pod_crashes(shared_state, &mut crashes, object);
+                            handle_stripped(shared_state, queue_des, &mut stripped, object);
+                            handle_applied(&shared_state.config, queue_des, &mut availability, object);
+                        }
+                        availability.retain(|pod_uid, _| {
+                            objects.iter().any(|o| o.metadata.uid.as_ref() == Some(pod_uid))
+                        });
+                        crashes.retain(|pod_uid, _| {
+                            objects.iter().any(|o| o.metadata.uid.as_ref() == Some(pod_uid))
+                        });
+                        stripped.retain(|pod_uid| {
+                            objects.iter().any(|o| o.metadata.uid.as_ref() == Some(pod_uid))
+                        });
                     }
                     Err(e) => {
                         println!("{}", e);
                     }
-                    _ => {
-                        println!("Pod Watcher - Nothing happened yet!");
-                    }
                 }
             }
 	    });
@@ -146,3 +238,115 @@ pub extern "C" fn pod_watcher(thread_data: *mut c_void) -> *mut c_void {
 
     ptr::null_mut()
 }
+
+/*
+This function returns the event message and the criticality
+(mapped onto the controller scale) of a pod related to an
+RTResource, or None for other pods.
+The owner is read from the owner labels (POD_WATCHER_*_LABEL).
+*/
+fn managed_pod_event(config: &ControllerConfig, pod: &Pod, kind: EventKind) -> Option<(QueueMessage, u32)> {
+    owner_labels().event("Pod Watcher", config, pod.metadata.labels.as_ref()?, kind)
+}
+
+/*
+This function summarizes the state of a pod that is relevant
+to the availability of its RTResource: phase, scheduling,
+readiness and disruption conditions, and the state of its
+containers (waiting/termination reasons such as OOMKilled
+or CrashLoopBackOff, restarts).
+Other changes (e.g. annotations, resource versions) leave it unchanged.
+*/
+fn availability_signature(pod: &Pod) -> String {
+    let Some(status) = pod.status.as_ref() else {
+        return String::new();
+    };
+    let mut signature = status.phase.clone().unwrap_or_default();
+    for condition in status.conditions.iter().flatten() {
+        if matches!(condition.type_.as_str(), "PodScheduled" | "Ready" | "DisruptionTarget") {
+            signature.push_str(&format!(
+                "|{}={}:{}",
+                condition.type_,
+                condition.status,
+                condition.reason.clone().unwrap_or_default()
+            ));
+        }
+    }
+    for container in status.container_statuses.iter().flatten() {
+        let state = container.state.as_ref();
+        let waiting = state.and_then(|s| s.waiting.as_ref()).and_then(|w| w.reason.clone());
+        let terminated = state.and_then(|s| s.terminated.as_ref()).and_then(|t| t.reason.clone());
+        let last_terminated = container.last_state.as_ref()
+            .and_then(|s| s.terminated.as_ref())
+            .and_then(|t| t.reason.clone());
+        signature.push_str(&format!(
+            "|{}:{}:{}:{}:{}:{}",
+            container.name,
+            container.ready,
+            container.restart_count,
+            waiting.unwrap_or_default(),
+            terminated.unwrap_or_default(),
+            last_terminated.unwrap_or_default()
+        ));
+    }
+    signature
+}
+
+/*
+This function records a managed pod whose owner labels were stripped
+the first time it is seen so, and enqueues its RTResource for the
+watchdog to re-adopt it.
+It must be called without holding the shared mutex.
+*/
+fn handle_stripped(shared_state: &mut SharedState, queue_des: mqd_t, stripped: &mut HashSet<String>, pod: &Pod) {
+    let (Some(pod_uid), Some((msg, criticality))) = (pod.metadata.uid.as_ref(), stripped_pod_owner(pod)) else {
+        if let Some(pod_uid) = pod.metadata.uid.as_ref() {
+            stripped.remove(pod_uid);
+        }
+        return;
+    };
+    if !stripped.insert(pod_uid.clone()) {
+        return;
+    }
+    println!(
+        "Pod Watcher - The owner labels of Pod {} related to RTResource {}, {} in namespace {} were stripped.",
+        pod.metadata.name.clone().unwrap_or_default(),
+        msg.name,
+        msg.uid,
+        msg.namespace
+    );
+    record_stripped_pod(shared_state, &msg.uid, pod);
+    if shared_state.config.pod_watcher_events.contains(&PodWatcherEvent::Stripped) {
+        let criticality = effective_criticality(&shared_state.config, &msg.namespace, criticality);
+        enqueue("Pod Watcher", queue_des, &msg, criticality);
+    }
+}
+
+/*
+This function handles an added or modified pod.
+The first time a managed pod is seen its state is only recorded;
+afterwards an event is sent each time its availability changes.
+*/
+fn handle_applied(config: &ControllerConfig, queue_des: mqd_t, availability: &mut HashMap<String, String>, pod: &Pod) {
+    let (Some(pod_uid), Some((msg, criticality))) = (pod.metadata.uid.as_ref(), managed_pod_event(config, pod, EventKind::PodChanged)) else {
+        return;
+    };
+    let signature = availability_signature(pod);
+    match availability.insert(pod_uid.clone(), signature.clone()) {
+        Some(previous) if previous != signature => {
+            println!(
+                "Pod Watcher - Detected availability change of Pod {} related to RTResource {}, {} in namespace {} with criticality {}: {}.",
+                pod.metadata.name.clone().unwrap_or_default(),
+                msg.name,
+                msg.uid,
+                msg.namespace,
+                criticality,
+                signature
+            );
+            if config.pod_watcher_events.contains(&PodWatcherEvent::Changed) {
+                enqueue("Pod Watcher", queue_des, &msg, criticality);
+            }
+        }
+        _ => {}
+    }
+}
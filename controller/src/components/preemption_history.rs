@@ -0,0 +1,204 @@
+/*
+This file contains the preemption history of the RTResources.
+Each preemption of a managed pod by kube-scheduler is recorded
+for the RTResource of the victim and, when the preemptor is a
+managed pod too, for the RTResource of the preemptor.
+The preemptor is found through the "Preempted" event kube-scheduler
+records on the victim, whose related object is the preemptor pod.
+The records wait in the shared state until the next reconcile of
+the RTResource, which appends them to status.preemptionHistory,
+keeping the last PREEMPTION_HISTORY_SIZE ones.
+Every preemption is also counted for the namespaces of the victim
+and of the preemptor, for their RTClusterStats.
+*/
+
+use std::time::Duration;
+use libc::{
+    pthread_mutex_lock,
+    pthread_mutex_unlock
+};
+use kube::{
+    Api,
+    Client,
+    api::ListParams
+};
+use k8s_openapi::api::core::v1::{
+    Event,
+    ObjectReference,
+    Pod
+};
+use chrono::Utc;
+
+use crate::components::cluster_stats::record_namespace_preemption;
+use crate::utils::vars::{
+    SharedState,
+    SharedStatePtr
+};
+use crate::utils::rtresource::PreemptionRecord;
+use crate::utils::owner_labels::owner_labels;
+
+
+
+/*
+Attempts made to find the "Preempted" event of a victim,
+since kube-scheduler may record it after deleting the pod
+*/
+const PREEMPTION_EVENT_ATTEMPTS: u32 = 3;
+const PREEMPTION_EVENT_RETRY: Duration = Duration::from_secs(1);
+
+/*
+This function finds the preemptor of a victim pod
+from the events recorded by kube-scheduler.
+*/
+async fn find_preemptor(client: Client, namespace: &str, pod_uid: &str) -> Option<ObjectReference> {
+    let events: Api<Event> = Api::namespaced(client, namespace);
+    let lp = ListParams::default().fields(&format!("involvedObject.uid={},reason=Preempted", pod_uid));
+    for attempt in 0..PREEMPTION_EVENT_ATTEMPTS {
+        if attempt > 0 {
+            tokio::time::sleep(PREEMPTION_EVENT_RETRY).await;
+        }
+        match events.list(&lp).await {
+            Ok(list) => {
+                if let Some(related) = list.items.into_iter().find_map(|e| e.related) {
+                    return Some(related);
+                }
+            }
+            Err(e) => {
+                eprintln!("Preemption History - An error occurred while listing the events of Pod {}: {}", pod_uid, e);
+                return None;
+            }
+        }
+    }
+    None
+}
+
+/*
+This function adds a record to the pending preemption
+history of an RTResource, dropping the oldest ones beyond the size.
+It must be called without holding the shared mutex.
+*/
+fn push_record(state: SharedStatePtr, uid: &str, record: PreemptionRecord) {
+    let shared_state = unsafe { &mut *state.0 };
+    let size = shared_state.config.preemption_history_size;
+    unsafe {
+        pthread_mutex_lock(&mut shared_state.mutex);
+        let records = shared_state.preemption_history.entry(uid.to_string()).or_default();
+        records.push(record);
+        if records.len() > size {
+            records.drain(..records.len() - size);
+        }
+        pthread_mutex_unlock(&mut shared_state.mutex);
+    }
+}
+
+/*
+This function records the preemption of a managed pod
+for its RTResource and for the RTResource of the preemptor, if any.
+It runs as a Tokio task spawned by the pod watcher.
+*/
+pub async fn record_preemption_history(state: SharedStatePtr, client: Client, victim: Pod) {
+    let (Some(pod_name), Some(pod_uid), Some(namespace)) = (
+        victim.metadata.name.clone(),
+        victim.metadata.uid.clone(),
+        victim.metadata.namespace.clone()
+    ) else {
+        return;
+    };
+    let labels = victim.metadata.labels.clone().unwrap_or_default();
+    let (Some(victim_rtresource), Some(victim_uid)) = (labels.get(&owner_labels().name), labels.get(&owner_labels().uid)) else {
+        return;
+    };
+    let node = victim.spec.as_ref().and_then(|s| s.node_name.clone()).unwrap_or_default();
+    let time = Utc::now().to_rfc3339();
+    record_namespace_preemption(unsafe { &mut *state.0 }, &namespace, false);
+
+    /*
+    The counterpart is the RTResource of the preemptor when it is
+    a managed pod, the preemptor pod otherwise.
+    */
+    let preemptor = find_preemptor(client.clone(), &namespace, &pod_uid).await;
+    let mut preemptor_rtresource: Option<(String, String)> = None;
+    let mut preemptor_pod: Option<String> = None;
+    if let Some(related) = preemptor.as_ref()
+        && let (Some(name), Some(related_namespace)) = (related.name.as_deref(), related.namespace.as_deref()) {
+        preemptor_pod = Some(format!("{}/{}", related_namespace, name));
+        if let Ok(Some(pod)) = Api::<Pod>::namespaced(client.clone(), related_namespace).get_opt(name).await {
+            let labels = pod.metadata.labels.unwrap_or_default();
+            if let (Some(rtresource), Some(uid)) = (labels.get(&owner_labels().name), labels.get(&owner_labels().uid)) {
+                preemptor_rtresource = Some((rtresource.clone(), uid.clone()));
+                record_namespace_preemption(unsafe { &mut *state.0 }, related_namespace, true);
+            }
+        }
+    }
+    let counterpart = preemptor_rtresource.as_ref().map(|(name, _)| name.clone())
+        .or(preemptor_pod.clone())
+        .unwrap_or_else(|| "unknown".to_string());
+    println!(
+        "Preemption History - Pod {} of RTResource {} was preempted on node {} by {}.",
+        pod_name,
+        victim_rtresource,
+        node,
+        counterpart
+    );
+    if unsafe { (*state.0).config.preemption_history_size } == 0 {
+        return;
+    }
+
+    push_record(state, victim_uid, PreemptionRecord {
+        time: time.clone(),
+        role: "Victim".to_string(),
+        pod: pod_name.clone(),
+        node: node.clone(),
+        counterpart,
+    });
+    if let Some((_, preemptor_uid)) = preemptor_rtresource {
+        push_record(state, &preemptor_uid, PreemptionRecord {
+            time,
+            role: "Preemptor".to_string(),
+            pod: preemptor_pod.unwrap_or_default(),
+            node,
+            counterpart: victim_rtresource.clone(),
+        });
+    }
+}
+
+/*
+This function takes the pending preemption records of an RTResource.
+It must be called without holding the shared mutex.
+*/
+pub fn take_preemption_records(shared_state: &mut SharedState, uid: &str) -> Vec<PreemptionRecord> {
+    unsafe {
+        pthread_mutex_lock(&mut shared_state.mutex);
+        let pending = shared_state.preemption_history.remove(uid).unwrap_or_default();
+        pthread_mutex_unlock(&mut shared_state.mutex);
+
+        pending
+    }
+}
+
+/*
+This function appends the pending preemption records to the
+status history of an RTResource, keeping the last ones.
+It returns None when the RTResource has no history.
+*/
+pub fn append_preemption_history(history: Option<Vec<PreemptionRecord>>, pending: Vec<PreemptionRecord>, size: usize) -> Option<Vec<PreemptionRecord>> {
+    let mut history = history.unwrap_or_default();
+    history.extend(pending);
+    if history.len() > size {
+        history.drain(..history.len() - size);
+    }
+    (!history.is_empty()).then_some(history)
+}
+
+/*
+This function forgets the pending preemption
+records of a deleted RTResource.
+It must be called without holding the shared mutex.
+*/
+pub fn forget_preemption_history(shared_state: &mut SharedState, uid: &str) {
+    unsafe {
+        pthread_mutex_lock(&mut shared_state.mutex);
+        shared_state.preemption_history.remove(uid);
+        pthread_mutex_unlock(&mut shared_state.mutex);
+    }
+}
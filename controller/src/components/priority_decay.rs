@@ -0,0 +1,98 @@
+/*
+This file contains the priority decay of the long reconciles
+(PRIORITY_DECAY_BUDGET_MS).
+A watchdog runs the critical phase of a reconcile at the priority of
+its event criticality. A reconcile that legitimately takes long (e.g.
+hundreds of pods) would keep that priority the whole time, starving
+the newer events of the same criticality. Once the reconcile exceeds
+its budget, the priority of the watchdog is therefore stepped down by
+one criticality level every PRIORITY_DECAY_INTERVAL_MS, down to the
+priority of the least critical level, until the reconcile finishes
+(the watchdog then returns to its base priority as usual).
+The decay runs on the watchdog thread alongside the reconcile, so it
+steps down whenever the reconcile waits on the apiserver.
+*/
+
+use std::{
+    future::Future,
+    time::Duration
+};
+use libc::{
+    pthread_t,
+    pthread_mutex_lock,
+    pthread_mutex_unlock,
+    pthread_setschedparam,
+    SCHED_FIFO
+};
+
+use crate::utils::vars::SharedStatePtr;
+use crate::utils::priorities::watchdog_priority;
+
+
+
+/*
+This function returns the decay budget and interval,
+and the least critical level.
+It must be called without holding the shared mutex.
+*/
+fn decay_settings(state: SharedStatePtr) -> (Duration, Duration, u32) {
+    let shared_state = unsafe { &*state.0 };
+    (
+        Duration::from_millis(shared_state.config.priority_decay_budget_ms),
+        Duration::from_millis(shared_state.config.priority_decay_interval_ms.max(1)),
+        shared_state.config.criticality_max
+    )
+}
+
+/*
+This function moves a watchdog to the priority of the given
+criticality level, recording its decay.
+It must be called without holding the shared mutex.
+*/
+fn decay_to(state: SharedStatePtr, thread: pthread_t, criticality: u32, level: u32) {
+    let shared_state = unsafe { &mut *state.0 };
+    unsafe {
+        pthread_mutex_lock(&mut shared_state.mutex);
+        if let Some(worker) = shared_state.workers.iter_mut().find(|w| w.id == thread) {
+            worker.decay = level - criticality;
+        }
+        let param = watchdog_priority(level).sched_param();
+        pthread_setschedparam(thread, SCHED_FIFO, &param);
+        pthread_mutex_unlock(&mut shared_state.mutex);
+    }
+}
+
+/*
+This function steps the priority of a watchdog down until it
+reaches the least critical level (it never returns when the
+decay is disabled).
+*/
+async fn decay(state: SharedStatePtr, thread: pthread_t, criticality: u32) {
+    let (budget, interval, least_critical) = decay_settings(state);
+    if budget.is_zero() {
+        return std::future::pending().await;
+    }
+    tokio::time::sleep(budget).await;
+    for level in criticality + 1..=least_critical {
+        decay_to(state, thread, criticality, level);
+        println!(
+            "Watchdog - The reconcile of criticality {} exceeded its priority budget, now running at the priority of criticality {}!",
+            criticality,
+            level
+        );
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/*
+This function runs the critical phase of a reconcile
+with the priority decay of the watchdog.
+*/
+pub async fn with_priority_decay<F: Future>(state: SharedStatePtr, thread: pthread_t, criticality: u32, reconcile: F) -> F::Output {
+    tokio::pin!(reconcile);
+    tokio::select! {
+        biased;
+        output = &mut reconcile => output,
+        _ = decay(state, thread, criticality) => reconcile.await,
+    }
+}
@@ -0,0 +1,368 @@
+/*
+This file contains the preemption oracle for the pods
+not managed by the controller. Given a pod and its namespace,
+it returns:
+    - the criticality the controller would give it (from its
+      criticality label, or the default of its namespace);
+    - the PriorityClass derived from that criticality, so that
+      kube-scheduler preempts in the same order as the controller
+      (the pods of Soft RTResources get the Soft PriorityClasses,
+      which never preempt and rank below every Hard one);
+    - whether the pod is allowed on the RT (dedicated) nodes,
+      which are reserved to the top criticality band.
+It is served by the admin API (POST /oracle) for the CI policy checks,
+and the same functions generate the PriorityClasses and the Kyverno
+policy enforcing the verdicts at admission (--print-policy), followed
+by the policy protecting the managed pods (POD_PROTECTION), if enabled.
+At admission the criticality is only read from the pod label, so pods
+tolerating the RT node taint must carry it.
+*/
+
+use std::{
+    collections::BTreeMap,
+    error::Error
+};
+use kube::Client;
+use k8s_openapi::{
+    api::{
+        core::v1::Pod,
+        scheduling::v1::PriorityClass
+    },
+    apimachinery::pkg::apis::meta::v1::ObjectMeta
+};
+use serde::Serialize;
+use schemars::JsonSchema;
+
+use crate::utils::configuration::ControllerConfig;
+use crate::utils::rtresource::CRITICALITY_CLASS_LABEL;
+use crate::utils::priorities::effective_criticality;
+use crate::components::node_taints::{
+    DEDICATED_TAINT_KEY,
+    dedicated_nodes_enabled
+};
+use crate::components::criticality_defaults::namespace_default;
+use crate::components::pod_protection::pod_protection_policy;
+use crate::components::namespace_policy::namespace_policy;
+use crate::utils::owner_labels::owner_labels;
+
+
+
+/*
+Prefix of the criticality-derived PriorityClasses
+*/
+pub const PRIORITY_CLASS_PREFIX: &str = "rt-criticality-";
+pub const SOFT_PRIORITY_CLASS_PREFIX: &str = "rt-criticality-soft-";
+
+/*
+Priority of the least critical level, below the
+system PriorityClasses (which start at 1000000000)
+*/
+const PRIORITY_BASE: i32 = 1_000_000;
+const PRIORITY_STEP: i32 = 1000;
+
+/*
+Name of the generated Kyverno policy
+*/
+const POLICY_NAME: &str = "preempt-k8s-criticality";
+
+/*
+Verdict of the oracle for a pod
+*/
+#[derive(Serialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct OracleVerdict {
+    pub criticality: u32,
+    #[serde(rename = "priorityClassName")]
+    pub priority_class_name: String,
+    pub priority: i32,
+    #[serde(rename = "allowedOnRtNodes")]
+    pub allowed_on_rt_nodes: bool,
+    pub reason: String,
+}
+
+/*
+This function returns the PriorityClass of
+an (effective) criticality level.
+*/
+pub fn priority_class_name(criticality: u32) -> String {
+    format!("{}{}", PRIORITY_CLASS_PREFIX, criticality)
+}
+
+/*
+This function returns the Soft PriorityClass of
+an (effective) criticality level.
+*/
+pub fn soft_priority_class_name(criticality: u32) -> String {
+    format!("{}{}", SOFT_PRIORITY_CLASS_PREFIX, criticality)
+}
+
+/*
+This function returns the priority of an (effective) criticality
+level: the more critical the level, the higher the priority.
+*/
+pub fn priority_value(config: &ControllerConfig, criticality: u32) -> i32 {
+    PRIORITY_BASE + (config.criticality_max + 1 - criticality.clamp(1, config.criticality_max)) as i32 * PRIORITY_STEP
+}
+
+/*
+This function returns the Soft priority of an (effective)
+criticality level, below the priority of every Hard level.
+*/
+pub fn soft_priority_value(config: &ControllerConfig, criticality: u32) -> i32 {
+    (config.criticality_max + 1 - criticality.clamp(1, config.criticality_max)) as i32 * PRIORITY_STEP
+}
+
+/*
+This function returns whether a pod belongs to a Soft RTResource.
+*/
+fn is_soft_pod(pod: &Pod) -> bool {
+    pod.metadata.labels.as_ref()
+        .and_then(|l| l.get(CRITICALITY_CLASS_LABEL))
+        .is_some_and(|c| c == "Soft")
+}
+
+/*
+This function returns whether a pod tolerates
+the taint of the dedicated nodes.
+*/
+fn tolerates_dedicated(pod: &Pod) -> bool {
+    pod.spec.as_ref()
+        .and_then(|s| s.tolerations.as_ref())
+        .is_some_and(|t| t.iter().any(|t| {
+            t.key.as_deref() == Some(DEDICATED_TAINT_KEY) || (t.key.is_none() && t.operator.as_deref() == Some("Exists"))
+        }))
+}
+
+/*
+This function evaluates a pod, given the spec
+criticality of its namespace default.
+*/
+pub fn evaluate(config: &ControllerConfig, namespace: &str, pod: &Pod, namespace_default: u32) -> OracleVerdict {
+    let raw = pod.metadata.labels.as_ref()
+        .and_then(|l| owner_labels().criticality(l))
+        .unwrap_or(namespace_default);
+    let criticality = effective_criticality(config, namespace, raw);
+    let in_band = criticality <= config.critical_band_max;
+    let soft = is_soft_pod(pod);
+    let (allowed_on_rt_nodes, reason) = match (dedicated_nodes_enabled(config), in_band) {
+        (false, _) => (true, "no RT nodes are dedicated".to_string()),
+        (true, _) if soft => (false, "the pods of Soft RTResources never run on the RT nodes".to_string()),
+        (true, true) => (true, format!("criticality {} is in the top band (<= {})", criticality, config.critical_band_max)),
+        (true, false) if tolerates_dedicated(pod) => (
+            false,
+            format!("criticality {} is outside the top band (<= {}) but the pod tolerates {}", criticality, config.critical_band_max, DEDICATED_TAINT_KEY)
+        ),
+        (true, false) => (false, format!("criticality {} is outside the top band (<= {})", criticality, config.critical_band_max)),
+    };
+    OracleVerdict {
+        criticality,
+        priority_class_name: if soft { soft_priority_class_name(criticality) } else { priority_class_name(criticality) },
+        priority: if soft { soft_priority_value(config, criticality) } else { priority_value(config, criticality) },
+        allowed_on_rt_nodes,
+        reason,
+    }
+}
+
+/*
+This function evaluates a pod submitted to the admin API,
+reading the default criticality of its namespace.
+*/
+pub async fn oracle(client: Client, config: &ControllerConfig, pod: &Pod) -> Result<OracleVerdict, String> {
+    let namespace = pod.metadata.namespace.clone().ok_or("The pod must set metadata.namespace")?;
+    let default = namespace_default(client, config, &namespace).await;
+    Ok(evaluate(config, &namespace, pod, default))
+}
+
+/*
+This function returns the spec criticality values allowed
+on the RT nodes in a namespace.
+*/
+fn allowed_values(config: &ControllerConfig, namespace: &str) -> Vec<String> {
+    (1..=config.criticality_max)
+        .filter(|raw| effective_criticality(config, namespace, *raw) <= config.critical_band_max)
+        .map(|raw| raw.to_string())
+        .collect()
+}
+
+/*
+This function returns the Kyverno rule denying the pods
+of Soft RTResources tolerating the dedicated taint.
+*/
+fn soft_deny_rule() -> serde_json::Value {
+    serde_json::json!({
+        "name": "rt-nodes-soft",
+        "match": {"any": [{"resources": {"kinds": ["Pod"]}}]},
+        "validate": {
+            "message": "The pods of Soft RTResources may not tolerate the RT node taint.",
+            "deny": {
+                "conditions": {
+                    "all": [
+                        {
+                            "key": format!("{{{{ request.object.spec.tolerations[?key=='{}'] || `[]` | length(@) }}}}", DEDICATED_TAINT_KEY),
+                            "operator": "GreaterThan",
+                            "value": 0
+                        },
+                        {
+                            "key": format!("{{{{ request.object.metadata.labels.{} || '' }}}}", CRITICALITY_CLASS_LABEL),
+                            "operator": "Equals",
+                            "value": "Soft"
+                        }
+                    ]
+                }
+            }
+        }
+    })
+}
+
+/*
+This function returns the Kyverno rule denying the pods
+tolerating the dedicated taint outside the top band.
+*/
+fn deny_rule(name: String, allowed: Vec<String>, namespaces: Option<Vec<String>>, excluded: Vec<String>) -> serde_json::Value {
+    let mut resources = serde_json::json!({"kinds": ["Pod"]});
+    if let Some(namespaces) = namespaces {
+        resources["namespaces"] = serde_json::json!(namespaces);
+    }
+    let mut rule = serde_json::json!({
+        "name": name,
+        "match": {"any": [{"resources": resources}]},
+        "validate": {
+            "message": "Only pods of the top criticality band may tolerate the RT node taint.",
+            "deny": {
+                "conditions": {
+                    "all": [
+                        {
+                            "key": format!("{{{{ request.object.spec.tolerations[?key=='{}'] || `[]` | length(@) }}}}", DEDICATED_TAINT_KEY),
+                            "operator": "GreaterThan",
+                            "value": 0
+                        },
+                        {
+                            "key": "{{ request.object.metadata.labels.criticality || '' }}",
+                            "operator": "AnyNotIn",
+                            "value": allowed
+                        }
+                    ]
+                }
+            }
+        }
+    });
+    if !excluded.is_empty() {
+        rule["exclude"] = serde_json::json!({"any": [{"resources": {"namespaces": excluded}}]});
+    }
+    rule
+}
+
+/*
+This function prints the PriorityClasses of the criticality levels
+and the Kyverno policy that assigns them to the pods labelled
+with a criticality and keeps the pods outside the top band
+off the RT nodes.
+*/
+pub fn print_policy_manifests(config: &ControllerConfig) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    let mut manifests: Vec<String> = Vec::new();
+    for criticality in 1..=config.criticality_max {
+        let priority_class = PriorityClass {
+            metadata: ObjectMeta {
+                name: Some(priority_class_name(criticality)),
+                ..Default::default()
+            },
+            value: priority_value(config, criticality),
+            global_default: Some(false),
+            description: Some(format!("Pods of criticality {} (Preempt-K8s scale)", criticality)),
+            ..Default::default()
+        };
+        manifests.push(serde_yaml::to_string(&priority_class)?);
+        let soft_priority_class = PriorityClass {
+            metadata: ObjectMeta {
+                name: Some(soft_priority_class_name(criticality)),
+                ..Default::default()
+            },
+            value: soft_priority_value(config, criticality),
+            global_default: Some(false),
+            preemption_policy: Some("Never".to_string()),
+            description: Some(format!("Pods of Soft RTResources of criticality {} (Preempt-K8s scale), never preempting", criticality)),
+        };
+        manifests.push(serde_yaml::to_string(&soft_priority_class)?);
+    }
+
+    /*
+    The PriorityClass is looked up from the spec criticality label
+    (the namespace caps are applied by the per-namespace rules).
+    */
+    let classes = |namespace: &str, soft: bool| -> BTreeMap<String, String> {
+        (1..=config.criticality_max)
+            .map(|raw| {
+                let criticality = effective_criticality(config, namespace, raw);
+                (raw.to_string(), if soft { soft_priority_class_name(criticality) } else { priority_class_name(criticality) })
+            })
+            .collect()
+    };
+    let mutate_rule = |name: String, namespace: Option<&str>, excluded: &[String], soft: bool| {
+        let mut resources = serde_json::json!({"kinds": ["Pod"], "selector": {"matchExpressions": [
+            {"key": owner_labels().criticality, "operator": "Exists"},
+            {"key": CRITICALITY_CLASS_LABEL, "operator": if soft { "In" } else { "NotIn" }, "values": ["Soft"]}
+        ]}});
+        if let Some(namespace) = namespace {
+            resources["namespaces"] = serde_json::json!([namespace]);
+        }
+        let mut rule = serde_json::json!({
+            "name": name,
+            "match": {"any": [{"resources": resources}]},
+            "context": [{
+                "name": "priorityClass",
+                "variable": {
+                    "value": classes(namespace.unwrap_or_default(), soft),
+                    "jmesPath": "\"{{ request.object.metadata.labels.criticality }}\"",
+                    "default": ""
+                }
+            }],
+            "preconditions": {"all": [{"key": "{{ priorityClass }}", "operator": "NotEquals", "value": ""}]},
+            "mutate": {"patchStrategicMerge": {"spec": {"+(priorityClassName)": "{{ priorityClass }}"}}}
+        });
+        if !excluded.is_empty() {
+            rule["exclude"] = serde_json::json!({"any": [{"resources": {"namespaces": excluded}}]});
+        }
+        rule
+    };
+
+    let capped: Vec<String> = config.namespace_criticality_caps.keys().cloned().collect();
+    let mut rules = vec![
+        mutate_rule("priority-class".to_string(), None, &capped, false),
+        mutate_rule("priority-class-soft".to_string(), None, &capped, true)
+    ];
+    for namespace in capped.iter() {
+        rules.push(mutate_rule(format!("priority-class-{}", namespace), Some(namespace), &[], false));
+        rules.push(mutate_rule(format!("priority-class-soft-{}", namespace), Some(namespace), &[], true));
+    }
+    if dedicated_nodes_enabled(config) {
+        rules.push(soft_deny_rule());
+        rules.push(deny_rule("rt-nodes".to_string(), allowed_values(config, ""), None, capped.clone()));
+        for namespace in capped.iter() {
+            rules.push(deny_rule(format!("rt-nodes-{}", namespace), allowed_values(config, namespace), Some(vec![namespace.clone()]), Vec::new()));
+        }
+    }
+    let policy = serde_json::json!({
+        "apiVersion": "kyverno.io/v1",
+        "kind": "ClusterPolicy",
+        "metadata": {
+            "name": POLICY_NAME,
+            "annotations": {
+                "policies.kyverno.io/description": "Generated by Preempt-K8s (--print-policy) from the controller configuration."
+            }
+        },
+        "spec": {
+            "validationFailureAction": "Enforce",
+            "background": false,
+            "rules": rules
+        }
+    });
+    manifests.push(serde_yaml::to_string(&policy)?);
+    if let Some(protection) = pod_protection_policy(config) {
+        manifests.push(serde_yaml::to_string(&protection)?);
+    }
+    if let Some(namespaces) = namespace_policy(config) {
+        manifests.push(serde_yaml::to_string(&namespaces)?);
+    }
+    print!("{}", manifests.join("---\n"));
+
+    Ok(())
+}
@@ -0,0 +1,184 @@
+/*
+This file contains the integration with cluster-autoscaler.
+When the replicas of a top criticality band RTResource cannot
+be placed, a ProvisioningRequest (autoscaling.x-k8s.io) sized
+to the missing replicas is submitted, referencing a PodTemplate
+built from the RTResource pod template, so that cluster-autoscaler
+provisions the missing capacity. The pending request is tracked
+in status.provisioning and it is withdrawn once every replica
+is placed.
+ProvisioningRequests are immutable, so a request is replaced
+when the number of missing replicas changes.
+*/
+
+use kube::{
+    Api,
+    Client,
+    api::{
+        ApiResource,
+        DeleteParams,
+        DynamicObject,
+        GroupVersionKind,
+        Patch,
+        PatchParams
+    }
+};
+use k8s_openapi::api::core::v1::PodTemplate;
+
+use crate::utils::rtresource::{
+    RTResource,
+    ProvisioningStatus
+};
+use crate::utils::owner_labels::owner_labels;
+
+
+
+/*
+Field manager used for the provisioning server-side applies
+*/
+const PROVISIONING_FIELD_MANAGER: &str = "preempt-k8s";
+
+/*
+This function returns the ProvisioningRequest API resource.
+*/
+fn provisioning_resource() -> ApiResource {
+    ApiResource::from_gvk_with_plural(
+        &GroupVersionKind::gvk("autoscaling.x-k8s.io", "v1beta1", "ProvisioningRequest"),
+        "provisioningrequests"
+    )
+}
+
+/*
+This function returns the name of the PodTemplate
+referenced by the requests of an RTResource.
+*/
+fn template_name(rtresource: &RTResource) -> String {
+    format!("{}-capacity", rtresource.metadata.name.as_deref().unwrap_or_default())
+}
+
+/*
+This function returns the state of a ProvisioningRequest
+from its conditions: "Provisioned", "Failed" or "Pending".
+*/
+fn request_state(request: &DynamicObject) -> String {
+    let is_true = |condition_type: &str| request.data["status"]["conditions"].as_array()
+        .into_iter()
+        .flatten()
+        .any(|c| c["type"] == condition_type && c["status"] == "True");
+    if is_true("Failed") {
+        "Failed".to_string()
+    } else if is_true("Provisioned") {
+        "Provisioned".to_string()
+    } else {
+        "Pending".to_string()
+    }
+}
+
+/*
+This function withdraws the ProvisioningRequest of an RTResource.
+*/
+async fn withdraw_request(thread_name: &str, client: Client, namespace: &str, name: &str) {
+    let request_api: Api<DynamicObject> = Api::namespaced_with(client, namespace, &provisioning_resource());
+    match request_api.delete(name, &DeleteParams::default()).await {
+        Ok(_) => println!("{} - ProvisioningRequest {} withdrawn from namespace {}!", thread_name, name, namespace),
+        Err(kube::Error::Api(e)) if e.code == 404 => {}
+        Err(e) => eprintln!("{} - An error occurred while withdrawing ProvisioningRequest {}: {}", thread_name, name, e),
+    }
+}
+
+/*
+This function reconciles the capacity requested for an RTResource
+with its number of unplaced replicas, and returns the
+provisioning status to report (None when nothing is pending).
+*/
+pub async fn reconcile_provisioning(thread_name: &str, client: Client, provisioning_class: &str, rtresource: &RTResource, missing: u32) -> Option<ProvisioningStatus> {
+    let namespace = rtresource.spec.namespace.as_str();
+    let current = rtresource.status.as_ref().and_then(|s| s.provisioning.clone());
+    let request_name = format!("{}-{}", template_name(rtresource), missing);
+
+    if let Some(current) = current.as_ref()
+        && (missing == 0 || current.request_name != request_name) {
+        withdraw_request(thread_name, client.clone(), namespace, &current.request_name).await;
+    }
+    if missing == 0 {
+        if current.is_some() {
+            let template_api: Api<PodTemplate> = Api::namespaced(client.clone(), namespace);
+            if let Err(e) = template_api.delete(&template_name(rtresource), &DeleteParams::default()).await
+                && !matches!(&e, kube::Error::Api(ae) if ae.code == 404) {
+                eprintln!("{} - An error occurred while deleting PodTemplate {}: {}", thread_name, template_name(rtresource), e);
+            }
+        }
+        return None;
+    }
+
+    /*
+    The PodTemplate and the request are owned by the RTResource,
+    so that they are garbage collected with it.
+    */
+    let owner = serde_json::json!([{
+        "apiVersion": "rtgroup.critical.com/v1",
+        "kind": "RTResource",
+        "name": rtresource.metadata.name,
+        "uid": rtresource.metadata.uid
+    }]);
+    let template = serde_json::json!({
+        "apiVersion": "v1",
+        "kind": "PodTemplate",
+        "metadata": {
+            "name": template_name(rtresource),
+            "namespace": namespace,
+            "ownerReferences": owner
+        },
+        "template": {
+            "metadata": {
+                "labels": {
+                    owner_labels().uid.as_str(): rtresource.metadata.uid
+                }
+            },
+            "spec": rtresource.spec.template.spec
+        }
+    });
+    let pp = PatchParams::apply(PROVISIONING_FIELD_MANAGER).force();
+    let template_api: Api<PodTemplate> = Api::namespaced(client.clone(), namespace);
+    if let Err(e) = template_api.patch(&template_name(rtresource), &pp, &Patch::Apply(&template)).await {
+        eprintln!("{} - An error occurred while applying PodTemplate {}: {}", thread_name, template_name(rtresource), e);
+        return current;
+    }
+
+    let request = serde_json::json!({
+        "apiVersion": "autoscaling.x-k8s.io/v1beta1",
+        "kind": "ProvisioningRequest",
+        "metadata": {
+            "name": request_name,
+            "namespace": namespace,
+            "ownerReferences": owner
+        },
+        "spec": {
+            "provisioningClassName": provisioning_class,
+            "podSets": [{
+                "podTemplateRef": {
+                    "name": template_name(rtresource)
+                },
+                "count": missing
+            }]
+        }
+    });
+    let request_api: Api<DynamicObject> = Api::namespaced_with(client, namespace, &provisioning_resource());
+    match request_api.patch(&request_name, &pp, &Patch::Apply(&request)).await {
+        Ok(applied) => {
+            let state = request_state(&applied);
+            if current.as_ref().is_none_or(|c| c.request_name != request_name) {
+                println!("{} - ProvisioningRequest {} submitted for {} replicas in namespace {}!", thread_name, request_name, missing, namespace);
+            }
+            Some(ProvisioningStatus {
+                request_name,
+                replicas: missing,
+                state,
+            })
+        }
+        Err(e) => {
+            eprintln!("{} - An error occurred while submitting ProvisioningRequest {}: {}", thread_name, request_name, e);
+            current
+        }
+    }
+}
@@ -0,0 +1,55 @@
+/*
+This file contains the event queue statistics sampler.
+It periodically reads the attributes of the event queue
+through mq_getattr, which only requires a queue descriptor
+(no access to /dev/mqueue nor root privileges), and
+publishes them through the controller metrics.
+*/
+
+use std::{
+    mem,
+    ffi::CString,
+    time::Duration
+};
+use libc::{
+    mqd_t,
+    mq_getattr,
+    mq_close,
+    mq_attr,
+    O_RDONLY,
+    O_NONBLOCK
+};
+
+use crate::utils::metrics::record_queue_sample;
+use crate::utils::event_queue::open_queue;
+
+
+
+/*
+This function samples the event queue depth every interval.
+The queue is opened read-only and non-blocking: the sampler
+never receives messages, so it does not compete with the watchdogs.
+*/
+pub async fn queue_stats_sampler(queue: CString, interval: Duration) {
+    let queue_des: mqd_t = open_queue(&queue, O_RDONLY | O_NONBLOCK);
+    if queue_des == -1 {
+        eprintln!("Queue Stats Sampler - An error occurred while opening the queue!");
+        return;
+    }
+    println!("Queue Stats Sampler - Sampling the event queue every {} ms!", interval.as_millis());
+
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        let mut attr: mq_attr = unsafe { mem::zeroed() };
+        if unsafe { mq_getattr(queue_des, &mut attr) } == -1 {
+            eprintln!("Queue Stats Sampler - An error occurred while reading the queue attributes!");
+            break;
+        }
+        record_queue_sample(attr.mq_curmsgs, attr.mq_maxmsg);
+    }
+
+    unsafe {
+        mq_close(queue_des);
+    }
+}
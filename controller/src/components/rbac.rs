@@ -0,0 +1,303 @@
+/*
+This file contains the RBAC rules of the controller (--print-rbac).
+Each API the controller calls is listed below with the verbs it
+uses, next to the feature using it and the configuration enabling
+that feature, so that the printed ClusterRole and Roles grant the
+controller exactly what the current configuration needs instead of
+cluster-admin. A component calling a new API must add it here.
+The namespaced permissions (the controller ConfigMap, the node
+Leases, the ConfigMap archive store) are granted by Roles in their
+namespace; the controller namespace is set with --namespace=<name>
+(realtime by default). The impersonation of the critical service
+account is printed by --print-apf.
+*/
+
+use std::{
+    collections::BTreeMap,
+    error::Error
+};
+use k8s_openapi::{
+    api::rbac::v1::{
+        ClusterRole,
+        ClusterRoleBinding,
+        PolicyRule,
+        Role,
+        RoleBinding,
+        RoleRef,
+        Subject
+    },
+    apimachinery::pkg::apis::meta::v1::ObjectMeta
+};
+
+use crate::utils::configuration::{
+    ControllerConfig,
+    ControllerMode,
+    SchedulerKind,
+    get_controller_namespace
+};
+use crate::components::node_heartbeats::NODE_LEASE_NAMESPACE;
+
+
+
+/*
+Name of the controller service account, ClusterRole and Roles
+*/
+const CONTROLLER_SERVICE_ACCOUNT: &str = "preempt-k8s";
+
+/*
+Namespace a permission is granted in
+*/
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Scope {
+    Cluster,
+    Namespace(String),
+}
+
+/*
+API permission needed by the controller
+*/
+struct Permission {
+    scope: Scope,
+    api_group: &'static str,
+    resource: &'static str,
+    verbs: &'static [&'static str],
+}
+
+/*
+This function returns the permissions
+the configured features of the controller need.
+*/
+fn permissions(config: &ControllerConfig, namespace: &str) -> Vec<Permission> {
+    let cluster = |api_group: &'static str, resource: &'static str, verbs: &'static [&'static str]| Permission {
+        scope: Scope::Cluster,
+        api_group,
+        resource,
+        verbs,
+    };
+    let namespaced = |namespace: &str, api_group: &'static str, resource: &'static str, verbs: &'static [&'static str]| Permission {
+        scope: Scope::Namespace(namespace.to_string()),
+        api_group,
+        resource,
+        verbs,
+    };
+
+    /*
+    Watchers, watchdogs, state updater and status verifier.
+    */
+    let mut permissions = vec![
+        cluster("rtgroup.critical.com", "rtresources", &["get", "list", "watch", "patch", "update"]),
+        cluster("rtgroup.critical.com", "rtresources/status", &["get", "patch", "update"]),
+        cluster("", "pods", &["get", "list", "watch", "create", "patch", "delete"]),
+        cluster("", "namespaces", &["get"]),
+        cluster("node.k8s.io", "runtimeclasses", &["get", "list"]),
+        cluster("", "services", &["get", "list", "create", "patch", "delete"]),
+        cluster("apps", "replicasets", &["get", "list", "create", "patch", "delete"]),
+        /*
+        Node maintenance and dedicated node taints.
+        */
+        cluster("", "nodes", &["get", "list", "watch", "patch"]),
+        cluster("", "nodes/status", &["patch"]),
+        cluster("", "pods/eviction", &["create"]),
+        /*
+        Info publisher.
+        */
+        namespaced(namespace, "", "configmaps", &["get", "create", "patch"]),
+    ];
+    if config.mode == ControllerMode::Observe {
+        permissions.push(cluster("rtgroup.critical.com", "rtdecisions", &["get", "create", "patch"]));
+    } else {
+        /*
+        ConfigMaps and Secrets referenced by spec.restartOn.
+        */
+        permissions.push(cluster("", "configmaps", &["list", "watch"]));
+        permissions.push(cluster("", "secrets", &["list", "watch"]));
+    }
+    if config.cluster_stats_interval_ms != 0 {
+        permissions.push(cluster("rtgroup.critical.com", "rtclusterstats", &["list", "create", "patch", "delete"]));
+    }
+    if config.preemption_history_size != 0 {
+        permissions.push(cluster("", "events", &["list"]));
+    }
+    if config.scheduler == SchedulerKind::Builtin {
+        permissions.push(cluster("", "persistentvolumeclaims", &["get", "list", "patch"]));
+        permissions.push(cluster("", "persistentvolumes", &["get"]));
+        permissions.push(cluster("storage.k8s.io", "storageclasses", &["get", "list"]));
+        permissions.push(cluster("metrics.k8s.io", "nodes", &["list"]));
+        if config.node_heartbeat_freshness_ms != 0 {
+            permissions.push(namespaced(NODE_LEASE_NAMESPACE, "coordination.k8s.io", "leases", &["get", "list", "watch"]));
+        }
+    }
+    if !config.provisioning_class.is_empty() {
+        permissions.push(cluster("", "podtemplates", &["get", "create", "patch", "delete"]));
+        permissions.push(cluster("autoscaling.x-k8s.io", "provisioningrequests", &["get", "create", "patch", "delete"]));
+    }
+    if let Some(archive_namespace) = config.archive_store.strip_prefix("configmap:") {
+        permissions.push(namespaced(archive_namespace, "", "configmaps", &["get", "create", "patch"]));
+    }
+    if config.admin_port != 0 && !config.admin_tls_dir.is_empty() {
+        permissions.push(cluster("authentication.k8s.io", "tokenreviews", &["create"]));
+        permissions.push(cluster("authorization.k8s.io", "subjectaccessreviews", &["create"]));
+    }
+    permissions
+}
+
+/*
+This function merges the permissions of a scope into rules,
+one per API group and verbs.
+*/
+fn rules(permissions: &[Permission], scope: &Scope) -> Vec<PolicyRule> {
+    let mut merged: BTreeMap<(&str, Vec<&str>), Vec<String>> = BTreeMap::new();
+    for permission in permissions.iter().filter(|p| &p.scope == scope) {
+        let resources = merged.entry((permission.api_group, permission.verbs.to_vec())).or_default();
+        if !resources.iter().any(|r| r == permission.resource) {
+            resources.push(permission.resource.to_string());
+        }
+    }
+    merged.into_iter()
+        .map(|((api_group, verbs), resources)| PolicyRule {
+            api_groups: Some(vec![api_group.to_string()]),
+            resources: Some(resources),
+            verbs: verbs.into_iter().map(str::to_string).collect(),
+            ..Default::default()
+        })
+        .collect()
+}
+
+/*
+This function prints the ClusterRole and Roles granting the
+controller the permissions of its configuration, with their
+bindings to the controller service account.
+*/
+pub fn print_rbac_manifests(config: &ControllerConfig) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    let namespace = get_controller_namespace();
+    let permissions = permissions(config, &namespace);
+    let subjects = Some(vec![Subject {
+        kind: "ServiceAccount".to_string(),
+        name: CONTROLLER_SERVICE_ACCOUNT.to_string(),
+        namespace: Some(namespace.clone()),
+        ..Default::default()
+    }]);
+
+    let mut manifests: Vec<String> = Vec::new();
+    let cluster_role = ClusterRole {
+        metadata: ObjectMeta {
+            name: Some(CONTROLLER_SERVICE_ACCOUNT.to_string()),
+            ..Default::default()
+        },
+        rules: Some(rules(&permissions, &Scope::Cluster)),
+        ..Default::default()
+    };
+    manifests.push(serde_yaml::to_string(&cluster_role)?);
+    let cluster_binding = ClusterRoleBinding {
+        metadata: ObjectMeta {
+            name: Some(CONTROLLER_SERVICE_ACCOUNT.to_string()),
+            ..Default::default()
+        },
+        role_ref: RoleRef {
+            api_group: "rbac.authorization.k8s.io".to_string(),
+            kind: "ClusterRole".to_string(),
+            name: CONTROLLER_SERVICE_ACCOUNT.to_string(),
+        },
+        subjects: subjects.clone(),
+    };
+    manifests.push(serde_yaml::to_string(&cluster_binding)?);
+
+    let mut scopes: Vec<&Scope> = permissions.iter().map(|p| &p.scope).filter(|s| **s != Scope::Cluster).collect();
+    scopes.sort();
+    scopes.dedup();
+    for scope in scopes {
+        let Scope::Namespace(role_namespace) = scope else {
+            continue;
+        };
+        let metadata = ObjectMeta {
+            name: Some(CONTROLLER_SERVICE_ACCOUNT.to_string()),
+            namespace: Some(role_namespace.clone()),
+            ..Default::default()
+        };
+        let role = Role {
+            metadata: metadata.clone(),
+            rules: Some(rules(&permissions, scope)),
+        };
+        manifests.push(serde_yaml::to_string(&role)?);
+        let binding = RoleBinding {
+            metadata,
+            role_ref: RoleRef {
+                api_group: "rbac.authorization.k8s.io".to_string(),
+                kind: "Role".to_string(),
+                name: CONTROLLER_SERVICE_ACCOUNT.to_string(),
+            },
+            subjects: subjects.clone(),
+        };
+        manifests.push(serde_yaml::to_string(&binding)?);
+    }
+    print!("{}", manifests.join("---\n"));
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::configuration::get_controller_configuration;
+
+    /*
+    Resources the controller writes with server-side apply (services,
+    replicaset_backend, provisioning, info_publisher, archival,
+    cluster_stats), with the namespace of their Role (None for the
+    ClusterRole): the apiserver authorizes an apply as "create"
+    when the object does not exist yet.
+    */
+    const APPLIED: [(Option<&str>, &str, &str); 7] = [
+        (None, "", "services"),
+        (None, "apps", "replicasets"),
+        (None, "", "podtemplates"),
+        (None, "autoscaling.x-k8s.io", "provisioningrequests"),
+        (Some("realtime"), "", "configmaps"),
+        (Some("archive"), "", "configmaps"),
+        (None, "rtgroup.critical.com", "rtclusterstats"),
+    ];
+
+    fn config(mode: ControllerMode) -> ControllerConfig {
+        let mut config = get_controller_configuration();
+        config.mode = mode;
+        config.cluster_stats_interval_ms = 1000;
+        config.provisioning_class = "check-capacity".to_string();
+        config.archive_store = "configmap:archive".to_string();
+        config
+    }
+
+    fn assert_appliable(granted: &[Permission], namespace: Option<&str>, api_group: &str, resource: &str) {
+        let scope = namespace.map_or(Scope::Cluster, |n| Scope::Namespace(n.to_string()));
+        assert!(
+            granted.iter().any(|p| p.scope == scope
+                && p.api_group == api_group
+                && p.resource == resource
+                && p.verbs.contains(&"create")
+                && p.verbs.contains(&"patch")),
+            "{}/{} is applied in {:?} without create and patch",
+            api_group,
+            resource,
+            namespace
+        );
+    }
+
+    #[test]
+    fn grants_create_on_applied_resources() {
+        let granted = permissions(&config(ControllerMode::Active), "realtime");
+        for (namespace, api_group, resource) in APPLIED {
+            assert_appliable(&granted, namespace, api_group, resource);
+        }
+    }
+
+    /*
+    The RTDecisions are applied by decisions.rs in observe mode only.
+    */
+    #[test]
+    fn grants_create_on_decisions_in_observe_mode() {
+        let granted = permissions(&config(ControllerMode::Observe), "realtime");
+        assert_appliable(&granted, None, "rtgroup.critical.com", "rtdecisions");
+        for (namespace, api_group, resource) in APPLIED {
+            assert_appliable(&granted, namespace, api_group, resource);
+        }
+    }
+}
@@ -0,0 +1,96 @@
+/*
+This file contains the readiness wait of the RTResources
+declaring spec.waitForReady. After creating replacement pods,
+the reconcile waits for them to become Ready before deleting
+the pods it replaces and marking the event as handled,
+so that the old replicas are only removed once the new ones
+serve (make-before-break, e.g. with standby replicas).
+The wait is bounded by the recovery deadline of the event
+(RECOVERY_DEADLINE_MS from the reception of the event).
+With spec.recoveryProbe, a replica answering the probe
+counts as Ready before the kubelet reports it.
+*/
+
+use std::{
+    collections::HashSet,
+    time::{
+        Duration,
+        Instant
+    }
+};
+use kube::{
+    Client,
+    api::ListParams
+};
+use k8s_openapi::api::core::v1::Pod;
+
+use crate::utils::rtresource::RTResource;
+use crate::utils::timed_list::list_timed;
+use crate::components::planner::ORDINAL_LABEL;
+use crate::components::recovery_probe::ready_replicas;
+use crate::utils::owner_labels::owner_labels;
+
+
+
+/*
+Interval between two readiness checks
+*/
+const READY_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/*
+This function returns whether an RTResource waits
+for its created replicas to become Ready.
+*/
+pub fn waits_for_ready(rtresource: &RTResource) -> bool {
+    rtresource.spec.wait_for_ready.unwrap_or(false)
+}
+
+/*
+This function waits for the replicas of the given ordinals
+to have a Ready pod (other than the ignored pods), until the deadline.
+It returns whether they all became Ready in time.
+*/
+pub async fn wait_for_ready(thread_name: &str, client: Client, rtresource: &RTResource, ordinals: &[u32], ignored_pods: &[String], deadline: Instant) -> bool {
+    if ordinals.is_empty() {
+        return true;
+    }
+    let uid = rtresource.metadata.uid.clone().unwrap_or_default();
+    let name = rtresource.metadata.name.clone().unwrap_or_default();
+    let exclusions = rtresource.spec.readiness_exclusions.clone().unwrap_or_default();
+    let lp = ListParams::default().labels(&owner_labels().uid_selector(&uid));
+    let start = Instant::now();
+    loop {
+        match list_timed::<Pod>(client.clone(), Some(rtresource.spec.namespace.as_str()), &lp).await {
+            Ok(pods) => {
+                let candidates: Vec<Pod> = pods.items.into_iter()
+                    .filter(|p| p.metadata.name.as_ref().is_none_or(|n| !ignored_pods.contains(n)))
+                    .collect();
+                let ready: HashSet<u32> = ready_replicas(&candidates, rtresource, &exclusions).await
+                    .into_iter()
+                    .filter_map(|p| p.metadata.labels.as_ref()?.get(ORDINAL_LABEL)?.parse().ok())
+                    .collect();
+                if ordinals.iter().all(|o| ready.contains(o)) {
+                    println!(
+                        "{} - The {} created replicas of RTResource {} are Ready after {} ms!",
+                        thread_name,
+                        ordinals.len(),
+                        name,
+                        start.elapsed().as_millis()
+                    );
+                    return true;
+                }
+            }
+            Err(e) => eprintln!("{} - An error occurred while listing the pods of RTResource {}: {}", thread_name, name, e),
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            eprintln!(
+                "{} - The created replicas of RTResource {} did not become Ready before the recovery deadline!",
+                thread_name,
+                name
+            );
+            return false;
+        }
+        tokio::time::sleep(READY_POLL_INTERVAL.min(deadline - now)).await;
+    }
+}
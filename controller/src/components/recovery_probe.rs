@@ -0,0 +1,68 @@
+/*
+This file contains the recovery probe of the RTResources declaring
+spec.recoveryProbe. The kubelet only reports a replica Ready after
+its readiness probe period, which adds seconds to the recovery of
+a recreated pod. With a recovery probe, the controller probes the
+declared endpoint of the running replicas directly (GET on the pod
+IP), and a replica answering 2xx counts as Ready even before the
+kubelet reports it, both for spec.waitForReady and for the Ready
+condition set by the state updater.
+*/
+
+use std::time::Duration;
+use futures::future::join_all;
+use k8s_openapi::api::core::v1::Pod;
+
+use crate::utils::rtresource::{
+    RTResource,
+    RecoveryProbe
+};
+use crate::utils::http::get_text;
+use crate::components::planner::is_ready;
+
+
+
+/*
+Timeout of a probe when spec.recoveryProbe.timeoutMs is not set
+*/
+const DEFAULT_PROBE_TIMEOUT: Duration = Duration::from_millis(1000);
+
+/*
+This function probes the recovery endpoint of a replica.
+It returns whether the replica is running and answered 2xx.
+*/
+async fn probe_pod(pod: &Pod, probe: &RecoveryProbe) -> bool {
+    let status = pod.status.as_ref();
+    if status.and_then(|s| s.phase.as_deref()) != Some("Running") || pod.metadata.deletion_timestamp.is_some() {
+        return false;
+    }
+    let Some(ip) = status.and_then(|s| s.pod_ip.as_ref()) else {
+        return false;
+    };
+    let url = format!("http://{}:{}{}", ip, probe.port, probe.path.as_deref().unwrap_or("/"));
+    let timeout = probe.timeout_ms.map_or(DEFAULT_PROBE_TIMEOUT, Duration::from_millis);
+    matches!(get_text(&url, timeout).await, Ok((status, _)) if (200..300).contains(&status))
+}
+
+/*
+This function returns whether a replica is Ready: reported so by
+the kubelet or, with spec.recoveryProbe, answering the probe.
+*/
+pub async fn replica_ready(pod: &Pod, rtresource: &RTResource, exclusions: &[String]) -> bool {
+    if is_ready(pod, exclusions) {
+        return true;
+    }
+    match rtresource.spec.recovery_probe.as_ref() {
+        Some(probe) => probe_pod(pod, probe).await,
+        None => false,
+    }
+}
+
+/*
+This function returns which of the given replicas are Ready,
+probing them concurrently.
+*/
+pub async fn ready_replicas<'a>(pods: &'a [Pod], rtresource: &RTResource, exclusions: &[String]) -> Vec<&'a Pod> {
+    let ready = join_all(pods.iter().map(|p| replica_ready(p, rtresource, exclusions))).await;
+    pods.iter().zip(ready).filter(|(_, ready)| *ready).map(|(p, _)| p).collect()
+}
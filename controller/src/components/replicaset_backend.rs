@@ -0,0 +1,164 @@
+/*
+This file contains the ReplicaSet workload backend
+(spec.workloadBackend: ReplicaSet), for the clusters whose policy
+forbids the management of bare pods. The ReplicaSet is named after
+the RTResource, lives in the namespace of its pods and selects them
+through the controller-owned UID owner label. Its template
+carries the labels, annotations and settings the controller injects
+into the pods it creates (priority hints, tolerations, operating
+system selector, anti-affinity...), so that the priority and the
+placement are set by the template, while the replica maintenance
+is left to kube-controller-manager. The controller still applies
+the ReplicaSets in criticality order, and preemption is carried by
+the PriorityClass of the pods.
+The replicas have no ordinal, so the features working per replica
+(built-in scheduler, spec.placementOverrides, stable hostnames,
+image variants, spec.restartOn) do not apply to this backend. The template carries
+the trace identifier of the event which last applied it.
+The ReplicaSet is removed (orphaning its pods, deleted by the
+watchdog like the raw ones) when the RTResource is deleted or
+switches back to RawPods.
+*/
+
+use kube::{
+    Api,
+    Client,
+    api::{
+        DeleteParams,
+        ListParams,
+        Patch,
+        PatchParams,
+        PropagationPolicy
+    }
+};
+use k8s_openapi::{
+    api::{
+        apps::v1::{
+            ReplicaSet,
+            ReplicaSetSpec
+        },
+        core::v1::PodTemplateSpec
+    },
+    apimachinery::pkg::apis::meta::v1::{
+        LabelSelector,
+        ObjectMeta
+    }
+};
+use std::collections::BTreeMap;
+
+use crate::utils::rtresource::RTResource;
+use crate::utils::errors::ControllerError;
+use crate::components::scheduling::{
+    PodInjections,
+    TRACE_ANNOTATION,
+    pod_metadata,
+    inject_pod_settings,
+    inject_rt_env
+};
+use crate::components::namespace_policy::check_target_namespace;
+use crate::utils::owner_labels::owner_labels;
+
+
+
+/*
+Field manager used for the ReplicaSet server-side applies
+*/
+const REPLICASET_FIELD_MANAGER: &str = "preempt-k8s";
+
+/*
+This function returns the ReplicaSet of an RTResource
+with the given number of replicas.
+*/
+fn replicaset_of(thread_name: &str, rtresource: &RTResource, replicas: i32, injections: &PodInjections<'_>) -> ReplicaSet {
+    let name = rtresource.metadata.name.clone().unwrap_or_default();
+    let uid = rtresource.metadata.uid.clone().unwrap_or_default();
+    let (labels, mut annotations) = pod_metadata(rtresource);
+    if !injections.trace_id.is_empty() {
+        annotations.insert(TRACE_ANNOTATION.to_string(), injections.trace_id.to_string());
+    }
+    let mut pod_spec = rtresource.spec.template.spec.clone().unwrap_or_default();
+    inject_pod_settings(thread_name, &mut pod_spec, rtresource, injections);
+    inject_rt_env(&mut pod_spec, rtresource, None);
+
+    ReplicaSet {
+        metadata: ObjectMeta {
+            name: Some(name.clone()),
+            namespace: Some(rtresource.spec.namespace.clone()),
+            labels: Some(BTreeMap::from([
+                (owner_labels().name.clone(), name),
+                (owner_labels().uid.clone(), uid.clone()),
+            ])),
+            ..Default::default()
+        },
+        spec: Some(ReplicaSetSpec {
+            replicas: Some(replicas),
+            selector: LabelSelector {
+                match_labels: Some(BTreeMap::from([(owner_labels().uid.clone(), uid)])),
+                ..Default::default()
+            },
+            template: Some(PodTemplateSpec {
+                metadata: Some(ObjectMeta {
+                    labels: Some(labels),
+                    annotations: Some(annotations),
+                    ..Default::default()
+                }),
+                spec: Some(pod_spec),
+            }),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+/*
+This function creates or updates the ReplicaSet of an RTResource
+with the desired number of replicas.
+*/
+pub async fn apply_replicaset(thread_name: &str, client: Client, rtresource: &RTResource, injections: &PodInjections<'_>) -> Result<(), ControllerError> {
+    check_target_namespace(injections.allowed_namespaces, rtresource)?;
+    let replicas = rtresource.spec.desired_replicas();
+    let replicaset = replicaset_of(thread_name, rtresource, replicas, injections);
+    let name = replicaset.metadata.name.clone().unwrap_or_default();
+    let api: Api<ReplicaSet> = Api::namespaced(client, &rtresource.spec.namespace);
+    match api.patch(&name, &PatchParams::apply(REPLICASET_FIELD_MANAGER).force(), &Patch::Apply(&replicaset)).await {
+        Ok(_) => {
+            println!("{} - ReplicaSet {} applied with {} replicas!", thread_name, name, replicas);
+            Ok(())
+        }
+        Err(e) => Err(ControllerError::api(&format!("{} - An error occurred while applying ReplicaSet {}", thread_name, name), e)),
+    }
+}
+
+/*
+This function deletes the ReplicaSets of an RTResource, orphaning
+their pods. It returns whether the deletion succeeded.
+*/
+pub async fn delete_replicasets(thread_name: &str, client: Client, uid: &str) -> bool {
+    let lp = ListParams::default().labels(&owner_labels().uid_selector(uid));
+    let replicasets = match Api::<ReplicaSet>::all(client.clone()).list(&lp).await {
+        Ok(list) => list.items,
+        Err(e) => {
+            eprintln!("{} - An error occurred while listing the ReplicaSets of RTResource {}: {}", thread_name, uid, e);
+            return false;
+        }
+    };
+    let dp = DeleteParams {
+        propagation_policy: Some(PropagationPolicy::Orphan),
+        ..Default::default()
+    };
+    let mut deleted = true;
+    for replicaset in replicasets {
+        let (Some(name), Some(namespace)) = (replicaset.metadata.name.as_deref(), replicaset.metadata.namespace.as_deref()) else {
+            continue;
+        };
+        match Api::<ReplicaSet>::namespaced(client.clone(), namespace).delete(name, &dp).await {
+            Ok(_) => println!("{} - ReplicaSet {} removed from namespace {}!", thread_name, name, namespace),
+            Err(kube::Error::Api(e)) if e.code == 404 => {}
+            Err(e) => {
+                eprintln!("{} - An error occurred while deleting ReplicaSet {} in namespace {}: {}", thread_name, name, namespace, e);
+                deleted = false;
+            }
+        }
+    }
+    deleted
+}
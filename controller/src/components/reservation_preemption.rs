@@ -0,0 +1,80 @@
+/*
+This file contains the first tier of the victim search of the
+built-in scheduler.
+When replicas of an RTResource do not fit on the nodes, the pending
+reservations of less critical RTResources (replicas pinned by
+spec.placementOverrides whose pod is not bound yet) are cancelled
+first, since cancelling a pending intent disrupts no running pod.
+The cancelled replicas are reported as preempted and their RTResource
+is re-enqueued each time capacity frees up (resurrection queue); they
+are reserved again once their node has room for them.
+Only when no pending reservation can make room do the replicas stay
+pending, the running pods being left to the second tier (kube-scheduler
+preemption, through the PriorityClasses of --print-policy).
+*/
+
+use std::collections::HashMap;
+use libc::{
+    pthread_mutex_lock,
+    pthread_mutex_unlock
+};
+
+use crate::utils::vars::SharedStatePtr;
+use crate::utils::rtresource::{
+    RTResource,
+    PendingPlacement
+};
+use crate::components::capacity_index::requested;
+use crate::components::resurrection::record_preemption;
+
+
+
+/*
+This function cancels the pending reservations of less critical
+RTResources making room for the given unplaceable replicas, on the
+feasible nodes they were discarded from for lack of capacity.
+It returns the number of reservations cancelled.
+It must be called without holding the shared mutex.
+*/
+pub fn preempt_pending_reservations(
+    state: SharedStatePtr,
+    rtresource: &RTResource,
+    criticality: u32,
+    unplaceable: &[PendingPlacement],
+    feasible: &[String]
+) -> usize {
+    let Some(spec) = rtresource.spec.template.spec.as_ref() else {
+        return 0;
+    };
+    let shape = requested(spec);
+    let shared_state = unsafe { &mut *state.0 };
+    let victims = unsafe {
+        pthread_mutex_lock(&mut shared_state.mutex);
+        let mut claimed: HashMap<String, (i64, i64)> = HashMap::new();
+        let mut victims = Vec::new();
+        for pending in unplaceable {
+            let nodes: Vec<String> = pending.nodes.iter().flatten()
+                .filter(|f| f.plugin == "Capacity" && feasible.contains(&f.node))
+                .map(|f| f.node.clone())
+                .collect();
+            victims.extend(shared_state.capacity.cancel_reservations(criticality, shape, &nodes, &mut claimed));
+        }
+        pthread_mutex_unlock(&mut shared_state.mutex);
+
+        victims
+    };
+
+    for (msg, victim_criticality, ordinal) in victims.iter() {
+        println!(
+            "Watchdog - Cancelled the pending reservation of replica {} of RTResource {}, {} (criticality {}) for RTResource {} (criticality {})!",
+            ordinal,
+            msg.name,
+            msg.uid,
+            victim_criticality,
+            rtresource.metadata.name.clone().unwrap_or_default(),
+            criticality
+        );
+        record_preemption(shared_state, msg, *victim_criticality);
+    }
+    victims.len()
+}
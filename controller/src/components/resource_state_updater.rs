@@ -12,6 +12,14 @@ use kube::Api;
 
 use crate::utils::vars::SharedState;
 use crate::utils::rtresource::RTResource;
+use crate::components::resurrection::{
+    PREEMPTED_CONDITION,
+    forget_preemptions
+};
+use crate::utils::configuration::ControllerMode;
+use crate::components::recovery_probe::ready_replicas;
+use crate::utils::priorities::effective_criticality;
+use crate::utils::owner_labels::owner_labels;
 
 
 
@@ -20,7 +28,8 @@ pub extern "C" fn resource_state_updater(thread_data: *mut c_void) -> *mut c_voi
     unsafe {
         let shared_state = &mut *(thread_data as *mut SharedState);
 
-        shared_state.runtime_handle.block_on(async {
+        let runtime_handle = shared_state.runtime_handle.clone();
+        runtime_handle.block_on(async {
             let mut error_count: usize = 0;
             let lp = kube::api::ListParams::default();
             'outer: loop {
@@ -32,7 +41,7 @@ pub extern "C" fn resource_state_updater(thread_data: *mut c_void) -> *mut c_voi
                     */
                     Ok(list) => {
                         let mut items = list.items;
-                        items.sort_by_key(|r| r.spec.criticality);
+                        items.sort_by_key(|r| effective_criticality(&shared_state.config, r.metadata.namespace.as_deref().unwrap_or_default(), r.spec.criticality));
                         for r in items {
                             if let Some(conditions) = r.status.as_ref().and_then(|s| s.conditions.as_ref()) {
                                 let is_progressing = conditions.iter().any(|c| c.condition_type == "Progressing" && c.status == "True");
@@ -42,10 +51,10 @@ pub extern "C" fn resource_state_updater(thread_data: *mut c_void) -> *mut c_voi
 
                                     /*
                                     1. We list the pods belonging to this RTResource
-                                    identified by the label rtresource_uid=uid.
+                                    identified by the UID owner label.
                                     */
                                     let pod_lp = kube::api::ListParams::default()
-                                        .labels(&format!("rtresource_uid={}", uid));
+                                        .labels(&owner_labels().uid_selector(uid));
                                     let pods = match shared_state.context.pods.list(&pod_lp).await {
                                         Ok(pod_list) => pod_list.items,
                                         Err(e) => {
@@ -55,7 +64,11 @@ pub extern "C" fn resource_state_updater(thread_data: *mut c_void) -> *mut c_voi
                                     };
 
                                     /*
-                                    2. We count the number of pods in Running state.
+                                    2. We count the number of pods in Running state,
+                                    and the number of ready ones: a replica is ready
+                                    when all its containers, except the ones excluded
+                                    in the spec (e.g. sidecars), are ready, or when it
+                                    answers its spec.recoveryProbe.
                                     */
                                     let running_count = pods.iter().filter(|p| {
                                         if let Some(status) = &p.status {
@@ -64,6 +77,8 @@ pub extern "C" fn resource_state_updater(thread_data: *mut c_void) -> *mut c_voi
                                             false
                                         }
                                     }).count() as i32;
+                                    let exclusions = r.spec.readiness_exclusions.clone().unwrap_or_default();
+                                    let ready_count = ready_replicas(&pods, &r, &exclusions).await.len() as i32;
 
                                     /*
                                     3. Check if the pod running count has changed compared to
@@ -71,13 +86,14 @@ pub extern "C" fn resource_state_updater(thread_data: *mut c_void) -> *mut c_voi
                                     there's an actual change.
                                     */
                                     let current_replicas = r.status.as_ref().and_then(|s| s.replicas).unwrap_or(-1);
+                                    let current_ready = r.status.as_ref().and_then(|s| s.ready_replicas).unwrap_or(-1);
                                     
-                                    if current_replicas != running_count {
+                                    if current_replicas != running_count || current_ready != ready_count {
                                         /*
                                         4. We update the RTResource status with the
-                                        current number of running replicas and update
+                                        current number of running and ready replicas and update
                                         the conditions accordingly.
-                                        If the number of running replicas matches the desired one,
+                                        If the number of ready replicas matches the desired one,
                                         we set the "Progressing" to 'False' and "Ready" to 'True',
                                         then we update running replicas status field.
                                         Otherwise, we only update the replicas count.
@@ -85,21 +101,22 @@ pub extern "C" fn resource_state_updater(thread_data: *mut c_void) -> *mut c_voi
                                         let mut new_status = r.status.clone().unwrap_or_default();
                                         
                                         new_status.replicas = Some(running_count);
+                                        new_status.ready_replicas = Some(ready_count);
 
                                         let mut new_conditions = new_status.conditions.unwrap_or_default();
                                         let transition_time = chrono::Utc::now().to_rfc3339();
-                                        if running_count == desired_replicas {
+                                        if ready_count == desired_replicas {
                                             for cond in &mut new_conditions {
                                                 if cond.condition_type == "Progressing" {
                                                     cond.status = "False".to_string();
-                                                    cond.reason = Some("All desired replicas are running!".to_string());
-                                                    cond.message = Some("All desired replicas are running!".to_string());
+                                                    cond.reason = Some("All desired replicas are ready!".to_string());
+                                                    cond.message = Some("All desired replicas are ready!".to_string());
                                                     cond.last_transition_time = Some(transition_time.clone());
                                                 }
                                                 if cond.condition_type == "Ready" {
                                                     cond.status = "True".to_string();
-                                                    cond.reason = Some("All desired replicas are running!".to_string());
-                                                    cond.message = Some("All desired replicas are running!".to_string());
+                                                    cond.reason = Some("All desired replicas are ready!".to_string());
+                                                    cond.message = Some("All desired replicas are ready!".to_string());
                                                     cond.last_transition_time = Some(transition_time.clone());
                                                 }
                                             }
@@ -107,10 +124,29 @@ pub extern "C" fn resource_state_updater(thread_data: *mut c_void) -> *mut c_voi
 
                                         new_status.conditions = Some(new_conditions);
 
+                                        /*
+                                        Once all desired replicas are ready,
+                                        preempted replicas (if any) have recovered.
+                                        */
+                                        if ready_count == desired_replicas && forget_preemptions(shared_state, uid) {
+                                            new_status.preempted_replicas = Some(0);
+                                            new_status.set_condition(
+                                                PREEMPTED_CONDITION,
+                                                "False",
+                                                "Recovered",
+                                                "All preempted replicas have recovered"
+                                            );
+                                        }
+
                                         /*
                                         5. We push the status update to the Kubernetes API
-                                        server for the RTResource.
+                                        server for the RTResource (unless in observe mode,
+                                        where the status is left to the active controller).
                                         */
+                                        if shared_state.config.mode == ControllerMode::Observe {
+                                            println!("State Updater - Observe mode: RTResource {} would be updated to replicas={}, ready={}, desired={}", uid, running_count, ready_count, desired_replicas);
+                                            continue;
+                                        }
                                         let mut updated_resource = r.clone();
                                         updated_resource.status = Some(new_status);
                                         let rtresource_namespaced_api = Api::<RTResource>::namespaced(
@@ -118,12 +154,12 @@ pub extern "C" fn resource_state_updater(thread_data: *mut c_void) -> *mut c_voi
                                             r.metadata.namespace.as_ref().unwrap()
                                         );
                                         match rtresource_namespaced_api.replace_status(
-                                            &r.metadata.name.as_ref().unwrap(),
+                                            r.metadata.name.as_ref().unwrap(),
                                             &Default::default(),
                                             serde_json::to_vec(&updated_resource).unwrap()
                                         ).await {
                                             Ok(_) => {
-                                                println!("State Updater - Updated status for RTResource {}: replicas={}, desired={}", uid, running_count, desired_replicas);
+                                                println!("State Updater - Updated status for RTResource {}: replicas={}, ready={}, desired={}", uid, running_count, ready_count, desired_replicas);
                                             }
                                             Err(e) => {
                                                 eprintln!("State Updater - An error occurred while updating status for RTResource {}: {}", uid, e);
@@ -136,7 +172,7 @@ pub extern "C" fn resource_state_updater(thread_data: *mut c_void) -> *mut c_voi
                     }
                     Err(e) => {
                         eprintln!("State Updater - An error occurred while listing RTResources: {}", e);
-                        error_count = error_count + 1;
+                        error_count += 1;
                         if error_count >= 10 {
                             eprintln!("State Updater - Too many errors occurred while listing RTResources! Exiting...");
                             break 'outer;
@@ -5,20 +5,13 @@ and forwarding them to the event priority queue.
 */
 
 use std::{
-    mem,
     ptr,
-    process::exit,
-    os::raw::c_char,
     ffi::c_void
 };
 use libc::{
     mqd_t,
-    mq_open,
-    mq_send,
     mq_close,
     mq_unlink,
-    mq_attr,
-    O_CREAT,
     O_WRONLY
 };
 use kube::runtime::watcher::{
@@ -29,7 +22,35 @@ use kube::runtime::watcher::{
 use futures::StreamExt;
 
 use crate::utils::vars::SharedState;
-use crate::utils::vars::QueueMessage;
+use crate::utils::vars::{
+    QueueMessage,
+    EventKind
+};
+use crate::utils::event_queue::{
+    open_queue,
+    enqueue
+};
+use crate::utils::priorities::effective_criticality;
+use crate::utils::configuration::ControllerMode;
+use crate::components::archival::has_archive_finalizer;
+use crate::components::criticality_class::{
+    track_criticality_class,
+    reset_criticality_classes,
+    forget_criticality_class
+};
+use crate::components::criticality_defaults::{
+    criticality_unset,
+    apply_default_criticality
+};
+use crate::components::crash_dump::crash_exit;
+use crate::components::dispatcher::in_top_band;
+use crate::components::event_compaction::EventCompactor;
+use crate::components::crash_loop::resume_requested;
+use crate::components::config_watcher::{
+    track_references,
+    forget_references,
+    reset_references
+};
 
 
 
@@ -47,21 +68,14 @@ pub extern "C" fn crd_watcher(thread_data: *mut c_void) -> *mut c_void {
 			name: "".to_string(),
 			uid: "".to_string(),
 			namespace: "".to_string(),
+			enqueued_at: 0,
+			kind: EventKind::Resync,
+			trace_id: String::new(),
 		};
-    	let mut queue_attr: mq_attr = { mem::zeroed() };
-		queue_attr.mq_flags = 0;
-		queue_attr.mq_maxmsg = 2000;
-		queue_attr.mq_msgsize = 256;
-		queue_attr.mq_curmsgs = 0;
-		let queue_des: mqd_t = mq_open(
-			shared_state.queue.as_ptr() as *const c_char,
-			O_CREAT | O_WRONLY,
-			0664,
-			&queue_attr
-		);
+		let queue_des: mqd_t = open_queue(&shared_state.queue, O_WRONLY);
 		if queue_des == -1 {
 			eprintln!("CRD Watcher - An error occurred while opening the queue!");
-			exit(-1);
+			crash_exit(shared_state, "CRD Watcher - The queue could not be opened");
 		}
 		
 		/*
@@ -73,7 +87,8 @@ pub extern "C" fn crd_watcher(thread_data: *mut c_void) -> *mut c_void {
 		If the event is an addition or a modification, we only
 		filter for spec modifications.
 		*/
-		shared_state.runtime_handle.block_on(async {
+		let runtime_handle = shared_state.runtime_handle.clone();
+		runtime_handle.block_on(async {
 			let watcher_config = Config {
 				timeout: Some(100),
 				..Config::default()
@@ -82,7 +97,34 @@ pub extern "C" fn crd_watcher(thread_data: *mut c_void) -> *mut c_void {
 				shared_state.context.rt_resources.clone(),
 				watcher_config
 			).boxed();
-			while let Some(event) = watcher.next().await {
+			let mut compactor = EventCompactor::new(shared_state.config.event_compaction_quiet_ms);
+			loop {
+				/*
+				The events held by the compaction are enqueued
+				once their quiet period elapses.
+				*/
+				let event = match compactor.next_due() {
+					Some(due) => tokio::select! {
+						event = watcher.next() => event,
+						_ = tokio::time::sleep_until(due.into()) => {
+							for (held, criticality) in compactor.take_due() {
+								println!(
+									"CRD Watcher - Enqueueing the compacted event for RTResource {}, {} in namespace {} with criticality {}",
+									held.name,
+									held.uid,
+									held.namespace,
+									criticality
+								);
+								enqueue("CRD Watcher", queue_des, &held, criticality);
+							}
+							continue;
+						}
+					},
+					None => watcher.next().await,
+				};
+				let Some(event) = event else {
+					break;
+				};
 				match event{
 					Ok(Event::Applied(object)) => {
 						if let (Some(name), Some(uid), Some(namespace)) = (
@@ -90,32 +132,66 @@ pub extern "C" fn crd_watcher(thread_data: *mut c_void) -> *mut c_void {
 							object.metadata.uid.clone(),
 							object.metadata.namespace.clone(),
 						) {
+							track_references(shared_state, &object);
+							/*
+							RTResources created without a criticality are first
+							defaulted from their namespace, the resulting
+							modification is then handled as usual.
+							*/
+							if criticality_unset(&object) {
+								if shared_state.config.mode != ControllerMode::Observe {
+									apply_default_criticality(shared_state.context.client.clone(), &shared_state.config, &object).await;
+								}
+								continue;
+							}
+							track_criticality_class(shared_state, &object);
+							let criticality = effective_criticality(&shared_state.config, &namespace, object.spec.criticality);
 							let generation = object.metadata.generation.unwrap_or(0);
 							let observed_generation = object.status.as_ref()
 								.and_then(|s| s.observed_generation)
 								.unwrap_or(0);
-							if generation != observed_generation {
+							/*
+							RTResources held by the archive finalizer
+							are handled once their deletion starts.
+							*/
+							let archiving = object.metadata.deletion_timestamp.is_some() && has_archive_finalizer(&object);
+							let replicas_only = compactor.replicas_only(&uid, &object);
+							/*
+							RTResources an operator asked to resume after a crash
+							loop are handled although their spec is unchanged.
+							*/
+							let resuming = resume_requested(&object);
+							if generation != observed_generation || archiving || resuming {
 								msg.name = name.clone();
 								msg.uid = uid.clone();
 								msg.namespace = namespace.clone();
+								msg.kind = EventKind::ResourceApplied;
+								/*
+								A replica-count-only change outside the top band
+								is held until no further change comes in.
+								*/
+								if replicas_only
+									&& !archiving
+									&& !resuming
+									&& !in_top_band(shared_state, criticality)
+									&& compactor.hold(&msg, criticality, object.spec.replicas) {
+									println!(
+										"CRD Watcher - Holding the replica change of RTResource {}, {} in namespace {} for the quiet period",
+										msg.name,
+										msg.uid,
+										msg.namespace
+									);
+									continue;
+								}
+								compactor.supersede(&uid);
 								println!(
 									"CRD Watcher - Detected event for RTResource {}, {} in namespace {} with criticality {}",
 									msg.name,
 									msg.uid,
 									msg.namespace,
-									object.spec.criticality
+									criticality
 								);
-								let mut c_msg = msg.clone().into_bytes();
-								c_msg.push(0);
-								let result = mq_send(
-									queue_des,
-									c_msg.as_ptr() as *const i8,
-									c_msg.len(),
-									object.spec.criticality
-								);
-								if result == -1 {
-									eprintln!("CRD Watcher - An error occurred while sending a message to the queue!");
-								}
+								enqueue("CRD Watcher", queue_des, &msg, criticality);
 							}
 						} else {
 							eprintln!("CRD Watcher - An error occurred while retrieving RTResource metadata!");
@@ -131,35 +207,32 @@ pub extern "C" fn crd_watcher(thread_data: *mut c_void) -> *mut c_void {
 							msg.name = name.clone();
 							msg.uid = uid.clone();
 							msg.namespace = namespace.clone();
+							msg.kind = EventKind::ResourceDeleted;
+							forget_criticality_class(shared_state, &uid);
+							forget_references(shared_state, &uid);
+							compactor.forget(&uid);
+							let criticality = effective_criticality(&shared_state.config, &namespace, object.spec.criticality);
 							println!(
 								"CRD Watcher - Detected deletion of RTResource {}, {} in namespace {} with criticality {}",
 								msg.name,
 								msg.uid,
 								msg.namespace,
-								object.spec.criticality
+								criticality
 							);
-							let mut c_msg = msg.clone().into_bytes();
-							c_msg.push(0);
-							let result = mq_send(
-								queue_des,
-								c_msg.as_ptr() as *const i8,
-								c_msg.len(),
-								object.spec.criticality
-							);
-							if result == -1 {
-								eprintln!("CRD Watcher - An error occurred while sending a message to the queue!");
-							}
+							enqueue("CRD Watcher", queue_des, &msg, criticality);
 						} else {
 							eprintln!("CRD Watcher - An error occurred while retrieving the RTResource metadata!");
 							continue;
 						}
 					}
+					Ok(Event::Restarted(objects)) => {
+						reset_criticality_classes(shared_state, &objects);
+						compactor.reset(&objects);
+						reset_references(shared_state, &objects);
+					}
 					Err(e) => {
 						println!("{}", e);
 					}
-					_ => {
-						println!("CRD Watcher - Nothing happened yet!");
-					}
 				}
 			}
 		});
@@ -173,3 +246,4 @@ pub extern "C" fn crd_watcher(thread_data: *mut c_void) -> *mut c_void {
 
 	ptr::null_mut()
 }
+
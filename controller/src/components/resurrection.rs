@@ -0,0 +1,122 @@
+/*
+This file contains the victim resurrection queue.
+When kube-scheduler preempts a pod of an RTResource to make room for a
+more critical pod, the RTResource becomes under-replicated and its
+replacement replicas usually cannot be placed right away.
+Preempted RTResources are remembered and re-enqueued at their own
+criticality each time capacity frees up in the cluster, until all
+their desired replicas are running again.
+The number of replicas waiting for recovery is shown in the
+RTResource status (preemptedReplicas) with a "Preempted" condition.
+*/
+
+use std::time::Duration;
+use libc::{
+    pthread_mutex_lock,
+    pthread_mutex_unlock
+};
+use k8s_openapi::api::core::v1::Pod;
+
+use crate::utils::vars::{
+    SharedState,
+    QueueMessage
+};
+
+
+
+/*
+Condition set on RTResources with preempted replicas
+*/
+pub const PREEMPTED_CONDITION: &str = "Preempted";
+
+/*
+Minimum time between two resurrection rounds,
+so that a burst of deletions does not flood the queue
+*/
+pub const RESURRECTION_MIN_INTERVAL: Duration = Duration::from_secs(1);
+
+/*
+RTResource waiting for the recovery of preempted replicas
+*/
+#[derive(Clone)]
+pub struct Victim {
+    pub msg: QueueMessage,
+    pub criticality: u32,
+    pub replicas: i32,
+}
+
+/*
+This function returns whether a deleted pod
+was preempted by kube-scheduler.
+*/
+pub fn is_preempted(pod: &Pod) -> bool {
+    pod.status.as_ref()
+        .and_then(|s| s.conditions.as_ref())
+        .map(|c| c.iter().any(|c| {
+            c.type_ == "DisruptionTarget" && c.status == "True" && c.reason.as_deref() == Some("PreemptionByScheduler")
+        }))
+        .unwrap_or(false)
+}
+
+/*
+This function records the preemption of a replica of an RTResource.
+It must be called without holding the shared mutex.
+*/
+pub fn record_preemption(shared_state: &mut SharedState, msg: &QueueMessage, criticality: u32) {
+    unsafe {
+        pthread_mutex_lock(&mut shared_state.mutex);
+        let victim = shared_state.preempted.entry(msg.uid.clone()).or_insert_with(|| Victim {
+            msg: msg.clone(),
+            criticality,
+            replicas: 0,
+        });
+        victim.criticality = criticality;
+        victim.replicas += 1;
+        pthread_mutex_unlock(&mut shared_state.mutex);
+    }
+}
+
+/*
+This function returns the number of preempted replicas
+of an RTResource still waiting for recovery.
+It must be called without holding the shared mutex.
+*/
+pub fn preempted_replicas(shared_state: &mut SharedState, uid: &str) -> i32 {
+    unsafe {
+        pthread_mutex_lock(&mut shared_state.mutex);
+        let replicas = shared_state.preempted.get(uid).map(|v| v.replicas).unwrap_or(0);
+        pthread_mutex_unlock(&mut shared_state.mutex);
+
+        replicas
+    }
+}
+
+/*
+This function forgets the preemptions of an RTResource,
+once recovered or deleted. It returns whether there were any.
+It must be called without holding the shared mutex.
+*/
+pub fn forget_preemptions(shared_state: &mut SharedState, uid: &str) -> bool {
+    unsafe {
+        pthread_mutex_lock(&mut shared_state.mutex);
+        let forgotten = shared_state.preempted.remove(uid).is_some();
+        pthread_mutex_unlock(&mut shared_state.mutex);
+
+        forgotten
+    }
+}
+
+/*
+This function returns the RTResources waiting for the
+recovery of preempted replicas, to be re-enqueued.
+It must be called without holding the shared mutex.
+*/
+pub fn pending_victims(shared_state: &mut SharedState) -> Vec<Victim> {
+    unsafe {
+        pthread_mutex_lock(&mut shared_state.mutex);
+        let victims: Vec<Victim> = shared_state.preempted.values().cloned().collect();
+        pthread_mutex_unlock(&mut shared_state.mutex);
+
+        victims
+    }
+}
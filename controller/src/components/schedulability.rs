@@ -0,0 +1,196 @@
+/*
+This file contains the offline schedulability analysis.
+Given a set of RTResources and the cluster nodes, it places
+every replica the way the built-in scheduler does (same
+scheduling policy, same placement overrides), most critical
+RTResources first, tracking the memory left on each node and
+keeping apart the RTResources that conflict with each other.
+With spec.partialPlacementPolicy AllOrNothing, none of the
+replicas of an RTResource is placed unless all of them fit.
+The Hard RTResources must all be placed (admission test) and are
+placed before the Soft ones, which only get the capacity left.
+It is only built in the library, for capacity planning.
+*/
+
+use std::collections::HashMap;
+use k8s_openapi::api::core::v1::Node;
+
+use crate::utils::rtresource::{
+    RTResource,
+    PartialPlacementPolicy
+};
+use crate::utils::quantity::{
+    pod_requests,
+    resource_amount
+};
+use crate::components::scheduling_policy::{
+    SchedulingPolicy,
+    PlacementRequest,
+    FilterFailure
+};
+
+
+
+/*
+Placement of a replica computed by the analysis
+*/
+#[derive(Clone, Debug)]
+pub struct ReplicaPlacement {
+    pub name: String,
+    pub namespace: String,
+    pub criticality: u32,
+    pub ordinal: u32,
+    /*
+    Whether the RTResource is Soft (spec.criticalityClass)
+    */
+    pub soft: bool,
+    /*
+    The node the replica is bound to,
+    None if the replica is unschedulable
+    */
+    pub node: Option<String>,
+    /*
+    The reason each node was discarded for
+    */
+    pub failures: Vec<FilterFailure>,
+}
+
+/*
+Result of the analysis
+*/
+#[derive(Clone, Debug, Default)]
+pub struct SchedulabilityReport {
+    pub placements: Vec<ReplicaPlacement>,
+}
+
+impl SchedulabilityReport {
+    /*
+    This function returns whether every replica of
+    the Hard RTResources was placed.
+    */
+    pub fn is_schedulable(&self) -> bool {
+        self.placements.iter().all(|p| p.soft || p.node.is_some())
+    }
+
+    /*
+    This function returns the replicas that could not be placed.
+    */
+    pub fn unschedulable(&self) -> Vec<&ReplicaPlacement> {
+        self.placements.iter().filter(|p| p.node.is_none()).collect()
+    }
+}
+
+/*
+This function places the replicas of the given RTResources on the
+given nodes, Hard RTResources first, in criticality order
+(lower values first).
+Nodes marked unschedulable, or without enough allocatable memory
+left for a replica, are discarded before the policy runs.
+*/
+pub fn analyze(policy: &SchedulingPolicy, rtresources: &[RTResource], nodes: &[Node]) -> SchedulabilityReport {
+    let mut free_memory: HashMap<String, f64> = nodes.iter()
+        .filter_map(|n| {
+            let allocatable = n.status.as_ref().and_then(|s| s.allocatable.as_ref());
+            Some((n.metadata.name.clone()?, resource_amount(allocatable, "memory")))
+        })
+        .collect();
+
+    let mut ordered: Vec<&RTResource> = rtresources.iter().collect();
+    ordered.sort_by_key(|r| (r.spec.is_soft(), r.spec.criticality));
+
+    let mut report = SchedulabilityReport::default();
+    let mut placed: Vec<(&RTResource, String)> = Vec::new();
+    for rtresource in ordered {
+        let spec = rtresource.spec.template.spec.clone().unwrap_or_default();
+        let memory = pod_requests(&spec).get("memory").copied().unwrap_or(0.0);
+        let tolerations = spec.tolerations.clone().unwrap_or_default();
+        let conflicting: Vec<String> = placed.iter()
+            .filter(|(owner, _)| owner.conflicts(rtresource))
+            .map(|(_, node)| node.clone())
+            .collect();
+        let first = report.placements.len();
+        for ordinal in 0..rtresource.spec.replicas.unwrap_or(1).max(0) as u32 {
+            let mut failures: Vec<FilterFailure> = Vec::new();
+            let fitting: Vec<Node> = nodes.iter()
+                .filter(|n| {
+                    let name = n.metadata.name.clone().unwrap_or_default();
+                    let reason = if n.spec.as_ref().and_then(|s| s.unschedulable).unwrap_or(false) {
+                        Some("node is unschedulable".to_string())
+                    } else if free_memory.get(&name).copied().unwrap_or(0.0) < memory {
+                        Some("insufficient memory".to_string())
+                    } else {
+                        None
+                    };
+                    if let Some(reason) = reason {
+                        failures.push(FilterFailure {node: name, plugin: "Capacity", reason});
+                        return false;
+                    }
+                    true
+                })
+                .cloned()
+                .collect();
+
+            let node = match rtresource.spec.pinned_node(ordinal) {
+                Some(pinned) => {
+                    fitting.iter()
+                        .any(|n| n.metadata.name.as_deref() == Some(pinned))
+                        .then(|| pinned.to_string())
+                }
+                None => {
+                    let request = PlacementRequest {
+                        rtresource,
+                        nodes: &fitting,
+                        failed_nodes: &[],
+                        tolerations: &tolerations,
+                        conflicting_nodes: &conflicting,
+                        node_cpu_usage: &HashMap::new(),
+                        volume_terms: &[],
+                        stale_nodes: &[],
+                    };
+                    match policy.place(&request) {
+                        Ok(node) => Some(node),
+                        Err(filtered) => {
+                            failures.extend(filtered);
+                            None
+                        }
+                    }
+                }
+            };
+            if let Some(node) = node.as_ref() {
+                if let Some(free) = free_memory.get_mut(node) {
+                    *free -= memory;
+                }
+                placed.push((rtresource, node.clone()));
+            }
+
+            report.placements.push(ReplicaPlacement {
+                name: rtresource.metadata.name.clone().unwrap_or_default(),
+                namespace: rtresource.spec.namespace.clone(),
+                criticality: rtresource.spec.criticality,
+                ordinal,
+                soft: rtresource.spec.is_soft(),
+                node,
+                failures,
+            });
+        }
+
+        let replicas = &mut report.placements[first..];
+        if rtresource.spec.partial_placement_policy == Some(PartialPlacementPolicy::AllOrNothing)
+            && replicas.iter().any(|p| p.node.is_none()) {
+            for replica in replicas.iter_mut() {
+                if let Some(node) = replica.node.take() {
+                    if let Some(free) = free_memory.get_mut(&node) {
+                        *free += memory;
+                    }
+                    replica.failures.push(FilterFailure {
+                        node,
+                        plugin: "AllOrNothing",
+                        reason: "other replicas of the RTResource do not fit".to_string(),
+                    });
+                }
+            }
+            placed.retain(|(owner, _)| !std::ptr::eq(*owner, rtresource));
+        }
+    }
+    report
+}
@@ -0,0 +1,77 @@
+/*
+This file contains the client of the external placement service.
+When a scheduler webhook is configured, the built-in scheduler
+sends it the pod spec, the criticality of the RTResource and the
+nodes feasible for the pod, and binds the pod to the node it picks.
+The service is optional: any error, timeout or invalid answer
+leaves the placement to the built-in scheduling policy.
+*/
+
+use std::time::Duration;
+use k8s_openapi::api::core::v1::{
+    Node,
+    Pod
+};
+use serde::Deserialize;
+
+use crate::utils::rtresource::RTResource;
+use crate::utils::http::post_json;
+
+
+
+/*
+Answer of the external placement service
+*/
+#[derive(Deserialize)]
+struct PlacementResponse {
+    node: String,
+}
+
+/*
+This function asks the external placement service for the node
+of a pod, among the given candidates.
+It returns None if the service did not pick a valid candidate
+in time, so that the caller falls back to the built-in policy.
+*/
+pub async fn external_placement(thread_name: &str, url: &str, timeout: Duration, pod: &Pod, rtresource: &RTResource, candidates: &[&Node]) -> Option<String> {
+    let names: Vec<String> = candidates.iter()
+        .filter_map(|n| n.metadata.name.clone())
+        .collect();
+    let payload = serde_json::json!({
+        "pod": {
+            "name": pod.metadata.name,
+            "namespace": pod.metadata.namespace,
+            "spec": pod.spec,
+        },
+        "rtresource": {
+            "name": rtresource.metadata.name,
+            "namespace": rtresource.metadata.namespace,
+            "uid": rtresource.metadata.uid,
+        },
+        "criticality": rtresource.spec.criticality,
+        "candidates": names,
+    });
+
+    let response = match post_json(url, &payload, timeout).await {
+        Ok((status, body)) if (200..300).contains(&status) => body,
+        Ok((status, _)) => {
+            eprintln!("{} - The scheduler webhook answered with status {}, falling back to the built-in policy!", thread_name, status);
+            return None;
+        }
+        Err(e) => {
+            eprintln!("{} - The scheduler webhook could not be reached ({}), falling back to the built-in policy!", thread_name, e);
+            return None;
+        }
+    };
+    match serde_json::from_slice::<PlacementResponse>(&response) {
+        Ok(answer) if names.contains(&answer.node) => Some(answer.node),
+        Ok(answer) => {
+            eprintln!("{} - The scheduler webhook picked node {} which is not a candidate, falling back to the built-in policy!", thread_name, answer.node);
+            None
+        }
+        Err(e) => {
+            eprintln!("{} - Invalid scheduler webhook answer ({}), falling back to the built-in policy!", thread_name, e);
+            None
+        }
+    }
+}
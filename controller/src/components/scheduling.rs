@@ -4,51 +4,303 @@ lifecycle functions.
 */
 
 use std::{
+    fmt,
     error::Error,
-    collections::BTreeMap,
+    collections::{
+        BTreeMap,
+        HashMap
+    },
     time::{
+        Duration,
+        Instant,
         SystemTime,
         UNIX_EPOCH
     }
 };
+use futures::future::join_all;
 use kube::{
     Client,
     Api,
     api::{
         PostParams,
-        DeleteParams
+        DeleteParams,
+        Patch,
+        PatchParams
     }
 };
-use k8s_openapi::api::core::v1::Pod;
-// use rand::Rng; // For the random scheduler (currently not used)
+use k8s_openapi::api::core::v1::{
+    EnvVar,
+    EnvVarSource,
+    Node,
+    ObjectFieldSelector,
+    Pod,
+    PodSpec,
+    Toleration
+};
 
-use crate::utils::rtresource::RTResource;
+use crate::utils::rtresource::{
+    RTResource,
+    PendingPlacement,
+    NodeFailure
+};
+use crate::components::planner::{
+    ORDINAL_LABEL,
+    LabelUpdate
+};
+use crate::components::scheduling_policy::{
+    SchedulingPolicy,
+    PlacementRequest,
+    FilterFailure,
+    OS_LABEL
+};
+use crate::components::scheduler_webhook::external_placement;
+use crate::components::conflicts::add_conflict_anti_affinity;
+use crate::components::services::set_replica_hostname;
+use crate::components::image_variants::apply_image_variant;
+use crate::components::security_hardening::harden_pod_security;
+use crate::components::volume_topology::{
+    VolumeTopology,
+    VolumeBindingPending
+};
+use crate::components::namespace_policy::check_target_namespace;
+use crate::components::label_guard::{
+    OWNER_ANNOTATION,
+    owner_annotation
+};
+use crate::components::config_watcher::CONFIG_HASH_ANNOTATION;
+use crate::utils::errors::ControllerError;
+use crate::components::pod_defaults::{
+    PodDefaults,
+    apply_pod_defaults
+};
 
 
 
 /*
-This function creates a Pod in the cluster.
+Pause between two checks of a yielding teardown,
+and longest time a teardown yields between two batches
+*/
+const TEARDOWN_YIELD: Duration = Duration::from_millis(10);
+const TEARDOWN_YIELD_MAX: Duration = Duration::from_secs(1);
+
+/*
+Annotation carrying the trace identifier of the
+event whose reconcile created the pod
+*/
+pub const TRACE_ANNOTATION: &str = "rtgroup.critical.com/trace-id";
+
+/*
+Settings the controller injects into the pods it creates
+*/
+pub struct PodInjections<'a> {
+    pub tolerations: &'a [Toleration],
+    pub defaults: Option<&'a PodDefaults>,
+    pub harden_security: bool,
+    pub priority: Option<PodPriority>,
+    pub allowed_namespaces: Option<&'a [String]>,
+    pub trace_id: &'a str,
+    pub config_hash: Option<&'a str>,
+}
+
+/*
+Priority hints of the pods (POD_PRIORITY_HINTS): the PriorityClass
+of the criticality (--print-policy) and its preemption policy
+*/
+pub struct PodPriority {
+    pub class_name: String,
+    pub preemption_policy: &'static str,
+}
+
+/*
+Error returned when no node is feasible for a pod,
+with the reason each node was discarded for
 */
-pub async fn create_pod(thread_name: String, client: Client, rtresource: &RTResource) -> Result<(), Box<dyn Error>> {
+#[derive(Debug)]
+pub struct NoFeasibleNode {
+    pub thread_name: String,
+    pub pod_name: String,
+    pub failures: Vec<FilterFailure>,
+}
+
+impl fmt::Display for NoFeasibleNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reasons: Vec<String> = self.failures.iter()
+            .map(|f| format!("{} ({}: {})", f.node, f.plugin, f.reason))
+            .collect();
+        write!(f, "{} - No feasible node for Pod {}: {}!", self.thread_name, self.pod_name, reasons.join(", "))
+    }
+}
+
+impl Error for NoFeasibleNode {}
+
+impl NoFeasibleNode {
     /*
-    We must create the Pod metadata:
-    - name = rtresource_name-timestamp
-      (usiamo un timestamp per dare unicità al nome)
-    - namespace = rtresource.spec.namespace
-    - labels = those specified in the
-      rtresource.spec.template.metadata.labels + rtresource_id (UID) + criticality + selector.match_labels
-    - annotations = those specified in the rtresource.spec.template.metadata.annotations
+    This function returns the pending placement
+    reported in the RTResource status.
+    */
+    pub fn pending_placement(&self, ordinal: u32) -> PendingPlacement {
+        PendingPlacement {
+            ordinal,
+            reason: "NoFeasibleNode".to_string(),
+            message: format!("0/{} nodes are available", self.failures.len()),
+            nodes: Some(self.failures.iter()
+                .map(|f| NodeFailure {
+                    node: f.node.clone(),
+                    plugin: f.plugin.to_string(),
+                    reason: f.reason.clone(),
+                })
+                .collect()),
+        }
+    }
+}
 
-    Note: match expressions are not yet supported
+/*
+This function returns the pending placement of a pod
+that kube-scheduler could not place, if any.
+*/
+pub fn unscheduled_placement(pod: &Pod) -> Option<PendingPlacement> {
+    let condition = pod.status.as_ref()?.conditions.as_ref()?.iter()
+        .find(|c| c.type_ == "PodScheduled" && c.status == "False" && c.reason.as_deref() == Some("Unschedulable"))?;
+    let ordinal = pod.metadata.labels.as_ref()?.get(ORDINAL_LABEL)?.parse().ok()?;
+    Some(PendingPlacement {
+        ordinal,
+        reason: "Unschedulable".to_string(),
+        message: condition.message.clone().unwrap_or_default(),
+        nodes: None,
+    })
+}
+
+/*
+Placement context of a reconcile,
+used when the built-in scheduler is enabled
+*/
+pub struct Placement<'a> {
+    /*
+    The scheduling policy
     */
-    let timestamp = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .expect("Time went backwards!")
-        .as_millis()
-        .to_string();
-    let pod_name = format!("{}-{}", rtresource.metadata.name.as_ref().unwrap(), timestamp);
-    let pod_namespace = rtresource.spec.namespace.clone();
+    pub policy: &'a SchedulingPolicy,
+    /*
+    The nodes of the cluster
+    */
+    pub nodes: Vec<Node>,
+    /*
+    Nodes where replicas of the RTResource
+    were recently lost to a node failure
+    */
+    pub failed_nodes: Vec<String>,
+    /*
+    Nodes running pods of the RTResources
+    conflicting with the RTResource
+    */
+    pub conflicting_nodes: Vec<String>,
+    /*
+    Recent CPU usage of the nodes
+    */
+    pub node_cpu_usage: HashMap<String, f64>,
+    /*
+    The external placement service
+    (empty if the policy places the pods)
+    */
+    pub webhook_url: &'a str,
+    pub webhook_timeout: Duration,
+    /*
+    The topology of the persistent volumes of the pods
+    */
+    pub volumes: VolumeTopology,
+    /*
+    Nodes whose heartbeat is stale
+    (top criticality band only)
+    */
+    pub stale_nodes: Vec<String>,
+}
+
+
+
+/*
+This function injects the RT metadata of the replica as environment
+variables into every container, so that applications can configure
+themselves (e.g. their thread priorities) from their criticality.
+Variables already set by the template are left untouched, and
+REPLICA_ORDINAL is omitted for the pods without an ordinal.
+*/
+pub fn inject_rt_env(spec: &mut PodSpec, rtresource: &RTResource, ordinal: Option<u32>) {
+    let value = |name: &str, value: String| EnvVar {
+        name: name.to_string(),
+        value: Some(value),
+        value_from: None,
+    };
+    let mut variables = vec![
+        value("RTRESOURCE_NAME", rtresource.metadata.name.clone().unwrap_or_default()),
+        value("RTRESOURCE_UID", rtresource.metadata.uid.clone().unwrap_or_default()),
+        value("CRITICALITY", rtresource.spec.criticality.to_string()),
+        EnvVar {
+            name: "ASSIGNED_NODE".to_string(),
+            value: None,
+            value_from: Some(EnvVarSource {
+                field_ref: Some(ObjectFieldSelector {
+                    field_path: "spec.nodeName".to_string(),
+                    api_version: None,
+                }),
+                ..Default::default()
+            }),
+        },
+    ];
+    if let Some(ordinal) = ordinal {
+        variables.push(value("REPLICA_ORDINAL", ordinal.to_string()));
+    }
+    let init_containers = spec.init_containers.iter_mut().flatten();
+    for container in spec.containers.iter_mut().chain(init_containers) {
+        let env = container.env.get_or_insert_with(Vec::new);
+        for variable in variables.iter() {
+            if !env.iter().any(|e| e.name == variable.name) {
+                env.push(variable.clone());
+            }
+        }
+    }
+}
+
+/*
+This function sets the PriorityClass and the preemption policy of the
+criticality, so that the kubelet evicts the critical pods last under
+node pressure. The priority itself is resolved from the class at
+admission (setting both is rejected when they differ).
+A template setting its own PriorityClass or priority is left untouched,
+as is a preemption policy set by the template.
+*/
+fn set_priority_hints(spec: &mut PodSpec, priority: &PodPriority) {
+    if spec.priority_class_name.is_some() || spec.priority.is_some() {
+        return;
+    }
+    spec.priority_class_name = Some(priority.class_name.clone());
+    if spec.preemption_policy.is_none() {
+        spec.preemption_policy = Some(priority.preemption_policy.to_string());
+    }
+}
 
+/*
+This function restricts the pod to the nodes of the operating system
+targeted by the RTResource, so that kube-scheduler never binds a Linux
+RT template to a Windows node. A template selecting the operating
+system itself is left untouched.
+*/
+fn set_os_selector(spec: &mut PodSpec, os: &str) {
+    spec.node_selector
+        .get_or_insert_with(BTreeMap::new)
+        .entry(OS_LABEL.to_string())
+        .or_insert_with(|| os.to_string());
+}
+
+/*
+This function returns the labels and annotations of the pods of an
+RTResource, except the replica ordinal:
+    - labels = those specified in the rtresource.spec.template.metadata.labels
+      + propagated labels + selector.match_labels + owner labels
+      (owner labels, criticality class...)
+    - annotations = those specified in the rtresource.spec.template.metadata.annotations
+      + propagated annotations + the owner annotation
+      (to restore the labels if a user strips them)
+*/
+pub fn pod_metadata(rtresource: &RTResource) -> (BTreeMap<String, String>, BTreeMap<String, String>) {
     let mut labels: BTreeMap<String, String> = BTreeMap::new();
     let mut annotations: BTreeMap<String, String> = BTreeMap::new();
     if let Some(pod_metadata) = rtresource.spec.template.metadata.as_ref() {
@@ -63,31 +315,96 @@ pub async fn create_pod(thread_name: String, client: Client, rtresource: &RTReso
             }
         }
     }
-    if let Some(selector) = rtresource.spec.selector.as_ref() {
-        if let Some(match_labels) = selector.match_labels.as_ref() {
-            for (key, value) in match_labels.iter() {
-                labels.insert(key.clone(), value.clone());
-            }
+    labels.extend(rtresource.propagated_labels());
+    annotations.extend(rtresource.propagated_annotations());
+    if let Some(selector) = rtresource.spec.selector.as_ref()
+        && let Some(match_labels) = selector.match_labels.as_ref() {
+        for (key, value) in match_labels.iter() {
+            labels.insert(key.clone(), value.clone());
         }
     }
+    labels.extend(rtresource.owner_labels());
+    annotations.insert(OWNER_ANNOTATION.to_string(), owner_annotation(rtresource));
+    (labels, annotations)
+}
+
+/*
+This function injects the settings of the controller into a pod spec.
+The platform defaults of the criticality band fill the fields
+the template leaves unset, then the tolerations required by the
+controller (e.g. for the dedicated nodes), the hardened security
+context (with POD_SECURITY_HARDENING), the priority hints
+(with POD_PRIORITY_HINTS), the operating system selector and
+the anti-affinity towards the conflicting RTResources are added.
+*/
+pub fn inject_pod_settings(thread_name: &str, spec: &mut PodSpec, rtresource: &RTResource, injections: &PodInjections<'_>) {
+    if let Some(defaults) = injections.defaults {
+        apply_pod_defaults(spec, defaults);
+    }
+    if injections.harden_security {
+        harden_pod_security(thread_name, spec, rtresource);
+    }
+    if let Some(priority) = injections.priority.as_ref() {
+        set_priority_hints(spec, priority);
+    }
+    set_os_selector(spec, rtresource.spec.os_target());
+    for toleration in injections.tolerations {
+        let spec_tolerations = spec.tolerations.get_or_insert_with(Vec::new);
+        if !spec_tolerations.contains(toleration) {
+            spec_tolerations.push(toleration.clone());
+        }
+    }
+    add_conflict_anti_affinity(spec, rtresource);
+}
+
+/*
+This function creates a Pod in the cluster.
+It returns the node the pod was bound to by the controller, if any.
+*/
+pub async fn create_pod(thread_name: String, client: Client, rtresource: &RTResource, ordinal: u32, injections: &PodInjections<'_>, placement: Option<&Placement<'_>>) -> Result<Option<String>, ControllerError> {
+    /*
+    We must create the Pod metadata:
+    - name = rtresource_name-timestamp
+      (usiamo un timestamp per dare unicità al nome)
+    - namespace = rtresource.spec.namespace
+    - labels = the pod labels of the RTResource (see pod_metadata) + replica ordinal
+    - annotations = the pod annotations of the RTResource (see pod_metadata)
+      + trace identifier of the triggering event
+      + hash of the referenced ConfigMaps and Secrets (spec.restartOn)
+
+    Note: match expressions are not yet supported
+    */
+    check_target_namespace(injections.allowed_namespaces, rtresource)?;
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards!")
+        .as_millis()
+        .to_string();
+    let pod_name = format!("{}-{}", rtresource.metadata.name.as_ref().unwrap(), timestamp);
+    let pod_namespace = rtresource.spec.namespace.clone();
+
+    let (mut labels, mut annotations) = pod_metadata(rtresource);
     labels.insert(
-        "rtresource_name".to_string(),
-        rtresource.metadata.name.clone().unwrap_or_default(),
-    );
-    labels.insert(
-        "rtresource_uid".to_string(),
-        rtresource.metadata.uid.clone().unwrap_or_default(),
-    );
-    labels.insert(
-        "rtresource_namespace".to_string(),
-        rtresource.metadata.namespace.clone().unwrap_or_default(),
-    );
-    labels.insert(
-        "criticality".to_string(),
-        rtresource.spec.criticality.to_string(),
+        ORDINAL_LABEL.to_string(),
+        ordinal.to_string(),
     );
+    if !injections.trace_id.is_empty() {
+        annotations.insert(TRACE_ANNOTATION.to_string(), injections.trace_id.to_string());
+    }
+    if let Some(config_hash) = injections.config_hash {
+        annotations.insert(CONFIG_HASH_ANNOTATION.to_string(), config_hash.to_string());
+    }
 
-    let pod_spec = rtresource.spec.template.spec.clone();
+    /*
+    The settings of the controller (see inject_pod_settings) and
+    the RT metadata environment variables are injected.
+    */
+    let mut pod_spec = rtresource.spec.template.spec.clone();
+    if let Some(spec) = pod_spec.as_mut() {
+        inject_pod_settings(&thread_name, spec, rtresource, injections);
+        inject_rt_env(spec, rtresource, Some(ordinal));
+        set_replica_hostname(spec, rtresource, ordinal);
+    }
 
     /*
     Now we can create the Pod object
@@ -108,54 +425,170 @@ pub async fn create_pod(thread_name: String, client: Client, rtresource: &RTReso
         ..Default::default()
     };
 
-    // let scheduled_pod = scheduler(thread_name.clone(), pod);
+    /*
+    Replicas pinned by spec.placementOverrides are bound to their node.
+    Otherwise, if the built-in scheduler is enabled, the pod is bound
+    to a node here, else it is left to kube-scheduler.
+    */
+    let pod = match (rtresource.spec.pinned_node(ordinal), placement) {
+        (Some(node_name), _) => {
+            let mut pod = pod;
+            if let Some(spec) = pod.spec.as_mut() {
+                spec.node_name = Some(node_name.to_string());
+            }
+            println!("{} - Pod {} pinned on node {}!", thread_name, pod_name, node_name);
+            pod
+        }
+        (None, Some(placement)) => scheduler(&thread_name, client.clone(), pod, rtresource, placement).await?,
+        (None, None) => pod,
+    };
+
+    /*
+    A pod bound to a node by the controller
+    gets the image variant matching the node.
+    */
+    let mut pod = pod;
+    if let Some(placement) = placement
+        && let Some(node_name) = pod.spec.as_ref().and_then(|s| s.node_name.clone())
+        && let Some(node) = placement.nodes.iter().find(|n| n.metadata.name.as_ref() == Some(&node_name)) {
+        apply_image_variant(&thread_name, &mut pod, rtresource, node);
+    }
 
     let pp = PostParams::default();
-    match pod_api.create(&pp, &pod).await { // Use scheduled_pod when scheduler function is used
+    match pod_api.create(&pp, &pod).await {
         Ok(o) => println!("{} - Pod created: {}!", thread_name, o.metadata.name.as_ref().unwrap()),
-        Err(e) => println!("{} - An error occurred while creating the Pod: {}!", thread_name, e),
+        Err(e) => return Err(ControllerError::api(&format!("{} - An error occurred while creating the Pod", thread_name), e)),
     }
 
-    Ok(())
+    Ok(pod.spec.and_then(|s| s.node_name))
 }
 
 /*
 This function deletes a Pod from the cluster.
 */
-pub async fn delete_pod(thread_name: String, client: Client, pod: Pod) -> Result<(), Box<dyn Error>> {
+pub async fn delete_pod(thread_name: String, client: Client, pod: Pod) -> Result<(), ControllerError> {
 
     let pod_name = pod.metadata.name.as_ref().unwrap();
     let pod_namespace = pod.metadata.namespace.as_ref().unwrap();
     let pod_api: Api<Pod> = Api::namespaced(client.clone(), pod_namespace);
-    pod_api.delete(pod_name,  &DeleteParams::default()).await?;
-    println!("{} - Pod {} removed from namespace {}!", thread_name, pod_name, pod_namespace);
+    match pod_api.delete(pod_name,  &DeleteParams::default()).await {
+        Ok(_) => println!("{} - Pod {} removed from namespace {}!", thread_name, pod_name, pod_namespace),
+        /*
+        A pod that is already gone counts as deleted,
+        so that deletions can be safely retried.
+        */
+        Err(e) => match ControllerError::api(&format!("{} - An error occurred while deleting Pod {}", thread_name, pod_name), e) {
+            ControllerError::NotFound(_) => println!("{} - Pod {} was already removed from namespace {}!", thread_name, pod_name, pod_namespace),
+            e => return Err(e),
+        },
+    }
 
     Ok(())
 }
 
 /*
-This function schedules a Pod on a node.
+This function deletes Pods in parallel batches of the given size.
+Between two batches it waits, up to TEARDOWN_YIELD_MAX, while
+should_yield returns true (e.g. while more critical events wait for
+a watchdog), so that large teardowns do not starve them.
+It returns the number of failed deletions.
+*/
+pub async fn delete_pods<F: Fn() -> bool>(thread_name: &str, client: Client, pods: Vec<Pod>, batch_size: usize, should_yield: F) -> usize {
+    let mut failures: usize = 0;
+    let batches: Vec<&[Pod]> = pods.chunks(batch_size.max(1)).collect();
+    for (i, batch) in batches.iter().enumerate() {
+        if i > 0 {
+            let start = Instant::now();
+            while should_yield() && start.elapsed() < TEARDOWN_YIELD_MAX {
+                tokio::time::sleep(TEARDOWN_YIELD).await;
+            }
+        }
+        let results = join_all(batch.iter()
+            .map(|pod| delete_pod(thread_name.to_string(), client.clone(), pod.clone()))).await;
+        for result in results {
+            if let Err(e) = result {
+                eprintln!("{}", e);
+                e.record();
+                failures += 1;
+            }
+        }
+    }
+    failures
+}
 
-WARNING: at the moment, we only chose a random node frome those available.
+/*
+This function sets the given labels and annotations on a Pod
+through a merge patch, leaving the other ones untouched.
 */
+pub async fn patch_pod_labels(thread_name: String, client: Client, update: &LabelUpdate) -> Result<(), ControllerError> {
+    let pod_api: Api<Pod> = Api::namespaced(client.clone(), &update.namespace);
+    let patch = serde_json::json!({
+        "metadata": {
+            "labels": update.labels,
+            "annotations": update.annotations
+        }
+    });
+    pod_api.patch(&update.name, &PatchParams::default(), &Patch::Merge(&patch)).await
+        .map_err(|e| ControllerError::api(&format!("{} - An error occurred while updating the metadata of Pod {}", thread_name, update.name), e))?;
+    println!("{} - Pod {} metadata updated in namespace {}!", thread_name, update.name, update.namespace);
+
+    Ok(())
+}
+
 /*
-fn scheduler(thread_name: String, mut pod: Pod) -> Pod {
-    // TODO: take node list from apiserver
-    let random_number = rand::thread_rng().gen_range(1..=3);
-    let node_name: &str;
-    match random_number {
-        1 => node_name = "dessertw1",
-        2 => node_name = "dessertw2",
-        3 => node_name = "dessertw3",
-        _ => node_name = "dessertw1" // Default
+This function schedules a Pod on a node
+according to the built-in scheduling policy.
+If a scheduler webhook is configured, the node is picked
+by the external placement service among the feasible ones.
+The placement is deferred while the topology of a persistent
+volume of the pod is unknown.
+*/
+async fn scheduler(thread_name: &str, client: Client, mut pod: Pod, rtresource: &RTResource, placement: &Placement<'_>) -> Result<Pod, ControllerError> {
+    if let Some(reason) = placement.volumes.pending.as_ref() {
+        return Err(ControllerError::PlacementDeferred(VolumeBindingPending {
+            thread_name: thread_name.to_string(),
+            pod_name: pod.metadata.name.clone().unwrap_or_default(),
+            reason: reason.clone(),
+        }));
+    }
+    let tolerations = pod.spec.as_ref().and_then(|s| s.tolerations.clone()).unwrap_or_default();
+    let volume_terms = placement.volumes.node_terms();
+    let request = PlacementRequest {
+        rtresource,
+        nodes: &placement.nodes,
+        failed_nodes: &placement.failed_nodes,
+        tolerations: &tolerations,
+        conflicting_nodes: &placement.conflicting_nodes,
+        node_cpu_usage: &placement.node_cpu_usage,
+        volume_terms: &volume_terms,
+        stale_nodes: &placement.stale_nodes,
+    };
+    let mut external = None;
+    if !placement.webhook_url.is_empty() {
+        let (feasible, _) = placement.policy.feasible(&request);
+        if !feasible.is_empty() {
+            external = external_placement(thread_name, placement.webhook_url, placement.webhook_timeout, &pod, rtresource, &feasible).await;
+        }
+    }
+    let node_name = match external.map_or_else(|| placement.policy.place(&request), Ok) {
+        Ok(node_name) => node_name,
+        Err(failures) => {
+            return Err(ControllerError::Unschedulable(NoFeasibleNode {
+                thread_name: thread_name.to_string(),
+                pod_name: pod.metadata.name.clone().unwrap_or_default(),
+                failures,
+            }));
+        }
+    };
+
+    if let Err(e) = placement.volumes.select_node(thread_name, client, &node_name).await {
+        return Err(ControllerError::api(&format!("{} - An error occurred while recording node {} on the volume claims", thread_name, node_name), e));
     }
-    
     if let Some(spec) = pod.spec.as_mut() {
-        spec.node_name = Some(node_name.to_string());
+        spec.node_name = Some(node_name.clone());
     }
 
     println!("{} - Pod {} scheduled on node {}!", thread_name, pod.metadata.name.as_ref().unwrap(), node_name);
 
-    pod
+    Ok(pod)
 }
-*/
@@ -0,0 +1,286 @@
+/*
+This file contains the plugins of the
+scheduling policy framework.
+*/
+
+use std::collections::BTreeMap;
+use k8s_openapi::api::core::v1::{
+    Node,
+    NodeSelectorRequirement,
+    NodeSelectorTerm,
+    Taint,
+    Toleration
+};
+
+use crate::components::scheduling_policy::{
+    FilterPlugin,
+    ScorePlugin,
+    PlacementRequest,
+    node_zone,
+    node_os
+};
+
+
+
+/*
+Taint plugin.
+    - Filter: nodes with a NoSchedule or NoExecute taint
+      not tolerated by the pod are discarded.
+*/
+pub struct TaintToleration;
+
+/*
+This function returns whether a toleration tolerates a taint.
+*/
+fn tolerates(toleration: &Toleration, taint: &Taint) -> bool {
+    if toleration.effect.as_ref().is_some_and(|e| !e.is_empty() && *e != taint.effect) {
+        return false;
+    }
+    match toleration.operator.as_deref() {
+        Some("Exists") => toleration.key.as_ref().is_none_or(|k| k.is_empty() || *k == taint.key),
+        _ => toleration.key.as_ref() == Some(&taint.key)
+            && toleration.value.as_deref().unwrap_or_default() == taint.value.as_deref().unwrap_or_default(),
+    }
+}
+
+impl FilterPlugin for TaintToleration {
+    fn name(&self) -> &'static str {
+        "TaintToleration"
+    }
+
+    fn filter(&self, request: &PlacementRequest, node: &Node) -> Result<(), String> {
+        let taints = node.spec.as_ref().and_then(|s| s.taints.as_ref());
+        for taint in taints.into_iter().flatten() {
+            if !matches!(taint.effect.as_str(), "NoSchedule" | "NoExecute") {
+                continue;
+            }
+            if !request.tolerations.iter().any(|t| tolerates(t, taint)) {
+                return Err(format!("taint {}:{} is not tolerated", taint.key, taint.effect));
+            }
+        }
+        Ok(())
+    }
+}
+
+/*
+Node heartbeat plugin (NODE_HEARTBEAT_FRESHNESS_MS).
+    - Filter: nodes whose Lease was not renewed within the
+      freshness window (e.g. about to be declared dead) are
+      discarded for the pods of the top criticality band.
+*/
+pub struct NodeHeartbeat;
+
+impl FilterPlugin for NodeHeartbeat {
+    fn name(&self) -> &'static str {
+        "NodeHeartbeat"
+    }
+
+    fn filter(&self, request: &PlacementRequest, node: &Node) -> Result<(), String> {
+        match node.metadata.name.as_ref() {
+            Some(name) if request.stale_nodes.contains(name) => Err("node heartbeat is stale".to_string()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/*
+Operating system plugin (spec.osTarget).
+    - Filter: nodes running another operating system than
+      the one targeted by the pod (e.g. the Windows nodes of
+      a mixed cluster for a Linux RT template) are discarded.
+*/
+pub struct NodeOs;
+
+impl FilterPlugin for NodeOs {
+    fn name(&self) -> &'static str {
+        "NodeOs"
+    }
+
+    fn filter(&self, request: &PlacementRequest, node: &Node) -> Result<(), String> {
+        let target = request.rtresource.spec.os_target();
+        match node_os(node) {
+            os if os == target => Ok(()),
+            os => Err(format!("node runs {}, the pod targets {}", os, target)),
+        }
+    }
+}
+
+/*
+Conflict plugin (spec.conflictsWith).
+    - Filter: nodes running pods of an RTResource
+      conflicting with the RTResource are discarded.
+*/
+pub struct ConflictExclusion;
+
+impl FilterPlugin for ConflictExclusion {
+    fn name(&self) -> &'static str {
+        "ConflictExclusion"
+    }
+
+    fn filter(&self, request: &PlacementRequest, node: &Node) -> Result<(), String> {
+        match node.metadata.name.as_ref() {
+            Some(name) if request.conflicting_nodes.contains(name) => {
+                Err("node runs pods of a conflicting RTResource".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/*
+This function returns whether a value satisfies a node selector requirement.
+*/
+fn requirement_matches(requirement: &NodeSelectorRequirement, value: Option<&String>) -> bool {
+    let values = requirement.values.as_deref().unwrap_or_default();
+    let number = |v: &str| v.parse::<i64>().ok();
+    match requirement.operator.as_str() {
+        "In" => value.is_some_and(|v| values.contains(v)),
+        "NotIn" => value.is_none_or(|v| !values.contains(v)),
+        "Exists" => value.is_some(),
+        "DoesNotExist" => value.is_none(),
+        "Gt" => matches!((value.and_then(|v| number(v)), values.first().and_then(|v| number(v))), (Some(v), Some(bound)) if v > bound),
+        "Lt" => matches!((value.and_then(|v| number(v)), values.first().and_then(|v| number(v))), (Some(v), Some(bound)) if v < bound),
+        _ => false,
+    }
+}
+
+/*
+This function returns whether a node matches at least one
+of the node selector terms (an empty list matches every node).
+*/
+pub fn matches_node_selector_terms(terms: &[NodeSelectorTerm], node: &Node) -> bool {
+    let empty = BTreeMap::new();
+    let labels = node.metadata.labels.as_ref().unwrap_or(&empty);
+    let name = node.metadata.name.clone().unwrap_or_default();
+    terms.is_empty() || terms.iter().any(|term| {
+        let expressions = term.match_expressions.iter().flatten()
+            .all(|r| requirement_matches(r, labels.get(&r.key)));
+        let fields = term.match_fields.iter().flatten()
+            .all(|r| r.key == "metadata.name" && requirement_matches(r, Some(&name)));
+        expressions && fields
+    })
+}
+
+/*
+Volume topology plugin.
+    - Filter: nodes that cannot mount a persistent volume of the
+      pod (node affinity of a bound volume, allowed topologies of
+      a WaitForFirstConsumer StorageClass) are discarded.
+*/
+pub struct VolumeBinding;
+
+impl FilterPlugin for VolumeBinding {
+    fn name(&self) -> &'static str {
+        "VolumeBinding"
+    }
+
+    fn filter(&self, request: &PlacementRequest, node: &Node) -> Result<(), String> {
+        if request.volume_terms.iter().all(|terms| matches_node_selector_terms(terms, node)) {
+            Ok(())
+        } else {
+            Err("node does not match the topology of a persistent volume".to_string())
+        }
+    }
+}
+
+/*
+Zone-aware failover plugin (spec.failoverPolicy).
+    - Filter: if forbidSameZoneAsFailure is set, nodes in a zone
+      where a replica was recently lost are discarded.
+    - Score: nodes in a preferred zone are favoured, nodes in
+      a zone where a replica was recently lost are penalized.
+*/
+pub struct ZoneFailover;
+
+impl ZoneFailover {
+    fn failed_zones<'a>(request: &PlacementRequest<'a>) -> Vec<&'a String> {
+        request.nodes.iter()
+            .filter(|n| n.metadata.name.as_ref().is_some_and(|name| request.failed_nodes.contains(name)))
+            .filter_map(node_zone)
+            .collect()
+    }
+}
+
+impl FilterPlugin for ZoneFailover {
+    fn name(&self) -> &'static str {
+        "ZoneFailover"
+    }
+
+    fn filter(&self, request: &PlacementRequest, node: &Node) -> Result<(), String> {
+        let forbid = request.rtresource.spec.failover_policy.as_ref()
+            .and_then(|p| p.forbid_same_zone_as_failure)
+            .unwrap_or(false);
+        if !forbid {
+            return Ok(());
+        }
+        if let Some(zone) = node_zone(node)
+            && ZoneFailover::failed_zones(request).contains(&zone) {
+            return Err(format!("zone {} recently lost a replica", zone));
+        }
+        Ok(())
+    }
+}
+
+impl ScorePlugin for ZoneFailover {
+    fn score(&self, request: &PlacementRequest, node: &Node) -> i64 {
+        let Some(zone) = node_zone(node) else {
+            return 0;
+        };
+        let mut score = 0;
+        let preferred = request.rtresource.spec.failover_policy.as_ref()
+            .and_then(|p| p.preferred_zones.as_ref());
+        if preferred.is_some_and(|zones| zones.contains(zone)) {
+            score += 100;
+        }
+        if ZoneFailover::failed_zones(request).contains(&zone) {
+            score -= 100;
+        }
+        score
+    }
+}
+
+/*
+Node health plugin.
+    - Filter: nodes that are not Ready, cordoned or reporting
+      memory, disk or PID pressure are discarded.
+    - Score: nodes with a lower recent CPU usage are preferred
+      (from metrics-server, 0 when unavailable).
+*/
+pub struct NodeHealth;
+
+/*
+Node conditions reporting a pressure
+*/
+const PRESSURE_CONDITIONS: [&str; 3] = ["MemoryPressure", "DiskPressure", "PIDPressure"];
+
+impl FilterPlugin for NodeHealth {
+    fn name(&self) -> &'static str {
+        "NodeHealth"
+    }
+
+    fn filter(&self, _request: &PlacementRequest, node: &Node) -> Result<(), String> {
+        if node.spec.as_ref().and_then(|s| s.unschedulable).unwrap_or(false) {
+            return Err("node is cordoned".to_string());
+        }
+        let conditions = node.status.as_ref().and_then(|s| s.conditions.as_ref());
+        let ready = conditions.iter().flat_map(|c| c.iter())
+            .find(|c| c.type_ == "Ready")
+            .is_some_and(|c| c.status == "True");
+        if !ready {
+            return Err("node is not ready".to_string());
+        }
+        if let Some(pressure) = conditions.iter().flat_map(|c| c.iter())
+            .find(|c| PRESSURE_CONDITIONS.contains(&c.type_.as_str()) && c.status == "True") {
+            return Err(format!("node reports {}", pressure.type_));
+        }
+        Ok(())
+    }
+}
+
+impl ScorePlugin for NodeHealth {
+    fn score(&self, request: &PlacementRequest, node: &Node) -> i64 {
+        node.metadata.name.as_ref()
+            .and_then(|name| request.node_cpu_usage.get(name))
+            .map_or(0, |usage| -(usage.clamp(0.0, 1.0) * 100.0) as i64)
+    }
+}
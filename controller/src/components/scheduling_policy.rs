@@ -0,0 +1,219 @@
+/*
+This file contains the scheduling policy framework
+used by the built-in scheduler to place managed Pods.
+A policy is made of:
+    - filter plugins, discarding the nodes a pod cannot run on;
+    - score plugins, ranking the remaining nodes (each with a weight).
+The pod is bound to the feasible node with the highest weighted score,
+ties are broken randomly.
+*/
+
+use std::collections::HashMap;
+use k8s_openapi::api::core::v1::{
+    Node,
+    NodeSelectorTerm,
+    Toleration
+};
+use rand::seq::SliceRandom;
+
+use crate::utils::rtresource::{
+    RTResource,
+    DEFAULT_OS_TARGET
+};
+use crate::components::scheduling_plugins::{
+    TaintToleration,
+    ConflictExclusion,
+    ZoneFailover,
+    NodeHealth,
+    NodeHeartbeat,
+    NodeOs,
+    VolumeBinding
+};
+
+
+
+/*
+Label storing the topology zone of a node
+*/
+pub const ZONE_LABEL: &str = "topology.kubernetes.io/zone";
+
+/*
+Label storing the operating system of a node
+*/
+pub const OS_LABEL: &str = "kubernetes.io/os";
+
+/*
+Placement request submitted to the policy
+*/
+pub struct PlacementRequest<'a> {
+    /*
+    The RTResource owning the pod
+    */
+    pub rtresource: &'a RTResource,
+    /*
+    All the nodes of the cluster
+    */
+    pub nodes: &'a [Node],
+    /*
+    Nodes where replicas of the RTResource
+    were recently lost to a node failure
+    */
+    pub failed_nodes: &'a [String],
+    /*
+    The tolerations of the pod
+    */
+    pub tolerations: &'a [Toleration],
+    /*
+    Nodes running pods of the RTResources
+    conflicting with the RTResource
+    */
+    pub conflicting_nodes: &'a [String],
+    /*
+    Recent CPU usage of the nodes (fraction of
+    the allocatable CPU), when metrics-server is available
+    */
+    pub node_cpu_usage: &'a HashMap<String, f64>,
+    /*
+    Node selector terms of the persistent volumes of the pod
+    (a node must match at least one term of every volume)
+    */
+    pub volume_terms: &'a [Vec<NodeSelectorTerm>],
+    /*
+    Nodes whose Lease was not renewed within the
+    freshness window (top criticality band only)
+    */
+    pub stale_nodes: &'a [String],
+}
+
+/*
+Filter plugin: returns an error message
+when the pod cannot be placed on the node
+*/
+pub trait FilterPlugin {
+    fn name(&self) -> &'static str;
+    fn filter(&self, request: &PlacementRequest, node: &Node) -> Result<(), String>;
+}
+
+/*
+Score plugin: returns the score
+of the node for the pod
+*/
+pub trait ScorePlugin {
+    fn score(&self, request: &PlacementRequest, node: &Node) -> i64;
+}
+
+/*
+Filter failure of a node
+*/
+#[derive(Clone, Debug)]
+pub struct FilterFailure {
+    pub node: String,
+    pub plugin: &'static str,
+    pub reason: String,
+}
+
+/*
+Scheduling policy
+*/
+#[derive(Default)]
+pub struct SchedulingPolicy {
+    filters: Vec<Box<dyn FilterPlugin>>,
+    scorers: Vec<(Box<dyn ScorePlugin>, i64)>,
+}
+
+impl SchedulingPolicy {
+    pub fn with_filter(mut self, plugin: Box<dyn FilterPlugin>) -> Self {
+        self.filters.push(plugin);
+        self
+    }
+
+    pub fn with_scorer(mut self, plugin: Box<dyn ScorePlugin>, weight: i64) -> Self {
+        self.scorers.push((plugin, weight));
+        self
+    }
+
+    /*
+    This function returns the policy used by the built-in scheduler.
+    */
+    pub fn default_policy() -> Self {
+        SchedulingPolicy::default()
+            .with_filter(Box::new(NodeHealth))
+            .with_filter(Box::new(NodeHeartbeat))
+            .with_filter(Box::new(NodeOs))
+            .with_filter(Box::new(TaintToleration))
+            .with_filter(Box::new(ConflictExclusion))
+            .with_filter(Box::new(VolumeBinding))
+            .with_filter(Box::new(ZoneFailover))
+            .with_scorer(Box::new(ZoneFailover), 1)
+            .with_scorer(Box::new(NodeHealth), 1)
+    }
+
+    /*
+    This function returns the nodes passing all the filters,
+    and the reason each other node was discarded for.
+    */
+    pub fn feasible<'a>(&self, request: &PlacementRequest<'a>) -> (Vec<&'a Node>, Vec<FilterFailure>) {
+        let mut feasible: Vec<&'a Node> = Vec::new();
+        let mut failures: Vec<FilterFailure> = Vec::new();
+        'nodes: for node in request.nodes.iter() {
+            for filter in self.filters.iter() {
+                if let Err(reason) = filter.filter(request, node) {
+                    failures.push(FilterFailure {
+                        node: node.metadata.name.clone().unwrap_or_default(),
+                        plugin: filter.name(),
+                        reason,
+                    });
+                    continue 'nodes;
+                }
+            }
+            feasible.push(node);
+        }
+        (feasible, failures)
+    }
+
+    /*
+    This function selects the node for the pod.
+    If no node is feasible, it returns the reason
+    each node was discarded for.
+    */
+    pub fn place(&self, request: &PlacementRequest) -> Result<String, Vec<FilterFailure>> {
+        let (feasible, failures) = self.feasible(request);
+        let mut best: Vec<&Node> = Vec::new();
+        let mut best_score = i64::MIN;
+        for node in feasible {
+            let score: i64 = self.scorers.iter()
+                .map(|(scorer, weight)| scorer.score(request, node) * weight)
+                .sum();
+            if score > best_score {
+                best_score = score;
+                best.clear();
+            }
+            if score == best_score {
+                best.push(node);
+            }
+        }
+
+        match best.choose(&mut rand::thread_rng()) {
+            Some(node) => Ok(node.metadata.name.clone().unwrap_or_default()),
+            None => Err(failures),
+        }
+    }
+}
+
+/*
+This function returns the topology zone of a node.
+*/
+pub fn node_zone(node: &Node) -> Option<&String> {
+    node.metadata.labels.as_ref().and_then(|l| l.get(ZONE_LABEL))
+}
+
+/*
+This function returns the operating system of a node
+(the nodes without the label are taken as Linux nodes).
+*/
+pub fn node_os(node: &Node) -> &str {
+    node.metadata.labels.as_ref()
+        .and_then(|l| l.get(OS_LABEL))
+        .map(String::as_str)
+        .unwrap_or(DEFAULT_OS_TARGET)
+}
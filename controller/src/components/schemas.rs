@@ -0,0 +1,82 @@
+/*
+This file contains the schemas of the controller APIs, generated
+from the Rust types, so that external tooling (e.g. the validation
+step of a GitOps pipeline) stays in sync with the controller:
+    - the OpenAPI v3 schema of every served version of the
+      custom resources (RTResource, RTDecision, RTClusterStats);
+    - the JSON Schemas of the typed admin API payloads (the
+      snapshot, its import report, the oracle verdict and the
+      priority change request).
+The schemas are printed with --print-schemas and served by the
+admin API (GET /schemas).
+*/
+
+use std::error::Error;
+use kube::CustomResourceExt;
+use k8s_openapi::apiextensions_apiserver::pkg::apis::apiextensions::v1::CustomResourceDefinition;
+use schemars::schema_for;
+
+use crate::utils::rtresource::RTResource;
+use crate::utils::rtdecision::RTDecision;
+use crate::utils::rtclusterstats::RTClusterStats;
+use crate::components::handover::{
+    Snapshot,
+    ImportReport
+};
+use crate::components::priority_oracle::OracleVerdict;
+use crate::components::admin_server::PriorityUpdate;
+
+
+
+/*
+This function returns the OpenAPI v3 schemas of a custom
+resource, keyed by version.
+*/
+fn versioned_schemas(crd: CustomResourceDefinition) -> serde_json::Value {
+    let versions: serde_json::Map<String, serde_json::Value> = crd.spec.versions.into_iter()
+        .map(|v| (
+            v.name,
+            serde_json::json!(v.schema.and_then(|s| s.open_api_v3_schema))
+        ))
+        .collect();
+    serde_json::Value::Object(versions)
+}
+
+/*
+This function returns the schemas of the custom resources
+and of the admin API payloads.
+*/
+pub fn schemas() -> serde_json::Value {
+    serde_json::json!({
+        "customResources": {
+            "RTResource": versioned_schemas(RTResource::crd()),
+            "RTDecision": versioned_schemas(RTDecision::crd()),
+            "RTClusterStats": versioned_schemas(RTClusterStats::crd())
+        },
+        "adminApi": {
+            "GET /snapshot": schema_for!(Snapshot),
+            "PUT /snapshot": {
+                "request": schema_for!(Snapshot),
+                "response": schema_for!(ImportReport)
+            },
+            "POST /oracle": {
+                "request": {
+                    "description": "A Kubernetes Pod (io.k8s.api.core.v1.Pod), with its namespace",
+                    "type": "object"
+                },
+                "response": schema_for!(OracleVerdict)
+            },
+            "PUT /priorities/{role}": {
+                "request": schema_for!(PriorityUpdate)
+            }
+        }
+    })
+}
+
+/*
+This function prints the schemas.
+*/
+pub fn print_schemas() -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    println!("{}", serde_json::to_string_pretty(&schemas())?);
+    Ok(())
+}
@@ -0,0 +1,109 @@
+/*
+This file contains the hardening of the security context of the
+pods created by the controller (POD_SECURITY_HARDENING), since the
+hand-written templates keep shipping overly permissive settings.
+The hardened pods:
+    - run as non-root, unless the template explicitly runs them as root;
+    - use the RuntimeDefault seccomp profile, unless the template
+      selects a Localhost profile;
+    - run unprivileged, without privilege escalation;
+    - drop every capability but those declared in spec.rt.capabilities
+      (e.g. SYS_NICE, IPC_LOCK for the RT workloads).
+*/
+
+use k8s_openapi::api::core::v1::{
+    Capabilities,
+    Container,
+    PodSecurityContext,
+    PodSpec,
+    SeccompProfile,
+    SecurityContext
+};
+
+use crate::utils::rtresource::RTResource;
+
+
+
+/*
+This function returns whether a seccomp profile is a Localhost profile,
+the only kind of profile kept by the hardening.
+*/
+fn is_localhost(profile: Option<&SeccompProfile>) -> bool {
+    profile.is_some_and(|p| p.type_ == "Localhost")
+}
+
+/*
+This function returns the seccomp profile enforced by the hardening.
+*/
+fn runtime_default() -> SeccompProfile {
+    SeccompProfile {
+        type_: "RuntimeDefault".to_string(),
+        ..Default::default()
+    }
+}
+
+/*
+This function returns whether the template explicitly runs a
+container as root, in which case runAsNonRoot is not enforced.
+*/
+fn runs_as_root(pod: Option<&PodSecurityContext>, container: Option<&SecurityContext>) -> bool {
+    let user = container.and_then(|c| c.run_as_user).or(pod.and_then(|p| p.run_as_user));
+    let non_root = container.and_then(|c| c.run_as_non_root).or(pod.and_then(|p| p.run_as_non_root));
+    user == Some(0) || non_root == Some(false)
+}
+
+/*
+This function hardens the security context of a container,
+logging the capabilities removed from the template.
+*/
+fn harden_container(thread_name: &str, container: &mut Container, pod: Option<&PodSecurityContext>, capabilities: &[String]) {
+    let root = runs_as_root(pod, container.security_context.as_ref());
+    let context = container.security_context.get_or_insert_with(Default::default);
+    if !root {
+        context.run_as_non_root = Some(true);
+    }
+    context.privileged = Some(false);
+    context.allow_privilege_escalation = Some(false);
+    if context.seccomp_profile.is_some() && !is_localhost(context.seccomp_profile.as_ref()) {
+        context.seccomp_profile = Some(runtime_default());
+    }
+    let requested = context.capabilities.as_ref().and_then(|c| c.add.clone()).unwrap_or_default();
+    let removed: Vec<&String> = requested.iter().filter(|c| !capabilities.contains(c)).collect();
+    if !removed.is_empty() {
+        println!(
+            "{} - Removing the undeclared capabilities {:?} from container {}!",
+            thread_name,
+            removed,
+            container.name
+        );
+    }
+    context.capabilities = Some(Capabilities {
+        add: if capabilities.is_empty() { None } else { Some(capabilities.to_vec()) },
+        drop: Some(vec!["ALL".to_string()]),
+    });
+}
+
+/*
+This function hardens the security context
+of a pod created for an RTResource.
+*/
+pub fn harden_pod_security(thread_name: &str, spec: &mut PodSpec, rtresource: &RTResource) {
+    let capabilities = rtresource.spec.rt.as_ref()
+        .and_then(|rt| rt.capabilities.clone())
+        .unwrap_or_default();
+    let pod_context = spec.security_context.get_or_insert_with(Default::default);
+    if !is_localhost(pod_context.seccomp_profile.as_ref()) {
+        pod_context.seccomp_profile = Some(runtime_default());
+    }
+    let pod_context = spec.security_context.clone();
+    for container in spec.containers.iter_mut().chain(spec.init_containers.iter_mut().flatten()) {
+        harden_container(thread_name, container, pod_context.as_ref(), &capabilities);
+    }
+    let all_non_root = spec.containers.iter()
+        .chain(spec.init_containers.iter().flatten())
+        .all(|c| c.security_context.as_ref().and_then(|s| s.run_as_non_root) == Some(true));
+    if all_non_root && let Some(context) = spec.security_context.as_mut()
+        && context.run_as_non_root.is_none() && context.run_as_user != Some(0) {
+        context.run_as_non_root = Some(true);
+    }
+}
@@ -0,0 +1,157 @@
+/*
+This file contains the Service maintained for the
+RTResources declaring spec.service. The Service is named after
+the RTResource, lives in the namespace of its pods and selects
+them through the controller-owned UID owner label, so that
+the selector always matches the generated pods.
+With a headless Service each replica also gets a stable DNS name,
+<rtresource>-<ordinal>.<service>.<namespace>.svc, through the
+hostname and subdomain set on its pod.
+The Service is removed when spec.service is disabled
+or when the RTResource is deleted.
+*/
+
+use kube::{
+    Api,
+    Client,
+    api::{
+        DeleteParams,
+        ListParams,
+        Patch,
+        PatchParams
+    }
+};
+use k8s_openapi::api::core::v1::{
+    PodSpec,
+    Service
+};
+
+use crate::utils::rtresource::RTResource;
+use crate::utils::owner_labels::owner_labels;
+
+
+
+/*
+Field manager used for the Service server-side applies
+*/
+const SERVICE_FIELD_MANAGER: &str = "preempt-k8s";
+
+/*
+This function returns whether an RTResource
+requests a Service.
+*/
+fn service_enabled(rtresource: &RTResource) -> bool {
+    rtresource.spec.service.as_ref().is_some_and(|s| s.enabled)
+}
+
+/*
+This function sets the hostname and subdomain of a replica,
+so that it gets a stable DNS name through the headless Service.
+The hostname and subdomain set by the template are kept.
+*/
+pub fn set_replica_hostname(spec: &mut PodSpec, rtresource: &RTResource, ordinal: u32) {
+    if !service_enabled(rtresource) || !rtresource.spec.service.as_ref().is_some_and(|s| s.headless.unwrap_or(false)) {
+        return;
+    }
+    let name = rtresource.metadata.name.clone().unwrap_or_default();
+    spec.hostname.get_or_insert_with(|| format!("{}-{}", name, ordinal));
+    spec.subdomain.get_or_insert(name);
+}
+
+/*
+This function deletes the Services of an RTResource,
+except the one with the given name, if any.
+*/
+async fn delete_stale_services(thread_name: &str, client: Client, uid: &str, keep: Option<(&str, &str)>) {
+    let lp = ListParams::default().labels(&owner_labels().uid_selector(uid));
+    let services = match Api::<Service>::all(client.clone()).list(&lp).await {
+        Ok(list) => list.items,
+        Err(e) => {
+            eprintln!("{} - An error occurred while listing the Services of RTResource {}: {}", thread_name, uid, e);
+            return;
+        }
+    };
+    for service in services {
+        let (Some(name), Some(namespace)) = (service.metadata.name.as_deref(), service.metadata.namespace.as_deref()) else {
+            continue;
+        };
+        if keep == Some((namespace, name)) {
+            continue;
+        }
+        match Api::<Service>::namespaced(client.clone(), namespace).delete(name, &DeleteParams::default()).await {
+            Ok(_) => println!("{} - Service {} removed from namespace {}!", thread_name, name, namespace),
+            Err(kube::Error::Api(e)) if e.code == 404 => {}
+            Err(e) => eprintln!("{} - An error occurred while deleting Service {} in namespace {}: {}", thread_name, name, namespace, e),
+        }
+    }
+}
+
+/*
+This function deletes the Services of a deleted RTResource.
+*/
+pub async fn delete_services(thread_name: &str, client: Client, uid: &str) {
+    delete_stale_services(thread_name, client, uid, None).await;
+}
+
+/*
+This function creates or updates the Service of an RTResource
+according to spec.service, removing the stale ones.
+*/
+pub async fn reconcile_service(thread_name: &str, client: Client, rtresource: &RTResource) {
+    let (Some(name), Some(uid)) = (rtresource.metadata.name.as_deref(), rtresource.metadata.uid.as_deref()) else {
+        return;
+    };
+    let namespace = rtresource.spec.namespace.as_str();
+    let Some(spec) = rtresource.spec.service.as_ref().filter(|_| service_enabled(rtresource)) else {
+        delete_stale_services(thread_name, client, uid, None).await;
+        return;
+    };
+
+    let labels = owner_labels();
+    let service = serde_json::json!({
+        "apiVersion": "v1",
+        "kind": "Service",
+        "metadata": {
+            "name": name,
+            "namespace": namespace,
+            "labels": {
+                labels.name.as_str(): name,
+                labels.uid.as_str(): uid
+            }
+        },
+        "spec": {
+            "clusterIP": if spec.headless.unwrap_or(false) { Some("None") } else { None },
+            "selector": {
+                labels.uid.as_str(): uid
+            },
+            "ports": spec.port.map(|port| vec![serde_json::json!({
+                "name": "rt",
+                "port": port,
+                "targetPort": port
+            })])
+        }
+    });
+    let service_api: Api<Service> = Api::namespaced(client.clone(), namespace);
+    match service_api.patch(name, &PatchParams::apply(SERVICE_FIELD_MANAGER).force(), &Patch::Apply(&service)).await {
+        Ok(_) => {}
+        /*
+        The cluster IP of a Service cannot be changed,
+        so switching to or from headless recreates it.
+        */
+        Err(kube::Error::Api(e)) if e.code == 422 => {
+            println!("{} - Recreating Service {} in namespace {}: {}", thread_name, name, namespace, e.message);
+            if let Err(e) = service_api.delete(name, &DeleteParams::default()).await {
+                eprintln!("{} - An error occurred while deleting Service {} in namespace {}: {}", thread_name, name, namespace, e);
+                return;
+            }
+            if let Err(e) = service_api.patch(name, &PatchParams::apply(SERVICE_FIELD_MANAGER).force(), &Patch::Apply(&service)).await {
+                eprintln!("{} - An error occurred while applying Service {} in namespace {}: {}", thread_name, name, namespace, e);
+            }
+        }
+        Err(e) => {
+            eprintln!("{} - An error occurred while applying Service {} in namespace {}: {}", thread_name, name, namespace, e);
+            return;
+        }
+    }
+    delete_stale_services(thread_name, client, uid, Some((namespace, name))).await;
+}
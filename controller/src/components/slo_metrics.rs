@@ -0,0 +1,156 @@
+/*
+This file contains the per-RTResource SLO metrics computed by the controller:
+    - the queue wait, from the arrival of an event at the dispatcher
+      to the start of its handling by a watchdog;
+    - the recovery latency, from the arrival of an event at the dispatcher
+      to the end of the reconcile restoring the desired state.
+They are rendered by the admin API next to the controller metrics, labelled
+with the RTResource namespace and name, so that prometheus-adapter can serve
+them through the custom metrics API (see resources/monitoring) to HPAs and
+external policy engines.
+The samples are kept in the shared state, since a plain mutex
+would not inherit the priority of the watchdogs recording them.
+*/
+
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    time::Duration
+};
+use libc::{
+    pthread_mutex_lock,
+    pthread_mutex_unlock
+};
+
+use crate::utils::vars::{
+    SharedState,
+    SharedStatePtr,
+    QueueMessage
+};
+
+
+
+/*
+SLO samples of an RTResource
+*/
+#[derive(Default)]
+pub struct SloStats {
+    namespace: String,
+    name: String,
+    criticality: u32,
+    events: u64,
+    queue_wait_last: f64,
+    queue_wait_sum: f64,
+    recovery_last: f64,
+    recovery_sum: f64,
+    recovery_max: f64,
+}
+
+/*
+Rendered SLO metric: name, help, type and value
+*/
+type SloMetric = (&'static str, &'static str, &'static str, fn(&SloStats) -> f64);
+
+/*
+This function records the latencies of a handled event.
+It must be called without holding the shared mutex.
+*/
+pub fn record_event_latency(shared_state: &mut SharedState, msg: &QueueMessage, criticality: u32, queue_wait: Duration, recovery: Duration) {
+    unsafe {
+        pthread_mutex_lock(&mut shared_state.mutex);
+        let stats = shared_state.slo.entry(msg.uid.clone()).or_default();
+        stats.namespace = msg.namespace.clone();
+        stats.name = msg.name.clone();
+        stats.criticality = criticality;
+        stats.events += 1;
+        stats.queue_wait_last = queue_wait.as_secs_f64();
+        stats.queue_wait_sum += stats.queue_wait_last;
+        stats.recovery_last = recovery.as_secs_f64();
+        stats.recovery_sum += stats.recovery_last;
+        stats.recovery_max = stats.recovery_max.max(stats.recovery_last);
+        pthread_mutex_unlock(&mut shared_state.mutex);
+    }
+}
+
+/*
+This function drops the samples of a deleted RTResource.
+It must be called without holding the shared mutex.
+*/
+pub fn forget_event_latency(shared_state: &mut SharedState, uid: &str) {
+    unsafe {
+        pthread_mutex_lock(&mut shared_state.mutex);
+        shared_state.slo.remove(uid);
+        pthread_mutex_unlock(&mut shared_state.mutex);
+    }
+}
+
+/*
+This function returns the SLO samples of an RTResource,
+for its archival record.
+It must be called holding the shared mutex.
+*/
+pub fn slo_summary(shared_state: &SharedState, uid: &str) -> Option<serde_json::Value> {
+    let stats = shared_state.slo.get(uid)?;
+    Some(serde_json::json!({
+        "events": stats.events,
+        "queueWaitSecondsSum": stats.queue_wait_sum,
+        "recoverySecondsLast": stats.recovery_last,
+        "recoverySecondsSum": stats.recovery_sum,
+        "recoverySecondsMax": stats.recovery_max,
+    }))
+}
+
+/*
+This function aggregates the recovery latencies per namespace:
+events, total and highest recovery latency (in seconds).
+It must be called holding the shared mutex.
+*/
+pub fn namespace_recovery(shared_state: &SharedState) -> HashMap<String, (u64, f64, f64)> {
+    let mut namespaces: HashMap<String, (u64, f64, f64)> = HashMap::new();
+    for stats in shared_state.slo.values() {
+        let aggregate = namespaces.entry(stats.namespace.clone()).or_default();
+        aggregate.0 += stats.events;
+        aggregate.1 += stats.recovery_sum;
+        aggregate.2 = aggregate.2.max(stats.recovery_max);
+    }
+    namespaces
+}
+
+/*
+This function renders the SLO metrics in the Prometheus text format.
+It must be called without holding the shared mutex.
+*/
+pub fn render_slo_metrics(state: SharedStatePtr) -> String {
+    let metrics: [SloMetric; 6] = [
+        ("preempt_k8s_rtresource_queue_wait_seconds", "Queue wait of the last event of the RTResource", "gauge", |s| s.queue_wait_last),
+        ("preempt_k8s_rtresource_queue_wait_seconds_sum", "Total queue wait of the RTResource events", "counter", |s| s.queue_wait_sum),
+        ("preempt_k8s_rtresource_recovery_seconds", "Recovery latency of the last event of the RTResource", "gauge", |s| s.recovery_last),
+        ("preempt_k8s_rtresource_recovery_seconds_sum", "Total recovery latency of the RTResource events", "counter", |s| s.recovery_sum),
+        ("preempt_k8s_rtresource_recovery_seconds_max", "Highest recovery latency of the RTResource", "gauge", |s| s.recovery_max),
+        ("preempt_k8s_rtresource_events_total", "Events handled for the RTResource", "counter", |s| s.events as f64),
+    ];
+
+    let shared_state = unsafe { &mut *state.0 };
+    let mut out = String::new();
+    unsafe {
+        pthread_mutex_lock(&mut shared_state.mutex);
+        for (name, help, kind, value) in metrics {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} {}", name, kind);
+            for stats in shared_state.slo.values() {
+                let _ = writeln!(
+                    out,
+                    "{}{{namespace=\"{}\",rtresource=\"{}\",criticality=\"{}\"}} {}",
+                    name,
+                    stats.namespace,
+                    stats.name,
+                    stats.criticality,
+                    value(stats)
+                );
+            }
+        }
+        pthread_mutex_unlock(&mut shared_state.mutex);
+    }
+
+    out
+}
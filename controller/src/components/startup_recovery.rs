@@ -0,0 +1,154 @@
+/*
+This file contains the consistency pass run at controller startup.
+The event queue is a kernel object that outlives the controller
+process, so after a crash it may hold stale events while events
+lost with the crashed process never come back. The queue content
+is therefore discarded, and the events are rebuilt from the cluster
+state instead: every RTResource whose pods do not match its spec
+(missing replicas, or duplicate/excess pods left by a partially
+completed reconcile) and every set of pods whose RTResource is
+gone gets a corrective event, handled by the watchdogs as usual.
+*/
+
+use std::{
+    ffi::CStr,
+    collections::HashMap
+};
+use libc::{
+    mqd_t,
+    mq_close,
+    mq_unlink,
+    O_WRONLY,
+    O_NONBLOCK
+};
+use kube::{
+    Api,
+    Client,
+    api::ListParams
+};
+use k8s_openapi::api::core::v1::Pod;
+
+use crate::utils::vars::{
+    QueueMessage,
+    EventKind
+};
+use crate::utils::rtresource::RTResource;
+use crate::utils::event_queue::{
+    open_queue,
+    enqueue
+};
+use crate::utils::configuration::ControllerConfig;
+use crate::utils::priorities::effective_criticality;
+use crate::components::planner::plan_reconcile;
+use crate::utils::owner_labels::owner_labels;
+
+
+
+/*
+This function returns the value of a pod label.
+*/
+fn label<'a>(pod: &'a Pod, key: &str) -> Option<&'a String> {
+    pod.metadata.labels.as_ref().and_then(|l| l.get(key))
+}
+
+/*
+This function discards the persisted event queue and enqueues
+the corrective events computed from the cluster state.
+It must run before the controller threads are started.
+It returns the number of events enqueued.
+*/
+pub async fn recover_startup_state(client: Client, config: &ControllerConfig, queue: &CStr) -> Result<usize, kube::Error> {
+    let rtresources = Api::<RTResource>::all(client.clone()).list(&ListParams::default()).await?.items;
+    let labels = owner_labels();
+    let pods = Api::<Pod>::all(client).list(&ListParams::default().labels(&labels.uid)).await?.items;
+
+    let mut pods_by_uid: HashMap<String, Vec<Pod>> = HashMap::new();
+    for pod in pods {
+        if let Some(uid) = label(&pod, &labels.uid) {
+            pods_by_uid.entry(uid.clone()).or_default().push(pod);
+        }
+    }
+
+    let mut events: Vec<(QueueMessage, u32)> = Vec::new();
+    for r in rtresources.iter() {
+        let (Some(name), Some(uid), Some(namespace)) = (
+            r.metadata.name.clone(),
+            r.metadata.uid.clone(),
+            r.metadata.namespace.clone()
+        ) else {
+            continue;
+        };
+        let current = pods_by_uid.remove(&uid).unwrap_or_default();
+        let plan = plan_reconcile(&current, r);
+        let observed = r.status.as_ref().and_then(|s| s.observed_generation);
+        if plan.is_empty() && observed == r.metadata.generation {
+            continue;
+        }
+        println!(
+            "Startup Recovery - RTResource {}, {} in namespace {} is inconsistent: {} missing replicas, {} duplicate or excess pods!",
+            name,
+            uid,
+            namespace,
+            plan.creates.len(),
+            plan.deletes.len()
+        );
+        let criticality = effective_criticality(config, &namespace, r.spec.criticality);
+        events.push((QueueMessage {name, uid, namespace, enqueued_at: 0, kind: EventKind::Resync, trace_id: String::new()}, criticality));
+    }
+
+    /*
+    Pods left by a deleted RTResource are removed by the
+    watchdog handling the event of their RTResource.
+    */
+    for (uid, orphans) in pods_by_uid {
+        let pod = &orphans[0];
+        let (Some(name), Some(namespace)) = (label(pod, &labels.name), label(pod, &labels.namespace)) else {
+            continue;
+        };
+        let criticality = effective_criticality(config, namespace, label(pod, &labels.criticality).and_then(|c| c.parse().ok()).unwrap_or(config.criticality_max));
+        println!(
+            "Startup Recovery - {} pods of the deleted RTResource {}, {} in namespace {} are still running!",
+            orphans.len(),
+            name,
+            uid,
+            namespace
+        );
+        events.push((QueueMessage {name: name.clone(), uid, namespace: namespace.clone(), enqueued_at: 0, kind: EventKind::ResourceDeleted, trace_id: String::new()}, criticality));
+    }
+
+    /*
+    If the queue fills up, the most critical events are the ones enqueued.
+    */
+    events.sort_by_key(|(_, criticality)| *criticality);
+    let mut sent: usize = 0;
+    unsafe {
+        /*
+        The events persisted in the queue by a previous
+        instance are not trusted and are discarded.
+        */
+        if mq_unlink(queue.as_ptr()) == 0 {
+            println!("Startup Recovery - Discarded the event queue left by a previous instance!");
+        }
+        /*
+        No consumer runs yet, so the queue is opened non-blocking:
+        a blocking send would never return once the queue is full.
+        */
+        let queue_des: mqd_t = open_queue(queue, O_WRONLY | O_NONBLOCK);
+        if queue_des == -1 {
+            eprintln!("Startup Recovery - An error occurred while opening the queue!");
+            return Ok(0);
+        }
+        for (msg, criticality) in events.iter() {
+            if enqueue("Startup Recovery", queue_des, msg, *criticality) {
+                sent += 1;
+            }
+        }
+        mq_close(queue_des);
+    }
+
+    println!("Startup Recovery - {} corrective events enqueued!", sent);
+    if sent < events.len() {
+        eprintln!("Startup Recovery - {} corrective events could not be enqueued!", events.len() - sent);
+    }
+    Ok(sent)
+}
@@ -0,0 +1,155 @@
+/*
+This file contains the startup sequencing of the reconciles.
+After a cluster-wide outage every RTResource needs a reconcile at once,
+and the less critical ones could take the capacity of the nodes before
+the critical ones. With STARTUP_SEQUENCING the dispatcher only hands
+the watchdogs the events up to the admitted criticality (the startup
+gate), the other events waiting in the ready queue.
+The gate starts at the most critical level, and is advanced to the next
+criticality level as soon as every RTResource up to the admitted one
+reports Ready; once the whole top band is Ready (or after
+STARTUP_SEQUENCING_TIMEOUT_MS) the gate is opened for every event.
+After a plain controller restart the RTResources are already Ready,
+so the gate opens within the first check.
+*/
+
+use std::{
+    collections::BTreeSet,
+    sync::atomic::Ordering,
+    time::{
+        Duration,
+        Instant
+    }
+};
+use libc::{
+    pthread_cond_broadcast,
+    pthread_mutex_lock,
+    pthread_mutex_unlock
+};
+use kube::{
+    Api,
+    Client,
+    api::ListParams
+};
+
+use crate::utils::vars::{
+    SharedState,
+    SharedStatePtr
+};
+use crate::utils::rtresource::RTResource;
+use crate::utils::metrics::METRICS;
+use crate::utils::configuration::ControllerConfig;
+use crate::utils::priorities::effective_criticality;
+
+
+
+/*
+Interval between two checks of the admitted RTResources
+*/
+const SEQUENCING_CHECK: Duration = Duration::from_secs(1);
+
+/*
+This function returns whether the events of a criticality
+may be handed to the watchdogs.
+It must be called holding the shared mutex.
+*/
+pub fn admitted(shared_state: &SharedState, criticality: u32) -> bool {
+    shared_state.startup_gate.is_none_or(|gate| criticality <= gate)
+}
+
+/*
+This function counts the events held by the startup gate.
+It must be called holding the shared mutex.
+*/
+fn held_events(shared_state: &SharedState) -> usize {
+    shared_state.ready.iter().filter(|e| !admitted(shared_state, e.criticality)).count()
+}
+
+/*
+This function moves the startup gate (None opens it),
+waking up the watchdogs waiting for an event.
+It must be called without holding the shared mutex.
+*/
+fn set_gate(state: SharedStatePtr, gate: Option<u32>) {
+    let shared_state = unsafe { &mut *state.0 };
+    unsafe {
+        pthread_mutex_lock(&mut shared_state.mutex);
+        shared_state.startup_gate = gate;
+        METRICS.startup_gate_active.store(gate.is_some() as i64, Ordering::Relaxed);
+        METRICS.startup_gate_level.store(gate.unwrap_or(0) as i64, Ordering::Relaxed);
+        METRICS.startup_held_events.store(held_events(shared_state) as i64, Ordering::Relaxed);
+        pthread_cond_broadcast(&mut shared_state.dispatch_cond);
+        pthread_mutex_unlock(&mut shared_state.mutex);
+    }
+}
+
+/*
+This function returns whether an RTResource reports Ready.
+*/
+fn is_ready(rtresource: &RTResource) -> bool {
+    rtresource.status.as_ref()
+        .and_then(|s| s.conditions.as_ref())
+        .and_then(|c| c.iter().find(|c| c.condition_type == "Ready"))
+        .is_some_and(|c| c.status == "True")
+}
+
+/*
+This function computes the next startup gate from the readiness
+of the RTResources: the gate is advanced over every level whose
+RTResources (and the more critical ones) are all Ready,
+and opened once it moves beyond the top band.
+*/
+async fn next_gate(client: Client, config: &ControllerConfig, gate: u32) -> Result<Option<u32>, kube::Error> {
+    let rtresources = Api::<RTResource>::all(client).list(&ListParams::default()).await?.items;
+    let levels: Vec<(u32, bool)> = rtresources.iter()
+        .map(|r| (effective_criticality(config, r.metadata.namespace.as_deref().unwrap_or_default(), r.spec.criticality), is_ready(r)))
+        .collect();
+    let mut gate = gate;
+    let present: BTreeSet<u32> = levels.iter().map(|(c, _)| *c).collect();
+    loop {
+        if levels.iter().any(|(c, ready)| *c <= gate && !ready) {
+            return Ok(Some(gate));
+        }
+        match present.range(gate + 1..).next() {
+            Some(next) if *next <= config.critical_band_max => gate = *next,
+            _ => return Ok(None),
+        }
+    }
+}
+
+/*
+This function runs the startup sequencing until the gate is open.
+*/
+pub async fn startup_sequencer(client: Client, state: SharedStatePtr) {
+    let (config, gate) = unsafe { ((*state.0).config.clone(), (*state.0).startup_gate) };
+    let timeout = Duration::from_millis(config.startup_sequencing_timeout_ms);
+    let started = Instant::now();
+    let mut interval = tokio::time::interval(SEQUENCING_CHECK);
+    let Some(mut gate) = gate else {
+        return;
+    };
+    println!("Startup Sequencing - Admitting the events up to criticality {}!", gate);
+    loop {
+        interval.tick().await;
+        if started.elapsed() >= timeout {
+            set_gate(state, None);
+            println!("Startup Sequencing - Timeout reached with criticality {} admitted, every event is now admitted!", gate);
+            return;
+        }
+        match next_gate(client.clone(), &config, gate).await {
+            Ok(Some(next)) => {
+                if next != gate {
+                    gate = next;
+                    println!("Startup Sequencing - Admitting the events up to criticality {}!", gate);
+                }
+                set_gate(state, Some(gate));
+            }
+            Ok(None) => {
+                set_gate(state, None);
+                println!("Startup Sequencing - The top band is Ready after {:?}, every event is now admitted!", started.elapsed());
+                return;
+            }
+            Err(e) => eprintln!("Startup Sequencing - An error occurred while listing the RTResources: {}", e),
+        }
+    }
+}
@@ -0,0 +1,145 @@
+/*
+This file contains the continuous verification of the RTResource
+statuses. The statuses are only updated by the event-driven path
+(watchdogs and resource state updater), so a bug or a lost update
+leaves a stale status until the next event of the RTResource.
+Each cycle, this task re-derives the status of a sample of settled
+RTResources (not progressing, latest generation observed) from their
+live pods, fixes the discrepancies and counts them in the drift metric.
+The sample moves round-robin over the RTResources, so that every
+RTResource is eventually verified.
+It runs as a Tokio task, since it is not time critical.
+*/
+
+use std::time::Duration;
+use kube::{
+    Api,
+    Client,
+    api::{
+        ListParams,
+        Patch,
+        PatchParams
+    }
+};
+use k8s_openapi::api::core::v1::Pod;
+
+use crate::utils::rtresource::RTResource;
+use crate::utils::configuration::ControllerConfig;
+use crate::utils::metrics::record_status_verification;
+use crate::components::planner::is_ready;
+use crate::utils::owner_labels::owner_labels;
+
+
+
+/*
+This function returns whether the status of an RTResource is
+settled, i.e. not being updated by the event-driven path.
+*/
+fn is_settled(rtresource: &RTResource) -> bool {
+    let Some(status) = rtresource.status.as_ref() else {
+        return false;
+    };
+    let progressing = status.conditions.iter().flatten()
+        .any(|c| c.condition_type == "Progressing" && c.status == "True");
+    rtresource.metadata.deletion_timestamp.is_none()
+        && !progressing
+        && status.observed_generation == rtresource.metadata.generation
+}
+
+/*
+This function verifies the status of an RTResource against
+its live pods and fixes it. It returns the drifted fields.
+*/
+async fn verify_status(client: Client, rtresource: &RTResource) -> Result<Vec<&'static str>, kube::Error> {
+    let uid = rtresource.metadata.uid.clone().unwrap_or_default();
+    let lp = ListParams::default().labels(&owner_labels().uid_selector(&uid));
+    let pods = Api::<Pod>::namespaced(client.clone(), &rtresource.spec.namespace).list(&lp).await?.items;
+    let exclusions = rtresource.spec.readiness_exclusions.clone().unwrap_or_default();
+    let running = pods.iter()
+        .filter(|p| p.status.as_ref().and_then(|s| s.phase.as_deref()) == Some("Running"))
+        .count() as i32;
+    let ready = pods.iter().filter(|p| is_ready(p, &exclusions)).count() as i32;
+
+    let mut status = rtresource.status.clone().unwrap_or_default();
+    let mut drifted = Vec::new();
+    if status.replicas != Some(running) {
+        status.replicas = Some(running);
+        drifted.push("replicas");
+    }
+    if status.ready_replicas != Some(ready) {
+        status.ready_replicas = Some(ready);
+        drifted.push("readyReplicas");
+    }
+    if let Some(desired) = status.desired_replicas {
+        let expected = if ready == desired { "True" } else { "False" };
+        let current = status.conditions.iter().flatten().find(|c| c.condition_type == "Ready").map(|c| c.status.clone());
+        if current.is_some_and(|c| c != expected) {
+            let reason = if ready == desired { "All desired replicas are ready!" } else { "Not all desired replicas are ready!" };
+            status.set_condition("Ready", expected, reason, &format!("{} of {} desired replicas are ready", ready, desired));
+            drifted.push("Ready");
+        }
+    }
+    if drifted.is_empty() {
+        return Ok(drifted);
+    }
+
+    let patch = serde_json::json!({
+        "status": {
+            "replicas": status.replicas,
+            "readyReplicas": status.ready_replicas,
+            "conditions": status.conditions
+        }
+    });
+    Api::<RTResource>::namespaced(client, rtresource.metadata.namespace.as_deref().unwrap_or_default())
+        .patch_status(rtresource.metadata.name.as_deref().unwrap_or_default(), &PatchParams::default(), &Patch::Merge(&patch))
+        .await?;
+    Ok(drifted)
+}
+
+/*
+This function periodically verifies the status
+of a sample of the RTResources.
+*/
+pub async fn status_verifier(client: Client, config: ControllerConfig) {
+    let mut interval = tokio::time::interval(Duration::from_millis(config.status_verify_interval_ms));
+    let mut cursor: usize = 0;
+    loop {
+        interval.tick().await;
+        let mut rtresources = match Api::<RTResource>::all(client.clone()).list(&ListParams::default()).await {
+            Ok(list) => list.items,
+            Err(e) => {
+                eprintln!("Status Verifier - An error occurred while listing the RTResources: {}", e);
+                continue;
+            }
+        };
+        rtresources.retain(is_settled);
+        if rtresources.is_empty() {
+            continue;
+        }
+        rtresources.sort_by(|a, b| a.metadata.uid.cmp(&b.metadata.uid));
+        let sample = config.status_verify_sample.min(rtresources.len());
+        for offset in 0..sample {
+            let r = &rtresources[(cursor + offset) % rtresources.len()];
+            match verify_status(client.clone(), r).await {
+                Ok(drifted) => {
+                    record_status_verification(drifted.len());
+                    if !drifted.is_empty() {
+                        println!(
+                            "Status Verifier - Fixed the drifted status of RTResource {}, {} in namespace {}: {}!",
+                            r.metadata.name.clone().unwrap_or_default(),
+                            r.metadata.uid.clone().unwrap_or_default(),
+                            r.metadata.namespace.clone().unwrap_or_default(),
+                            drifted.join(", ")
+                        );
+                    }
+                }
+                Err(e) => eprintln!(
+                    "Status Verifier - An error occurred while verifying RTResource {}: {}",
+                    r.metadata.uid.clone().unwrap_or_default(),
+                    e
+                ),
+            }
+        }
+        cursor = (cursor + sample) % rtresources.len();
+    }
+}
@@ -0,0 +1,198 @@
+/*
+This file contains the validation of RTResource pod templates
+against the capabilities of the target cluster.
+It runs on the first reconcile of each spec generation (and until
+the template becomes valid), so that unsupported templates are
+reported with a typed "TemplateValid" condition instead of
+failing later with runtime pod creation errors.
+The checks are:
+    - the runtime class, if any, exists;
+    - the requested hugepage sizes are enabled on at least one node;
+    - the requested extended (device plugin) resources exist on at least one node;
+    - the requested CPU fits the largest node;
+    - at least one node runs the targeted operating system (spec.osTarget);
+    - the placement overrides target existing nodes, one per replica;
+    - the RTResource does not conflict with itself, and the cluster has
+      enough schedulable nodes to keep it apart from the RTResources
+      it conflicts with (one node each).
+*/
+
+use kube::{
+    Api,
+    Client
+};
+use k8s_openapi::api::{
+    core::v1::{
+        Node,
+        PodSpec
+    },
+    node::v1::RuntimeClass
+};
+
+use crate::utils::rtresource::RTResource;
+use crate::components::conflicts::conflicting_rtresources;
+use crate::components::scheduling_policy::node_os;
+use crate::utils::quantity::{
+    pod_requests,
+    resource_amount
+};
+
+
+
+/*
+Condition reporting the outcome of the template validation
+*/
+pub const TEMPLATE_CONDITION: &str = "TemplateValid";
+
+/*
+Template validation failures
+*/
+#[derive(Clone, Debug, PartialEq)]
+pub enum TemplateIssue {
+    RuntimeClassNotFound(String),
+    HugepagesUnavailable(String),
+    ResourceUnavailable(String),
+    InsufficientNodeCPU { requested: f64, largest: f64 },
+    OverrideNodeNotFound { ordinal: u32, node: String },
+    DuplicateOverride(u32),
+    SelfConflict,
+    ConflictInfeasible { required: usize, available: usize },
+    OsUnavailable(String),
+}
+
+impl TemplateIssue {
+    /*
+    This function returns the condition reason of the issue.
+    */
+    pub fn reason(&self) -> &'static str {
+        match self {
+            TemplateIssue::RuntimeClassNotFound(_) => "RuntimeClassNotFound",
+            TemplateIssue::HugepagesUnavailable(_) => "HugepagesUnavailable",
+            TemplateIssue::ResourceUnavailable(_) => "ResourceUnavailable",
+            TemplateIssue::InsufficientNodeCPU { .. } => "InsufficientNodeCPU",
+            TemplateIssue::OverrideNodeNotFound { .. } => "OverrideNodeNotFound",
+            TemplateIssue::DuplicateOverride(_) => "DuplicateOverride",
+            TemplateIssue::SelfConflict => "SelfConflict",
+            TemplateIssue::ConflictInfeasible { .. } => "ConflictInfeasible",
+            TemplateIssue::OsUnavailable(_) => "OsUnavailable",
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            TemplateIssue::RuntimeClassNotFound(name) => format!("runtime class {} does not exist", name),
+            TemplateIssue::HugepagesUnavailable(size) => format!("no node has {} enabled", size),
+            TemplateIssue::ResourceUnavailable(name) => format!("no node provides resource {}", name),
+            TemplateIssue::InsufficientNodeCPU { requested, largest } => {
+                format!("requested CPU {} exceeds the largest node allocatable CPU {}", requested, largest)
+            }
+            TemplateIssue::OverrideNodeNotFound { ordinal, node } => {
+                format!("replica {} is pinned to node {} which does not exist", ordinal, node)
+            }
+            TemplateIssue::DuplicateOverride(ordinal) => format!("replica {} has more than one placement override", ordinal),
+            TemplateIssue::SelfConflict => "the RTResource lists itself in conflictsWith".to_string(),
+            TemplateIssue::ConflictInfeasible { required, available } => {
+                format!("the conflicting RTResources need {} distinct nodes, only {} are schedulable", required, available)
+            }
+            TemplateIssue::OsUnavailable(os) => format!("no node runs the targeted operating system {}", os),
+        }
+    }
+}
+
+/*
+This function returns whether a resource is an extended
+resource (e.g. advertised by a device plugin).
+*/
+fn is_extended_resource(name: &str) -> bool {
+    !matches!(name, "cpu" | "memory" | "ephemeral-storage" | "storage")
+        && !name.starts_with("hugepages-")
+        && !name.starts_with("requests.")
+}
+
+/*
+This function checks the pod template of an RTResource against
+the given nodes, with the number of RTResources it conflicts with.
+It does not reach the cluster, so that the offline linter runs the
+same checks (the runtime class is checked by validate_template).
+*/
+pub fn check_template(rtresource: &RTResource, spec: &PodSpec, nodes: &[Node], conflicting: usize) -> Vec<TemplateIssue> {
+    let mut issues: Vec<TemplateIssue> = Vec::new();
+
+    let mut pinned: Vec<u32> = Vec::new();
+    for o in rtresource.spec.placement_overrides.iter().flatten() {
+        if pinned.contains(&o.ordinal) {
+            issues.push(TemplateIssue::DuplicateOverride(o.ordinal));
+        }
+        pinned.push(o.ordinal);
+        if !nodes.iter().any(|n| n.metadata.name.as_ref() == Some(&o.node_name)) {
+            issues.push(TemplateIssue::OverrideNodeNotFound { ordinal: o.ordinal, node: o.node_name.clone() });
+        }
+    }
+
+    if rtresource.spec.conflicts_with.iter().flatten().any(|c| Some(c) == rtresource.metadata.name.as_ref()) {
+        issues.push(TemplateIssue::SelfConflict);
+    }
+    if conflicting > 0 {
+        let required = conflicting + 1;
+        let available = nodes.iter()
+            .filter(|n| !n.spec.as_ref().and_then(|s| s.unschedulable).unwrap_or(false))
+            .count();
+        if available < required {
+            issues.push(TemplateIssue::ConflictInfeasible { required, available });
+        }
+    }
+
+    let requests = pod_requests(spec);
+    let allocatable = |node: &Node, name: &str| {
+        resource_amount(node.status.as_ref().and_then(|s| s.allocatable.as_ref()), name)
+    };
+
+    for (name, amount) in requests.iter() {
+        if *amount <= 0.0 {
+            continue;
+        }
+        let available = nodes.iter().any(|n| allocatable(n, name) > 0.0);
+        if name.starts_with("hugepages-") && !available {
+            issues.push(TemplateIssue::HugepagesUnavailable(name.clone()));
+        } else if is_extended_resource(name) && !available {
+            issues.push(TemplateIssue::ResourceUnavailable(name.clone()));
+        }
+    }
+
+    if let Some(requested) = requests.get("cpu").copied() {
+        let largest = nodes.iter().map(|n| allocatable(n, "cpu")).fold(0.0, f64::max);
+        if requested > largest {
+            issues.push(TemplateIssue::InsufficientNodeCPU { requested, largest });
+        }
+    }
+
+    let os = rtresource.spec.os_target();
+    if !nodes.is_empty() && !nodes.iter().any(|n| node_os(n) == os) {
+        issues.push(TemplateIssue::OsUnavailable(os.to_string()));
+    }
+
+    issues
+}
+
+/*
+This function validates the pod template of an RTResource.
+It returns the issues found, or an error if the cluster
+capabilities could not be retrieved.
+*/
+pub async fn validate_template(client: Client, rtresource: &RTResource) -> Result<Vec<TemplateIssue>, kube::Error> {
+    let Some(spec) = rtresource.spec.template.spec.as_ref() else {
+        return Ok(Vec::new());
+    };
+    let nodes = Api::<Node>::all(client.clone()).list(&Default::default()).await?.items;
+    let conflicting = conflicting_rtresources(client.clone(), rtresource).await?;
+    let mut issues = check_template(rtresource, spec, &nodes, conflicting.len());
+
+    if let Some(runtime_class) = spec.runtime_class_name.as_ref() {
+        let runtime_classes = Api::<RuntimeClass>::all(client.clone());
+        if runtime_classes.get_opt(runtime_class).await?.is_none() {
+            issues.insert(0, TemplateIssue::RuntimeClassNotFound(runtime_class.clone()));
+        }
+    }
+
+    Ok(issues)
+}
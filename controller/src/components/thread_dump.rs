@@ -0,0 +1,81 @@
+/*
+This file contains the on-demand dump of the controller threads,
+served by the admin API: for each thread its pthread id, role and
+current scheduling priority, and for each watchdog the event it is
+handling and for how long, so that stalls can be diagnosed without
+correlating the interleaved logs of the threads.
+*/
+
+use libc::{
+    pthread_t,
+    pthread_getschedparam,
+    pthread_mutex_lock,
+    pthread_mutex_unlock,
+    sched_param
+};
+
+use crate::utils::vars::SharedStatePtr;
+use crate::utils::priorities::realtime_scheduling;
+
+
+
+/*
+This function returns the current priority of a thread,
+or None if it could not be retrieved.
+*/
+fn current_priority(thread: pthread_t) -> Option<i32> {
+    let mut policy = 0;
+    let mut param = sched_param {sched_priority: 0};
+    let result = unsafe { pthread_getschedparam(thread, &mut policy, &mut param) };
+    (result == 0).then_some(param.sched_priority)
+}
+
+/*
+This function captures the state of the controller threads.
+It must be called without holding the shared mutex.
+*/
+pub fn thread_dump(state: SharedStatePtr) -> serde_json::Value {
+    let shared_state = unsafe { &mut *state.0 };
+    unsafe {
+        pthread_mutex_lock(&mut shared_state.mutex);
+        let components: Vec<serde_json::Value> = shared_state.component_threads.iter()
+            .map(|(role, thread)| serde_json::json!({
+                "role": role.to_string(),
+                "thread": thread,
+                "priority": current_priority(*thread)
+            }))
+            .collect();
+        let watchdogs: Vec<serde_json::Value> = shared_state.workers.iter()
+            .enumerate()
+            .filter(|(_, w)| w.active && w.id != 0)
+            .map(|(slot, w)| {
+                let event = shared_state.handling.get(&w.id).map(|(msg, since)| serde_json::json!({
+                    "name": msg.name,
+                    "uid": msg.uid,
+                    "namespace": msg.namespace,
+                    "criticality": w.criticality,
+                    "borrowed": w.borrowed,
+                    "priorityDecay": w.decay,
+                    "handlingForMs": since.elapsed().as_millis() as u64
+                }));
+                serde_json::json!({
+                    "slot": slot,
+                    "thread": w.id,
+                    "priority": current_priority(w.id),
+                    "event": event
+                })
+            })
+            .collect();
+        let dump = serde_json::json!({
+            "realtimeScheduling": realtime_scheduling(),
+            "activeWatchdogs": shared_state.active_threads,
+            "workingWatchdogs": shared_state.working_threads,
+            "readyEvents": shared_state.ready.len(),
+            "components": components,
+            "watchdogs": watchdogs
+        });
+        pthread_mutex_unlock(&mut shared_state.mutex);
+
+        dump
+    }
+}
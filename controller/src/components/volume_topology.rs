@@ -0,0 +1,246 @@
+/*
+This file contains the volume topology resolution
+of the built-in scheduler.
+Pods bound by the controller skip kube-scheduler, so the node must
+be able to mount the persistent volumes of the pod:
+    - a bound claim restricts the nodes to the node affinity of its volume;
+    - an unbound claim of a WaitForFirstConsumer StorageClass restricts
+      the nodes to the allowed topologies of the class, and the chosen
+      node is recorded on the claim (as kube-scheduler does), so that
+      the volume is provisioned where the pod runs;
+    - an unbound claim of an Immediate StorageClass (or a missing claim)
+      has no known topology yet, so the placement is deferred until the
+      claim is bound.
+Generic ephemeral volumes are created with the pod and are not resolved.
+*/
+
+use std::{
+    error::Error,
+    fmt,
+    sync::Mutex
+};
+use kube::{
+    Api,
+    Client,
+    api::{
+        ListParams,
+        Patch,
+        PatchParams
+    }
+};
+use k8s_openapi::api::core::v1::{
+    NodeSelectorRequirement,
+    NodeSelectorTerm,
+    PersistentVolume,
+    PersistentVolumeClaim,
+    PodSpec
+};
+use k8s_openapi::api::storage::v1::StorageClass;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+
+use crate::utils::rtresource::PendingPlacement;
+
+
+
+/*
+Annotation recording the node chosen for the volume of a
+WaitForFirstConsumer claim, and annotation marking the default StorageClass
+*/
+pub const SELECTED_NODE_ANNOTATION: &str = "volume.kubernetes.io/selected-node";
+const DEFAULT_CLASS_ANNOTATION: &str = "storageclass.kubernetes.io/is-default-class";
+
+/*
+Volume topology of the pods of an RTResource
+*/
+#[derive(Default)]
+pub struct VolumeTopology {
+    /*
+    Node selector terms of each volume: a node must
+    match at least one term of every volume
+    */
+    pub terms: Vec<Vec<NodeSelectorTerm>>,
+    /*
+    Reason the placement is deferred, if any
+    */
+    pub pending: Option<String>,
+    /*
+    Namespace and unbound WaitForFirstConsumer claims
+    to record the chosen node on
+    */
+    namespace: String,
+    unbound_claims: Vec<String>,
+    /*
+    Node recorded on the unbound claims
+    */
+    selected_node: Mutex<Option<String>>,
+}
+
+/*
+Error returned when the placement of a pod
+waits for the binding of its claims
+*/
+#[derive(Debug)]
+pub struct VolumeBindingPending {
+    pub thread_name: String,
+    pub pod_name: String,
+    pub reason: String,
+}
+
+impl fmt::Display for VolumeBindingPending {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} - Placement of Pod {} deferred: {}!", self.thread_name, self.pod_name, self.reason)
+    }
+}
+
+impl Error for VolumeBindingPending {}
+
+impl VolumeBindingPending {
+    /*
+    This function returns the pending placement
+    reported in the RTResource status.
+    */
+    pub fn pending_placement(&self, ordinal: u32) -> PendingPlacement {
+        PendingPlacement {
+            ordinal,
+            reason: "VolumeBindingPending".to_string(),
+            message: self.reason.clone(),
+            nodes: None,
+        }
+    }
+}
+
+impl VolumeTopology {
+    /*
+    This function returns the node selector terms of the volumes,
+    including the node already recorded on the unbound claims.
+    */
+    pub fn node_terms(&self) -> Vec<Vec<NodeSelectorTerm>> {
+        let mut terms = self.terms.clone();
+        if let Some(node) = self.selected_node.lock().unwrap().as_ref() {
+            terms.push(vec![node_name_term(node)]);
+        }
+        terms
+    }
+
+    /*
+    This function records the node chosen for the pod
+    on the unbound WaitForFirstConsumer claims.
+    */
+    pub async fn select_node(&self, thread_name: &str, client: Client, node: &str) -> Result<(), kube::Error> {
+        if self.unbound_claims.is_empty() || self.selected_node.lock().unwrap().is_some() {
+            return Ok(());
+        }
+        let api: Api<PersistentVolumeClaim> = Api::namespaced(client, &self.namespace);
+        let patch = serde_json::json!({
+            "metadata": {
+                "annotations": {
+                    SELECTED_NODE_ANNOTATION: node
+                }
+            }
+        });
+        for claim in self.unbound_claims.iter() {
+            api.patch(claim, &PatchParams::default(), &Patch::Merge(&patch)).await?;
+            println!("{} - Volume of PersistentVolumeClaim {} will be provisioned for node {}!", thread_name, claim, node);
+        }
+        *self.selected_node.lock().unwrap() = Some(node.to_string());
+        Ok(())
+    }
+}
+
+/*
+This function returns the node selector term matching a node name.
+*/
+fn node_name_term(node: &str) -> NodeSelectorTerm {
+    NodeSelectorTerm {
+        match_fields: Some(vec![NodeSelectorRequirement {
+            key: "metadata.name".to_string(),
+            operator: "In".to_string(),
+            values: Some(vec![node.to_string()]),
+        }]),
+        match_expressions: None,
+    }
+}
+
+/*
+This function returns the StorageClass of an unbound claim
+(the default class when the claim names none).
+*/
+async fn claim_class(client: Client, claim: &PersistentVolumeClaim) -> Result<Option<StorageClass>, kube::Error> {
+    let api: Api<StorageClass> = Api::all(client);
+    match claim.spec.as_ref().and_then(|s| s.storage_class_name.as_deref()) {
+        Some("") => Ok(None),
+        Some(name) => api.get_opt(name).await,
+        None => Ok(api.list(&ListParams::default()).await?.items.into_iter()
+            .find(|c| is_default_class(&c.metadata))),
+    }
+}
+
+fn is_default_class(metadata: &ObjectMeta) -> bool {
+    metadata.annotations.as_ref()
+        .and_then(|a| a.get(DEFAULT_CLASS_ANNOTATION))
+        .is_some_and(|v| v == "true")
+}
+
+/*
+This function resolves the volume topology of a pod spec.
+*/
+pub async fn resolve_volume_topology(client: Client, namespace: &str, spec: &PodSpec) -> Result<VolumeTopology, kube::Error> {
+    let mut topology = VolumeTopology {
+        namespace: namespace.to_string(),
+        ..Default::default()
+    };
+    let claims: Vec<&String> = spec.volumes.iter().flatten()
+        .filter_map(|v| v.persistent_volume_claim.as_ref())
+        .map(|c| &c.claim_name)
+        .collect();
+    let claim_api: Api<PersistentVolumeClaim> = Api::namespaced(client.clone(), namespace);
+    for claim_name in claims {
+        let Some(claim) = claim_api.get_opt(claim_name).await? else {
+            topology.pending = Some(format!("PersistentVolumeClaim {} does not exist", claim_name));
+            continue;
+        };
+        if let Some(volume_name) = claim.spec.as_ref().and_then(|s| s.volume_name.as_ref()).filter(|v| !v.is_empty()) {
+            let Some(volume) = Api::<PersistentVolume>::all(client.clone()).get_opt(volume_name).await? else {
+                topology.pending = Some(format!("PersistentVolume {} of claim {} does not exist", volume_name, claim_name));
+                continue;
+            };
+            if let Some(required) = volume.spec.and_then(|s| s.node_affinity).and_then(|a| a.required) {
+                topology.terms.push(required.node_selector_terms);
+            }
+            continue;
+        }
+        let class = claim_class(client.clone(), &claim).await?;
+        let wait_for_first_consumer = class.as_ref()
+            .and_then(|c| c.volume_binding_mode.as_deref())
+            .is_some_and(|m| m == "WaitForFirstConsumer");
+        if !wait_for_first_consumer {
+            topology.pending = Some(format!("PersistentVolumeClaim {} is not bound yet", claim_name));
+            continue;
+        }
+        let allowed: Vec<NodeSelectorTerm> = class.iter()
+            .flat_map(|c| c.allowed_topologies.iter().flatten())
+            .map(|t| NodeSelectorTerm {
+                match_expressions: Some(t.match_label_expressions.iter().flatten()
+                    .map(|e| NodeSelectorRequirement {
+                        key: e.key.clone(),
+                        operator: "In".to_string(),
+                        values: Some(e.values.clone()),
+                    })
+                    .collect()),
+                match_fields: None,
+            })
+            .collect();
+        if !allowed.is_empty() {
+            topology.terms.push(allowed);
+        }
+        /*
+        A node already recorded on the claim (by a previous
+        replica) is kept, the volume being provisioned there.
+        */
+        match claim.metadata.annotations.as_ref().and_then(|a| a.get(SELECTED_NODE_ANNOTATION)) {
+            Some(node) => topology.terms.push(vec![node_name_term(node)]),
+            None => topology.unbound_claims.push(claim_name.clone()),
+        }
+    }
+    Ok(topology)
+}
@@ -5,37 +5,299 @@ retrieved from the event priority queue.
 */
 
 use std::{
-    mem,
     ptr,
-    process::exit,
-    os::raw::c_char,
-    ffi::c_void
+    ffi::c_void,
+    time::{
+        Duration,
+        Instant
+    }
 };
 use libc::{
     sched_param,
     SCHED_FIFO,
     pthread_self,
     pthread_setschedparam,
-    pthread_getschedparam,
-    mqd_t,
-    O_RDONLY,
-    mq_attr,
-    mq_open,
-    mq_close,
-    mq_receive,
-    pthread_cond_signal,
-    pthread_mutex_lock,
-    pthread_mutex_unlock
-};
-use kube::Api;
-
-use crate::utils::vars::SharedState;
-use crate::utils::vars::QueueMessage;
+    pthread_getschedparam
+};
+use kube::{
+    Api,
+    Client,
+    core::ObjectMeta
+};
+use k8s_openapi::api::core::v1::{
+    Node,
+    Pod
+};
+
+use crate::utils::vars::{
+    SharedState,
+    SharedStatePtr,
+    EventKind
+};
+use crate::components::capacity_index::{
+    placement_candidates,
+    node_cpu_usage,
+    node_free_capacity,
+    reserve_placement_overrides,
+    release_placement_overrides
+};
 use crate::utils::rtresource::RTResource;
 use crate::utils::rtresource::Condition;
+use crate::utils::rtresource::LastReconcile;
+use crate::utils::rtresource::PendingPlacement;
+use crate::utils::timed_list::list_timed;
+use crate::utils::lock_metrics::{
+    LockSite,
+    lock_shared,
+    unlock_shared
+};
+use crate::utils::event_bus::{
+    PipelineEventType,
+    publish_event
+};
 
 use crate::components::scheduling::create_pod;
-use crate::components::scheduling::delete_pod;
+use crate::components::partial_placement::{
+    unplaceable_replicas,
+    feasible_nodes,
+    apply_partial_placement
+};
+use crate::components::ready_wait::{
+    waits_for_ready,
+    wait_for_ready
+};
+use crate::components::scheduling::delete_pods;
+use crate::components::scheduling::patch_pod_labels;
+use crate::components::scheduling::Placement;
+use crate::components::scheduling::PodInjections;
+use crate::components::scheduling::PodPriority;
+use crate::components::priority_oracle::{
+    priority_class_name,
+    soft_priority_class_name
+};
+use crate::utils::errors::ControllerError;
+use crate::components::scheduling::unscheduled_placement;
+use crate::components::volume_topology::{
+    VolumeTopology,
+    resolve_volume_topology
+};
+use crate::components::node_failures::recent_node_failures;
+use crate::components::node_heartbeats::stale_nodes;
+use crate::components::config_watcher::{
+    restart_hash,
+    has_stale_config
+};
+use crate::utils::configuration::SchedulerKind;
+use crate::components::criticality_class::is_soft_resource;
+use crate::components::planner::{
+    plan_reconcile,
+    pod_ordinal
+};
+use crate::components::node_maintenance::{
+    maintenance_nodes,
+    evict_migrated_pods
+};
+use crate::utils::configuration::ControllerMode;
+use crate::components::decisions::record_decision;
+use crate::components::planner::Plan;
+use crate::components::adoption::adopt_pods;
+use crate::components::label_guard::{
+    take_stripped_pods,
+    restore_owner_labels
+};
+use crate::components::template_validation::{
+    TEMPLATE_CONDITION,
+    TemplateIssue,
+    validate_template
+};
+use crate::components::pod_defaults::band_defaults;
+use crate::components::priority_decay::with_priority_decay;
+use crate::components::reservation_preemption::preempt_pending_reservations;
+use crate::components::crash_loop::{
+    CrashLoopGate,
+    crash_loop_gate,
+    recent_pod_crashes,
+    forget_pod_crashes,
+    clear_resume_annotation
+};
+use crate::components::namespace_policy::{
+    NAMESPACE_CONDITION,
+    allowed_namespaces,
+    check_target_namespace
+};
+use crate::components::node_taints::{
+    dedicated_nodes_enabled,
+    dedicated_toleration
+};
+use crate::components::dispatcher::{
+    in_top_band,
+    take_event,
+    release_event,
+    more_critical_waiting
+};
+use crate::components::resurrection::{
+    PREEMPTED_CONDITION,
+    preempted_replicas,
+    forget_preemptions
+};
+use crate::utils::priorities::{
+    ThreadRole,
+    band_of
+};
+#[cfg(feature = "alloc-tracker")]
+use crate::utils::alloc_tracker::{
+    start_tracking,
+    stop_tracking
+};
+use crate::utils::metrics::{
+    record_critical_operations,
+    record_critical_phase
+};
+#[cfg(feature = "chaos")]
+use crate::components::chaos::kill_watchdog;
+use crate::components::slo_metrics::{
+    record_event_latency,
+    forget_event_latency
+};
+use crate::components::alerting::{
+    ReconcileStreak,
+    track_reconcile_outcome,
+    forget_reconcile_outcome,
+    notify_reconcile_streak
+};
+use crate::components::conflicts::conflicting_nodes;
+use crate::components::provisioning::reconcile_provisioning;
+use crate::components::image_variants::image_variant_placements;
+use crate::components::preemption_history::{
+    take_preemption_records,
+    append_preemption_history,
+    forget_preemption_history
+};
+use crate::components::replicaset_backend::{
+    apply_replicaset,
+    delete_replicasets
+};
+use crate::components::services::{
+    reconcile_service,
+    delete_services
+};
+use crate::components::archival::{
+    has_archive_finalizer,
+    add_archive_finalizer,
+    remove_archive_finalizer,
+    archive_record,
+    archive_rtresource
+};
+use crate::components::circuit_breaker::{
+    CircuitTransition,
+    update_circuit,
+    forget_circuit,
+    schedule_retry,
+    notify_circuit
+};
+use crate::utils::owner_labels::owner_labels;
+
+
+
+/*
+Writes left by the critical phase of a reconcile
+to the housekeeping phase
+*/
+#[derive(Default)]
+struct Housekeeping {
+    /*
+    The RTResource with the status to write
+    */
+    status: Option<RTResource>,
+    /*
+    The observe mode decision to record
+    (generation, deletion and plan)
+    */
+    decision: Option<(Option<i64>, bool, Plan)>,
+    /*
+    The deleted RTResource to archive, with its last pods
+    */
+    archive: Option<(RTResource, Vec<Pod>)>,
+    /*
+    Whether operations were deferred by spec.maxConcurrentOperations
+    */
+    deferred: bool,
+    /*
+    Whether an operation failed with a transient error
+    */
+    transient: bool,
+    /*
+    The replicas placed by the built-in scheduler, with their node,
+    published on the event bus once off the hot path
+    */
+    placed: Vec<(u32, String)>,
+}
+
+/*
+This function writes the status of an RTResource.
+It returns whether the write succeeded.
+*/
+async fn write_status(thread_name: &str, client: Client, updated_resource: &RTResource) -> bool {
+    let (Some(name), Some(namespace)) = (
+        updated_resource.metadata.name.as_ref(),
+        updated_resource.metadata.namespace.as_ref()
+    ) else {
+        eprintln!("{} - Cannot update the status of an RTResource without name or namespace!", thread_name);
+        return false;
+    };
+    let body = match serde_json::to_vec(updated_resource) {
+        Ok(body) => body,
+        Err(e) => {
+            eprintln!("{} - An error occurred while serializing RTResource {} in namespace {}: {}", thread_name, name, namespace, e);
+            return false;
+        }
+    };
+    let rtresource_namespaced_api = Api::<RTResource>::namespaced(client, namespace);
+    match rtresource_namespaced_api.replace_status(
+        name,
+        &Default::default(),
+        body
+    ).await {
+        Ok(_) => {
+            println!(
+                "{} - Updated status for RTResource {} in namespace {}",
+                thread_name,
+                name,
+                namespace
+            );
+            true
+        }
+        Err(e) => {
+            eprintln!(
+                "{} - An error occurred while updating status for RTResource {} in namespace {}: {}",
+                thread_name,
+                name,
+                namespace,
+                e
+            );
+            false
+        }
+    }
+}
+
+/*
+Outcome of the handling of an event
+*/
+enum ReconcileOutcome {
+    /*
+    The RTResource exists and was reconciled,
+    the flag tells whether any step failed
+    */
+    Reconciled(Box<RTResource>, bool),
+    /*
+    The RTResource was deleted
+    */
+    Deleted,
+    /*
+    The RTResource could not be retrieved
+    */
+    Failed,
+}
 
 
 
@@ -53,102 +315,144 @@ pub extern "C" fn watchdog(thread_data: *mut c_void) -> *mut c_void {
         */
         let thread = pthread_self();
 
-        /*
-        We open the priority queue to
-        retrieve events published on it.
-        */
-        let mut queue_attr: mq_attr = { mem::zeroed() };
-        queue_attr.mq_flags = 0;
-        queue_attr.mq_maxmsg = 2000;
-        queue_attr.mq_msgsize = 256;
-        queue_attr.mq_curmsgs = 0;
-        let queue_des: mqd_t = mq_open(
-            shared_state.queue.as_ptr() as *const c_char,
-            O_RDONLY,
-            0664,
-            &queue_attr
-        );
-        if queue_des == -1 {
-            eprintln!("Watchdog - An error occurred while opening the queue!");
-            exit(-1);
-        }
-	
         loop {
             /*
             Each time the watchdog start the infinite loop,
             it waits for a new event to handle.
-            Once events are available, it will retrieve the
-            higher priority one not already collected by
-            concurrent watchdogs.
-            The message retrieved containe name, UID and
-            namespace of the RTResource related to the event
-            and a priority equal to the criticality level.
+            Once events are available, the dispatcher hands it the
+            most critical one not already collected by concurrent
+            watchdogs, as long as the watchdog slots reserved to the
+            top criticality band are respected (an idle general watchdog
+            may also be handed a top band event).
+            The event contains name, UID and namespace of the
+            RTResource and its criticality level.
             */
-            let mut msg: [u8; 1024] = [0; 1024];
-            let mut criticality: u32 = 0;
-            let result = mq_receive(
-                queue_des,
-                msg.as_mut_ptr() as *mut c_char,
-                msg.len(),
-                &mut criticality as *mut u32
-            );
-            if result == -1 {
-                eprintln!("Watchdog - An error occurred while retrieving a message from the queue!");
-                continue;
-            }
-            let actual_data = &msg[..result as usize];
-            let rtresource_data = match QueueMessage::from_bytes(actual_data) {
-                Ok(data) => {
-                    data
-                },
-                Err(e) => {
-                    eprintln!("Watchdog - An error occurred while deserializing the message from the queue: {}", e);
-                    continue;
-                }
-            };
+            let event = take_event(shared_state, thread);
+            let rtresource_data = event.msg;
+            let criticality = event.criticality;
+            let queue_wait = event.received_at.elapsed();
+            let queued = rtresource_data.queued_for();
+            let handling_start = Instant::now();
+            /*
+            The logs of the reconcile carry the trace identifier
+            of the event, also stamped on the pods it creates.
+            */
+            let thread_name = format!("Watchdog [{}]", rtresource_data.trace_id);
             println!(
-                "Watchdog - Retrieved event for RTResource {}, {} in namespace {}!",
+                "{} - Retrieved event for RTResource {}, {} in namespace {} after {} ms in queue!",
+                thread_name,
                 rtresource_data.name,
                 rtresource_data.uid,
-                rtresource_data.namespace
+                rtresource_data.namespace,
+                queued.as_millis()
             );
             
             /*
-            The event server must be aware theat the watchdog
-            is now working on an event, so that it can decide
-            whether to spawn new watchdogs or not.
-            */
-            pthread_mutex_lock(&mut shared_state.mutex);
-            shared_state.working_threads = shared_state.working_threads + 1;
-            pthread_cond_signal(&mut shared_state.cond);
-            pthread_mutex_unlock(&mut shared_state.mutex);
-            
-            /*
-            The thread priority is temporarily changed
+            The thread priority was temporarily changed by the dispatcher
             according to the criticality of the event being handled.
             */
-            let param = sched_param{sched_priority: 94 - criticality as i32};
-            pthread_setschedparam(thread, SCHED_FIFO, &param);
             let mut debug_param = sched_param {sched_priority: 0};
             let mut debug_policy = 0;
     	    pthread_getschedparam(thread, &mut debug_policy, &mut debug_param);
-    	    println!("Watchdog - Started handling event with priority {}!", debug_param.sched_priority);
+    	    println!("{} - Started handling {} event with priority {}!", thread_name, rtresource_data.kind, debug_param.sched_priority);
 
-            let client = shared_state.context.client.clone();
+            /*
+            The apiserver requests of the critical phase go through
+            the critical path client, the housekeeping ones do not.
+            */
+            let client = shared_state.context.critical_client.clone();
             let rtresource_api = Api::<RTResource>::namespaced(
-                shared_state.context.client.clone(),
+                client.clone(),
                 rtresource_data.namespace.as_str()
             );
-            let pods_api = shared_state.context.pods.clone();
             let pod_lp = kube::api::ListParams::default()
-                .labels(&format!("rtresource_uid={}", rtresource_data.uid));
+                .labels(&owner_labels().uid_selector(&rtresource_data.uid));
             let rtresource_data_clone = rtresource_data.clone();
-            shared_state.runtime_handle.block_on(async {
+            let observe = shared_state.config.mode == ControllerMode::Observe;
+            let builtin_scheduler = shared_state.config.scheduler == SchedulerKind::Builtin;
+            let failed_nodes = if builtin_scheduler {
+                recent_node_failures(shared_state, &rtresource_data.uid)
+            } else {
+                Vec::new()
+            };
+            /*
+            The pods of the top criticality band are not bound
+            to the nodes whose heartbeat is stale.
+            */
+            let stale_nodes = if builtin_scheduler && in_top_band(shared_state, criticality) {
+                stale_nodes(shared_state)
+            } else {
+                Vec::new()
+            };
+            let preempted = preempted_replicas(shared_state, &rtresource_data.uid);
+            let recent_crashes = recent_pod_crashes(shared_state, &rtresource_data.uid);
+            let preemption_records = if observe {
+                Vec::new()
+            } else {
+                take_preemption_records(shared_state, &rtresource_data.uid)
+            };
+            let maintenance = maintenance_nodes(shared_state);
+            let soft = is_soft_resource(shared_state, &rtresource_data.uid);
+            let preemption_history_size = shared_state.config.preemption_history_size;
+            let scheduling_policy = &shared_state.scheduling_policy;
+            let scheduler_webhook_url = shared_state.config.scheduler_webhook_url.clone();
+            let webhook_timeout = Duration::from_millis(shared_state.config.scheduler_webhook_timeout_ms);
+            let teardown_batch_size = shared_state.config.teardown_batch_size;
+            let recovery_deadline = event.received_at + Duration::from_millis(shared_state.config.recovery_deadline_ms);
+            /*
+            The pods of the top criticality band tolerate the dedicated nodes,
+            unless their RTResource is Soft.
+            */
+            let mut tolerations = Vec::new();
+            if dedicated_nodes_enabled(&shared_state.config)
+                && in_top_band(shared_state, criticality)
+                && !soft {
+                tolerations.push(dedicated_toleration());
+            }
+            let pod_security_hardening = shared_state.config.pod_security_hardening;
+            /*
+            The pods get the PriorityClass of their criticality (the Soft
+            one, never preempting, for the Soft RTResources).
+            */
+            let pod_priority = shared_state.config.pod_priority_hints.then(|| match soft {
+                true => PodPriority {
+                    class_name: soft_priority_class_name(criticality),
+                    preemption_policy: "Never",
+                },
+                false => PodPriority {
+                    class_name: priority_class_name(criticality),
+                    preemption_policy: "PreemptLowerPriority",
+                },
+            });
+            let pod_defaults = band_defaults(&shared_state.config.pod_defaults, criticality);
+            let allowed_namespaces = allowed_namespaces(&shared_state.config, &rtresource_data.namespace);
+            let state = SharedStatePtr(thread_data as *mut SharedState);
+            /*
+            The reconcile is split in two phases:
+                1. the critical phase (pod placement, creations and deletions)
+                   runs at the priority of the event, decaying one criticality
+                   level at a time once it exceeds PRIORITY_DECAY_BUDGET_MS,
+                   on the critical-path runtime;
+                2. the housekeeping (status and decision writes, alerts, metrics)
+                   runs after dropping back to the base priority, so that it
+                   does not steal CPU from the RT pods, on the housekeeping runtime.
+            */
+            let mut housekeeping = Housekeeping::default();
+            #[cfg(feature = "alloc-tracker")]
+            start_tracking();
+            let outcome = shared_state.critical_runtime_handle.block_on(with_priority_decay(state, thread, criticality, async {
                 /*
                 We proceed to acquire the RTResource
                 with the corresponding UID.
+                The events of a deleted RTResource skip the lookup:
+                the RTResource is handled as not found.
                 */
-		        match rtresource_api.get(rtresource_data_clone.name.as_str()).await {
+                let fetched = match rtresource_data_clone.kind {
+                    EventKind::ResourceDeleted => Err(ControllerError::NotFound(format!("RTResource {} was deleted", rtresource_data_clone.name))),
+                    _ => rtresource_api.get(rtresource_data_clone.name.as_str()).await
+                        .map_err(|e| ControllerError::api(&format!("{} - An error occurred while retrieving the RTResource", thread_name), e)),
+                };
+		        match fetched {
 		        	/*
                     The next step is to understand wether the RTResource still exists or not.
                     If it doesn't exist, it means that it has been deleted and we have to delete
@@ -161,29 +465,105 @@ pub extern "C" fn watchdog(thread_data: *mut c_void) -> *mut c_void {
                     equal to the UID of the RTResource) and, then we compare the number of deployed replicas 
                     to the desired one and decide whether to scale up or down.
 		        	*/
+                    /*
+                    An RTResource held by the archive finalizer is being deleted:
+                    its pods are deleted as if it were gone, and its archival
+                    (then the finalizer removal) is left to the housekeeping phase.
+                    */
+                    Ok(r) if r.metadata.deletion_timestamp.is_some() && has_archive_finalizer(&r) => {
+                        println!(
+                            "{} - The RTResource {}, {} in namespace {} is being deleted!",
+                            thread_name,
+                            rtresource_data_clone.name,
+                            rtresource_data_clone.uid,
+                            rtresource_data_clone.namespace
+                        );
+                        let pods = match list_timed::<Pod>(client.clone(), None, &pod_lp).await {
+                            Ok(pod_list) => pod_list.items,
+                            Err(e) => {
+                                eprintln!("{} - An error occurred while listing the pods of RTResource {}: {}", thread_name, rtresource_data_clone.uid, e);
+                                return ReconcileOutcome::Failed;
+                            }
+                        };
+                        if observe {
+                            let plan = Plan {
+                                deletes: pods,
+                                ..Default::default()
+                            };
+                            housekeeping.decision = Some((r.metadata.generation, true, plan));
+                            return ReconcileOutcome::Deleted;
+                        }
+                        if r.spec.uses_replicaset() && !delete_replicasets(&thread_name, client.clone(), &rtresource_data_clone.uid).await {
+                            return ReconcileOutcome::Failed;
+                        }
+                        let yield_to_critical = || more_critical_waiting(state, criticality);
+                        delete_pods(&thread_name, client.clone(), pods.clone(), teardown_batch_size, yield_to_critical).await;
+                        housekeeping.archive = Some((r, pods));
+
+                        ReconcileOutcome::Deleted
+                    }
+                    /*
+                    A spec event whose generation was already reconciled
+                    (e.g. by the watchdog of an earlier event) has nothing
+                    left to do: the pods are not listed again.
+                    */
+                    Ok(r) if rtresource_data_clone.kind == EventKind::ResourceApplied
+                        && r.metadata.generation.is_some()
+                        && r.status.as_ref().and_then(|s| s.observed_generation) == r.metadata.generation => {
+                        println!(
+                            "{} - The generation of RTResource {}, {} in namespace {} was already reconciled!",
+                            thread_name,
+                            rtresource_data_clone.name,
+                            rtresource_data_clone.uid,
+                            rtresource_data_clone.namespace
+                        );
+                        ReconcileOutcome::Reconciled(Box::new(r), false)
+                    }
                     Ok(r) => {
 		        		println!(
-                            "Watchdog - The RTResource {}, {} in namespace {} was either created/updated or some of its pods were deleted!",
+                            "{} - The RTResource {}, {} in namespace {} was either created/updated or some of its pods were deleted!",
+                            thread_name,
                             rtresource_data_clone.name,
                             rtresource_data_clone.uid,
                             rtresource_data_clone.namespace
                         );
 
                         /*
-                        If the RTResource exists, we must update its status first.
+                        If the RTResource exists, we must compute its new status first.
                             1. We set the observed generation to the current one.
-                            2. We set the desired replicas to the current spec.replicas
+                            2. We set the desired replicas to the current spec.replicas, or to the
+                               standby replicas outside the spec.schedule active windows
                                (current replicas will be updated by the status updater accordingly).
                             3. We set the conditions accordingly (creating them if it is a new RTResource):
                                 - Progressing = True
                                 - Ready = False
-                            4. We update the status in the apiserver.
+                            4. We leave the status write to the housekeeping phase.
                         */
+                        let mut failed = false;
                         let mut new_rtresource_status = r.status.clone().unwrap_or_default();
 
+                        /*
+                        The pod template is validated against the cluster capabilities
+                        on the first reconcile of each spec generation, and again on
+                        each reconcile while it is invalid (so it recovers when the
+                        node pool gains the missing capabilities).
+                        */
+                        let generation_changed = new_rtresource_status.observed_generation != r.metadata.generation;
+                        let template_invalid = new_rtresource_status.conditions.iter().flatten()
+                            .any(|c| c.condition_type == TEMPLATE_CONDITION && c.status == "False");
+                        let mut template_issues: Vec<TemplateIssue> = Vec::new();
+                        if generation_changed || template_invalid {
+                            match validate_template(client.clone(), &r).await {
+                                Ok(issues) => template_issues = issues,
+                                Err(e) => {
+                                    eprintln!("{} - An error occurred while validating the template of RTResource {}: {}", thread_name, rtresource_data_clone.uid, e);
+                                }
+                            }
+                        }
+
                         new_rtresource_status.observed_generation = r.metadata.generation;
 
-                        new_rtresource_status.desired_replicas = r.spec.replicas;
+                        new_rtresource_status.desired_replicas = Some(r.spec.desired_replicas());
 
                         let mut new_rtresource_conditions =  new_rtresource_status.conditions.unwrap_or_default();
                         let transition_time = chrono::Utc::now().to_rfc3339();
@@ -221,64 +601,528 @@ pub extern "C" fn watchdog(thread_data: *mut c_void) -> *mut c_void {
                         }
                         new_rtresource_status.conditions = Some(new_rtresource_conditions);
 
-                        let mut updated_resource = r.clone();
-                        updated_resource.status = Some(new_rtresource_status);
-                        let rtresource_namespaced_api = Api::<RTResource>::namespaced(
-                            client.clone(),
-                            r.metadata.namespace.as_ref().unwrap()
-                        );
-                        match rtresource_namespaced_api.replace_status(
-                            &r.metadata.name.as_ref().unwrap(),
-                            &Default::default(),
-                            serde_json::to_vec(&updated_resource).unwrap()
-                        ).await {
-                            Ok(_) => {
-                                println!(
-                                    "State Updater - Updated status for RTResource: {}, {} in namespace {}",
-                                    rtresource_data_clone.name,
-                                    rtresource_data_clone.uid,
-                                    rtresource_data_clone.namespace
+                        if let Some(issue) = template_issues.first() {
+                            let messages: Vec<String> = template_issues.iter().map(|i| i.message()).collect();
+                            eprintln!(
+                                "{} - The template of RTResource {} is not supported by the cluster: {}!",
+                                thread_name,
+                                rtresource_data_clone.uid,
+                                messages.join(", ")
+                            );
+                            new_rtresource_status.set_condition(TEMPLATE_CONDITION, "False", issue.reason(), &messages.join("; "));
+                        } else if generation_changed || template_invalid {
+                            new_rtresource_status.set_condition(
+                                TEMPLATE_CONDITION,
+                                "True",
+                                "Validated",
+                                "The pod template is supported by the cluster"
+                            );
+                        }
+
+                        /*
+                        An RTResource whose spec.namespace is not allowed
+                        (NAMESPACE_POLICY) gets no new pod.
+                        */
+                        let namespace_violation = check_target_namespace(allowed_namespaces.as_deref(), &r).err();
+                        match namespace_violation.as_ref() {
+                            Some(violation) => {
+                                eprintln!("{} - {}", thread_name, violation);
+                                new_rtresource_status.set_condition(NAMESPACE_CONDITION, "False", "NamespaceNotAllowed", &violation.to_string());
+                            }
+                            None if allowed_namespaces.is_some() => {
+                                new_rtresource_status.set_condition(
+                                    NAMESPACE_CONDITION,
+                                    "True",
+                                    "Allowed",
+                                    "spec.namespace is allowed by the namespace policy"
                                 );
                             }
-                            Err(e) => {
-                                eprintln!(
-                                    "State Updater - An error occurred while updating status for RTResource {}, {} in namespace {}: {}",
-                                    rtresource_data_clone.name,
+                            None => {}
+                        }
+
+                        /*
+                        An RTResource whose pods crashed spec.failureThreshold
+                        times gets no new pod until an operator resumes it.
+                        */
+                        let crash_loop = crash_loop_gate(&r, recent_crashes, &mut new_rtresource_status);
+                        if crash_loop == CrashLoopGate::Resumed {
+                            println!("{} - Resuming the recreation of the replicas of RTResource {}!", thread_name, rtresource_data_clone.uid);
+                            forget_pod_crashes(state, &rtresource_data_clone.uid);
+                            if !observe && let Err(e) = clear_resume_annotation(client.clone(), &r).await {
+                                eprintln!("{} - An error occurred while removing the resume annotation of RTResource {}: {}", thread_name, rtresource_data_clone.uid, e);
+                            }
+                        }
+
+                        /*
+                        Replicas preempted by more critical pods are reported
+                        until the state updater sees the RTResource recovered.
+                        */
+                        if preempted > 0 {
+                            new_rtresource_status.preempted_replicas = Some(preempted);
+                            new_rtresource_status.set_condition(
+                                PREEMPTED_CONDITION,
+                                "True",
+                                "PreemptedByScheduler",
+                                &format!("{} replicas were preempted by more critical pods, recovery is pending", preempted)
+                            );
+                        }
+
+                        /*
+                        In observe mode the status is left to the active controller.
+                        */
+                        if observe {
+                            println!(
+                                "{} - Observe mode: skipping status update for RTResource {}, {} in namespace {}!",
+                                thread_name,
+                                rtresource_data_clone.name,
+                                rtresource_data_clone.uid,
+                                rtresource_data_clone.namespace
+                            );
+                        } else {
+                            /*
+                            The preemptions recorded since the last
+                            reconcile are appended to the history.
+                            */
+                            new_rtresource_status.preemption_history = append_preemption_history(
+                                new_rtresource_status.preemption_history.take(),
+                                preemption_records,
+                                preemption_history_size
+                            );
+                            let mut updated_resource = r.clone();
+                            updated_resource.status = Some(new_rtresource_status);
+                            housekeeping.status = Some(updated_resource);
+                        }
+
+                        /*
+                        With spec.workloadBackend: ReplicaSet the replicas are
+                        maintained by the ReplicaSet of the RTResource: only its
+                        template and desired replicas are applied, in criticality
+                        order, unless the template, the namespace or a crash loop
+                        forbid new replicas.
+                        */
+                        if r.spec.uses_replicaset() {
+                            if observe {
+                                println!(
+                                    "{} - Observe mode: ReplicaSet of RTResource {} would be applied with {} replicas!",
+                                    thread_name,
                                     rtresource_data_clone.uid,
-                                    rtresource_data_clone.namespace,
-                                    e
+                                    r.spec.desired_replicas()
                                 );
+                                return ReconcileOutcome::Reconciled(Box::new(r), false);
+                            }
+                            if !template_issues.is_empty() || crash_loop == CrashLoopGate::Closed || namespace_violation.is_some() {
+                                println!("{} - Leaving the ReplicaSet of RTResource {} unchanged until it can get new replicas!", thread_name, rtresource_data_clone.uid);
+                                return ReconcileOutcome::Reconciled(Box::new(r), false);
                             }
+                            let injections = PodInjections {
+                                tolerations: &tolerations,
+                                defaults: pod_defaults,
+                                harden_security: pod_security_hardening,
+                                priority: pod_priority,
+                                allowed_namespaces: allowed_namespaces.as_deref(),
+                                trace_id: &rtresource_data_clone.trace_id,
+                                config_hash: None,
+                            };
+                            if let Err(e) = apply_replicaset(&thread_name, client.clone(), &r, &injections).await {
+                                if e.is_transient() {
+                                    housekeeping.transient = true;
+                                }
+                                eprintln!("{}", e);
+                                e.record();
+                                failed = true;
+                            }
+                            return ReconcileOutcome::Reconciled(Box::new(r), failed);
+                        }
+                        /*
+                        An RTResource switched back to RawPods drops its
+                        ReplicaSet, whose pods are then planned as raw ones.
+                        */
+                        if generation_changed && !observe && !delete_replicasets(&thread_name, client.clone(), &rtresource_data_clone.uid).await {
+                            return ReconcileOutcome::Failed;
                         }
 
                         /*
                         Now we can proceed to scale the number of pods
                         associated to the RTResource according to the desired
                         number of replicas.
+                        The whole transition plan is computed from a single
+                        snapshot of the pods before acting, then executed
+                        step by step (label updates, creations, deletions).
+                        Every step is idempotent, so a plan interrupted by
+                        an error is completed by the next reconcile.
                         */
-                        let pod_list = pods_api.list(&pod_lp).await.unwrap();
-                        let pod_count = pod_list.items.len() as i32;
-                        let desired_pod_count = r.spec.replicas.unwrap_or(0);
-                        let pods_needed = (desired_pod_count - pod_count as i32).abs();
-                        if desired_pod_count > pod_count {
-                            for _i in 0..pods_needed {
-                                if let Err(e) = create_pod("Watchdog".to_string(), client.clone(), &r).await{
-                                    eprintln!("{}", e);
+                        let pod_list = match list_timed::<Pod>(client.clone(), None, &pod_lp).await {
+                            Ok(list) => list,
+                            Err(e) => {
+                                eprintln!("{} - An error occurred while listing the pods of RTResource {}: {}", thread_name, rtresource_data_clone.uid, e);
+                                return ReconcileOutcome::Reconciled(Box::new(r), true);
+                            }
+                        };
+                        let mut pods = pod_list.items;
+
+                        /*
+                        The pods whose owner labels were stripped by a user
+                        are re-adopted, restoring their labels.
+                        */
+                        if !observe {
+                            for (namespace, name) in take_stripped_pods(state, &rtresource_data_clone.uid) {
+                                if pods.iter().any(|p| p.metadata.name.as_ref() == Some(&name)) {
+                                    continue;
+                                }
+                                if let Some(pod) = restore_owner_labels(&thread_name, client.clone(), &rtresource_data_clone.uid, &namespace, &name).await {
+                                    pods.push(pod);
+                                }
+                            }
+                        }
+
+                        /*
+                        With spec.adoptExisting, missing replicas are first taken
+                        from the unmanaged pods matching the selector.
+                        */
+                        if r.spec.adopt_existing.unwrap_or(false) && !observe {
+                            let desired = r.spec.desired_replicas() as usize;
+                            let live = pods.iter().filter(|p| p.metadata.deletion_timestamp.is_none()).count();
+                            let adopted = adopt_pods(&thread_name, client.clone(), &r, desired.saturating_sub(live)).await;
+                            pods.extend(adopted);
+                        }
+                        /*
+                        The pods on the nodes under maintenance are planned as
+                        missing, so that their replacements are created elsewhere;
+                        they are evicted once the replacements are Ready.
+                        */
+                        let (mut migrating, kept): (Vec<Pod>, Vec<Pod>) = pods.into_iter().partition(|p| {
+                            p.metadata.deletion_timestamp.is_none()
+                                && p.spec.as_ref().and_then(|s| s.node_name.as_ref()).is_some_and(|n| maintenance.contains(n))
+                        });
+                        pods = kept;
+                        if !migrating.is_empty() {
+                            println!(
+                                "{} - Migrating {} pods of RTResource {} off the nodes under maintenance!",
+                                thread_name,
+                                migrating.len(),
+                                rtresource_data_clone.uid
+                            );
+                        }
+                        /*
+                        With spec.restartOn, a pod started with older versions of the
+                        referenced ConfigMaps and Secrets is replaced like a migrated
+                        one. The pods are rolled one at a time: the next one is left
+                        to a new event, once the replacement of this one is Ready.
+                        */
+                        let config_hash = restart_hash(state, &r);
+                        if let Some(hash) = config_hash.as_deref()
+                            && migrating.is_empty() {
+                            let stale: Vec<usize> = pods.iter().enumerate()
+                                .filter(|(_, p)| p.metadata.deletion_timestamp.is_none() && has_stale_config(p, hash))
+                                .map(|(i, _)| i)
+                                .collect();
+                            if let Some(first) = stale.first() {
+                                let pod = pods.remove(*first);
+                                println!(
+                                    "{} - Rolling Pod {} of RTResource {} to the new configuration ({} more to roll)!",
+                                    thread_name,
+                                    pod.metadata.name.clone().unwrap_or_default(),
+                                    rtresource_data_clone.uid,
+                                    stale.len() - 1
+                                );
+                                migrating.push(pod);
+                                if stale.len() > 1 {
+                                    housekeeping.deferred = true;
+                                }
+                            }
+                        }
+                        let mut plan = plan_reconcile(&pods, &r);
+
+                        /*
+                        No replica is created from a template the cluster cannot run.
+                        */
+                        if !template_issues.is_empty() && !plan.creates.is_empty() {
+                            println!(
+                                "{} - Skipping {} creations for RTResource {} until its template is valid!",
+                                thread_name,
+                                plan.creates.len(),
+                                rtresource_data_clone.uid
+                            );
+                            plan.creates.clear();
+                        }
+                        if crash_loop == CrashLoopGate::Closed && !plan.creates.is_empty() {
+                            println!(
+                                "{} - Skipping {} creations for RTResource {} until it is resumed after its crash loop!",
+                                thread_name,
+                                plan.creates.len(),
+                                rtresource_data_clone.uid
+                            );
+                            plan.creates.clear();
+                        }
+                        if namespace_violation.is_some() && !plan.creates.is_empty() {
+                            println!(
+                                "{} - Skipping {} creations for RTResource {} until its namespace is allowed!",
+                                thread_name,
+                                plan.creates.len(),
+                                rtresource_data_clone.uid
+                            );
+                            plan.creates.clear();
+                        }
+                        /*
+                        With spec.maxConcurrentOperations a reconcile performs
+                        a bounded number of creations and deletions: the others
+                        are left to a new event, queued at the same criticality,
+                        so that the watchdog goes back to the more critical events.
+                        */
+                        if let Some(max) = r.spec.max_concurrent_operations {
+                            let deferred = plan.limit_operations(max.max(1) as usize);
+                            if deferred > 0 {
+                                println!(
+                                    "{} - Deferring {} operations of RTResource {} (at most {} per reconcile)!",
+                                    thread_name,
+                                    deferred,
+                                    rtresource_data_clone.uid,
+                                    max
+                                );
+                                housekeeping.deferred = true;
+                            }
+                        }
+                        if plan.is_empty() {
+                            println!("{} - RTResource {} pods already match the desired state!", thread_name, rtresource_data_clone.uid);
+                        } else {
+                            println!(
+                                "{} - Reconcile plan for RTResource {}: {} creations, {} deletions, {} label updates!",
+                                thread_name,
+                                rtresource_data_clone.uid,
+                                plan.creates.len(),
+                                plan.deletes.len(),
+                                plan.updates.len()
+                            );
+                        }
+
+                        /*
+                        In observe mode the plan is only recorded.
+                        */
+                        if observe {
+                            housekeeping.decision = Some((r.metadata.generation, false, plan));
+                            return ReconcileOutcome::Reconciled(Box::new(r), false);
+                        }
+                        for update in plan.updates.iter() {
+                            if let Err(e) = patch_pod_labels(thread_name.clone(), client.clone(), update).await {
+                                eprintln!("{}", e);
+                                e.record();
+                                failed = true;
+                            }
+                        }
+                        /*
+                        With the built-in scheduler the new replicas are placed by the policy,
+                        scoring the candidate nodes taken from the capacity index
+                        (the nodes are listed only until the index is populated).
+                        The pinned replicas are reserved on their node first,
+                        so that the other replicas are not placed on their capacity.
+                        */
+                        let mut cancelled_pinned = Vec::new();
+                        if builtin_scheduler {
+                            cancelled_pinned = reserve_placement_overrides(state, &r, criticality);
+                        }
+                        let mut conflicting = Vec::new();
+                        if builtin_scheduler && !plan.creates.is_empty() {
+                            match conflicting_nodes(client.clone(), &r).await {
+                                Ok(nodes) => conflicting = nodes,
+                                Err(e) => {
+                                    eprintln!("{} - An error occurred while listing the conflicting RTResources: {}", thread_name, e);
+                                    return ReconcileOutcome::Failed;
                                 }
                             }
-                        } else if desired_pod_count < pod_count {
-                            for i in pod_list.items.iter().take(pods_needed as usize) {
-                                if let Err(e) = delete_pod("Watchdog".to_string(), client.clone(), i.clone()).await{
+                        }
+                        /*
+                        The pods are only bound to nodes able to mount their persistent volumes.
+                        */
+                        let mut volumes = VolumeTopology::default();
+                        if builtin_scheduler && !plan.creates.is_empty()
+                            && let Some(spec) = r.spec.template.spec.as_ref() {
+                            match resolve_volume_topology(client.clone(), &r.spec.namespace, spec).await {
+                                Ok(topology) => volumes = topology,
+                                Err(e) => {
+                                    eprintln!("{} - An error occurred while resolving the volume topology: {}", thread_name, e);
+                                    return ReconcileOutcome::Failed;
+                                }
+                            }
+                        }
+                        let mut placement = None;
+                        let candidates = r.spec.template.spec.as_ref()
+                            .filter(|_| builtin_scheduler && !plan.creates.is_empty())
+                            .and_then(|spec| placement_candidates(state, spec, r.spec.os_target()));
+                        if let Some(nodes) = candidates {
+                            placement = Some(Placement {
+                                policy: scheduling_policy,
+                                nodes,
+                                failed_nodes: failed_nodes.clone(),
+                                conflicting_nodes: conflicting.clone(),
+                                node_cpu_usage: node_cpu_usage(state),
+                                webhook_url: &scheduler_webhook_url,
+                                webhook_timeout,
+                                volumes,
+                                stale_nodes: stale_nodes.clone(),
+                            });
+                        } else if builtin_scheduler && !plan.creates.is_empty() {
+                            match Api::<Node>::all(client.clone()).list(&Default::default()).await {
+                                Ok(nodes) => {
+                                    placement = Some(Placement {
+                                        policy: scheduling_policy,
+                                        nodes: nodes.items,
+                                        failed_nodes: failed_nodes.clone(),
+                                        conflicting_nodes: conflicting.clone(),
+                                        node_cpu_usage: node_cpu_usage(state),
+                                        webhook_url: &scheduler_webhook_url,
+                                        webhook_timeout,
+                                        volumes,
+                                        stale_nodes: stale_nodes.clone(),
+                                    });
+                                }
+                                Err(e) => {
+                                    eprintln!("{} - An error occurred while listing the nodes: {}", thread_name, e);
+                                    return ReconcileOutcome::Failed;
+                                }
+                            }
+                        }
+                        /*
+                        The replicas that cannot be placed (no feasible node for the
+                        built-in scheduler, or pods kube-scheduler reports as unschedulable)
+                        are listed in the status with the reasons, so that users do not
+                        have to read the controller logs.
+                        */
+                        let mut pending: Vec<PendingPlacement> = pods.iter()
+                            .filter(|p| p.metadata.deletion_timestamp.is_none())
+                            .filter_map(unscheduled_placement)
+                            .collect();
+                        /*
+                        Pinned replicas whose reservation was cancelled by a more
+                        critical placement wait until their node has room for them.
+                        */
+                        for ordinal in cancelled_pinned.iter().filter(|o| plan.creates.contains(o)) {
+                            pending.push(PendingPlacement {
+                                ordinal: *ordinal,
+                                reason: "ReservationPreempted".to_string(),
+                                message: "the reservation was cancelled for a more critical placement".to_string(),
+                                nodes: None,
+                            });
+                        }
+                        plan.creates.retain(|o| !cancelled_pinned.contains(o));
+                        /*
+                        The replicas beyond the capacity of the nodes are left
+                        out according to spec.partialPlacementPolicy.
+                        */
+                        let mut unplaceable = Vec::new();
+                        if let Some(placement) = placement.as_ref()
+                            && !plan.creates.is_empty()
+                            && let Some(free) = node_free_capacity(state, r.spec.os_target()) {
+                            unplaceable = unplaceable_replicas(&thread_name, placement, &r, &plan.creates, &tolerations, free);
+                        }
+                        /*
+                        The pending reservations of less critical RTResources are
+                        cancelled first to make room (no running pod is disrupted).
+                        */
+                        if !soft
+                            && !unplaceable.is_empty()
+                            && let Some(placement) = placement.as_ref() {
+                            let feasible = feasible_nodes(placement, &r, &tolerations);
+                            if preempt_pending_reservations(state, &r, criticality, &unplaceable, &feasible) > 0
+                                && let Some(free) = node_free_capacity(state, r.spec.os_target()) {
+                                unplaceable = unplaceable_replicas(&thread_name, placement, &r, &plan.creates, &tolerations, free);
+                            }
+                        }
+                        let left_out = apply_partial_placement(
+                            &r,
+                            &mut plan.creates,
+                            unplaceable,
+                            housekeeping.status.as_mut().and_then(|u| u.status.as_mut())
+                        );
+                        if !left_out.is_empty() {
+                            println!(
+                                "{} - {} replicas of RTResource {} do not fit on the nodes and are pending!",
+                                thread_name,
+                                left_out.len(),
+                                rtresource_data_clone.uid
+                            );
+                        }
+                        pending.extend(left_out);
+                        let mut created = Vec::with_capacity(plan.creates.len());
+                        housekeeping.placed.reserve(plan.creates.len());
+                        let injections = PodInjections {
+                            tolerations: &tolerations,
+                            defaults: pod_defaults,
+                            harden_security: pod_security_hardening,
+                            priority: pod_priority,
+                            allowed_namespaces: allowed_namespaces.as_deref(),
+                            trace_id: &rtresource_data_clone.trace_id,
+                            config_hash: config_hash.as_deref(),
+                        };
+                        for ordinal in plan.creates.iter() {
+                            match create_pod(thread_name.clone(), client.clone(), &r, *ordinal, &injections, placement.as_ref()).await {
+                                Ok(node) => {
+                                    if let Some(node) = node {
+                                        housekeeping.placed.push((*ordinal, node));
+                                    }
+                                    created.push(*ordinal);
+                                }
+                                Err(e) => {
+                                    match &e {
+                                        ControllerError::Unschedulable(unschedulable) => pending.push(unschedulable.pending_placement(*ordinal)),
+                                        ControllerError::PlacementDeferred(deferred) => pending.push(deferred.pending_placement(*ordinal)),
+                                        /*
+                                        A transient error (e.g. a pod name conflict)
+                                        is retried at once with a new event.
+                                        */
+                                        e if e.is_transient() => housekeeping.transient = true,
+                                        _ => {}
+                                    }
                                     eprintln!("{}", e);
+                                    e.record();
+                                    failed = true;
                                 }
                             }
                         }
+                        pending.sort_by_key(|p| p.ordinal);
+                        if let Some(status) = housekeeping.status.as_mut().and_then(|u| u.status.as_mut()) {
+                            status.pending_placements = if pending.is_empty() { None } else { Some(pending) };
+                            status.image_variants = image_variant_placements(&pods);
+                        }
+                        /*
+                        With spec.waitForReady the replaced pods are only
+                        deleted once the created replicas are Ready.
+                        */
+                        if waits_for_ready(&r) {
+                            wait_for_ready(&thread_name, client.clone(), &r, &created, &[], recovery_deadline).await;
+                        }
+                        /*
+                        The migrated pods are evicted (respecting the PodDisruptionBudgets)
+                        once the replicas replacing them are Ready, within the recovery
+                        deadline; those without a replacement yet are left to a later event.
+                        */
+                        let replaced: Vec<Pod> = migrating.into_iter()
+                            .filter(|p| pod_ordinal(p).is_some_and(|o| created.contains(&o) || pods.iter().any(|q| pod_ordinal(q) == Some(o))))
+                            .collect();
+                        if !replaced.is_empty() {
+                            let ordinals: Vec<u32> = replaced.iter().filter_map(pod_ordinal).collect();
+                            let names: Vec<String> = replaced.iter().filter_map(|p| p.metadata.name.clone()).collect();
+                            if wait_for_ready(&thread_name, client.clone(), &r, &ordinals, &names, recovery_deadline).await {
+                                evict_migrated_pods(&thread_name, client.clone(), &replaced).await;
+                            } else {
+                                failed = true;
+                            }
+                        }
+                        let yield_to_critical = || more_critical_waiting(state, criticality);
+                        let deletes = plan.deletes.len();
+                        let delete_failures = delete_pods(&thread_name, client.clone(), plan.deletes, teardown_batch_size, yield_to_critical).await;
+                        if delete_failures > 0 {
+                            failed = true;
+                        }
+                        record_critical_operations(created.len(), deletes - delete_failures, failed);
+
+                        ReconcileOutcome::Reconciled(Box::new(r), failed)
                     }
 		        	Err(e) => {
-		        		match e.to_string().find("404") {
-		        			Some(_found) => {
+		        		match e {
+		        			ControllerError::NotFound(_) => {
 		        				println!(
-                                    "Watchdog - The RTResource {}, {} in namespace {} was deleted!",
+                                    "{} - The RTResource {}, {} in namespace {} was deleted!",
+                                    thread_name,
                                     rtresource_data_clone.name,
                                     rtresource_data_clone.uid,
                                     rtresource_data_clone.namespace
@@ -286,52 +1130,260 @@ pub extern "C" fn watchdog(thread_data: *mut c_void) -> *mut c_void {
 
                                 /*
                                 If the RTResource received from the priority queue was deleted,
-                                then we must delete all the pods associated to it.
+                                then we must delete all the pods associated to it,
+                                in parallel batches yielding to more critical events.
+                                The pods whose owner labels were stripped are deleted too.
                                 */
-                                let pod_list = pods_api.list(&pod_lp).await.unwrap();
-                                for i in pod_list.items.iter() {
-                                    if let Err(e) = delete_pod("Watchdog".to_string(), client.clone(), i.clone()).await{
+                                let mut pods = match list_timed::<Pod>(client.clone(), None, &pod_lp).await {
+                                    Ok(pod_list) => pod_list.items,
+                                    Err(e) => {
+                                        let e = ControllerError::api(
+                                            &format!("{} - An error occurred while listing the pods of the deleted RTResource {}", thread_name, rtresource_data_clone.uid),
+                                            e
+                                        );
                                         eprintln!("{}", e);
+                                        e.record();
+                                        return ReconcileOutcome::Failed;
                                     }
+                                };
+                                pods.extend(take_stripped_pods(state, &rtresource_data_clone.uid).into_iter()
+                                    .map(|(namespace, name)| Pod {
+                                        metadata: ObjectMeta {
+                                            name: Some(name),
+                                            namespace: Some(namespace),
+                                            ..Default::default()
+                                        },
+                                        ..Default::default()
+                                    }));
+                                if observe {
+                                    let plan = Plan {
+                                        deletes: pods,
+                                        ..Default::default()
+                                    };
+                                    housekeeping.decision = Some((None, true, plan));
+                                    return ReconcileOutcome::Deleted;
                                 }
+                                /*
+                                The ReplicaSet, if any, is removed first
+                                so that it does not recreate the pods;
+                                if it could not be, the retry deletes both.
+                                */
+                                if !delete_replicasets(&thread_name, client.clone(), &rtresource_data_clone.uid).await {
+                                    return ReconcileOutcome::Failed;
+                                }
+                                let yield_to_critical = || more_critical_waiting(state, criticality);
+                                delete_pods(&thread_name, client.clone(), pods, teardown_batch_size, yield_to_critical).await;
+
+                                ReconcileOutcome::Deleted
                                 }
-		        			None => {
-		        				println!("Watchdog - An error occurred while retrieving Custom Resource List: {}", e);
+		        			e => {
+		        				println!("{}", e);
+                                e.record();
+                                ReconcileOutcome::Failed
 		        			}
 		        		}
 		        	}
-		        };
-            });
-	    
+		        }
+            }));
+            let recovery = event.received_at.elapsed();
+            let handled = handling_start.elapsed();
+            #[cfg(feature = "alloc-tracker")]
+            let allocations = stop_tracking();
+            #[cfg(not(feature = "alloc-tracker"))]
+            let allocations = 0;
+            record_critical_phase(allocations);
+            println!(
+                "{} - Handled event for RTResource {}, {} in namespace {} in {} ms!",
+                thread_name,
+                rtresource_data.name,
+                rtresource_data.uid,
+                rtresource_data.namespace,
+                handled.as_millis()
+            );
+
 	        /*
-            Once the event has been handled, the watchdog
+            Once the critical phase is over, the watchdog
             it must return to its original schedling priority,
-            which is '94', since it must retrieve new events and
+            which is the watchdog base priority, since it must retrieve new events and
             it must not be slowed down by other watchdogs (this is
             imperative since a new event could have higher priority
             than those being handled).
             */
-            let param = sched_param {sched_priority: 94};
+            let param = band_of(ThreadRole::Watchdogs).sched_param();
             pthread_setschedparam(thread, SCHED_FIFO, &param);
             debug_param = sched_param { sched_priority: 0 };
             debug_policy = 0;
     	    pthread_getschedparam(thread, &mut debug_policy, &mut debug_param);
-    	    println!("Watchdog - Returned to base priority {}!", debug_param.sched_priority);
+    	    println!("{} - Returned to base priority {}!", thread_name, debug_param.sched_priority);
+            #[cfg(feature = "alloc-tracker")]
+            println!("{} - The critical phase made {} allocations!", thread_name, allocations);
+
+            /*
+            The pipeline events of the critical phase are
+            formatted and published off the hot path.
+            */
+            for (ordinal, node) in housekeeping.placed.iter() {
+                publish_event(
+                    PipelineEventType::Placed,
+                    &rtresource_data,
+                    criticality,
+                    format!("replica {} on node {}", ordinal, node)
+                );
+            }
+
+            /*
+            The housekeeping phase writes the status and the
+            observe mode decision computed by the critical phase.
+            Its requests go through the general client, so that they
+            do not compete with the reconciles of the other watchdogs
+            in the priority level of the critical path client.
+            */
+            let client = shared_state.context.client.clone();
+            let mut status_failed = false;
+            if let Some(updated_resource) = housekeeping.status.as_mut()
+                && let Some(status) = updated_resource.status.as_mut() {
+                status.last_reconcile = Some(LastReconcile {
+                    queued_ms: queued.as_millis() as u64,
+                    handled_ms: handled.as_millis() as u64,
+                });
+            }
+            /*
+            The capacity of the unplaced replicas of the top criticality band
+            is requested to cluster-autoscaler.
+            */
+            if !shared_state.config.provisioning_class.is_empty()
+                && in_top_band(shared_state, criticality)
+                && let Some(updated_resource) = housekeeping.status.as_mut() {
+                let missing = updated_resource.status.as_ref()
+                    .and_then(|s| s.pending_placements.as_ref())
+                    .map_or(0, |p| p.len() as u32);
+                let provisioning = shared_state.runtime_handle.block_on(reconcile_provisioning(
+                    &thread_name,
+                    client.clone(),
+                    &shared_state.config.provisioning_class,
+                    updated_resource,
+                    missing
+                ));
+                if let Some(status) = updated_resource.status.as_mut() {
+                    status.provisioning = provisioning;
+                }
+            }
+            /*
+            The Service of the RTResource follows its spec.service
+            (housekeeping.status is only set outside the observe mode).
+            */
+            if let Some(updated_resource) = housekeeping.status.as_ref() {
+                shared_state.runtime_handle.block_on(reconcile_service(&thread_name, client.clone(), updated_resource));
+            }
+            if let Some(updated_resource) = housekeeping.status.as_ref() {
+                status_failed = !shared_state.runtime_handle.block_on(write_status(&thread_name, client.clone(), updated_resource));
+            }
+            if let Some((generation, deleted, plan)) = housekeeping.decision.as_ref() {
+                shared_state.runtime_handle.block_on(record_decision(&thread_name, client.clone(), &rtresource_data, *generation, *deleted, plan));
+            }
+            /*
+            A deleted RTResource is archived before its finalizer is removed,
+            while the controller still holds its SLO samples and preemptions.
+            */
+            if let Some((r, pods)) = housekeeping.archive.as_ref() {
+                let record = archive_record(state, r, pods);
+                shared_state.runtime_handle.block_on(async {
+                    if !shared_state.config.archive_store.is_empty() {
+                        archive_rtresource(&thread_name, client.clone(), &shared_state.config.archive_store, &record).await;
+                    }
+                    remove_archive_finalizer(&thread_name, client.clone(), r).await;
+                });
+            }
+
+            /*
+            We keep track of consecutive reconcile failures:
+            when an RTResource reaches the configured threshold
+            it is marked as stalled and an alert is raised,
+            which is resolved on the next successful reconcile.
+            */
+            match outcome {
+                ReconcileOutcome::Reconciled(r, failed) => {
+                    record_event_latency(shared_state, &rtresource_data, criticality, queue_wait, recovery);
+                    let streak = track_reconcile_outcome(shared_state, &rtresource_data.uid, failed || status_failed);
+                    if streak != ReconcileStreak::Unchanged && !observe {
+                        shared_state.runtime_handle.block_on(notify_reconcile_streak(
+                            &thread_name,
+                            &shared_state.config,
+                            client.clone(),
+                            &r,
+                            streak
+                        ));
+                    }
+                    /*
+                    After too many consecutive failures the RTResource
+                    is backed off: its events are dropped until a retry
+                    is enqueued, after an exponentially growing delay.
+                    */
+                    let transition = update_circuit(shared_state, &rtresource_data.uid, failed || status_failed);
+                    if let CircuitTransition::Opened(failures, delay) = transition {
+                        println!("{} - RTResource {} failed {} consecutive reconciles, backing off for {:?}!", thread_name, rtresource_data.uid, failures, delay);
+                        shared_state.runtime_handle.spawn(schedule_retry(shared_state.queue.clone(), rtresource_data.clone(), criticality, delay));
+                    } else if (housekeeping.deferred || housekeeping.transient) && !observe {
+                        shared_state.runtime_handle.spawn(schedule_retry(shared_state.queue.clone(), rtresource_data.clone(), criticality, Duration::ZERO));
+                    }
+                    if transition != CircuitTransition::Unchanged && !observe {
+                        shared_state.runtime_handle.block_on(notify_circuit(&thread_name, client.clone(), &r, transition));
+                    }
+                    if !shared_state.config.archive_store.is_empty() && !observe && !has_archive_finalizer(&r) {
+                        shared_state.runtime_handle.block_on(add_archive_finalizer(
+                            &thread_name,
+                            client.clone(),
+                            &rtresource_data.namespace,
+                            &rtresource_data.name
+                        ));
+                    }
+                }
+                ReconcileOutcome::Deleted => {
+                    forget_reconcile_outcome(shared_state, &rtresource_data.uid);
+                    forget_preemptions(shared_state, &rtresource_data.uid);
+                    release_placement_overrides(shared_state, &rtresource_data.uid);
+                    forget_event_latency(shared_state, &rtresource_data.uid);
+                    forget_circuit(shared_state, &rtresource_data.uid);
+                    forget_preemption_history(shared_state, &rtresource_data.uid);
+                    if !observe {
+                        shared_state.runtime_handle.block_on(delete_services(&thread_name, client.clone(), &rtresource_data.uid));
+                    }
+                }
+                ReconcileOutcome::Failed => {
+                    track_reconcile_outcome(shared_state, &rtresource_data.uid, true);
+                    let transition = update_circuit(shared_state, &rtresource_data.uid, true);
+                    if let CircuitTransition::Opened(_, delay) = transition {
+                        shared_state.runtime_handle.spawn(schedule_retry(shared_state.queue.clone(), rtresource_data.clone(), criticality, delay));
+                    }
+                }
+            }
     	    
     	    /*
             The watchdog must now check whether there are too many
             active watchdogs in the system. If so, it must terminate itself
-            to free resources.
+            to free resources, unless the pool was scaled during the cooldown
+            (the scale-down threshold and the cooldown keep it from
+            oscillating under bursty load).
             In any case, it first notifies the event server that it is no longer
             working on an event.
             */
-    	    pthread_mutex_lock(&mut shared_state.mutex);
-            shared_state.working_threads = shared_state.working_threads - 1;
+    	    lock_shared(&mut shared_state.mutex, shared_state.config.lock_wait_warn_us, LockSite::WatchdogRelease);
+            release_event(shared_state, thread);
+            #[cfg(feature = "chaos")]
+            if kill_watchdog() {
+                println!("Watchdog - Terminated by the chaos module!");
+                break;
+            }
             let decision = shared_state.active_threads - shared_state.working_threads;
-            if decision > shared_state.config.threshold && shared_state.active_threads > shared_state.config.min_watchdogs {
+            let cooled_down = shared_state.last_scaled
+                .is_none_or(|t| t.elapsed() >= Duration::from_millis(shared_state.config.scale_cooldown_ms));
+            if decision > shared_state.config.scale_down_threshold
+                && shared_state.active_threads > shared_state.config.min_watchdogs
+                && cooled_down {
+                shared_state.last_scaled = Some(Instant::now());
                 break;
             }
-            pthread_mutex_unlock(&mut shared_state.mutex);
+            unlock_shared(&mut shared_state.mutex);
         }
         
         /*
@@ -345,17 +1397,15 @@ pub extern "C" fn watchdog(thread_data: *mut c_void) -> *mut c_void {
         	if shared_state.workers[i].id == thread {
                 shared_state.workers[i].id = 0;
         		shared_state.workers[i].active = false;
+        		shared_state.workers[i].criticality = None;
+        		shared_state.workers[i].borrowed = false;
         		found = true;
-        		shared_state.active_threads = shared_state.active_threads - 1;
-	    		pthread_mutex_unlock(&mut shared_state.mutex);
+        		shared_state.active_threads -= 1;
+	    		unlock_shared(&mut shared_state.mutex);
         	}
-        	i = i + 1;
+        	i += 1;
         }
         
-        /*
-        Cleanup phase.
-        */
-    	mq_close(queue_des);
     }
     
     println!("Watchdog - Too many Watchdogs! Terminating...");
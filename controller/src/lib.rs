@@ -0,0 +1,23 @@
+/*
+This file contains the Preempt-K8s library.
+It exports the placement logic run by the controller
+(reconcile planner, scheduling policy and plugins) and the
+schedulability analysis built on top of it, so that offline
+tools (e.g. capacity planning) reuse exactly the same decisions.
+It is only built with the "library" feature, and does not depend
+on the controller threads nor on the event priority queue.
+*/
+
+#[cfg(feature = "library")]
+pub mod utils {
+    pub mod rtresource;
+    pub mod quantity;
+}
+
+#[cfg(feature = "library")]
+pub mod components {
+    pub mod planner;
+    pub mod scheduling_policy;
+    pub mod scheduling_plugins;
+    pub mod schedulability;
+}
@@ -5,10 +5,15 @@ to create the controller pipeline.
 */
 
 use std::{
+    env,
     mem,
     ptr,
     error::Error,
-    ffi::c_void
+    ffi::{
+        c_void,
+        CString
+    },
+    time::Duration
 };
 use libc::{
     pthread_t,
@@ -16,14 +21,10 @@ use libc::{
     pthread_join,
     pthread_attr_t,
     pthread_attr_init,
-    pthread_attr_setschedpolicy,
     pthread_attr_setschedparam,
-    pthread_attr_setinheritsched,
     pthread_attr_destroy,
     sched_param,
-    SCHED_FIFO,
     PTHREAD_PRIO_INHERIT,
-    PTHREAD_EXPLICIT_SCHED,
     pthread_cond_t,
     pthread_cond_init,
     pthread_cond_destroy,
@@ -33,24 +34,101 @@ use libc::{
     pthread_mutexattr_t,
     pthread_mutexattr_init,
     pthread_mutexattr_setprotocol,
-    pthread_mutexattr_destroy
+    pthread_mutexattr_destroy,
+    pthread_mutex_lock,
+    pthread_mutex_unlock
 };
 use kube::Client;
-use tokio::runtime::Runtime;
 use anyhow::Result;
 
 mod utils;
 use utils::configuration::get_controller_configuration;
-use utils::vars::new_shared_state;
+use utils::owner_labels::init_owner_labels;
+use utils::vars::{
+    SharedState,
+    SharedStatePtr,
+    new_shared_state
+};
+use utils::priorities::{
+    ThreadRole,
+    band_of,
+    probe_realtime_scheduling,
+    set_realtime_policy
+};
+use utils::rate_limit::limited_client;
+use utils::apf::{
+    critical_path_client,
+    print_apf_manifests
+};
 
 mod components;
 use components::resource_watcher::crd_watcher;
 use components::pod_watcher::pod_watcher;
 use components::resource_state_updater::resource_state_updater;
 use components::event_server::server;
+use components::dispatcher::dispatcher;
+use components::queue_stats::queue_stats_sampler;
+use components::orphan_sweeper::orphan_sweeper;
+use components::activation_windows::activation_windows;
+use components::config_watcher::config_watcher;
+use components::node_maintenance::node_maintenance_manager;
+use components::node_heartbeats::node_lease_watcher;
+use components::schemas::print_schemas;
+use components::status_verifier::status_verifier;
+use components::pod_janitor::pod_janitor;
+use components::concurrency_metrics::concurrency_scraper;
+use components::cluster_stats::cluster_stats_publisher;
+use components::startup_sequencing::startup_sequencer;
+use components::lint::run_lint;
+use components::migration::run_migration;
+use components::crash_dump::{
+    install_crash_handler,
+    inspect_crash
+};
+use components::admin_server::admin_server;
+use components::priority_oracle::print_policy_manifests;
+use components::rbac::print_rbac_manifests;
+use components::analysis::{
+    DEFAULT_CREATE_LATENCY,
+    run_analysis
+};
+use components::info_publisher::publish_info;
+use components::capacity_index::{
+    node_capacity_watcher,
+    node_metrics_watcher
+};
+use components::node_taints::{
+    dedicated_nodes_enabled,
+    node_taint_manager
+};
+use components::startup_recovery::recover_startup_state;
+use components::label_guard::foreign_owner_labels;
+#[cfg(feature = "chaos")]
+use components::chaos::{
+    init_chaos,
+    chaos_client
+};
+use utils::configuration::{
+    SchedulerKind,
+    OrphanPolicy,
+    ControllerMode
+};
+use utils::runtimes::build_runtime;
+use utils::flight_recorder::{
+    init_flight_recorder,
+    inspect_flight_recorder
+};
 
 
 
+/*
+With the "alloc-tracker" feature, the allocations
+of the watchdog hot path are counted.
+*/
+#[cfg(feature = "alloc-tracker")]
+#[global_allocator]
+static ALLOCATOR: utils::alloc_tracker::TrackingAllocator = utils::alloc_tracker::TrackingAllocator;
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
     unsafe {
@@ -59,8 +137,113 @@ async fn main() -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
         We must first retrieve the controller configuration.
         */
         let config = get_controller_configuration();
+        init_owner_labels(&config);
+
+        /*
+        If requested, we only print the API Priority and Fairness
+        manifests matching the configuration (--print-apf,
+        --namespace=<controller namespace>) and exit.
+        */
+        if env::args().any(|arg| arg == "--print-apf") {
+            return print_apf_manifests(&config);
+        }
+
+        /*
+        If requested, we only print the criticality PriorityClasses
+        and the matching Kyverno policies and exit.
+        */
+        if env::args().any(|arg| arg == "--print-policy") {
+            return print_policy_manifests(&config);
+        }
+
+        /*
+        If requested, we only print the RBAC rules the configured
+        features need (--print-rbac, --namespace=<controller namespace>)
+        and exit.
+        */
+        if env::args().any(|arg| arg == "--print-rbac") {
+            return print_rbac_manifests(&config);
+        }
+
+        /*
+        If requested, we only print the schemas of the custom
+        resources and of the admin API payloads and exit.
+        */
+        if env::args().any(|arg| arg == "--print-schemas") {
+            return print_schemas();
+        }
+
+        /*
+        If requested, we only print the static schedulability report
+        of the RTResources and nodes of the cluster (--analyze)
+        or of an exported YAML file (--analyze=<file>) and exit.
+        The estimated creation time of a replica can be set
+        with --create-latency-ms=<ms>.
+        */
+        if let Some(analyze) = env::args().find(|arg| arg == "--analyze" || arg.starts_with("--analyze=")) {
+            let export = analyze.strip_prefix("--analyze=").map(str::to_string);
+            let create_latency = env::args()
+                .find_map(|arg| arg.strip_prefix("--create-latency-ms=").and_then(|v| v.parse().ok()))
+                .map_or(DEFAULT_CREATE_LATENCY, Duration::from_millis);
+            let json = env::args().any(|arg| arg == "--json");
+            return run_analysis(&config, export.as_deref(), json, create_latency).await;
+        }
+
+        /*
+        If requested, we only lint the RTResources of a YAML file
+        (lint <file>, or --lint=<file>) and exit.
+        */
+        let lint = match env::args().nth(1).as_deref() {
+            Some("lint") => Some(env::args().nth(2).ok_or("usage: lint <file>")?),
+            _ => env::args().find_map(|arg| arg.strip_prefix("--lint=").map(str::to_string)),
+        };
+        if let Some(path) = lint {
+            return run_lint(&config, &path);
+        }
+
+        /*
+        If requested, we only migrate a Deployment to an RTResource
+        (migrate deployment/<name> --criticality=<level>, printing
+        the RTResource, or performing the cutover with --cutover) and exit.
+        */
+        if env::args().nth(1).as_deref() == Some("migrate") {
+            let target = env::args().nth(2).ok_or("usage: migrate deployment/<name> --criticality=<level>")?;
+            return run_migration(&config, &target).await;
+        }
+
+        /*
+        If requested, we only print a crash file
+        (--inspect-crash=<file>, as is with --json) and exit.
+        */
+        if let Some(path) = env::args().find_map(|arg| arg.strip_prefix("--inspect-crash=").map(str::to_string)) {
+            return inspect_crash(&path, env::args().any(|arg| arg == "--json"));
+        }
+
+        /*
+        If requested, we only print the events of a flight recorder
+        file (--inspect-flight-recorder=<file>, as is with --json) and exit.
+        */
+        if let Some(path) = env::args().find_map(|arg| arg.strip_prefix("--inspect-flight-recorder=").map(str::to_string)) {
+            return inspect_flight_recorder(&path, env::args().any(|arg| arg == "--json"));
+        }
         println!("{}", config);
 
+        /*
+        Without CAP_SYS_NICE the controller threads cannot run
+        under SCHED_FIFO: unless the degraded mode is allowed,
+        we fail fast instead of running with undefined priorities.
+        */
+        if !probe_realtime_scheduling() {
+            if !config.allow_non_rt {
+                eprintln!("Real-time scheduling (SCHED_FIFO) is unavailable: the container needs CAP_SYS_NICE! Set ALLOW_NON_RT=true to run degraded.");
+                return Err("real-time scheduling is unavailable".into());
+            }
+            eprintln!("********************************************************************************");
+            eprintln!("WARNING - Real-time scheduling (SCHED_FIFO) is unavailable (missing CAP_SYS_NICE)!");
+            eprintln!("WARNING - Running DEGRADED: controller threads use the default scheduling policy.");
+            eprintln!("********************************************************************************");
+        }
+
         /*
         We create a mutex and a condition variable
         to access the shared state.
@@ -72,6 +255,17 @@ async fn main() -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
         pthread_mutex_init(&mut mutex as *mut _, &mutex_attr);
         let mut cond: pthread_cond_t = mem::zeroed();
         pthread_cond_init(&mut cond as *mut _, ptr::null());
+        let mut dispatch_cond: pthread_cond_t = mem::zeroed();
+        pthread_cond_init(&mut dispatch_cond as *mut _, ptr::null());
+
+        /*
+        We create the Tokio runtimes: the critical-path one
+        and the housekeeping one, with separate worker pools.
+        */
+        let critical_runtime = build_runtime("critical", config.critical_runtime_workers, config.critical_runtime_cpuset.clone())
+            .expect("Failed to create the critical-path Tokio Runtime!");
+        let runtime = build_runtime("housekeeping", config.housekeeping_runtime_workers, config.housekeeping_runtime_cpuset.clone())
+            .expect("Failed to create the housekeeping Tokio Runtime!");
 
         /*
         We create the client to interact with
@@ -80,9 +274,76 @@ async fn main() -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
         let client = Client::try_default().await?;
 
         /*
-        We create the Tokio runtime.
+        The pods are stamped and selected with the configured owner
+        labels (POD_WATCHER_*_LABEL): we refuse to start while managed
+        pods carry other keys, which no selector would match anymore.
         */
-        let runtime = Runtime::new().expect("Failed to create Tokio Runtime!");
+        let foreign = foreign_owner_labels(client.clone()).await?;
+        if !foreign.is_empty() {
+            eprintln!(
+                "{} managed pods carry other owner labels than the configured ones (e.g. {}): restore POD_WATCHER_*_LABEL or relabel the pods!",
+                foreign.len(),
+                foreign[0]
+            );
+            return Err("the managed pods carry other owner labels".into());
+        }
+
+        /*
+        We create the client used on the critical reconcile path,
+        tagged for API Priority and Fairness if configured.
+        It is built on the critical-path runtime, so that its
+        connections are driven by the critical-path workers.
+        The critical path and the bulk paths get separate
+        request rate limits and timeouts.
+        With the chaos feature, the apiserver requests
+        of the reconcile path fail at the configured rate.
+        */
+        #[cfg(feature = "chaos")]
+        init_chaos();
+        let critical_config = config.clone();
+        let critical_client = critical_runtime.spawn(async move {
+            let critical_client = critical_path_client(&critical_config, Client::try_default().await?).await?;
+            let critical_client = limited_client(
+                critical_client,
+                "critical",
+                critical_config.critical_api_qps,
+                critical_config.critical_api_burst,
+                critical_config.critical_api_timeout_ms
+            );
+            #[cfg(feature = "chaos")]
+            let critical_client = chaos_client(critical_client);
+            Ok::<Client, Box<dyn Error + Send + Sync + 'static>>(critical_client)
+        }).await??;
+        let client = limited_client(
+            client,
+            "bulk",
+            config.bulk_api_qps,
+            config.bulk_api_burst,
+            config.bulk_api_timeout_ms
+        );
+
+        /*
+        We publish the controller version, feature flags
+        and configuration for auditing.
+        */
+        publish_info(client.clone(), &config).await;
+
+        /*
+        We map the flight recorder of the event queue
+        before any event is sent to the queue.
+        */
+        if let Err(e) = init_flight_recorder(&config.flight_recorder_file, config.flight_recorder_events) {
+            eprintln!("An error occurred while mapping the flight recorder, events will not be recorded: {}", e);
+        }
+
+        /*
+        We rebuild the event queue from the cluster state,
+        so that the reconciles interrupted by a crash are completed.
+        */
+        let queue = CString::new(config.event_queue_path.clone()).unwrap();
+        if let Err(e) = recover_startup_state(client.clone(), &config, &queue).await {
+            eprintln!("An error occurred during the startup consistency pass: {}", e);
+        }
 
         /*
         We must now create the shared state used by the controller threads
@@ -91,14 +352,73 @@ async fn main() -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
         let shared_state = new_shared_state(
             config.clone(),
             client.clone(),
+            critical_client,
             runtime.handle().clone(),
+            critical_runtime.handle().clone(),
             cond,
             mutex,
-            config.event_queue_path.as_str(),
-            config.max_watchdogs
+            dispatch_cond
         );
         let share_state_ptr = Box::into_raw(shared_state) as *mut c_void;
 
+        /*
+        From now on, a panic dumps the dispatcher state to a crash file.
+        */
+        install_crash_handler(SharedStatePtr(share_state_ptr as *mut SharedState));
+
+        /*
+        The event queue statistics sampler, the node capacity and
+        Lease watchers (built-in scheduler only), the node taint manager (dedicated
+        nodes only), the orphaned pod sweeper, the terminated pod janitor,
+        the startup sequencer (STARTUP_SEQUENCING only), the activation windows
+        checker, the spec.restartOn ConfigMap and Secret watcher, the node
+        maintenance manager, the status verifier, the
+        RTClusterStats publisher and the sidecar concurrency scraper
+        (active mode only) and the admin API
+        are not time critical, so they run as Tokio tasks
+        instead of real-time threads.
+        */
+        runtime.spawn(queue_stats_sampler(
+            CString::new(config.event_queue_path.clone()).unwrap(),
+            Duration::from_millis(config.queue_stats_interval_ms)
+        ));
+        if config.scheduler == SchedulerKind::Builtin {
+            runtime.spawn(node_capacity_watcher(client.clone(), SharedStatePtr(share_state_ptr as *mut SharedState)));
+            runtime.spawn(node_metrics_watcher(client.clone(), SharedStatePtr(share_state_ptr as *mut SharedState)));
+            if config.node_heartbeat_freshness_ms != 0 {
+                runtime.spawn(node_lease_watcher(client.clone(), SharedStatePtr(share_state_ptr as *mut SharedState)));
+            }
+        }
+        if dedicated_nodes_enabled(&config) {
+            runtime.spawn(node_taint_manager(client.clone(), config.clone()));
+        }
+        if config.orphan_policy != OrphanPolicy::Off {
+            runtime.spawn(orphan_sweeper(client.clone(), config.clone()));
+        }
+        if config.pod_janitor_interval_s != 0 {
+            runtime.spawn(pod_janitor(client.clone(), config.clone()));
+        }
+        if config.startup_sequencing {
+            runtime.spawn(startup_sequencer(client.clone(), SharedStatePtr(share_state_ptr as *mut SharedState)));
+        }
+        if config.mode != ControllerMode::Observe {
+            runtime.spawn(activation_windows(client.clone(), config.clone()));
+            runtime.spawn(config_watcher(client.clone(), SharedStatePtr(share_state_ptr as *mut SharedState)));
+            runtime.spawn(node_maintenance_manager(client.clone(), SharedStatePtr(share_state_ptr as *mut SharedState)));
+            if config.status_verify_interval_ms != 0 {
+                runtime.spawn(status_verifier(client.clone(), config.clone()));
+            }
+            if config.cluster_stats_interval_ms != 0 {
+                runtime.spawn(cluster_stats_publisher(client.clone(), SharedStatePtr(share_state_ptr as *mut SharedState)));
+            }
+            if config.concurrency_scrape_interval_ms != 0 {
+                runtime.spawn(concurrency_scraper(client.clone(), config.clone()));
+            }
+        }
+        if config.admin_port != 0 {
+            runtime.spawn(admin_server(client.clone(), config.admin_port, config.admin_tls_dir.clone(), SharedStatePtr(share_state_ptr as *mut SharedState)));
+        }
+
         /*
         We must now create all the threads needed
         for the controller pipeline, in order:
@@ -107,26 +427,27 @@ async fn main() -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
               for pods related to the RTResources;
             - a resource state updater that updates the status of RTResources
               accordingly to the relative pods state;
+            - a dispatcher handing the queued events to the watchdogs;
             - a server in charge of spwning new watchdogs when needed.
         Note: a watchdog is a thread that handles events from the event queue.
         */
         let mut crd_watcher_thread: pthread_t = 0;
         let mut pod_watcher_thread: pthread_t = 0;
         let mut resource_state_updater_thread: pthread_t = 0;
+        let mut dispatcher_thread: pthread_t = 0;
         let mut server_thread: pthread_t = 0;
         let mut attr: pthread_attr_t = mem::zeroed();
-        let mut param: sched_param = sched_param{sched_priority: 0};
+        let mut param: sched_param;
         let mut result: i32;
         pthread_attr_init(&mut attr);
-        pthread_attr_setschedpolicy(&mut attr, SCHED_FIFO);
-        pthread_attr_setinheritsched(&mut attr, PTHREAD_EXPLICIT_SCHED);
+        set_realtime_policy(&mut attr);
 
-        param.sched_priority = 96;
+        param = band_of(ThreadRole::Watchers).sched_param();
         pthread_attr_setschedparam(&mut attr, &param);
 
         result = pthread_create(
             &mut crd_watcher_thread,
-            &attr as *const _ as *const pthread_attr_t,
+            &attr,
             crd_watcher,
             share_state_ptr
         );
@@ -134,9 +455,15 @@ async fn main() -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
             eprintln!("An error occurred while creating the CRD Watcher thread! {}", result);
         }
 
+        /*
+        The pod watcher runs above the other watchers,
+        since it forwards the pod failures.
+        */
+        param = band_of(ThreadRole::PodWatcher).sched_param();
+        pthread_attr_setschedparam(&mut attr, &param);
         result = pthread_create(
             &mut pod_watcher_thread,
-            &attr as *const _ as *const pthread_attr_t,
+            &attr,
             pod_watcher,
             share_state_ptr
         );
@@ -144,9 +471,11 @@ async fn main() -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
             eprintln!("An error occurred while creating the Pod Event Watcher thread!");
         }
 
+        param = band_of(ThreadRole::Watchers).sched_param();
+        pthread_attr_setschedparam(&mut attr, &param);
         result = pthread_create(
             &mut resource_state_updater_thread,
-            &attr as *const _ as *const pthread_attr_t,
+            &attr,
             resource_state_updater,
             share_state_ptr
         );
@@ -154,11 +483,21 @@ async fn main() -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
             eprintln!("An error occurred while creating the Resource State Updater thread!");
         }
 
-        param.sched_priority = 95;
+        param = band_of(ThreadRole::Server).sched_param();
         pthread_attr_setschedparam(&mut attr, &param);
+        result = pthread_create(
+            &mut dispatcher_thread,
+            &attr,
+            dispatcher,
+            share_state_ptr
+        );
+        if result != 0 {
+            eprintln!("An error occurred while creating the Dispatcher thread! {}", result);
+        }
+
         result = pthread_create(
             &mut server_thread,
-            &attr as *const _ as *const pthread_attr_t,
+            &attr,
             server,
             share_state_ptr
         );
@@ -166,6 +505,21 @@ async fn main() -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
             eprintln!("An error occurred while creating the Server thread! {}", result);
         }
 
+        /*
+        The threads are registered with their role, so that
+        their priority can be changed at runtime.
+        */
+        let shared_state = &mut *(share_state_ptr as *mut SharedState);
+        pthread_mutex_lock(&mut shared_state.mutex);
+        shared_state.component_threads = vec![
+            (ThreadRole::Watchers, crd_watcher_thread),
+            (ThreadRole::PodWatcher, pod_watcher_thread),
+            (ThreadRole::Watchers, resource_state_updater_thread),
+            (ThreadRole::Server, dispatcher_thread),
+            (ThreadRole::Server, server_thread),
+        ];
+        pthread_mutex_unlock(&mut shared_state.mutex);
+
         /*
         Now we wait for the created threads to terminate.
         Note: in the current implementation these threads should
@@ -175,6 +529,7 @@ async fn main() -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
         pthread_join(crd_watcher_thread, ptr::null_mut());
         pthread_join(pod_watcher_thread, ptr::null_mut());
         pthread_join(resource_state_updater_thread, ptr::null_mut());
+        pthread_join(dispatcher_thread, ptr::null_mut());
         pthread_join(server_thread, ptr::null_mut());
 
         /*
@@ -184,6 +539,7 @@ async fn main() -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
         pthread_mutexattr_destroy(&mut mutex_attr);
         pthread_mutex_destroy(&mut mutex);
         pthread_cond_destroy(&mut cond);
+        pthread_cond_destroy(&mut dispatch_cond);
     }
     
     Ok(())
@@ -0,0 +1,77 @@
+/*
+This file contains the allocation tracker of the watchdog hot path,
+only built with the "alloc-tracker" feature. It wraps the system
+allocator and counts the allocations made by a thread while it runs
+the critical phase of a reconcile, so that the allocations left on
+the hot path (e.g. by the apiserver client) can be measured and
+chased down. The counters live in thread-locals initialized at
+compile time, so that the allocator itself never allocates.
+*/
+
+use std::{
+    alloc::{
+        GlobalAlloc,
+        Layout,
+        System
+    },
+    cell::Cell
+};
+
+
+
+thread_local! {
+    static TRACKING: Cell<bool> = const { Cell::new(false) };
+    static ALLOCATIONS: Cell<u64> = const { Cell::new(0) };
+}
+
+/*
+System allocator counting the allocations
+of the threads on the hot path
+*/
+pub struct TrackingAllocator;
+
+unsafe impl GlobalAlloc for TrackingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count_allocation();
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        count_allocation();
+        unsafe { System.alloc_zeroed(layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count_allocation();
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+fn count_allocation() {
+    let _ = TRACKING.try_with(|tracking| {
+        if tracking.get() {
+            let _ = ALLOCATIONS.try_with(|a| a.set(a.get() + 1));
+        }
+    });
+}
+
+/*
+This function starts counting the allocations of the calling thread.
+*/
+pub fn start_tracking() {
+    ALLOCATIONS.with(|a| a.set(0));
+    TRACKING.with(|t| t.set(true));
+}
+
+/*
+This function stops counting the allocations of the calling
+thread and returns the allocations made since start_tracking.
+*/
+pub fn stop_tracking() -> u64 {
+    TRACKING.with(|t| t.set(false));
+    ALLOCATIONS.with(|a| a.get())
+}
@@ -0,0 +1,260 @@
+/*
+This file contains the utilities used to integrate
+the Preempt-K8s controller with the Kubernetes API
+Priority and Fairness (APF) subsystem.
+Requests issued on the critical reconcile path are sent
+impersonating a dedicated service account, which is mapped
+by a FlowSchema to a high-priority PriorityLevelConfiguration,
+so they are not throttled behind bulk listers.
+*/
+
+use std::error::Error;
+use kube::{
+    Client,
+    Config
+};
+use k8s_openapi::{
+    api::{
+        core::v1::ServiceAccount,
+        flowcontrol::v1beta3::{
+            FlowSchema,
+            FlowSchemaSpec,
+            FlowDistinguisherMethod,
+            PolicyRulesWithSubjects,
+            ResourcePolicyRule,
+            Subject,
+            ServiceAccountSubject,
+            PriorityLevelConfiguration,
+            PriorityLevelConfigurationSpec,
+            PriorityLevelConfigurationReference,
+            LimitedPriorityLevelConfiguration,
+            LimitResponse,
+            QueuingConfiguration
+        },
+        rbac::v1::{
+            ClusterRole,
+            ClusterRoleBinding,
+            PolicyRule,
+            RoleRef,
+            Subject as RbacSubject
+        }
+    },
+    apimachinery::pkg::apis::meta::v1::ObjectMeta
+};
+
+use crate::utils::configuration::{
+    ControllerConfig,
+    get_controller_namespace
+};
+
+
+
+/*
+Name shared by the generated FlowSchema and PriorityLevelConfiguration
+*/
+const APF_OBJECT_NAME: &str = "preempt-k8s-critical";
+
+/*
+Service account used for the critical path when none is configured
+*/
+const DEFAULT_CRITICAL_SERVICE_ACCOUNT: &str = "realtime/preempt-k8s-critical";
+
+/*
+Name of the controller service account and ClusterRole
+(see resources/auth)
+*/
+const CONTROLLER_SERVICE_ACCOUNT: &str = "preempt-k8s";
+
+/*
+This function splits a "namespace/name" service account
+reference into its namespace and name.
+*/
+fn parse_service_account(reference: &str) -> Option<(String, String)> {
+    let (namespace, name) = reference.split_once('/')?;
+    if namespace.is_empty() || name.is_empty() {
+        return None;
+    }
+    Some((namespace.to_string(), name.to_string()))
+}
+
+/*
+This function builds the kube Client used by the watchdogs
+on the critical reconcile path.
+If a critical service account is configured, the client impersonates it,
+otherwise the default client is shared with the rest of the controller.
+*/
+pub async fn critical_path_client(config: &ControllerConfig, default_client: Client) -> Result<Client, Box<dyn Error + Send + Sync + 'static>> {
+    if config.critical_service_account.is_empty() {
+        return Ok(default_client);
+    }
+    let (namespace, name) = match parse_service_account(&config.critical_service_account) {
+        Some(account) => account,
+        None => {
+            eprintln!(
+                "APF - Invalid critical service account \"{}\" (expected namespace/name), using the default client!",
+                config.critical_service_account
+            );
+            return Ok(default_client);
+        }
+    };
+    let mut kube_config = Config::infer().await?;
+    kube_config.auth_info.impersonate = Some(format!("system:serviceaccount:{}:{}", namespace, name));
+    println!("APF - Critical reconciles will impersonate service account {}/{}!", namespace, name);
+
+    Ok(Client::try_from(kube_config)?)
+}
+
+/*
+This function prints the manifests needed to map the critical
+reconcile path onto a dedicated APF priority level:
+    - the critical service account;
+    - the RBAC rules allowing the controller (in the namespace given
+      by --namespace) to impersonate it and granting it the
+      controller permissions;
+    - the PriorityLevelConfiguration and FlowSchema.
+*/
+pub fn print_apf_manifests(config: &ControllerConfig) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    let reference = if config.critical_service_account.is_empty() {
+        DEFAULT_CRITICAL_SERVICE_ACCOUNT
+    } else {
+        config.critical_service_account.as_str()
+    };
+    let (namespace, name) = parse_service_account(reference)
+        .ok_or(format!("Invalid critical service account \"{}\" (expected namespace/name)", reference))?;
+
+    let service_account = ServiceAccount {
+        metadata: ObjectMeta {
+            name: Some(name.clone()),
+            namespace: Some(namespace.clone()),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let impersonate_role = ClusterRole {
+        metadata: ObjectMeta {
+            name: Some(format!("{}-impersonate", CONTROLLER_SERVICE_ACCOUNT)),
+            ..Default::default()
+        },
+        rules: Some(vec![PolicyRule {
+            api_groups: Some(vec!["".to_string()]),
+            resources: Some(vec!["serviceaccounts".to_string()]),
+            resource_names: Some(vec![name.clone()]),
+            verbs: vec!["impersonate".to_string()],
+            ..Default::default()
+        }]),
+        ..Default::default()
+    };
+
+    let impersonate_binding = ClusterRoleBinding {
+        metadata: ObjectMeta {
+            name: Some(format!("{}-impersonate", CONTROLLER_SERVICE_ACCOUNT)),
+            ..Default::default()
+        },
+        role_ref: RoleRef {
+            api_group: "rbac.authorization.k8s.io".to_string(),
+            kind: "ClusterRole".to_string(),
+            name: format!("{}-impersonate", CONTROLLER_SERVICE_ACCOUNT),
+        },
+        subjects: Some(vec![RbacSubject {
+            kind: "ServiceAccount".to_string(),
+            name: CONTROLLER_SERVICE_ACCOUNT.to_string(),
+            namespace: Some(get_controller_namespace()),
+            ..Default::default()
+        }]),
+    };
+
+    let critical_binding = ClusterRoleBinding {
+        metadata: ObjectMeta {
+            name: Some(name.clone()),
+            ..Default::default()
+        },
+        role_ref: RoleRef {
+            api_group: "rbac.authorization.k8s.io".to_string(),
+            kind: "ClusterRole".to_string(),
+            name: CONTROLLER_SERVICE_ACCOUNT.to_string(),
+        },
+        subjects: Some(vec![RbacSubject {
+            kind: "ServiceAccount".to_string(),
+            name: name.clone(),
+            namespace: Some(namespace.clone()),
+            ..Default::default()
+        }]),
+    };
+
+    let priority_level = PriorityLevelConfiguration {
+        metadata: ObjectMeta {
+            name: Some(APF_OBJECT_NAME.to_string()),
+            ..Default::default()
+        },
+        spec: Some(PriorityLevelConfigurationSpec {
+            type_: "Limited".to_string(),
+            limited: Some(LimitedPriorityLevelConfiguration {
+                nominal_concurrency_shares: Some(100),
+                lendable_percent: Some(0),
+                limit_response: Some(LimitResponse {
+                    type_: "Queue".to_string(),
+                    queuing: Some(QueuingConfiguration {
+                        queues: Some(16),
+                        hand_size: Some(4),
+                        queue_length_limit: Some(50),
+                    }),
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+
+    let flow_schema = FlowSchema {
+        metadata: ObjectMeta {
+            name: Some(APF_OBJECT_NAME.to_string()),
+            ..Default::default()
+        },
+        spec: Some(FlowSchemaSpec {
+            matching_precedence: Some(500),
+            priority_level_configuration: PriorityLevelConfigurationReference {
+                name: APF_OBJECT_NAME.to_string(),
+            },
+            distinguisher_method: Some(FlowDistinguisherMethod {
+                type_: "ByNamespace".to_string(),
+            }),
+            rules: Some(vec![PolicyRulesWithSubjects {
+                subjects: vec![Subject {
+                    kind: "ServiceAccount".to_string(),
+                    service_account: Some(ServiceAccountSubject {
+                        name: name.clone(),
+                        namespace: namespace.clone(),
+                    }),
+                    ..Default::default()
+                }],
+                resource_rules: Some(vec![ResourcePolicyRule {
+                    api_groups: vec!["".to_string(), "rtgroup.critical.com".to_string()],
+                    resources: vec![
+                        "pods".to_string(),
+                        "rtresources".to_string(),
+                        "rtresources/status".to_string(),
+                    ],
+                    namespaces: Some(vec!["*".to_string()]),
+                    cluster_scope: Some(true),
+                    verbs: vec!["*".to_string()],
+                }]),
+                ..Default::default()
+            }]),
+        }),
+        ..Default::default()
+    };
+
+    let manifests = [
+        serde_yaml::to_string(&service_account)?,
+        serde_yaml::to_string(&impersonate_role)?,
+        serde_yaml::to_string(&impersonate_binding)?,
+        serde_yaml::to_string(&critical_binding)?,
+        serde_yaml::to_string(&priority_level)?,
+        serde_yaml::to_string(&flow_schema)?,
+    ];
+    print!("{}", manifests.join("---\n"));
+
+    Ok(())
+}
@@ -5,20 +5,247 @@ the Preempt-K8s controller configuration.
 
 use std::{
     env,
-    fmt
+    fmt,
+    collections::{
+        BTreeMap,
+        BTreeSet
+    }
+};
+use libc::{
+    sysconf,
+    _SC_MQ_PRIO_MAX,
+    CPU_SETSIZE
 };
 
+use crate::components::pod_defaults::PodDefaults;
+
+
+
+/*
+Controller operating mode
+*/
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ControllerMode {
+    Active,     // The controller reconciles RTResources
+    Observe,    // The controller only records its decisions in RTDecisions
+}
+
+impl fmt::Display for ControllerMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ControllerMode::Active => write!(f, "active"),
+            ControllerMode::Observe => write!(f, "observe"),
+        }
+    }
+}
+
+/*
+Scheduler placing the managed pods
+*/
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SchedulerKind {
+    Kube,       // Pods are left to kube-scheduler
+    Builtin,    // Pods are bound by the controller scheduling policy
+}
+
+impl fmt::Display for SchedulerKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchedulerKind::Kube => write!(f, "kube"),
+            SchedulerKind::Builtin => write!(f, "builtin"),
+        }
+    }
+}
 
+/*
+Handling of the managed pods whose RTResource no longer exists
+*/
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OrphanPolicy {
+    Off,        // Orphaned pods are left alone
+    Flag,       // Orphaned pods are labelled rtresource_orphaned=true
+    Delete,     // Orphaned pods are deleted
+}
+
+impl fmt::Display for OrphanPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OrphanPolicy::Off => write!(f, "off"),
+            OrphanPolicy::Flag => write!(f, "flag"),
+            OrphanPolicy::Delete => write!(f, "delete"),
+        }
+    }
+}
+
+/*
+Protection of the managed pods against direct changes
+*/
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PodProtection {
+    Off,        // Direct changes are allowed
+    Audit,      // Direct changes are reported by the policy
+    Enforce,    // Direct changes are rejected by the policy
+}
+
+impl fmt::Display for PodProtection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PodProtection::Off => write!(f, "off"),
+            PodProtection::Audit => write!(f, "audit"),
+            PodProtection::Enforce => write!(f, "enforce"),
+        }
+    }
+}
+
+/*
+Namespaces an RTResource may deploy its pods to (spec.namespace)
+*/
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NamespacePolicy {
+    Any,        // Any namespace
+    Same,       // The namespace of the RTResource only
+    Allowlist,  // The namespace of the RTResource and those of its NAMESPACE_ALLOWLIST entry
+}
+
+impl fmt::Display for NamespacePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NamespacePolicy::Any => write!(f, "any"),
+            NamespacePolicy::Same => write!(f, "same"),
+            NamespacePolicy::Allowlist => write!(f, "allowlist"),
+        }
+    }
+}
+
+/*
+Order in which the dispatcher hands the ready events to the watchdogs
+*/
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DispatchMode {
+    Strict,         // Most critical event first
+    WeightedFair,   // Proportional share between the criticality bands (WFQ_WEIGHTS)
+}
+
+impl fmt::Display for DispatchMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DispatchMode::Strict => write!(f, "strict"),
+            DispatchMode::WeightedFair => write!(f, "wfq"),
+        }
+    }
+}
+
+/*
+Pod event forwarded by the pod watcher to the event queue
+*/
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PodWatcherEvent {
+    Deleted,        // A managed pod was deleted
+    Changed,        // The availability of a managed pod changed
+    Stripped,       // The owner labels of a managed pod were stripped
+    CapacityFreed,  // A pod left a node, re-enqueuing the preempted RTResources
+}
+
+impl fmt::Display for PodWatcherEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PodWatcherEvent::Deleted => write!(f, "deleted"),
+            PodWatcherEvent::Changed => write!(f, "changed"),
+            PodWatcherEvent::Stripped => write!(f, "stripped"),
+            PodWatcherEvent::CapacityFreed => write!(f, "freed"),
+        }
+    }
+}
 
 /*
 Controller configuration parameters
 */
 #[derive(Clone)]
 pub struct ControllerConfig {
+    pub mode: ControllerMode,           // Operating mode (active or observe)
     pub min_watchdogs: usize,           // Minimum number of watchdog threads
     pub max_watchdogs: usize,           // Maximum number of watchdog threads
-    pub threshold: usize,               // Threshold triggering watchdog threads scaling
+    pub threshold: usize,               // Free watchdogs below which the pool scales up
+    pub scale_down_threshold: usize,    // Free watchdogs above which the pool scales down
+    pub scale_cooldown_ms: u64,         // Minimum time between a pool scaling and the next scale-down
+    pub recovery_deadline_ms: u64,      // Time from the reception of an event within which its replicas should be Ready
+    pub status_verify_interval_ms: u64, // Interval between two status verification cycles (0 = disabled)
+    pub status_verify_sample: usize,    // RTResources whose status is verified each cycle
+    pub maintenance_on_cordon: bool,    // Whether cordoned nodes are also put under maintenance
+    pub cluster_stats_interval_ms: u64, // Interval between two RTClusterStats publications (0 = disabled)
+    pub startup_sequencing: bool,       // Whether the reconciles are admitted by criticality at startup
+    pub startup_sequencing_timeout_ms: u64, // Longest time the startup sequencing holds the less critical events
+    pub pod_protection: PodProtection,  // Protection of the managed pods against direct changes
+    pub pod_protection_exempt: Vec<String>, // Service accounts ("namespace/name") allowed to change the managed pods
+    pub crash_dump_dir: String,         // Directory of the crash dumps (empty = disabled)
+    pub headroom_cpu: String,           // CPU request of the standard pod shape of the headroom query
+    pub headroom_memory: String,        // Memory request of the standard pod shape of the headroom query
+    pub pod_priority_hints: bool,       // Whether the created pods get the PriorityClass of their criticality
+    pub namespace_policy: NamespacePolicy, // Namespaces an RTResource may deploy its pods to
+    pub namespace_allowlist: BTreeMap<String, Vec<String>>, // Further target namespaces allowed per RTResource namespace
+    pub event_compaction_quiet_ms: u64, // Quiet period before a replica-count-only change is enqueued (0 = disabled)
+    pub priority_decay_budget_ms: u64,  // Time a reconcile runs at the priority of its event before decaying (0 = disabled)
+    pub priority_decay_interval_ms: u64, // Interval between two steps of the priority decay
+    pub crash_loop_window_ms: u64,      // Window within which the pod crashes count towards spec.failureThreshold
+    pub node_heartbeat_freshness_ms: u64,   // Window within which a node Lease must be renewed to receive top-band pods
+    pub flight_recorder_file: String,   // Ring buffer file mirroring the event queue (empty = disabled)
+    pub flight_recorder_events: usize,  // Events kept by the flight recorder
+    pub concurrency_scrape_interval_ms: u64, // Interval between two scrapes of the sidecar concurrency metrics (0 = disabled)
+    pub concurrency_sidecar: String,     // Name of the sidecar container exposing the concurrency metrics
+    pub concurrency_metrics_port: u16,   // Port of the sidecar metrics endpoint
+    pub concurrency_metrics_path: String, // Path of the sidecar metrics endpoint
+    pub concurrency_metric: String,      // Prometheus metric holding the concurrency of a pod
+    pub pod_janitor_interval_s: u64,     // Interval between two deletions of the terminated pods (0 = disabled)
+    pub pod_janitor_keep_failed: usize,  // Failed pods of an RTResource kept for debugging
+    pub critical_runtime_workers: usize, // Worker threads of the critical-path Tokio runtime
+    pub critical_runtime_cpuset: Vec<usize>, // Cores the critical-path runtime workers are pinned to (empty = no pinning)
+    pub housekeeping_runtime_workers: usize, // Worker threads of the housekeeping Tokio runtime (0 = one per core)
+    pub housekeeping_runtime_cpuset: Vec<usize>, // Cores the housekeeping runtime workers are pinned to (empty = no pinning)
+    pub dispatch_mode: DispatchMode,    // Order in which the ready events are handed to the watchdogs
+    pub wfq_bands: Vec<(u32, u32)>,     // Criticality bands (highest criticality, weight) of the weighted fair dispatch
+    pub pod_watcher_name_label: String, // Pod label holding the name of the owning object
+    pub pod_watcher_uid_label: String,  // Pod label holding the UID of the owning object
+    pub pod_watcher_namespace_label: String, // Pod label holding the namespace of the owning object
+    pub pod_watcher_criticality_label: String, // Pod label holding the criticality of the owning object
+    pub pod_watcher_events: Vec<PodWatcherEvent>, // Pod events forwarded by the pod watcher to the event queue
     pub event_queue_path: String,       // Path to the event priority queue
+    pub critical_service_account: String, // Service account impersonated on the critical path ("namespace/name")
+    pub watchdog_cpuset: Vec<usize>,    // Housekeeping cores watchdog threads are pinned to (empty = no pinning)
+    pub alert_webhook_url: String,      // Alert webhook called on stalled reconciles (empty = disabled)
+    pub alert_failure_threshold: u32,   // Consecutive reconcile failures raising an alert
+    pub backoff_threshold: u32,         // Consecutive reconcile failures opening the circuit (0 = disabled)
+    pub backoff_max_seconds: u64,       // Highest delay between retries of an open circuit
+    pub scheduler: SchedulerKind,       // Scheduler placing the managed pods
+    pub admin_port: u16,                // Port of the admin API (0 = disabled)
+    pub admin_tls_dir: String,          // Directory of the admin API mTLS certificates (empty = loopback only)
+    pub queue_stats_interval_ms: u64,   // Sampling interval of the event queue statistics
+    pub criticality_max: u32,           // Highest criticality value of the RTResource spec scale
+    pub criticality_inverted: bool,     // Whether higher spec criticality values are more critical
+    pub critical_band_max: u32,         // Highest criticality value of the top criticality band
+    pub reserved_watchdogs: usize,      // Watchdog slots reserved to the top criticality band
+    pub steal_limit: usize,             // General watchdogs that may help drain the top band backlog
+    pub scoring_candidates: usize,      // Nodes scored per placement by the built-in scheduler (0 = all)
+    pub scheduler_webhook_url: String,  // External placement service of the built-in scheduler (empty = disabled)
+    pub scheduler_webhook_timeout_ms: u64, // Timeout of the external placement service
+    pub dedicated_nodes: Vec<String>,   // Nodes dedicated to the top criticality band
+    pub dedicated_node_label: String,   // Label (key=value) designating the dedicated nodes (empty = none)
+    pub pod_security_hardening: bool,   // Whether the security context of the created pods is hardened
+    pub pod_defaults: Vec<PodDefaults>, // Default tolerations, nodeSelector and runtimeClass per criticality range
+    pub archive_store: String,          // Store of the records of deleted RTResources (empty = disabled)
+    pub orphan_policy: OrphanPolicy,    // Handling of the managed pods whose RTResource no longer exists
+    pub orphan_sweep_interval_s: u64,   // Interval between two orphaned pod sweeps
+    pub provisioning_class: String,     // Class of the ProvisioningRequests for unplaced top band replicas (empty = disabled)
+    pub allow_non_rt: bool,             // Whether the controller runs degraded without real-time scheduling
+    pub namespace_criticality_caps: BTreeMap<String, u32>, // Most critical spec criticality allowed per namespace
+    pub teardown_batch_size: usize,     // Pods deleted in parallel per batch by a watchdog
+    pub lock_wait_warn_us: u64,         // Shared mutex wait reported as contention (microseconds)
+    pub preemption_history_size: usize, // Preemptions kept in the status of each RTResource (0 = disabled)
+    pub critical_api_qps: f64,          // Requests per second of the critical path client (0 = unlimited)
+    pub critical_api_burst: u32,        // Request burst of the critical path client
+    pub critical_api_timeout_ms: u64,   // Request timeout of the critical path client (0 = none)
+    pub bulk_api_qps: f64,              // Requests per second of the bulk client (0 = unlimited)
+    pub bulk_api_burst: u32,            // Request burst of the bulk client
+    pub bulk_api_timeout_ms: u64,       // Request timeout of the bulk client, watches excluded (0 = none)
 }
 
 /*
@@ -28,10 +255,114 @@ ControllerConfig struct to allow easy printing of its values.
 impl fmt::Display for ControllerConfig {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "Controller configuration:")?;
+        writeln!(f, "    Mode: {}", self.mode)?;
         writeln!(f, "    Min watchdogs: {}", self.min_watchdogs)?;
         writeln!(f, "    Max watchdogs: {}", self.max_watchdogs)?;
         writeln!(f, "    Threshold: {}", self.threshold)?;
-        writeln!(f, "    Event Queue Path: {}", self.event_queue_path)
+        writeln!(f, "    Scale Down Threshold: {}", self.scale_down_threshold)?;
+        writeln!(f, "    Scale Cooldown (ms): {}", self.scale_cooldown_ms)?;
+        writeln!(f, "    Recovery Deadline (ms): {}", self.recovery_deadline_ms)?;
+        writeln!(f, "    Status Verify Interval (ms): {}", self.status_verify_interval_ms)?;
+        writeln!(f, "    Status Verify Sample: {}", self.status_verify_sample)?;
+        writeln!(f, "    Maintenance On Cordon: {}", self.maintenance_on_cordon)?;
+        writeln!(f, "    Cluster Stats Interval (ms): {}", self.cluster_stats_interval_ms)?;
+        writeln!(f, "    Startup Sequencing: {}", self.startup_sequencing)?;
+        writeln!(f, "    Startup Sequencing Timeout (ms): {}", self.startup_sequencing_timeout_ms)?;
+        writeln!(f, "    Pod Protection: {}", self.pod_protection)?;
+        writeln!(f, "    Pod Protection Exempt: {}", self.pod_protection_exempt.join(","))?;
+        writeln!(f, "    Crash Dump Dir: {}", self.crash_dump_dir)?;
+        writeln!(f, "    Headroom Shape: cpu={} memory={}", self.headroom_cpu, self.headroom_memory)?;
+        writeln!(f, "    Pod Priority Hints: {}", self.pod_priority_hints)?;
+        writeln!(f, "    Namespace Policy: {}", self.namespace_policy)?;
+        let allowlist: Vec<String> = self.namespace_allowlist.iter()
+            .map(|(namespace, targets)| format!("{}={}", namespace, targets.join("|")))
+            .collect();
+        writeln!(f, "    Namespace Allowlist: {}", allowlist.join(","))?;
+        writeln!(f, "    Event Compaction Quiet Period (ms): {}", self.event_compaction_quiet_ms)?;
+        writeln!(f, "    Priority Decay Budget (ms): {}", self.priority_decay_budget_ms)?;
+        writeln!(f, "    Priority Decay Interval (ms): {}", self.priority_decay_interval_ms)?;
+        writeln!(f, "    Crash Loop Window (ms): {}", self.crash_loop_window_ms)?;
+        writeln!(f, "    Node Heartbeat Freshness (ms): {}", self.node_heartbeat_freshness_ms)?;
+        writeln!(f, "    Flight Recorder File: {}", self.flight_recorder_file)?;
+        writeln!(f, "    Flight Recorder Events: {}", self.flight_recorder_events)?;
+        writeln!(f, "    Concurrency Scrape Interval (ms): {}", self.concurrency_scrape_interval_ms)?;
+        writeln!(f, "    Concurrency Sidecar: {}", self.concurrency_sidecar)?;
+        writeln!(f, "    Concurrency Metrics Port: {}", self.concurrency_metrics_port)?;
+        writeln!(f, "    Concurrency Metrics Path: {}", self.concurrency_metrics_path)?;
+        writeln!(f, "    Concurrency Metric: {}", self.concurrency_metric)?;
+        writeln!(f, "    Pod Janitor Interval (s): {}", self.pod_janitor_interval_s)?;
+        writeln!(f, "    Pod Janitor Keep Failed: {}", self.pod_janitor_keep_failed)?;
+        writeln!(f, "    Critical Runtime Workers: {}", self.critical_runtime_workers)?;
+        writeln!(f, "    Critical Runtime CPU Set: {:?}", self.critical_runtime_cpuset)?;
+        writeln!(f, "    Housekeeping Runtime Workers: {}", self.housekeeping_runtime_workers)?;
+        writeln!(f, "    Housekeeping Runtime CPU Set: {:?}", self.housekeeping_runtime_cpuset)?;
+        writeln!(f, "    Dispatch Mode: {}", self.dispatch_mode)?;
+        let bands: Vec<String> = self.wfq_bands.iter()
+            .map(|(max, weight)| format!("{}={}", max, weight))
+            .collect();
+        writeln!(f, "    WFQ Weights: {}", bands.join(","))?;
+        writeln!(
+            f,
+            "    Pod Watcher Labels: name={} uid={} namespace={} criticality={}",
+            self.pod_watcher_name_label,
+            self.pod_watcher_uid_label,
+            self.pod_watcher_namespace_label,
+            self.pod_watcher_criticality_label
+        )?;
+        let events: Vec<String> = self.pod_watcher_events.iter().map(|e| e.to_string()).collect();
+        writeln!(f, "    Pod Watcher Events: {}", events.join(","))?;
+        writeln!(f, "    Event Queue Path: {}", self.event_queue_path)?;
+        writeln!(f, "    Critical Service Account: {}", self.critical_service_account)?;
+        writeln!(f, "    Watchdog CPU Set: {:?}", self.watchdog_cpuset)?;
+        writeln!(f, "    Alert Webhook URL: {}", self.alert_webhook_url)?;
+        writeln!(f, "    Alert Failure Threshold: {}", self.alert_failure_threshold)?;
+        writeln!(f, "    Backoff Threshold: {}", self.backoff_threshold)?;
+        writeln!(f, "    Backoff Max Seconds: {}", self.backoff_max_seconds)?;
+        writeln!(f, "    Scheduler: {}", self.scheduler)?;
+        writeln!(f, "    Admin Port: {}", self.admin_port)?;
+        writeln!(f, "    Admin TLS Dir: {}", self.admin_tls_dir)?;
+        writeln!(f, "    Queue Stats Interval (ms): {}", self.queue_stats_interval_ms)?;
+        writeln!(f, "    Criticality Max: {}", self.criticality_max)?;
+        writeln!(f, "    Criticality Inverted: {}", self.criticality_inverted)?;
+        writeln!(f, "    Critical Band Max: {}", self.critical_band_max)?;
+        writeln!(f, "    Reserved Watchdogs: {}", self.reserved_watchdogs)?;
+        writeln!(f, "    Steal Limit: {}", self.steal_limit)?;
+        writeln!(f, "    Scoring Candidates: {}", self.scoring_candidates)?;
+        writeln!(f, "    Scheduler Webhook URL: {}", self.scheduler_webhook_url)?;
+        writeln!(f, "    Scheduler Webhook Timeout (ms): {}", self.scheduler_webhook_timeout_ms)?;
+        writeln!(f, "    Dedicated Nodes: {}", self.dedicated_nodes.join(","))?;
+        writeln!(f, "    Dedicated Node Label: {}", self.dedicated_node_label)?;
+        writeln!(f, "    Pod Security Hardening: {}", self.pod_security_hardening)?;
+        writeln!(f, "    Pod Defaults: {}", serde_json::to_string(&self.pod_defaults).unwrap_or_default())?;
+        writeln!(f, "    Archive Store: {}", self.archive_store)?;
+        writeln!(f, "    Orphan Policy: {}", self.orphan_policy)?;
+        writeln!(f, "    Orphan Sweep Interval (s): {}", self.orphan_sweep_interval_s)?;
+        writeln!(f, "    Provisioning Class: {}", self.provisioning_class)?;
+        writeln!(f, "    Allow Non-RT: {}", self.allow_non_rt)?;
+        let caps: Vec<String> = self.namespace_criticality_caps.iter()
+            .map(|(namespace, cap)| format!("{}={}", namespace, cap))
+            .collect();
+        writeln!(f, "    Namespace Criticality Caps: {}", caps.join(","))?;
+        writeln!(f, "    Teardown Batch Size: {}", self.teardown_batch_size)?;
+        writeln!(f, "    Lock Wait Warn (us): {}", self.lock_wait_warn_us)?;
+        writeln!(f, "    Preemption History Size: {}", self.preemption_history_size)?;
+        writeln!(f, "    Critical API Limits: {} QPS, burst {}, timeout {} ms", self.critical_api_qps, self.critical_api_burst, self.critical_api_timeout_ms)?;
+        writeln!(f, "    Bulk API Limits: {} QPS, burst {}, timeout {} ms", self.bulk_api_qps, self.bulk_api_burst, self.bulk_api_timeout_ms)
+    }
+}
+
+/*
+This function retrieves the controller operating mode
+from the environment variable "MODE".
+*/
+fn get_controller_mode() -> ControllerMode {
+    match env::var("MODE").unwrap_or_default().to_lowercase().as_str() {
+        "observe" => ControllerMode::Observe,
+        "" | "active" => ControllerMode::Active, // active is the Default Value
+        other => {
+            eprintln!("Configuration - Unknown MODE \"{}\", falling back to active!", other);
+            ControllerMode::Active
+        }
     }
 }
 
@@ -68,6 +399,552 @@ fn get_threshold_number() -> usize {
         .unwrap_or(3) // 3 is the Default Value
 }
 
+/*
+This function retrieves the scale-down threshold value
+from the environment variable "SCALE_DOWN_THRESHOLD".
+It cannot be lower than the (scale-up) threshold, otherwise
+the pool would oscillate between the two.
+*/
+fn get_scale_down_threshold() -> usize {
+    let threshold = get_threshold_number();
+    env::var("SCALE_DOWN_THRESHOLD")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|v| *v >= threshold)
+        .unwrap_or(threshold * 2) // Twice the threshold is the Default Value
+}
+
+/*
+This function retrieves the cooldown of the pool scaling (in milliseconds)
+from the environment variable "SCALE_COOLDOWN_MS".
+*/
+fn get_scale_cooldown_ms() -> u64 {
+    env::var("SCALE_COOLDOWN_MS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(5000) // 5000 is the Default Value
+}
+
+/*
+This function retrieves the recovery deadline (in milliseconds),
+bounding the readiness wait of spec.waitForReady,
+from the environment variable "RECOVERY_DEADLINE_MS".
+*/
+fn get_recovery_deadline_ms() -> u64 {
+    env::var("RECOVERY_DEADLINE_MS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(30000) // 30000 is the Default Value
+}
+
+/*
+This function retrieves whether the nodes cordoned by the
+administrators are also put under maintenance (besides the
+nodes labelled rtgroup.critical.com/maintenance)
+from the environment variable "MAINTENANCE_ON_CORDON".
+*/
+fn get_maintenance_on_cordon() -> bool {
+    env::var("MAINTENANCE_ON_CORDON")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(true) // true is the Default Value
+}
+
+/*
+This function retrieves whether the security context of the pods
+created by the controller is hardened (non-root, seccomp profile,
+capabilities limited to spec.rt.capabilities)
+from the environment variable "POD_SECURITY_HARDENING".
+*/
+fn get_pod_security_hardening() -> bool {
+    env::var("POD_SECURITY_HARDENING")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(false) // false is the Default Value
+}
+
+/*
+This function retrieves the interval between two verifications
+of the RTResource statuses (in milliseconds, 0 disables them)
+from the environment variable "STATUS_VERIFY_INTERVAL_MS".
+*/
+fn get_status_verify_interval_ms() -> u64 {
+    env::var("STATUS_VERIFY_INTERVAL_MS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(30000) // 30000 is the Default Value
+}
+
+/*
+This function retrieves the number of RTResources
+whose status is verified each cycle
+from the environment variable "STATUS_VERIFY_SAMPLE".
+*/
+fn get_status_verify_sample() -> usize {
+    env::var("STATUS_VERIFY_SAMPLE")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|&n: &usize| n > 0)
+        .unwrap_or(10) // 10 is the Default Value
+}
+
+/*
+This function retrieves the interval between two publications
+of the RTClusterStats (in milliseconds, 0 disables them)
+from the environment variable "CLUSTER_STATS_INTERVAL_MS".
+*/
+fn get_cluster_stats_interval_ms() -> u64 {
+    env::var("CLUSTER_STATS_INTERVAL_MS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(30000) // 30000 is the Default Value
+}
+
+/*
+This function retrieves whether the reconciles are sequenced
+by criticality at startup from the environment variable "STARTUP_SEQUENCING".
+*/
+fn get_startup_sequencing() -> bool {
+    env::var("STARTUP_SEQUENCING")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(false) // false is the Default Value
+}
+
+/*
+This function retrieves the longest time the startup sequencing
+holds the less critical events (in milliseconds)
+from the environment variable "STARTUP_SEQUENCING_TIMEOUT_MS".
+*/
+fn get_startup_sequencing_timeout_ms() -> u64 {
+    env::var("STARTUP_SEQUENCING_TIMEOUT_MS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(300000) // 300000 is the Default Value
+}
+
+/*
+This function retrieves the protection of the managed pods
+against direct changes from the environment variable "POD_PROTECTION".
+*/
+fn get_pod_protection() -> PodProtection {
+    match env::var("POD_PROTECTION").unwrap_or_default().to_lowercase().as_str() {
+        "" | "off" => PodProtection::Off, // off is the Default Value
+        "audit" => PodProtection::Audit,
+        "enforce" => PodProtection::Enforce,
+        other => {
+            eprintln!("Configuration - Unknown POD_PROTECTION \"{}\", falling back to audit!", other);
+            PodProtection::Audit
+        }
+    }
+}
+
+/*
+This function retrieves the service accounts allowed to change
+the managed pods from the environment variable "POD_PROTECTION_EXEMPT"
+(comma-separated, format "namespace/name").
+*/
+fn get_pod_protection_exempt() -> Vec<String> {
+    env::var("POD_PROTECTION_EXEMPT")
+        .unwrap_or_else(|_| "realtime/preempt-k8s".to_string()) // realtime/preempt-k8s is the Default Value
+        .split(',')
+        .map(|a| a.trim().to_string())
+        .filter(|a| !a.is_empty())
+        .collect()
+}
+
+/*
+This function retrieves the directory the crash dumps are written to
+from the environment variable "CRASH_DUMP_DIR" (empty disables them).
+*/
+fn get_crash_dump_dir() -> String {
+    env::var("CRASH_DUMP_DIR")
+        .unwrap_or_else(|_| "/tmp".to_string()) // /tmp is the Default Value
+}
+
+/*
+This function retrieves the CPU request of the standard pod shape
+of the headroom query from the environment variable "HEADROOM_CPU".
+*/
+fn get_headroom_cpu() -> String {
+    env::var("HEADROOM_CPU")
+        .unwrap_or_else(|_| "500m".to_string()) // 500m is the Default Value
+}
+
+/*
+This function retrieves the memory request of the standard pod shape
+of the headroom query from the environment variable "HEADROOM_MEMORY".
+*/
+fn get_headroom_memory() -> String {
+    env::var("HEADROOM_MEMORY")
+        .unwrap_or_else(|_| "512Mi".to_string()) // 512Mi is the Default Value
+}
+
+/*
+This function retrieves whether the created pods get the PriorityClass
+of their criticality (the PriorityClasses of --print-policy must be
+installed) from the environment variable "POD_PRIORITY_HINTS".
+*/
+fn get_pod_priority_hints() -> bool {
+    env::var("POD_PRIORITY_HINTS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(true) // true is the Default Value
+}
+
+/*
+This function retrieves the namespaces an RTResource may deploy its
+pods to from the environment variable "NAMESPACE_POLICY"
+(any, same or allowlist).
+*/
+fn get_namespace_policy() -> NamespacePolicy {
+    match env::var("NAMESPACE_POLICY").unwrap_or_default().to_lowercase().as_str() {
+        "" | "any" => NamespacePolicy::Any, // any is the Default Value
+        "same" => NamespacePolicy::Same,
+        "allowlist" => NamespacePolicy::Allowlist,
+        other => {
+            eprintln!("Configuration - Unknown NAMESPACE_POLICY \"{}\", falling back to same!", other);
+            NamespacePolicy::Same
+        }
+    }
+}
+
+/*
+This function retrieves the further target namespaces the RTResources
+of each namespace may use (NAMESPACE_POLICY=allowlist) from the
+environment variable "NAMESPACE_ALLOWLIST" (a comma-separated list of
+namespace=target|target).
+Namespaces not listed may only deploy to themselves.
+*/
+fn get_namespace_allowlist() -> BTreeMap<String, Vec<String>> {
+    let mut allowlist = BTreeMap::new();
+    for entry in env::var("NAMESPACE_ALLOWLIST").unwrap_or_default().split(',') { // empty is the Default Value
+        let entry = entry.trim();
+        if entry.is_empty() {
+            continue;
+        }
+        match entry.split_once('=').map(|(ns, targets)| (ns.trim(), targets)) {
+            Some((namespace, targets)) if !namespace.is_empty() => {
+                let targets: Vec<String> = targets.split('|')
+                    .map(|t| t.trim().to_string())
+                    .filter(|t| !t.is_empty())
+                    .collect();
+                allowlist.insert(namespace.to_string(), targets);
+            }
+            _ => eprintln!("Configuration - Invalid NAMESPACE_ALLOWLIST entry \"{}\"!", entry),
+        }
+    }
+    allowlist
+}
+
+/*
+This function retrieves the quiet period after which the CRD watcher
+enqueues a replica-count-only change of a non top band RTResource
+from the environment variable "EVENT_COMPACTION_QUIET_MS".
+*/
+fn get_event_compaction_quiet_ms() -> u64 {
+    env::var("EVENT_COMPACTION_QUIET_MS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(500) // 500 is the Default Value
+}
+
+/*
+This function retrieves the time a reconcile runs at the priority
+of its event before the priority decays from the environment
+variable "PRIORITY_DECAY_BUDGET_MS".
+*/
+fn get_priority_decay_budget_ms() -> u64 {
+    env::var("PRIORITY_DECAY_BUDGET_MS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(5000) // 5000 is the Default Value
+}
+
+/*
+This function retrieves the interval between two steps of the
+priority decay from the environment variable "PRIORITY_DECAY_INTERVAL_MS".
+*/
+fn get_priority_decay_interval_ms() -> u64 {
+    env::var("PRIORITY_DECAY_INTERVAL_MS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(1000) // 1000 is the Default Value
+}
+
+/*
+This function retrieves the window within which the crashes of the
+pods of an RTResource count towards its spec.failureThreshold
+from the environment variable "CRASH_LOOP_WINDOW_MS".
+*/
+fn get_crash_loop_window_ms() -> u64 {
+    env::var("CRASH_LOOP_WINDOW_MS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(600000) // 600000 is the Default Value
+}
+
+/*
+This function retrieves the window within which the Lease of a node
+must have been renewed for the node to receive top-band pods
+from the environment variable "NODE_HEARTBEAT_FRESHNESS_MS" (0 disables the check).
+*/
+fn get_node_heartbeat_freshness_ms() -> u64 {
+    env::var("NODE_HEARTBEAT_FRESHNESS_MS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(20000) // 20000 is the Default Value
+}
+
+/*
+This function retrieves the file of the event flight recorder
+from the environment variable "FLIGHT_RECORDER_FILE" (empty disables it).
+*/
+fn get_flight_recorder_file() -> String {
+    env::var("FLIGHT_RECORDER_FILE")
+        .unwrap_or_else(|_| "/tmp/preempt-k8s-flight-recorder".to_string()) // /tmp/preempt-k8s-flight-recorder is the Default Value
+}
+
+/*
+This function retrieves the number of events kept by the flight recorder
+from the environment variable "FLIGHT_RECORDER_EVENTS".
+*/
+fn get_flight_recorder_events() -> usize {
+    env::var("FLIGHT_RECORDER_EVENTS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(8192) // 8192 is the Default Value
+}
+
+/*
+This function retrieves the interval between two scrapes of the
+concurrency metrics of the pod sidecars (in milliseconds, 0 disables them)
+from the environment variable "CONCURRENCY_SCRAPE_INTERVAL_MS".
+*/
+fn get_concurrency_scrape_interval_ms() -> u64 {
+    env::var("CONCURRENCY_SCRAPE_INTERVAL_MS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0) // 0 is the Default Value
+}
+
+/*
+This function retrieves the name of the sidecar container
+exposing the concurrency metrics
+from the environment variable "CONCURRENCY_SIDECAR".
+*/
+fn get_concurrency_sidecar() -> String {
+    env::var("CONCURRENCY_SIDECAR")
+        .unwrap_or_else(|_| "queue-proxy".to_string()) // queue-proxy is the Default Value
+}
+
+/*
+This function retrieves the port of the sidecar metrics endpoint
+from the environment variable "CONCURRENCY_METRICS_PORT".
+*/
+fn get_concurrency_metrics_port() -> u16 {
+    env::var("CONCURRENCY_METRICS_PORT")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(9090) // 9090 is the Default Value
+}
+
+/*
+This function retrieves the path of the sidecar metrics endpoint
+from the environment variable "CONCURRENCY_METRICS_PATH".
+*/
+fn get_concurrency_metrics_path() -> String {
+    env::var("CONCURRENCY_METRICS_PATH")
+        .unwrap_or_else(|_| "/metrics".to_string()) // /metrics is the Default Value
+}
+
+/*
+This function retrieves the name of the Prometheus metric
+holding the concurrency of a pod
+from the environment variable "CONCURRENCY_METRIC".
+*/
+fn get_concurrency_metric() -> String {
+    env::var("CONCURRENCY_METRIC")
+        .unwrap_or_else(|_| "queue_average_concurrent_requests".to_string()) // queue_average_concurrent_requests is the Default Value
+}
+
+/*
+This function retrieves the interval between two deletions of the
+terminated (Failed or Succeeded) managed pods (in seconds, 0 disables them)
+from the environment variable "POD_JANITOR_INTERVAL_S".
+*/
+fn get_pod_janitor_interval_s() -> u64 {
+    env::var("POD_JANITOR_INTERVAL_S")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(300) // 300 is the Default Value
+}
+
+/*
+This function retrieves the number of Failed pods of an RTResource
+kept for debugging by the pod janitor
+from the environment variable "POD_JANITOR_KEEP_FAILED".
+*/
+fn get_pod_janitor_keep_failed() -> usize {
+    env::var("POD_JANITOR_KEEP_FAILED")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(3) // 3 is the Default Value
+}
+
+/*
+This function retrieves the number of worker threads of the
+Tokio runtime running the critical-path apiserver requests
+from the environment variable "CRITICAL_RUNTIME_WORKERS".
+*/
+fn get_critical_runtime_workers() -> usize {
+    env::var("CRITICAL_RUNTIME_WORKERS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(2) // 2 is the Default Value
+}
+
+/*
+This function retrieves the set of cores the critical-path runtime
+workers are pinned to from the environment variable "CRITICAL_RUNTIME_CPUSET".
+*/
+fn get_critical_runtime_cpuset() -> Vec<usize> {
+    parse_cpuset("CRITICAL_RUNTIME_CPUSET")
+}
+
+/*
+This function retrieves the number of worker threads of the
+housekeeping Tokio runtime (0 uses one per core)
+from the environment variable "HOUSEKEEPING_RUNTIME_WORKERS".
+*/
+fn get_housekeeping_runtime_workers() -> usize {
+    env::var("HOUSEKEEPING_RUNTIME_WORKERS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0) // 0 is the Default Value
+}
+
+/*
+This function retrieves the set of cores the housekeeping runtime
+workers are pinned to from the environment variable "HOUSEKEEPING_RUNTIME_CPUSET".
+*/
+fn get_housekeeping_runtime_cpuset() -> Vec<usize> {
+    parse_cpuset("HOUSEKEEPING_RUNTIME_CPUSET")
+}
+
+/*
+This function retrieves the order in which the dispatcher hands
+the ready events to the watchdogs from the environment variable
+"DISPATCH_MODE".
+*/
+fn get_dispatch_mode() -> DispatchMode {
+    match env::var("DISPATCH_MODE").unwrap_or_default().to_lowercase().as_str() {
+        "wfq" => DispatchMode::WeightedFair,
+        "" | "strict" => DispatchMode::Strict, // strict is the Default Value
+        other => {
+            eprintln!("Configuration - Unknown DISPATCH_MODE \"{}\", falling back to strict!", other);
+            DispatchMode::Strict
+        }
+    }
+}
+
+/*
+This function retrieves the criticality bands of the weighted fair
+dispatch from the environment variable "WFQ_WEIGHTS", a list of
+"<highest criticality>=<weight>" entries (e.g. "10=8,40=3,80=1"):
+each band holds the criticalities above the previous one, the last
+band also holding the less critical ones.
+By default the top criticality band weighs 4 and the others 1.
+*/
+fn get_wfq_bands() -> Vec<(u32, u32)> {
+    let mut bands = Vec::new();
+    for entry in env::var("WFQ_WEIGHTS").unwrap_or_default().split(',') {
+        let entry = entry.trim();
+        if entry.is_empty() {
+            continue;
+        }
+        match entry.split_once('=').map(|(max, weight)| (max.trim().parse::<u32>(), weight.trim().parse::<u32>())) {
+            Some((Ok(max), Ok(weight))) if weight > 0 => bands.push((max, weight)),
+            _ => eprintln!("Configuration - Invalid WFQ_WEIGHTS entry \"{}\"!", entry),
+        }
+    }
+    if bands.is_empty() {
+        bands = vec![(get_critical_band_max(), 4), (u32::MAX, 1)]; // 4 for the top band, 1 for the others is the Default Value
+    }
+    bands.sort();
+    bands.dedup_by_key(|(max, _)| *max);
+    bands
+}
+
+/*
+This function retrieves a pod label key read by the pod watcher
+from the given environment variable, or the given default
+(the label the controller stamps on the pods it creates).
+*/
+fn get_pod_watcher_label(var: &str, default: &str) -> String {
+    env::var(var)
+        .ok()
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+        .unwrap_or_else(|| default.to_string())
+}
+
+/*
+This function retrieves the pod events the pod watcher forwards
+to the event queue from the environment variable "POD_WATCHER_EVENTS",
+a comma-separated list of "deleted", "changed", "stripped" and "freed"
+(all of them when none is valid).
+*/
+fn get_pod_watcher_events() -> Vec<PodWatcherEvent> {
+    let all = vec![
+        PodWatcherEvent::Deleted,
+        PodWatcherEvent::Changed,
+        PodWatcherEvent::Stripped,
+        PodWatcherEvent::CapacityFreed,
+    ]; // all the events is the Default Value
+    let value = env::var("POD_WATCHER_EVENTS").unwrap_or_default();
+    if value.trim().is_empty() {
+        return all;
+    }
+    let mut events = Vec::new();
+    for entry in value.split(',').map(|e| e.trim().to_lowercase()).filter(|e| !e.is_empty()) {
+        let event = match entry.as_str() {
+            "deleted" => PodWatcherEvent::Deleted,
+            "changed" => PodWatcherEvent::Changed,
+            "stripped" => PodWatcherEvent::Stripped,
+            "freed" => PodWatcherEvent::CapacityFreed,
+            other => {
+                eprintln!("Configuration - Unknown POD_WATCHER_EVENTS entry \"{}\"!", other);
+                continue;
+            }
+        };
+        if !events.contains(&event) {
+            events.push(event);
+        }
+    }
+    if events.is_empty() {
+        eprintln!("Configuration - No valid POD_WATCHER_EVENTS entry, using the default value!");
+        return all;
+    }
+    events
+}
+
+/*
+Namespace of the controller when --namespace is not given
+*/
+const DEFAULT_CONTROLLER_NAMESPACE: &str = "realtime";
+
+/*
+This function retrieves the namespace of the controller given to
+the manifest printing modes (--print-apf, --print-rbac) through
+the "--namespace=<namespace>" argument.
+*/
+pub fn get_controller_namespace() -> String {
+    env::args()
+        .find_map(|arg| arg.strip_prefix("--namespace=").map(str::to_string))
+        .unwrap_or_else(|| DEFAULT_CONTROLLER_NAMESPACE.to_string())
+}
+
 /*
 This function retrieves the event queue path
 from the environment variable "EVENT_QUEUE".
@@ -77,6 +954,453 @@ fn get_event_queue_path() -> String {
     .unwrap_or_else(|_| "/eventqueue".to_string())
 }
 
+/*
+This function retrieves the service account impersonated by
+the critical reconcile path from the environment variable
+"CRITICAL_SERVICE_ACCOUNT" (format "namespace/name").
+An empty value disables impersonation.
+*/
+fn get_critical_service_account() -> String {
+    env::var("CRITICAL_SERVICE_ACCOUNT")
+    .unwrap_or_default()
+}
+
+/*
+This function parses a set of cores from the given environment
+variable, in the cpuset list format (e.g. "0-1,4").
+Invalid entries, and the ones beyond CPU_SETSIZE, are discarded.
+*/
+fn parse_cpuset(var: &str) -> Vec<usize> {
+    let mut cpuset: BTreeSet<usize> = BTreeSet::new();
+    let value = env::var(var).unwrap_or_default();
+    for entry in value.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let parsed = match entry.split_once('-') {
+            Some((first, last)) => match (first.trim().parse::<usize>(), last.trim().parse::<usize>()) {
+                (Ok(first), Ok(last)) if first <= last => Some((first, last)),
+                _ => None,
+            },
+            None => entry.parse::<usize>().ok().map(|cpu| (cpu, cpu)),
+        };
+        match parsed {
+            Some((_, last)) if last >= CPU_SETSIZE as usize => {
+                eprintln!("Configuration - {} entry \"{}\" is beyond CPU {}, ignoring it!", var, entry, CPU_SETSIZE - 1);
+            }
+            Some((first, last)) => cpuset.extend(first..=last),
+            None => eprintln!("Configuration - Invalid {} entry \"{}\", ignoring it!", var, entry),
+        }
+    }
+    cpuset.into_iter().collect()
+}
+
+/*
+This function retrieves the set of cores watchdog threads
+are pinned to from the environment variable "WATCHDOG_CPUSET".
+*/
+fn get_watchdog_cpuset() -> Vec<usize> {
+    parse_cpuset("WATCHDOG_CPUSET")
+}
+
+/*
+This function retrieves the alert webhook URL
+from the environment variable "ALERT_WEBHOOK_URL".
+An empty value disables alerting.
+*/
+fn get_alert_webhook_url() -> String {
+    env::var("ALERT_WEBHOOK_URL")
+    .unwrap_or_default()
+}
+
+/*
+This function retrieves the number of consecutive reconcile
+failures raising an alert from the environment
+variable "ALERT_FAILURE_THRESHOLD".
+*/
+fn get_alert_failure_threshold() -> u32 {
+    env::var("ALERT_FAILURE_THRESHOLD")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|v| *v > 0)
+        .unwrap_or(5) // 5 is the Default Value
+}
+
+/*
+This function retrieves the number of consecutive reconcile
+failures opening the circuit of an RTResource from the
+environment variable "BACKOFF_THRESHOLD". A value of 0 disables it.
+*/
+fn get_backoff_threshold() -> u32 {
+    env::var("BACKOFF_THRESHOLD")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(5) // 5 is the Default Value
+}
+
+/*
+This function retrieves the highest delay (in seconds) between the
+retries of an open circuit from the environment variable "BACKOFF_MAX_SECONDS".
+*/
+fn get_backoff_max_seconds() -> u64 {
+    env::var("BACKOFF_MAX_SECONDS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|v| *v > 0)
+        .unwrap_or(300) // 300 is the Default Value
+}
+
+/*
+This function retrieves the scheduler placing
+the managed pods from the environment variable "SCHEDULER".
+*/
+fn get_scheduler() -> SchedulerKind {
+    match env::var("SCHEDULER").unwrap_or_default().to_lowercase().as_str() {
+        "builtin" => SchedulerKind::Builtin,
+        "" | "kube" => SchedulerKind::Kube, // kube is the Default Value
+        other => {
+            eprintln!("Configuration - Unknown SCHEDULER \"{}\", falling back to kube!", other);
+            SchedulerKind::Kube
+        }
+    }
+}
+
+/*
+This function retrieves the port of the admin API
+from the environment variable "ADMIN_PORT".
+A value of 0 disables the admin API.
+*/
+fn get_admin_port() -> u16 {
+    env::var("ADMIN_PORT")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(80) // 80 is the Default Value
+}
+
+/*
+This function retrieves the directory of the admin API
+mTLS certificates ("tls.crt", "tls.key" and "ca.crt")
+from the environment variable "ADMIN_TLS_DIR".
+An empty value restricts the admin API to the loopback interface.
+*/
+fn get_admin_tls_dir() -> String {
+    env::var("ADMIN_TLS_DIR")
+    .unwrap_or_default()
+}
+
+/*
+This function retrieves the sampling interval of the event queue
+statistics from the environment variable "QUEUE_STATS_INTERVAL_MS".
+*/
+fn get_queue_stats_interval() -> u64 {
+    env::var("QUEUE_STATS_INTERVAL_MS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|v| *v > 0)
+        .unwrap_or(1000) // 1000 is the Default Value
+}
+
+/*
+This function retrieves the number of preemptions kept in the
+status of each RTResource from the environment variable "PREEMPTION_HISTORY_SIZE".
+*/
+fn get_preemption_history_size() -> usize {
+    env::var("PREEMPTION_HISTORY_SIZE")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(10) // 10 is the Default Value
+}
+
+/*
+This function retrieves the requests per second allowed to a client
+from the environment variable "<PREFIX>_API_QPS".
+*/
+fn get_api_qps(prefix: &str, default: f64) -> f64 {
+    env::var(format!("{}_API_QPS", prefix))
+        .ok()
+        .and_then(|v| v.parse::<f64>().ok())
+        .filter(|v| *v >= 0.0)
+        .unwrap_or(default)
+}
+
+/*
+This function retrieves the request burst allowed to a client
+from the environment variable "<PREFIX>_API_BURST".
+*/
+fn get_api_burst(prefix: &str, default: u32) -> u32 {
+    env::var(format!("{}_API_BURST", prefix))
+        .ok()
+        .and_then(|v| v.parse::<u32>().ok())
+        .filter(|v| *v > 0)
+        .unwrap_or(default)
+}
+
+/*
+This function retrieves the request timeout (in milliseconds) of a client
+from the environment variable "<PREFIX>_API_TIMEOUT_MS".
+*/
+fn get_api_timeout_ms(prefix: &str, default: u64) -> u64 {
+    env::var(format!("{}_API_TIMEOUT_MS", prefix))
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(default)
+}
+
+/*
+This function retrieves the highest criticality value of the
+RTResource spec scale (the lowest being 1) from the environment
+variable "CRITICALITY_MAX". It is bounded by the number of
+priorities of the POSIX message queues.
+*/
+fn get_criticality_max() -> u32 {
+    let mq_prio_max = unsafe { sysconf(_SC_MQ_PRIO_MAX) }.max(32) as u32;
+    let value = env::var("CRITICALITY_MAX")
+        .ok()
+        .and_then(|v| v.parse::<u32>().ok())
+        .filter(|v| *v > 0)
+        .unwrap_or(80); // 80 is the Default Value
+    if value >= mq_prio_max {
+        eprintln!("Configuration - CRITICALITY_MAX {} exceeds the queue priorities, clamping it to {}!", value, mq_prio_max - 1);
+        return mq_prio_max - 1;
+    }
+    value
+}
+
+/*
+This function retrieves whether higher criticality values in the
+RTResource spec are more critical from the environment variable
+"CRITICALITY_INVERTED" ("true" or "false").
+*/
+fn get_criticality_inverted() -> bool {
+    env::var("CRITICALITY_INVERTED")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(false) // false (lower values are more critical) is the Default Value
+}
+
+/*
+This function retrieves the highest criticality value of the
+top criticality band from the environment variable "CRITICAL_BAND_MAX".
+Lower criticality values are more critical.
+*/
+fn get_critical_band_max() -> u32 {
+    env::var("CRITICAL_BAND_MAX")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(10) // 10 is the Default Value
+}
+
+/*
+This function retrieves the number of watchdog slots reserved
+to the top criticality band from the environment variable
+"RESERVED_WATCHDOGS".
+*/
+fn get_reserved_watchdogs() -> usize {
+    env::var("RESERVED_WATCHDOGS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(2) // 2 is the Default Value
+}
+
+/*
+This function retrieves the number of general watchdogs that may
+concurrently handle top criticality band events while the general
+pool is idle from the environment variable "STEAL_LIMIT".
+*/
+fn get_steal_limit() -> usize {
+    env::var("STEAL_LIMIT")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(2) // 2 is the Default Value
+}
+
+/*
+This function retrieves the number of nodes scored per placement
+by the built-in scheduler from the environment variable
+"SCORING_CANDIDATES". A value of 0 scores all the fitting nodes.
+*/
+fn get_scoring_candidates() -> usize {
+    env::var("SCORING_CANDIDATES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(16) // 16 is the Default Value
+}
+
+/*
+This function retrieves the URL of the external placement
+service from the environment variable "SCHEDULER_WEBHOOK_URL".
+An empty value leaves the placement to the built-in policy.
+*/
+fn get_scheduler_webhook_url() -> String {
+    env::var("SCHEDULER_WEBHOOK_URL")
+    .unwrap_or_default()
+}
+
+/*
+This function retrieves the timeout (in milliseconds) of the
+external placement service from the environment variable
+"SCHEDULER_WEBHOOK_TIMEOUT_MS".
+*/
+fn get_scheduler_webhook_timeout() -> u64 {
+    env::var("SCHEDULER_WEBHOOK_TIMEOUT_MS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(200) // 200 is the Default Value
+}
+
+/*
+This function retrieves the nodes dedicated to the top criticality
+band from the environment variable "DEDICATED_NODES"
+(a comma-separated list of node names).
+*/
+fn get_dedicated_nodes() -> Vec<String> {
+    env::var("DEDICATED_NODES")
+        .unwrap_or_default() // empty is the Default Value
+        .split(',')
+        .map(|n| n.trim().to_string())
+        .filter(|n| !n.is_empty())
+        .collect()
+}
+
+/*
+This function retrieves the label designating the nodes dedicated
+to the top criticality band from the environment variable
+"DEDICATED_NODE_LABEL" (key=value, or key for any value).
+*/
+fn get_dedicated_node_label() -> String {
+    env::var("DEDICATED_NODE_LABEL")
+    .unwrap_or_default()
+}
+
+/*
+This function retrieves the pod defaults per criticality range
+from the environment variable "POD_DEFAULTS", a YAML (or JSON) list
+of {minCriticality, maxCriticality, tolerations, nodeSelector,
+runtimeClassName} entries. The first matching range applies.
+*/
+fn get_pod_defaults() -> Vec<PodDefaults> {
+    let value = env::var("POD_DEFAULTS").unwrap_or_default();
+    if value.trim().is_empty() {
+        return Vec::new(); // no defaults is the Default Value
+    }
+    match serde_yaml::from_str(&value) {
+        Ok(rules) => rules,
+        Err(e) => {
+            eprintln!("Configuration - Invalid POD_DEFAULTS ({}), no defaults will be injected!", e);
+            Vec::new()
+        }
+    }
+}
+
+/*
+This function retrieves the store of the archival records
+of deleted RTResources from the environment variable "ARCHIVE_STORE":
+"configmap:<namespace>", "file:<directory>" or an http(s) URL
+(e.g. an S3-compatible bucket accepting PUT requests).
+An empty value disables archival.
+*/
+fn get_archive_store() -> String {
+    env::var("ARCHIVE_STORE")
+    .unwrap_or_default()
+}
+
+/*
+This function retrieves the handling of the managed pods whose
+RTResource no longer exists from the environment variable
+"ORPHAN_POLICY" ("off", "flag" or "delete").
+*/
+fn get_orphan_policy() -> OrphanPolicy {
+    match env::var("ORPHAN_POLICY").unwrap_or_default().to_lowercase().as_str() {
+        "off" => OrphanPolicy::Off,
+        "flag" => OrphanPolicy::Flag,
+        "" | "delete" => OrphanPolicy::Delete, // delete is the Default Value
+        other => {
+            eprintln!("Configuration - Unknown ORPHAN_POLICY \"{}\", falling back to flag!", other);
+            OrphanPolicy::Flag
+        }
+    }
+}
+
+/*
+This function retrieves the interval (in seconds) between two
+orphaned pod sweeps from the environment variable "ORPHAN_SWEEP_INTERVAL_S".
+*/
+fn get_orphan_sweep_interval() -> u64 {
+    env::var("ORPHAN_SWEEP_INTERVAL_S")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|v| *v > 0)
+        .unwrap_or(300) // 300 is the Default Value
+}
+
+/*
+This function retrieves the class of the ProvisioningRequests
+submitted to cluster-autoscaler for the unplaced replicas of the
+top criticality band from the environment variable "PROVISIONING_CLASS"
+(e.g. "best-effort-atomic-scale-up.autoscaling.x-k8s.io").
+An empty value disables the provisioning requests.
+*/
+fn get_provisioning_class() -> String {
+    env::var("PROVISIONING_CLASS")
+    .unwrap_or_default()
+}
+
+/*
+This function retrieves whether the controller may run degraded,
+without real-time scheduling, from the environment variable "ALLOW_NON_RT".
+*/
+fn get_allow_non_rt() -> bool {
+    env::var("ALLOW_NON_RT")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(false) // false is the Default Value
+}
+
+/*
+This function retrieves the most critical spec criticality the
+RTResources of each namespace may use from the environment variable
+"NAMESPACE_CRITICALITY_CAPS" (a comma-separated list of namespace=criticality).
+Namespaces not listed are not capped.
+*/
+fn get_namespace_criticality_caps() -> BTreeMap<String, u32> {
+    let mut caps = BTreeMap::new();
+    for entry in env::var("NAMESPACE_CRITICALITY_CAPS").unwrap_or_default().split(',') { // empty is the Default Value
+        let entry = entry.trim();
+        if entry.is_empty() {
+            continue;
+        }
+        match entry.split_once('=').map(|(ns, cap)| (ns.trim(), cap.trim().parse::<u32>())) {
+            Some((namespace, Ok(cap))) if !namespace.is_empty() => {
+                caps.insert(namespace.to_string(), cap);
+            }
+            _ => eprintln!("Configuration - Invalid NAMESPACE_CRITICALITY_CAPS entry \"{}\"!", entry),
+        }
+    }
+    caps
+}
+
+/*
+This function retrieves the number of pods a watchdog deletes
+in parallel per batch from the environment variable "TEARDOWN_BATCH_SIZE".
+*/
+fn get_teardown_batch_size() -> usize {
+    env::var("TEARDOWN_BATCH_SIZE")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .filter(|v| *v > 0)
+        .unwrap_or(20) // 20 is the Default Value
+}
+
+/*
+This function retrieves the wait for the shared mutex (in microseconds)
+beyond which the holder is reported, and checked for priority inversion,
+from the environment variable "LOCK_WAIT_WARN_US".
+*/
+fn get_lock_wait_warn_us() -> u64 {
+    env::var("LOCK_WAIT_WARN_US")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|v| *v > 0)
+        .unwrap_or(1000) // 1000 is the Default Value
+}
+
 
 /*
 This function retrieves the
@@ -84,9 +1408,89 @@ controller configuration parameters.
 */
 pub fn get_controller_configuration() -> ControllerConfig{
     ControllerConfig {
+        mode: get_controller_mode(),
         min_watchdogs: get_minimum_watchdog_thread_number(),
         max_watchdogs: get_maximum_watchdog_thread_number(),
         threshold: get_threshold_number(),
+        scale_down_threshold: get_scale_down_threshold(),
+        scale_cooldown_ms: get_scale_cooldown_ms(),
+        recovery_deadline_ms: get_recovery_deadline_ms(),
+        status_verify_interval_ms: get_status_verify_interval_ms(),
+        status_verify_sample: get_status_verify_sample(),
+        maintenance_on_cordon: get_maintenance_on_cordon(),
+        cluster_stats_interval_ms: get_cluster_stats_interval_ms(),
+        startup_sequencing: get_startup_sequencing(),
+        startup_sequencing_timeout_ms: get_startup_sequencing_timeout_ms(),
+        pod_protection: get_pod_protection(),
+        pod_protection_exempt: get_pod_protection_exempt(),
+        crash_dump_dir: get_crash_dump_dir(),
+        headroom_cpu: get_headroom_cpu(),
+        headroom_memory: get_headroom_memory(),
+        pod_priority_hints: get_pod_priority_hints(),
+        namespace_policy: get_namespace_policy(),
+        namespace_allowlist: get_namespace_allowlist(),
+        event_compaction_quiet_ms: get_event_compaction_quiet_ms(),
+        priority_decay_budget_ms: get_priority_decay_budget_ms(),
+        priority_decay_interval_ms: get_priority_decay_interval_ms(),
+        crash_loop_window_ms: get_crash_loop_window_ms(),
+        node_heartbeat_freshness_ms: get_node_heartbeat_freshness_ms(),
+        flight_recorder_file: get_flight_recorder_file(),
+        flight_recorder_events: get_flight_recorder_events(),
+        concurrency_scrape_interval_ms: get_concurrency_scrape_interval_ms(),
+        concurrency_sidecar: get_concurrency_sidecar(),
+        concurrency_metrics_port: get_concurrency_metrics_port(),
+        concurrency_metrics_path: get_concurrency_metrics_path(),
+        concurrency_metric: get_concurrency_metric(),
+        pod_janitor_interval_s: get_pod_janitor_interval_s(),
+        pod_janitor_keep_failed: get_pod_janitor_keep_failed(),
+        critical_runtime_workers: get_critical_runtime_workers(),
+        critical_runtime_cpuset: get_critical_runtime_cpuset(),
+        housekeeping_runtime_workers: get_housekeeping_runtime_workers(),
+        housekeeping_runtime_cpuset: get_housekeeping_runtime_cpuset(),
+        dispatch_mode: get_dispatch_mode(),
+        wfq_bands: get_wfq_bands(),
+        pod_watcher_name_label: get_pod_watcher_label("POD_WATCHER_NAME_LABEL", "rtresource_name"),
+        pod_watcher_uid_label: get_pod_watcher_label("POD_WATCHER_UID_LABEL", "rtresource_uid"),
+        pod_watcher_namespace_label: get_pod_watcher_label("POD_WATCHER_NAMESPACE_LABEL", "rtresource_namespace"),
+        pod_watcher_criticality_label: get_pod_watcher_label("POD_WATCHER_CRITICALITY_LABEL", "criticality"),
+        pod_watcher_events: get_pod_watcher_events(),
         event_queue_path: get_event_queue_path(),
+        critical_service_account: get_critical_service_account(),
+        watchdog_cpuset: get_watchdog_cpuset(),
+        alert_webhook_url: get_alert_webhook_url(),
+        alert_failure_threshold: get_alert_failure_threshold(),
+        backoff_threshold: get_backoff_threshold(),
+        backoff_max_seconds: get_backoff_max_seconds(),
+        scheduler: get_scheduler(),
+        admin_port: get_admin_port(),
+        admin_tls_dir: get_admin_tls_dir(),
+        queue_stats_interval_ms: get_queue_stats_interval(),
+        criticality_max: get_criticality_max(),
+        criticality_inverted: get_criticality_inverted(),
+        critical_band_max: get_critical_band_max(),
+        reserved_watchdogs: get_reserved_watchdogs(),
+        steal_limit: get_steal_limit(),
+        scoring_candidates: get_scoring_candidates(),
+        scheduler_webhook_url: get_scheduler_webhook_url(),
+        scheduler_webhook_timeout_ms: get_scheduler_webhook_timeout(),
+        dedicated_nodes: get_dedicated_nodes(),
+        dedicated_node_label: get_dedicated_node_label(),
+        pod_security_hardening: get_pod_security_hardening(),
+        pod_defaults: get_pod_defaults(),
+        archive_store: get_archive_store(),
+        orphan_policy: get_orphan_policy(),
+        orphan_sweep_interval_s: get_orphan_sweep_interval(),
+        provisioning_class: get_provisioning_class(),
+        allow_non_rt: get_allow_non_rt(),
+        namespace_criticality_caps: get_namespace_criticality_caps(),
+        teardown_batch_size: get_teardown_batch_size(),
+        lock_wait_warn_us: get_lock_wait_warn_us(),
+        preemption_history_size: get_preemption_history_size(),
+        critical_api_qps: get_api_qps("CRITICAL", 100.0), // 100 is the Default Value
+        critical_api_burst: get_api_burst("CRITICAL", 200), // 200 is the Default Value
+        critical_api_timeout_ms: get_api_timeout_ms("CRITICAL", 2000), // 2000 is the Default Value
+        bulk_api_qps: get_api_qps("BULK", 20.0), // 20 is the Default Value
+        bulk_api_burst: get_api_burst("BULK", 40), // 40 is the Default Value
+        bulk_api_timeout_ms: get_api_timeout_ms("BULK", 30000), // 30000 is the Default Value
     }
 }
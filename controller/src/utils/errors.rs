@@ -0,0 +1,168 @@
+/*
+This file contains the error taxonomy of the controller.
+The operations on the critical path (pod creation, deletion and
+relabeling, RTResource lookups, enqueues) return a ControllerError,
+so that the callers branch on the class of the error (e.g. a
+deleted RTResource, a name conflict worth retrying at once, an
+exhausted quota worth alerting on) instead of matching on messages.
+The errors of each class are counted by the metrics.
+*/
+
+use std::{
+    fmt,
+    error::Error,
+    io
+};
+
+use crate::components::scheduling::NoFeasibleNode;
+use crate::components::volume_topology::VolumeBindingPending;
+use crate::components::namespace_policy::NamespaceNotAllowed;
+use crate::utils::metrics::record_error;
+
+
+
+/*
+Names of the error classes, indexed by ControllerError::index
+*/
+pub const ERROR_CLASSES: [&str; 9] = [
+    "NotFound",
+    "Conflict",
+    "QuotaExceeded",
+    "Unschedulable",
+    "PlacementDeferred",
+    "NamespaceNotAllowed",
+    "QueueFull",
+    "SerializationError",
+    "Api",
+];
+
+/*
+Errors of the controller operations
+*/
+#[derive(Debug)]
+pub enum ControllerError {
+    NotFound(String),                           // The object does not exist (404)
+    Conflict(String),                           // The object already exists or changed meanwhile (409)
+    QuotaExceeded(String),                      // A ResourceQuota of the namespace is exhausted (403)
+    Unschedulable(NoFeasibleNode),              // No node is feasible for the pod
+    PlacementDeferred(VolumeBindingPending),    // The placement waits for the volume topology
+    NamespaceNotAllowed(NamespaceNotAllowed),   // spec.namespace violates NAMESPACE_POLICY
+    QueueFull(String),                          // The event queue has no room left
+    SerializationError(String),                 // An object could not be (de)serialized
+    Api(String),                                // Any other apiserver or transport error
+}
+
+impl ControllerError {
+    /*
+    This function classifies an apiserver error,
+    prefixing its message with the given context.
+    */
+    pub fn api(context: &str, e: kube::Error) -> Self {
+        let message = format!("{}: {}!", context, e);
+        match e {
+            kube::Error::Api(response) if response.code == 404 => ControllerError::NotFound(message),
+            kube::Error::Api(response) if response.code == 409 => ControllerError::Conflict(message),
+            kube::Error::Api(response) if response.code == 403 && response.message.contains("exceeded quota") => ControllerError::QuotaExceeded(message),
+            kube::Error::SerdeError(_) => ControllerError::SerializationError(message),
+            _ => ControllerError::Api(message),
+        }
+    }
+
+    /*
+    This function classifies the error of a failed mq_send
+    (errno is read right after the call).
+    */
+    pub fn enqueue(context: &str) -> Self {
+        let e = io::Error::last_os_error();
+        let message = format!("{}: {}!", context, e);
+        match e.raw_os_error() {
+            Some(libc::EAGAIN) => ControllerError::QueueFull(message),
+            _ => ControllerError::Api(message),
+        }
+    }
+
+    /*
+    This function returns the index of the class of the error.
+    */
+    pub fn index(&self) -> usize {
+        match self {
+            ControllerError::NotFound(_) => 0,
+            ControllerError::Conflict(_) => 1,
+            ControllerError::QuotaExceeded(_) => 2,
+            ControllerError::Unschedulable(_) => 3,
+            ControllerError::PlacementDeferred(_) => 4,
+            ControllerError::NamespaceNotAllowed(_) => 5,
+            ControllerError::QueueFull(_) => 6,
+            ControllerError::SerializationError(_) => 7,
+            ControllerError::Api(_) => 8,
+        }
+    }
+
+    /*
+    This function returns the name of the class of the error.
+    */
+    pub fn class(&self) -> &'static str {
+        ERROR_CLASSES[self.index()]
+    }
+
+    /*
+    This function returns whether the failed operation
+    is worth retrying at once (the errors of the other
+    classes persist until the cluster or the spec change).
+    */
+    pub fn is_transient(&self) -> bool {
+        matches!(self, ControllerError::Conflict(_) | ControllerError::QueueFull(_) | ControllerError::Api(_))
+    }
+
+    /*
+    This function counts the error in the metrics of its class.
+    */
+    pub fn record(&self) {
+        record_error(self.index());
+    }
+}
+
+impl fmt::Display for ControllerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ControllerError::Unschedulable(e) => e.fmt(f),
+            ControllerError::PlacementDeferred(e) => e.fmt(f),
+            ControllerError::NamespaceNotAllowed(e) => e.fmt(f),
+            ControllerError::NotFound(message)
+            | ControllerError::Conflict(message)
+            | ControllerError::QuotaExceeded(message)
+            | ControllerError::QueueFull(message)
+            | ControllerError::SerializationError(message)
+            | ControllerError::Api(message) => f.write_str(message),
+        }
+    }
+}
+
+impl Error for ControllerError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ControllerError::Unschedulable(e) => Some(e),
+            ControllerError::PlacementDeferred(e) => Some(e),
+            ControllerError::NamespaceNotAllowed(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<NoFeasibleNode> for ControllerError {
+    fn from(e: NoFeasibleNode) -> Self {
+        ControllerError::Unschedulable(e)
+    }
+}
+
+impl From<VolumeBindingPending> for ControllerError {
+    fn from(e: VolumeBindingPending) -> Self {
+        ControllerError::PlacementDeferred(e)
+    }
+}
+
+impl From<NamespaceNotAllowed> for ControllerError {
+    fn from(e: NamespaceNotAllowed) -> Self {
+        ControllerError::NamespaceNotAllowed(e)
+    }
+}
@@ -0,0 +1,90 @@
+/*
+This file contains the event bus of the controller pipeline.
+The controller threads publish the pipeline events of the RTResources:
+    - Enqueued: the dispatcher received the event from the queue;
+    - Dispatched: a watchdog was handed the event;
+    - Placed: a replica was created on a node chosen by the controller;
+    - Preempted: a replica was preempted by a more critical pod.
+Publishing never blocks: without subscribers the event is dropped,
+and subscribers that fall behind lose the oldest events.
+The bus is consumed by the admin API (GET /events/stream).
+*/
+
+use std::sync::OnceLock;
+use serde::Serialize;
+use tokio::sync::broadcast::{
+    self,
+    Receiver,
+    Sender
+};
+use chrono::Utc;
+
+use crate::utils::vars::QueueMessage;
+
+
+
+/*
+Events kept for the subscribers falling behind
+*/
+const EVENT_BUS_CAPACITY: usize = 1024;
+
+/*
+Type of a pipeline event
+*/
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+pub enum PipelineEventType {
+    Enqueued,
+    Dispatched,
+    Placed,
+    Preempted,
+}
+
+/*
+Pipeline event of an RTResource
+*/
+#[derive(Serialize, Clone, Debug)]
+pub struct PipelineEvent {
+    #[serde(rename = "type")]
+    pub type_: PipelineEventType,
+    pub time: String,
+    pub rtresource: String,
+    pub namespace: String,
+    pub uid: String,
+    pub criticality: u32,   // Effective criticality
+    pub detail: String,
+    #[serde(rename = "traceId", skip_serializing_if = "String::is_empty")]
+    pub trace_id: String,   // Trace identifier of the triggering event
+}
+
+static EVENT_BUS: OnceLock<Sender<PipelineEvent>> = OnceLock::new();
+
+fn bus() -> &'static Sender<PipelineEvent> {
+    EVENT_BUS.get_or_init(|| broadcast::channel(EVENT_BUS_CAPACITY).0)
+}
+
+/*
+This function publishes a pipeline event of an RTResource.
+*/
+pub fn publish_event(type_: PipelineEventType, msg: &QueueMessage, criticality: u32, detail: String) {
+    let bus = bus();
+    if bus.receiver_count() == 0 {
+        return;
+    }
+    let _ = bus.send(PipelineEvent {
+        type_,
+        time: Utc::now().to_rfc3339(),
+        rtresource: msg.name.clone(),
+        namespace: msg.namespace.clone(),
+        uid: msg.uid.clone(),
+        criticality,
+        detail,
+        trace_id: msg.trace_id.clone(),
+    });
+}
+
+/*
+This function subscribes to the pipeline events.
+*/
+pub fn subscribe_events() -> Receiver<PipelineEvent> {
+    bus().subscribe()
+}
@@ -0,0 +1,120 @@
+/*
+This file contains the helpers shared by the components
+using the event priority queue: its attributes, the opening
+of a descriptor and the sending of an event.
+*/
+
+use std::{
+    mem,
+    ffi::CStr,
+    os::raw::{
+        c_char,
+        c_int,
+        c_long
+    }
+};
+use libc::{
+    mqd_t,
+    mq_open,
+    mq_send,
+    mq_close,
+    mq_attr,
+    O_CREAT,
+    O_WRONLY
+};
+
+use crate::utils::vars::QueueMessage;
+use crate::utils::metrics::record_enqueue;
+use crate::utils::flight_recorder::record_enqueue_event;
+use crate::utils::errors::ControllerError;
+
+
+
+/*
+Maximum number of messages held by the event queue
+*/
+pub const QUEUE_MAX_MESSAGES: c_long = 2000;
+
+/*
+Maximum size of a message of the event queue.
+A message holds the name (up to 253 bytes), UID (36 bytes)
+and namespace (up to 63 bytes) of an RTResource, the trace
+identifier (16 bytes), the enqueue time, the event kind, the
+length prefixes of the strings and the trailing NUL, i.e. at
+most 413 bytes.
+*/
+pub const QUEUE_MESSAGE_SIZE: c_long = 512;
+
+/*
+This function opens the event queue with the given access flags
+(O_RDONLY, O_WRONLY, O_NONBLOCK, ...), creating it if needed.
+It returns -1 if the queue could not be opened.
+*/
+pub fn open_queue(queue: &CStr, flags: c_int) -> mqd_t {
+    unsafe {
+        let mut queue_attr: mq_attr = mem::zeroed();
+        queue_attr.mq_flags = 0;
+        queue_attr.mq_maxmsg = QUEUE_MAX_MESSAGES;
+        queue_attr.mq_msgsize = QUEUE_MESSAGE_SIZE;
+        queue_attr.mq_curmsgs = 0;
+        mq_open(queue.as_ptr(), O_CREAT | flags, 0o664, &queue_attr)
+    }
+}
+
+/*
+This function sends an event to the event priority queue
+with the criticality as message priority.
+A failure is logged on behalf of the given component.
+It returns whether the event was sent.
+*/
+pub fn enqueue(component: &str, queue_des: mqd_t, msg: &QueueMessage, criticality: u32) -> bool {
+    let mut c_msg = msg.to_bytes();
+    c_msg.push(0);
+    if c_msg.len() > QUEUE_MESSAGE_SIZE as usize {
+        eprintln!(
+            "{} - The event of RTResource {} in namespace {} exceeds the queue message size ({} > {} bytes)!",
+            component,
+            msg.uid,
+            msg.namespace,
+            c_msg.len(),
+            QUEUE_MESSAGE_SIZE
+        );
+        record_enqueue(criticality, false);
+        return false;
+    }
+    let result = unsafe {
+        mq_send(
+            queue_des,
+            c_msg.as_ptr() as *const c_char,
+            c_msg.len(),
+            criticality
+        )
+    };
+    if result == -1 {
+        let e = ControllerError::enqueue(&format!("{} - An error occurred while sending a message to the queue", component));
+        eprintln!("{} ({})", e, e.class());
+        e.record();
+    }
+    record_enqueue(criticality, result != -1);
+    record_enqueue_event(criticality, result != -1, &c_msg);
+    result != -1
+}
+
+/*
+This function opens the event queue, sends an event and
+closes the queue again, for the components that only
+send events occasionally.
+It returns whether the event was sent.
+*/
+pub fn enqueue_once(component: &str, queue: &CStr, msg: &QueueMessage, criticality: u32) -> bool {
+    let queue_des = open_queue(queue, O_WRONLY);
+    if queue_des == -1 {
+        eprintln!("{} - An error occurred while opening the queue!", component);
+        return false;
+    }
+    let sent = enqueue(component, queue_des, msg, criticality);
+    unsafe {
+        mq_close(queue_des);
+    }
+    sent
+}
@@ -0,0 +1,304 @@
+/*
+This file contains the flight recorder of the event queue.
+The content of the POSIX message queue is opaque (and lost once
+the events are received), so every enqueue, failed enqueue and
+dequeue is mirrored into a fixed-size ring buffer file mapped in
+memory (FLIGHT_RECORDER_FILE), keeping the last
+FLIGHT_RECORDER_EVENTS events with a sequence number.
+Recording an event only claims a slot with an atomic counter and
+copies the message into the mapping: no lock, no system call, no
+allocation, so the hot path latency is not affected. The mapping is
+shared with the file, so the events survive a crash of the
+controller; the next run appends to the same ring.
+The file is read with --inspect-flight-recorder=<file> (--json
+prints the events as JSON).
+*/
+
+use std::{
+    error::Error,
+    ffi::CString,
+    fs,
+    mem,
+    ptr,
+    sync::atomic::{
+        AtomicPtr,
+        AtomicU64,
+        AtomicUsize,
+        Ordering
+    },
+    time::{
+        SystemTime,
+        UNIX_EPOCH
+    }
+};
+use libc::{
+    c_void,
+    close,
+    ftruncate,
+    mmap,
+    open,
+    MAP_FAILED,
+    MAP_SHARED,
+    O_CREAT,
+    O_RDWR,
+    PROT_READ,
+    PROT_WRITE
+};
+use serde::Serialize;
+
+use crate::utils::vars::QueueMessage;
+
+
+
+/*
+Magic number and version of the ring buffer file
+*/
+const MAGIC: [u8; 8] = *b"PK8SFLT1";
+const VERSION: u32 = 1;
+
+/*
+Size of the file header and of a slot,
+and bytes of the message kept per slot
+*/
+const HEADER_SIZE: usize = 64;
+const SLOT_SIZE: usize = 256;
+const PAYLOAD_SIZE: usize = SLOT_SIZE - 24;
+
+/*
+File header
+*/
+#[repr(C)]
+struct Header {
+    magic: [u8; 8],
+    version: u32,
+    slots: u32,
+    /*
+    Sequence number of the next event
+    */
+    next: AtomicU64,
+    reserved: [u8; HEADER_SIZE - 24],
+}
+
+/*
+Slot of the ring buffer; seq is written last, so that
+a slot being written (seq = 0) is skipped by the readers
+*/
+#[repr(C)]
+struct Slot {
+    seq: AtomicU64,
+    timestamp_us: u64,
+    priority: u32,
+    kind: u8,
+    len: u8,
+    reserved: [u8; 2],
+    payload: [u8; PAYLOAD_SIZE],
+}
+
+/*
+Kinds of recorded events
+*/
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FlightEventKind {
+    Enqueued = 1,
+    EnqueueFailed = 2,
+    Dequeued = 3,
+}
+
+impl FlightEventKind {
+    fn from_u8(kind: u8) -> Option<Self> {
+        match kind {
+            1 => Some(FlightEventKind::Enqueued),
+            2 => Some(FlightEventKind::EnqueueFailed),
+            3 => Some(FlightEventKind::Dequeued),
+            _ => None,
+        }
+    }
+}
+
+/*
+Mapping of the ring buffer (null when disabled) and its slots
+*/
+static RECORDER: AtomicPtr<u8> = AtomicPtr::new(ptr::null_mut());
+static SLOTS: AtomicUsize = AtomicUsize::new(0);
+
+/*
+This function maps the ring buffer file, initializing it unless it
+holds a ring of the same size (whose events are then kept).
+It must be called before the controller threads are started.
+*/
+pub fn init_flight_recorder(path: &str, events: usize) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    if path.is_empty() || events == 0 {
+        return Ok(());
+    }
+    let slots = events.min(u32::MAX as usize);
+    let size = HEADER_SIZE + slots * SLOT_SIZE;
+    let c_path = CString::new(path)?;
+    unsafe {
+        let fd = open(c_path.as_ptr(), O_RDWR | O_CREAT, 0o644);
+        if fd == -1 {
+            return Err(format!("cannot open {}: {}", path, std::io::Error::last_os_error()).into());
+        }
+        let current = fs::metadata(path).map(|m| m.len() as usize).unwrap_or(0);
+        if current != size && ftruncate(fd, 0) == -1 || ftruncate(fd, size as i64) == -1 {
+            close(fd);
+            return Err(format!("cannot resize {}: {}", path, std::io::Error::last_os_error()).into());
+        }
+        let base = mmap(ptr::null_mut(), size, PROT_READ | PROT_WRITE, MAP_SHARED, fd, 0);
+        close(fd);
+        if base == MAP_FAILED {
+            return Err(format!("cannot map {}: {}", path, std::io::Error::last_os_error()).into());
+        }
+        let header = &mut *(base as *mut Header);
+        if header.magic != MAGIC || header.version != VERSION || header.slots as usize != slots {
+            ptr::write_bytes(base as *mut u8, 0, size);
+            header.magic = MAGIC;
+            header.version = VERSION;
+            header.slots = slots as u32;
+            header.next.store(1, Ordering::Release);
+        } else {
+            /*
+            The pages are touched now, so that
+            the first events do not fault them in.
+            */
+            for offset in (0..size).step_by(4096) {
+                ptr::read_volatile((base as *const u8).add(offset));
+            }
+        }
+        SLOTS.store(slots, Ordering::Release);
+        RECORDER.store(base as *mut u8, Ordering::Release);
+    }
+    Ok(())
+}
+
+/*
+This function records an event of the queue (the message
+as sent, possibly truncated to the slot payload).
+*/
+fn record(kind: FlightEventKind, priority: u32, msg: &[u8]) {
+    let base = RECORDER.load(Ordering::Acquire);
+    if base.is_null() {
+        return;
+    }
+    let slots = SLOTS.load(Ordering::Acquire);
+    let timestamp_us = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_micros() as u64);
+    unsafe {
+        let header = &*(base as *const Header);
+        let seq = header.next.fetch_add(1, Ordering::AcqRel);
+        let slot = base.add(HEADER_SIZE + (seq as usize % slots) * SLOT_SIZE) as *mut Slot;
+        (*slot).seq.store(0, Ordering::Release);
+        let len = msg.len().min(PAYLOAD_SIZE);
+        (*slot).timestamp_us = timestamp_us;
+        (*slot).priority = priority;
+        (*slot).kind = kind as u8;
+        (*slot).len = len as u8;
+        ptr::copy_nonoverlapping(msg.as_ptr(), (*slot).payload.as_mut_ptr(), len);
+        (*slot).seq.store(seq, Ordering::Release);
+    }
+}
+
+/*
+This function records the outcome of an mq_send.
+*/
+pub fn record_enqueue_event(priority: u32, succeeded: bool, msg: &[u8]) {
+    let kind = if succeeded { FlightEventKind::Enqueued } else { FlightEventKind::EnqueueFailed };
+    record(kind, priority, msg);
+}
+
+/*
+This function records a message received by the dispatcher.
+*/
+pub fn record_dequeue_event(priority: u32, msg: &[u8]) {
+    record(FlightEventKind::Dequeued, priority, msg);
+}
+
+/*
+Event read back from the ring buffer file
+*/
+#[derive(Serialize, Debug)]
+pub struct FlightEvent {
+    pub seq: u64,
+    #[serde(rename = "timestampUs")]
+    pub timestamp_us: u64,
+    pub kind: String,
+    pub priority: u32,
+    pub name: Option<String>,
+    pub uid: Option<String>,
+    pub namespace: Option<String>,
+    #[serde(rename = "eventKind")]
+    pub event_kind: Option<String>,
+}
+
+/*
+This function reads the events of a ring buffer file, oldest first.
+*/
+pub fn read_flight_recorder(path: &str) -> Result<Vec<FlightEvent>, Box<dyn Error + Send + Sync + 'static>> {
+    let bytes = fs::read(path)?;
+    if bytes.len() < HEADER_SIZE || bytes[..8] != MAGIC {
+        return Err(format!("{} is not a flight recorder file", path).into());
+    }
+    let u32_at = |offset: usize| u32::from_ne_bytes(bytes[offset..offset + 4].try_into().unwrap());
+    let u64_at = |offset: usize| u64::from_ne_bytes(bytes[offset..offset + 8].try_into().unwrap());
+    let version = u32_at(8);
+    if version != VERSION {
+        return Err(format!("unsupported flight recorder version {}", version).into());
+    }
+    let slots = (u32_at(12) as usize).min((bytes.len() - HEADER_SIZE) / SLOT_SIZE);
+    let mut events: Vec<FlightEvent> = Vec::new();
+    for i in 0..slots {
+        let offset = HEADER_SIZE + i * SLOT_SIZE;
+        let seq = u64_at(offset + mem::offset_of!(Slot, seq));
+        let Some(kind) = FlightEventKind::from_u8(bytes[offset + mem::offset_of!(Slot, kind)]) else {
+            continue;
+        };
+        if seq == 0 {
+            continue;
+        }
+        let len = bytes[offset + mem::offset_of!(Slot, len)] as usize;
+        let payload_offset = offset + mem::offset_of!(Slot, payload);
+        let payload = &bytes[payload_offset..payload_offset + len.min(PAYLOAD_SIZE)];
+        let msg = QueueMessage::from_bytes(payload.strip_suffix(&[0]).unwrap_or(payload)).ok();
+        events.push(FlightEvent {
+            seq,
+            timestamp_us: u64_at(offset + mem::offset_of!(Slot, timestamp_us)),
+            kind: format!("{:?}", kind),
+            priority: u32_at(offset + mem::offset_of!(Slot, priority)),
+            name: msg.as_ref().map(|m| m.name.clone()),
+            uid: msg.as_ref().map(|m| m.uid.clone()),
+            namespace: msg.as_ref().map(|m| m.namespace.clone()),
+            event_kind: msg.as_ref().map(|m| m.kind.to_string()),
+        });
+    }
+    events.sort_by_key(|e| e.seq);
+    Ok(events)
+}
+
+/*
+This function prints the events of a ring buffer file.
+*/
+pub fn inspect_flight_recorder(path: &str, json: bool) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    let events = read_flight_recorder(path)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&events)?);
+        return Ok(());
+    }
+    println!("Flight recorder {}: {} events", path, events.len());
+    for e in events.iter() {
+        let time = chrono::DateTime::from_timestamp_micros(e.timestamp_us as i64)
+            .map_or("-".to_string(), |t| t.to_rfc3339());
+        match (e.name.as_ref(), e.uid.as_ref(), e.namespace.as_ref()) {
+            (Some(name), Some(uid), Some(namespace)) => println!(
+                "  #{} {} {} priority {}: {}/{} ({}) {}",
+                e.seq,
+                time,
+                e.kind,
+                e.priority,
+                namespace,
+                name,
+                uid,
+                e.event_kind.as_deref().unwrap_or_default()
+            ),
+            _ => println!("  #{} {} {} priority {}: <truncated message>", e.seq, time, e.kind, e.priority),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,96 @@
+/*
+This file contains a minimal HTTP client used by the
+Preempt-K8s controller to reach external endpoints
+(e.g. alert webhooks).
+*/
+
+use std::{
+    error::Error,
+    time::Duration
+};
+use hyper::{
+    Body,
+    Client,
+    Method,
+    Request
+};
+use hyper_rustls::HttpsConnectorBuilder;
+
+
+
+/*
+This function sends a JSON document to the given URL
+with a POST request and returns the response status code
+and body. Both http and https URLs are supported.
+The whole request is bounded by the given timeout.
+*/
+pub async fn post_json(url: &str, body: &serde_json::Value, timeout: Duration) -> Result<(u16, Vec<u8>), Box<dyn Error + Send + Sync + 'static>> {
+    let connector = HttpsConnectorBuilder::new()
+        .with_native_roots()
+        .https_or_http()
+        .enable_http1()
+        .build();
+    let client: Client<_, Body> = Client::builder().build(connector);
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri(url)
+        .header("content-type", "application/json")
+        .body(Body::from(serde_json::to_vec(body)?))?;
+
+    let response = tokio::time::timeout(timeout, async {
+        let response = client.request(request).await?;
+        let status = response.status().as_u16();
+        let bytes = hyper::body::to_bytes(response.into_body()).await?;
+        Ok::<(u16, Vec<u8>), hyper::Error>((status, bytes.to_vec()))
+    }).await??;
+
+    Ok(response)
+}
+
+/*
+This function stores a JSON document at the given URL
+with a PUT request and returns the response status code.
+The whole request is bounded by the given timeout.
+*/
+pub async fn put_json(url: &str, body: &serde_json::Value, timeout: Duration) -> Result<u16, Box<dyn Error + Send + Sync + 'static>> {
+    let connector = HttpsConnectorBuilder::new()
+        .with_native_roots()
+        .https_or_http()
+        .enable_http1()
+        .build();
+    let client: Client<_, Body> = Client::builder().build(connector);
+    let request = Request::builder()
+        .method(Method::PUT)
+        .uri(url)
+        .header("content-type", "application/json")
+        .body(Body::from(serde_json::to_vec(body)?))?;
+
+    let status = tokio::time::timeout(timeout, async {
+        let response = client.request(request).await?;
+        Ok::<u16, hyper::Error>(response.status().as_u16())
+    }).await??;
+
+    Ok(status)
+}
+
+/*
+This function retrieves the body of the given URL
+with a GET request and returns the response status code
+and body. The whole request is bounded by the given timeout.
+*/
+pub async fn get_text(url: &str, timeout: Duration) -> Result<(u16, String), Box<dyn Error + Send + Sync + 'static>> {
+    let client: Client<_, Body> = Client::builder().build_http();
+    let request = Request::builder()
+        .method(Method::GET)
+        .uri(url)
+        .body(Body::empty())?;
+
+    let (status, bytes) = tokio::time::timeout(timeout, async {
+        let response = client.request(request).await?;
+        let status = response.status().as_u16();
+        let bytes = hyper::body::to_bytes(response.into_body()).await?;
+        Ok::<(u16, Vec<u8>), hyper::Error>((status, bytes.to_vec()))
+    }).await??;
+
+    Ok((status, String::from_utf8_lossy(&bytes).into_owned()))
+}
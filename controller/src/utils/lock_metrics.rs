@@ -0,0 +1,161 @@
+/*
+This file contains the instrumentation of the shared mutex
+acquisitions of the watchdogs and of the event server.
+Each acquisition is timed; the instrumented holders record
+their identity (thread, site and priority at acquisition), so that
+a thread waiting longer than LOCK_WAIT_WARN_US can report who
+holds the mutex. The wait is reported as a priority inversion when
+the holder acquired the mutex at a lower priority than the waiter's
+(priority inheritance bounds the inversion, it does not prevent it).
+Holders acquiring the mutex through non instrumented sites are
+reported as unknown.
+*/
+
+use std::{
+    fmt,
+    mem,
+    time::Instant,
+    sync::atomic::{
+        AtomicI32,
+        AtomicU8,
+        AtomicU64,
+        Ordering
+    }
+};
+use libc::{
+    pthread_t,
+    pthread_self,
+    pthread_cond_t,
+    pthread_cond_wait,
+    pthread_mutex_t,
+    pthread_mutex_lock,
+    pthread_mutex_timedlock,
+    pthread_mutex_unlock,
+    pthread_getschedparam,
+    sched_param,
+    clock_gettime,
+    timespec,
+    CLOCK_REALTIME,
+    ETIMEDOUT
+};
+
+use crate::utils::metrics::record_lock_wait;
+
+
+
+/*
+Instrumented acquisition site of the shared mutex
+*/
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LockSite {
+    WatchdogTake,       // Watchdog taking an event from the ready queue
+    WatchdogRelease,    // Watchdog releasing its event
+    ServerScale,        // Event server scaling the watchdogs
+}
+
+impl fmt::Display for LockSite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LockSite::WatchdogTake => write!(f, "watchdog take"),
+            LockSite::WatchdogRelease => write!(f, "watchdog release"),
+            LockSite::ServerScale => write!(f, "server scale"),
+        }
+    }
+}
+
+impl LockSite {
+    const ALL: [LockSite; 3] = [LockSite::WatchdogTake, LockSite::WatchdogRelease, LockSite::ServerScale];
+}
+
+/*
+Identity of the instrumented holder of the shared mutex
+(site index + 1, 0 when unknown)
+*/
+static HOLDER_THREAD: AtomicU64 = AtomicU64::new(0);
+static HOLDER_SITE: AtomicU8 = AtomicU8::new(0);
+static HOLDER_PRIORITY: AtomicI32 = AtomicI32::new(0);
+
+fn thread_priority(thread: pthread_t) -> i32 {
+    let mut policy = 0;
+    let mut param = sched_param {sched_priority: 0};
+    unsafe { pthread_getschedparam(thread, &mut policy, &mut param) };
+    param.sched_priority
+}
+
+fn set_holder(site: Option<LockSite>) {
+    match site {
+        Some(site) => {
+            let thread = unsafe { pthread_self() };
+            HOLDER_PRIORITY.store(thread_priority(thread), Ordering::Relaxed);
+            HOLDER_SITE.store(LockSite::ALL.iter().position(|s| *s == site).unwrap() as u8 + 1, Ordering::Relaxed);
+            HOLDER_THREAD.store(thread as u64, Ordering::Relaxed);
+        }
+        None => {
+            HOLDER_THREAD.store(0, Ordering::Relaxed);
+            HOLDER_SITE.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+/*
+This function acquires the shared mutex from an instrumented site.
+When the wait exceeds the threshold, the holder is reported
+and the wait is checked for priority inversion.
+*/
+pub fn lock_shared(mutex: &mut pthread_mutex_t, warn_us: u64, site: LockSite) {
+    let start = Instant::now();
+    let mut reported = false;
+    unsafe {
+        let mut deadline: timespec = mem::zeroed();
+        clock_gettime(CLOCK_REALTIME, &mut deadline);
+        let nanos = deadline.tv_nsec as u64 + warn_us * 1000;
+        deadline.tv_sec += (nanos / 1_000_000_000) as libc::time_t;
+        deadline.tv_nsec = (nanos % 1_000_000_000) as libc::c_long;
+        if pthread_mutex_timedlock(mutex, &deadline) == ETIMEDOUT {
+            reported = true;
+            let holder = HOLDER_THREAD.load(Ordering::Relaxed);
+            let holder_site = HOLDER_SITE.load(Ordering::Relaxed);
+            let holder_priority = HOLDER_PRIORITY.load(Ordering::Relaxed);
+            let priority = thread_priority(pthread_self());
+            let inversion = holder != 0 && holder_priority < priority;
+            let holder_identity = match holder_site.checked_sub(1).map(|i| LockSite::ALL[i as usize]) {
+                Some(holder_site) if holder != 0 => format!("thread {} at {} (priority {})", holder, holder_site, holder_priority),
+                _ => "an unknown holder".to_string(),
+            };
+            pthread_mutex_lock(mutex);
+            let waited = start.elapsed();
+            eprintln!(
+                "Lock Monitor - {}{} (priority {}) waited {} us for the shared mutex held by {}!",
+                if inversion { "Priority inversion: " } else { "" },
+                site,
+                priority,
+                waited.as_micros(),
+                holder_identity
+            );
+            record_lock_wait(waited, true, inversion);
+        }
+    }
+    if !reported {
+        record_lock_wait(start.elapsed(), false, false);
+    }
+    set_holder(Some(site));
+}
+
+/*
+This function releases the shared mutex
+acquired from an instrumented site.
+*/
+pub fn unlock_shared(mutex: &mut pthread_mutex_t) {
+    set_holder(None);
+    unsafe { pthread_mutex_unlock(mutex) };
+}
+
+/*
+This function waits on a condition variable while holding
+the shared mutex from an instrumented site.
+*/
+pub fn wait_shared(cond: &mut pthread_cond_t, mutex: &mut pthread_mutex_t, site: LockSite) {
+    set_holder(None);
+    unsafe { pthread_cond_wait(cond, mutex) };
+    set_holder(Some(site));
+}
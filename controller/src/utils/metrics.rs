@@ -0,0 +1,381 @@
+/*
+This file contains the controller metrics.
+Metrics are plain atomic counters and gauges, so that they can be
+updated from the real-time threads without taking the shared mutex,
+and are rendered in the Prometheus text format by the admin API.
+*/
+
+use std::{
+    fmt::Write,
+    time::Duration,
+    sync::atomic::{
+        AtomicI64,
+        AtomicU64,
+        Ordering
+    }
+};
+
+use crate::utils::priorities::realtime_scheduling;
+use crate::utils::errors::ERROR_CLASSES;
+
+
+
+/*
+Highest message priority tracked by the per-priority
+histogram (the maximum RTResource criticality level)
+*/
+pub const MAX_TRACKED_PRIORITY: usize = 80;
+
+/*
+Criticality bands tracked by the per-band dispatch
+counters; the last bucket also counts the bands beyond
+*/
+pub const MAX_TRACKED_BANDS: usize = 8;
+
+/*
+Controller metrics
+*/
+pub struct Metrics {
+    /*
+    Messages currently in the event queue (last sample)
+    */
+    pub queue_depth: AtomicI64,
+    /*
+    Highest event queue depth observed since startup
+    */
+    pub queue_depth_max: AtomicI64,
+    /*
+    Capacity of the event queue
+    */
+    pub queue_capacity: AtomicI64,
+    /*
+    Messages enqueued per priority; the last bucket
+    also counts priorities beyond MAX_TRACKED_PRIORITY
+    */
+    pub enqueued: [AtomicU64; MAX_TRACKED_PRIORITY + 1],
+    /*
+    Failed enqueues (e.g. queue full)
+    */
+    pub enqueue_failures: AtomicU64,
+    /*
+    Replica-count-only events merged into a later one
+    by the CRD watcher (EVENT_COMPACTION_QUIET_MS)
+    */
+    pub compacted_events: AtomicU64,
+    /*
+    Errors of the controller operations per class
+    (indexed as ERROR_CLASSES)
+    */
+    pub errors: [AtomicU64; ERROR_CLASSES.len()],
+    /*
+    Events waiting in the dispatcher ready queue
+    */
+    pub ready_depth: AtomicI64,
+    /*
+    Events handed to the watchdogs per criticality band (WFQ_WEIGHTS)
+    */
+    pub dispatched: [AtomicU64; MAX_TRACKED_BANDS],
+    /*
+    List requests issued on the watchdog critical path, with their
+    total response size, transfer time and deserialization time
+    */
+    pub list_requests: AtomicU64,
+    pub list_bytes: AtomicU64,
+    pub list_transfer_us: AtomicU64,
+    pub list_decode_us: AtomicU64,
+    /*
+    Instrumented acquisitions of the shared mutex, with their total
+    and highest wait, the waits beyond LOCK_WAIT_WARN_US and the
+    ones on a holder of lower priority
+    */
+    pub lock_acquisitions: AtomicU64,
+    pub lock_wait_us: AtomicU64,
+    pub lock_wait_max_us: AtomicU64,
+    pub lock_contentions: AtomicU64,
+    pub priority_inversions: AtomicU64,
+    /*
+    RTResource statuses verified against their pods,
+    and status fields found drifted (and fixed)
+    */
+    pub status_verifications: AtomicU64,
+    pub status_drift: AtomicU64,
+    /*
+    Critical phases run by the watchdogs, with the pods they
+    created and deleted and the ones that failed, updated with
+    atomics only so that the hot path neither locks nor allocates
+    */
+    pub critical_phases: AtomicU64,
+    pub critical_creations: AtomicU64,
+    pub critical_deletions: AtomicU64,
+    pub critical_failures: AtomicU64,
+    /*
+    Allocations made by the critical phases, total and highest
+    (only counted with the "alloc-tracker" feature)
+    */
+    pub hot_path_allocations: AtomicU64,
+    pub hot_path_allocations_max: AtomicU64,
+    /*
+    Whether the startup sequencing holds events, the highest
+    criticality it admits and the events it holds
+    */
+    pub startup_gate_active: AtomicI64,
+    pub startup_gate_level: AtomicI64,
+    pub startup_held_events: AtomicI64,
+}
+
+pub static METRICS: Metrics = Metrics {
+    queue_depth: AtomicI64::new(0),
+    queue_depth_max: AtomicI64::new(0),
+    queue_capacity: AtomicI64::new(0),
+    enqueued: [const { AtomicU64::new(0) }; MAX_TRACKED_PRIORITY + 1],
+    enqueue_failures: AtomicU64::new(0),
+    compacted_events: AtomicU64::new(0),
+    errors: [const { AtomicU64::new(0) }; ERROR_CLASSES.len()],
+    ready_depth: AtomicI64::new(0),
+    dispatched: [const { AtomicU64::new(0) }; MAX_TRACKED_BANDS],
+    list_requests: AtomicU64::new(0),
+    list_bytes: AtomicU64::new(0),
+    list_transfer_us: AtomicU64::new(0),
+    list_decode_us: AtomicU64::new(0),
+    lock_acquisitions: AtomicU64::new(0),
+    lock_wait_us: AtomicU64::new(0),
+    lock_wait_max_us: AtomicU64::new(0),
+    lock_contentions: AtomicU64::new(0),
+    priority_inversions: AtomicU64::new(0),
+    status_verifications: AtomicU64::new(0),
+    status_drift: AtomicU64::new(0),
+    critical_phases: AtomicU64::new(0),
+    critical_creations: AtomicU64::new(0),
+    critical_deletions: AtomicU64::new(0),
+    critical_failures: AtomicU64::new(0),
+    hot_path_allocations: AtomicU64::new(0),
+    hot_path_allocations_max: AtomicU64::new(0),
+    startup_gate_active: AtomicI64::new(0),
+    startup_gate_level: AtomicI64::new(0),
+    startup_held_events: AtomicI64::new(0),
+};
+
+/*
+This function records the outcome of an mq_send
+issued by one of the watchers.
+*/
+pub fn record_enqueue(priority: u32, succeeded: bool) {
+    if succeeded {
+        let bucket = (priority as usize).min(MAX_TRACKED_PRIORITY);
+        METRICS.enqueued[bucket].fetch_add(1, Ordering::Relaxed);
+    } else {
+        METRICS.enqueue_failures.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/*
+This function records an event merged into a later one by the CRD watcher.
+*/
+pub fn record_compaction() {
+    METRICS.compacted_events.fetch_add(1, Ordering::Relaxed);
+}
+
+/*
+This function records an error of the given class.
+*/
+pub fn record_error(class: usize) {
+    if let Some(count) = METRICS.errors.get(class) {
+        count.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/*
+This function records a sample of the event queue attributes.
+*/
+pub fn record_queue_sample(depth: i64, capacity: i64) {
+    METRICS.queue_depth.store(depth, Ordering::Relaxed);
+    METRICS.queue_depth_max.fetch_max(depth, Ordering::Relaxed);
+    METRICS.queue_capacity.store(capacity, Ordering::Relaxed);
+}
+
+/*
+This function records the depth of the dispatcher ready queue.
+*/
+pub fn record_ready_depth(depth: usize) {
+    METRICS.ready_depth.store(depth as i64, Ordering::Relaxed);
+}
+
+/*
+This function records a list request issued on the watchdog critical path.
+*/
+pub fn record_list(bytes: usize, transfer: Duration, decode: Duration) {
+    METRICS.list_requests.fetch_add(1, Ordering::Relaxed);
+    METRICS.list_bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+    METRICS.list_transfer_us.fetch_add(transfer.as_micros() as u64, Ordering::Relaxed);
+    METRICS.list_decode_us.fetch_add(decode.as_micros() as u64, Ordering::Relaxed);
+}
+
+/*
+This function records an event handed to a watchdog.
+*/
+pub fn record_dispatch(band: usize) {
+    METRICS.dispatched[band.min(MAX_TRACKED_BANDS - 1)].fetch_add(1, Ordering::Relaxed);
+}
+
+/*
+This function returns the non-empty buckets
+of the per-priority enqueue histogram.
+*/
+pub fn enqueued_by_priority() -> Vec<(usize, u64)> {
+    METRICS.enqueued.iter()
+        .enumerate()
+        .map(|(priority, count)| (priority, count.load(Ordering::Relaxed)))
+        .filter(|(_, count)| *count > 0)
+        .collect()
+}
+
+/*
+This function records an instrumented acquisition of the shared mutex.
+*/
+pub fn record_lock_wait(wait: Duration, contended: bool, inversion: bool) {
+    let wait_us = wait.as_micros() as u64;
+    METRICS.lock_acquisitions.fetch_add(1, Ordering::Relaxed);
+    METRICS.lock_wait_us.fetch_add(wait_us, Ordering::Relaxed);
+    METRICS.lock_wait_max_us.fetch_max(wait_us, Ordering::Relaxed);
+    if contended {
+        METRICS.lock_contentions.fetch_add(1, Ordering::Relaxed);
+    }
+    if inversion {
+        METRICS.priority_inversions.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/*
+This function records the verification of an RTResource status.
+*/
+pub fn record_status_verification(drifted_fields: usize) {
+    METRICS.status_verifications.fetch_add(1, Ordering::Relaxed);
+    METRICS.status_drift.fetch_add(drifted_fields as u64, Ordering::Relaxed);
+}
+
+/*
+This function records the pod operations of a critical phase.
+*/
+pub fn record_critical_operations(created: usize, deleted: usize, failed: bool) {
+    METRICS.critical_creations.fetch_add(created as u64, Ordering::Relaxed);
+    METRICS.critical_deletions.fetch_add(deleted as u64, Ordering::Relaxed);
+    if failed {
+        METRICS.critical_failures.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/*
+This function records the end of a critical phase,
+with the allocations it made (0 when not tracked).
+*/
+pub fn record_critical_phase(allocations: u64) {
+    METRICS.critical_phases.fetch_add(1, Ordering::Relaxed);
+    METRICS.hot_path_allocations.fetch_add(allocations, Ordering::Relaxed);
+    METRICS.hot_path_allocations_max.fetch_max(allocations, Ordering::Relaxed);
+}
+
+/*
+This function renders the metrics in the Prometheus text format.
+*/
+pub fn render_metrics() -> String {
+    let mut out = String::new();
+    let gauges = [
+        ("preempt_k8s_event_queue_depth", "Messages currently in the event queue", &METRICS.queue_depth),
+        ("preempt_k8s_event_queue_depth_max", "Highest event queue depth observed", &METRICS.queue_depth_max),
+        ("preempt_k8s_event_queue_capacity", "Capacity of the event queue", &METRICS.queue_capacity),
+        ("preempt_k8s_ready_queue_depth", "Events waiting in the dispatcher ready queue", &METRICS.ready_depth),
+        ("preempt_k8s_startup_sequencing_active", "Whether the startup sequencing holds the less critical events", &METRICS.startup_gate_active),
+        ("preempt_k8s_startup_sequencing_level", "Highest criticality admitted by the startup sequencing", &METRICS.startup_gate_level),
+        ("preempt_k8s_startup_sequencing_held_events", "Events held by the startup sequencing", &METRICS.startup_held_events),
+    ];
+    for (name, help, value) in gauges {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} gauge", name);
+        let _ = writeln!(out, "{} {}", name, value.load(Ordering::Relaxed));
+    }
+
+    let _ = writeln!(out, "# HELP preempt_k8s_realtime_scheduling Whether the controller threads run under SCHED_FIFO");
+    let _ = writeln!(out, "# TYPE preempt_k8s_realtime_scheduling gauge");
+    let _ = writeln!(out, "preempt_k8s_realtime_scheduling {}", realtime_scheduling() as u8);
+
+    let _ = writeln!(out, "# HELP preempt_k8s_event_queue_enqueued_total Messages enqueued per priority");
+    let _ = writeln!(out, "# TYPE preempt_k8s_event_queue_enqueued_total counter");
+    for (priority, count) in enqueued_by_priority() {
+        let _ = writeln!(out, "preempt_k8s_event_queue_enqueued_total{{priority=\"{}\"}} {}", priority, count);
+    }
+
+    let _ = writeln!(out, "# HELP preempt_k8s_dispatched_events_total Events handed to the watchdogs per criticality band");
+    let _ = writeln!(out, "# TYPE preempt_k8s_dispatched_events_total counter");
+    for (band, count) in METRICS.dispatched.iter().enumerate() {
+        let _ = writeln!(out, "preempt_k8s_dispatched_events_total{{band=\"{}\"}} {}", band, count.load(Ordering::Relaxed));
+    }
+
+    let _ = writeln!(out, "# HELP preempt_k8s_event_queue_enqueue_failures_total Failed enqueues");
+    let _ = writeln!(out, "# TYPE preempt_k8s_event_queue_enqueue_failures_total counter");
+    let _ = writeln!(out, "preempt_k8s_event_queue_enqueue_failures_total {}", METRICS.enqueue_failures.load(Ordering::Relaxed));
+    let _ = writeln!(out, "# HELP preempt_k8s_compacted_events_total Replica-count-only events merged into a later one");
+    let _ = writeln!(out, "# TYPE preempt_k8s_compacted_events_total counter");
+    let _ = writeln!(out, "preempt_k8s_compacted_events_total {}", METRICS.compacted_events.load(Ordering::Relaxed));
+    let _ = writeln!(out, "# HELP preempt_k8s_errors_total Errors of the controller operations per class");
+    let _ = writeln!(out, "# TYPE preempt_k8s_errors_total counter");
+    for (class, count) in ERROR_CLASSES.iter().zip(METRICS.errors.iter()) {
+        let _ = writeln!(out, "preempt_k8s_errors_total{{class=\"{}\"}} {}", class, count.load(Ordering::Relaxed));
+    }
+
+    let lists = [
+        ("preempt_k8s_list_requests_total", "List requests issued by the watchdogs", &METRICS.list_requests),
+        ("preempt_k8s_list_response_bytes_total", "Size of the list responses", &METRICS.list_bytes),
+        ("preempt_k8s_list_transfer_microseconds_total", "Time spent retrieving the list responses", &METRICS.list_transfer_us),
+        ("preempt_k8s_list_deserialization_microseconds_total", "Time spent deserializing the list responses", &METRICS.list_decode_us),
+    ];
+    for (name, help, value) in lists {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} counter", name);
+        let _ = writeln!(out, "{} {}", name, value.load(Ordering::Relaxed));
+    }
+
+    let locks = [
+        ("preempt_k8s_lock_acquisitions_total", "Instrumented acquisitions of the shared mutex", &METRICS.lock_acquisitions, "counter"),
+        ("preempt_k8s_lock_wait_microseconds_total", "Time spent waiting for the shared mutex", &METRICS.lock_wait_us, "counter"),
+        ("preempt_k8s_lock_wait_max_microseconds", "Longest wait for the shared mutex", &METRICS.lock_wait_max_us, "gauge"),
+        ("preempt_k8s_lock_contentions_total", "Waits for the shared mutex beyond LOCK_WAIT_WARN_US", &METRICS.lock_contentions, "counter"),
+        ("preempt_k8s_priority_inversions_total", "Long waits for the shared mutex held at a lower priority", &METRICS.priority_inversions, "counter"),
+    ];
+    for (name, help, value, kind) in locks {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} {}", name, kind);
+        let _ = writeln!(out, "{} {}", name, value.load(Ordering::Relaxed));
+    }
+
+    let verifications = [
+        ("preempt_k8s_status_verifications_total", "RTResource statuses verified against their pods", &METRICS.status_verifications),
+        ("preempt_k8s_status_drift_total", "RTResource status fields found drifted from the pods and fixed", &METRICS.status_drift),
+    ];
+    for (name, help, value) in verifications {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} counter", name);
+        let _ = writeln!(out, "{} {}", name, value.load(Ordering::Relaxed));
+    }
+
+    let critical = [
+        ("preempt_k8s_critical_phases_total", "Critical phases run by the watchdogs", &METRICS.critical_phases),
+        ("preempt_k8s_critical_pod_creations_total", "Pods created by the critical phases", &METRICS.critical_creations),
+        ("preempt_k8s_critical_pod_deletions_total", "Pods deleted by the critical phases", &METRICS.critical_deletions),
+        ("preempt_k8s_critical_failures_total", "Critical phases with failed operations", &METRICS.critical_failures),
+    ];
+    for (name, help, value) in critical {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} counter", name);
+        let _ = writeln!(out, "{} {}", name, value.load(Ordering::Relaxed));
+    }
+    if cfg!(feature = "alloc-tracker") {
+        let _ = writeln!(out, "# HELP preempt_k8s_hot_path_allocations_total Allocations made by the critical phases");
+        let _ = writeln!(out, "# TYPE preempt_k8s_hot_path_allocations_total counter");
+        let _ = writeln!(out, "preempt_k8s_hot_path_allocations_total {}", METRICS.hot_path_allocations.load(Ordering::Relaxed));
+        let _ = writeln!(out, "# HELP preempt_k8s_hot_path_allocations_max Most allocations made by a critical phase");
+        let _ = writeln!(out, "# TYPE preempt_k8s_hot_path_allocations_max gauge");
+        let _ = writeln!(out, "preempt_k8s_hot_path_allocations_max {}", METRICS.hot_path_allocations_max.load(Ordering::Relaxed));
+    }
+
+    out
+}
@@ -1,3 +1,22 @@
 pub mod configuration;
 pub mod vars;
-pub mod rtresource;
\ No newline at end of file
+pub mod rtresource;
+pub mod apf;
+pub mod http;
+pub mod rtdecision;
+pub mod rtclusterstats;
+pub mod metrics;
+pub mod priorities;
+pub mod ready_queue;
+pub mod event_queue;
+pub mod owner_labels;
+pub mod quantity;
+pub mod timed_list;
+pub mod lock_metrics;
+pub mod rate_limit;
+pub mod event_bus;
+pub mod errors;
+pub mod flight_recorder;
+pub mod runtimes;
+#[cfg(feature = "alloc-tracker")]
+pub mod alloc_tracker;
\ No newline at end of file
@@ -0,0 +1,125 @@
+/*
+This file contains the keys of the labels tying the managed pods
+(and the other owned objects) to their RTResource: its name, UID
+and namespace, and its criticality. They default to rtresource_name,
+rtresource_uid, rtresource_namespace and criticality, and can be
+changed with POD_WATCHER_*_LABEL. Every component stamping, selecting
+or reading these labels goes through the keys set at startup, so that
+the pods created by the controller are the ones it watches. The
+controller refuses to start while managed pods carry other keys
+(see foreign_owner_labels in label_guard.rs).
+*/
+
+use std::{
+    collections::BTreeMap,
+    sync::OnceLock
+};
+
+use crate::utils::vars::{
+    QueueMessage,
+    EventKind
+};
+use crate::utils::configuration::ControllerConfig;
+use crate::utils::priorities::effective_criticality;
+
+
+
+/*
+Keys of the owner labels
+*/
+pub struct OwnerLabels {
+    pub name: String,
+    pub uid: String,
+    pub namespace: String,
+    pub criticality: String,
+}
+
+impl Default for OwnerLabels {
+    fn default() -> Self {
+        OwnerLabels {
+            name: "rtresource_name".to_string(),
+            uid: "rtresource_uid".to_string(),
+            namespace: "rtresource_namespace".to_string(),
+            criticality: "criticality".to_string(),
+        }
+    }
+}
+
+impl OwnerLabels {
+    /*
+    This function returns the label selector of the
+    objects owned by the RTResource with the given UID.
+    */
+    pub fn uid_selector(&self, uid: &str) -> String {
+        format!("{}={}", self.uid, uid)
+    }
+
+    /*
+    This function returns the owner of an object from its labels:
+    the name, UID and namespace of its RTResource.
+    */
+    pub fn owner<'a>(&self, labels: &'a BTreeMap<String, String>) -> Option<(&'a String, &'a String, &'a String)> {
+        Some((labels.get(&self.name)?, labels.get(&self.uid)?, labels.get(&self.namespace)?))
+    }
+
+    /*
+    This function returns the criticality label of an object, if valid.
+    */
+    pub fn criticality(&self, labels: &BTreeMap<String, String>) -> Option<u32> {
+        labels.get(&self.criticality)?.parse().ok()
+    }
+
+    /*
+    This function returns the event message and the criticality
+    (mapped onto the controller scale) of an object owned by an
+    RTResource, or None for the objects missing an owner label.
+    An invalid criticality is logged on behalf of the given component.
+    */
+    pub fn event(&self, component: &str, config: &ControllerConfig, labels: &BTreeMap<String, String>, kind: EventKind) -> Option<(QueueMessage, u32)> {
+        let (name, uid, namespace) = self.owner(labels)?;
+        let criticality_str = labels.get(&self.criticality)?;
+        match criticality_str.parse::<u32>() {
+            Ok(criticality) => Some((
+                QueueMessage {
+                    name: name.clone(),
+                    uid: uid.clone(),
+                    namespace: namespace.clone(),
+                    enqueued_at: 0,
+                    kind,
+                    trace_id: String::new(),
+                },
+                effective_criticality(config, namespace, criticality)
+            )),
+            Err(_) => {
+                eprintln!("{} - Error while parsing criticality!", component);
+                None
+            }
+        }
+    }
+}
+
+static OWNER_LABELS: OnceLock<OwnerLabels> = OnceLock::new();
+
+/*
+This function sets the keys of the owner labels from the configuration.
+It must be called at startup, before any owner label is used.
+*/
+pub fn init_owner_labels(config: &ControllerConfig) {
+    let labels = OwnerLabels {
+        name: config.pod_watcher_name_label.clone(),
+        uid: config.pod_watcher_uid_label.clone(),
+        namespace: config.pod_watcher_namespace_label.clone(),
+        criticality: config.pod_watcher_criticality_label.clone(),
+    };
+    if OWNER_LABELS.set(labels).is_err() {
+        eprintln!("Owner Labels - The owner labels were already set!");
+    }
+}
+
+/*
+This function returns the keys of the owner labels
+(the default ones before they are set).
+*/
+pub fn owner_labels() -> &'static OwnerLabels {
+    OWNER_LABELS.get_or_init(OwnerLabels::default)
+}
@@ -0,0 +1,377 @@
+/*
+This file contains the real-time priorities of the controller threads.
+They start from the historical values (watchers 96, server and dispatcher 95,
+watchdog base 94), except for the pod watcher: it forwards the pod failures
+(the recovery path) and runs above the RTResource watcher (97), so that a
+flood of low-criticality RTResource edits cannot delay the forwarding of a
+critical pod deletion. These bands are typed (PriorityBand), so that their
+ordering is checked at compile time and every thread creation and priority
+change goes through them. The priorities can be changed at runtime through the
+admin API, to de-conflict with other RT processes on the node
+without restarting the controller and losing the event queue.
+SCHED_FIFO requires CAP_SYS_NICE (or a high enough RLIMIT_RTPRIO):
+it is probed at startup and, when unavailable, the controller either
+fails fast or (with ALLOW_NON_RT=true) runs degraded, with all its
+threads under the default policy.
+*/
+
+use std::{
+    fmt,
+    sync::atomic::{
+        AtomicBool,
+        AtomicI32,
+        Ordering
+    }
+};
+use libc::{
+    pthread_t,
+    pthread_self,
+    pthread_attr_t,
+    pthread_attr_setschedpolicy,
+    pthread_attr_setinheritsched,
+    pthread_getschedparam,
+    pthread_setschedparam,
+    PTHREAD_EXPLICIT_SCHED,
+    pthread_mutex_lock,
+    pthread_mutex_unlock,
+    sched_get_priority_min,
+    sched_get_priority_max,
+    sched_param,
+    SCHED_FIFO
+};
+
+use crate::utils::vars::SharedState;
+use crate::utils::configuration::ControllerConfig;
+
+
+
+/*
+Role of a controller thread
+*/
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ThreadRole {
+    Watchers,   // RTResource watcher and resource state updater
+    PodWatcher, // Pod watcher (failure recovery path)
+    Server,     // Event server and dispatcher
+    Watchdogs,  // Watchdogs (base priority, lowered by the event criticality)
+}
+
+impl fmt::Display for ThreadRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ThreadRole::Watchers => write!(f, "watchers"),
+            ThreadRole::PodWatcher => write!(f, "podWatcher"),
+            ThreadRole::Server => write!(f, "server"),
+            ThreadRole::Watchdogs => write!(f, "watchdogs"),
+        }
+    }
+}
+
+impl ThreadRole {
+    pub fn parse(role: &str) -> Option<ThreadRole> {
+        match role {
+            "watchers" => Some(ThreadRole::Watchers),
+            "podWatcher" => Some(ThreadRole::PodWatcher),
+            "server" => Some(ThreadRole::Server),
+            "watchdogs" => Some(ThreadRole::Watchdogs),
+            _ => None,
+        }
+    }
+}
+
+/*
+SCHED_FIFO priority of a controller thread.
+The default priorities of the roles and their ordering are checked
+at compile time; the priorities set at runtime are validated
+against the SCHED_FIFO range of the system.
+*/
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PriorityBand(i32);
+
+impl PriorityBand {
+    /*
+    SCHED_FIFO range guaranteed by POSIX on Linux
+    */
+    pub const FIFO_MIN: i32 = 1;
+    pub const FIFO_MAX: i32 = 99;
+
+    /*
+    Default priorities of the roles
+    */
+    pub const POD_WATCHER: PriorityBand = PriorityBand::new(97);
+    pub const WATCHERS: PriorityBand = PriorityBand::new(96);
+    pub const SERVER: PriorityBand = PriorityBand::new(95);
+    pub const WATCHDOG_BASE: PriorityBand = PriorityBand::new(94);
+
+    /*
+    This function returns the band of a priority, failing
+    (at compile time in constants) outside the SCHED_FIFO range.
+    */
+    pub const fn new(priority: i32) -> PriorityBand {
+        assert!(priority >= PriorityBand::FIFO_MIN && priority <= PriorityBand::FIFO_MAX, "priority outside the SCHED_FIFO range");
+        PriorityBand(priority)
+    }
+
+    /*
+    This function returns the band of a priority set at runtime,
+    checked against the SCHED_FIFO range of the system.
+    */
+    pub fn validate(priority: i32) -> Result<PriorityBand, String> {
+        let (min, max) = unsafe { (sched_get_priority_min(SCHED_FIFO), sched_get_priority_max(SCHED_FIFO)) };
+        if priority < min || priority > max {
+            return Err(format!("priority {} is out of the SCHED_FIFO range [{}, {}]", priority, min, max));
+        }
+        Ok(PriorityBand(priority))
+    }
+
+    pub const fn get(self) -> i32 {
+        self.0
+    }
+
+    /*
+    This function returns the band of a watchdog handling an event
+    of the given criticality, below this base band by the criticality
+    and never below the minimum SCHED_FIFO priority.
+    */
+    pub fn watchdog(self, criticality: u32) -> PriorityBand {
+        let min = unsafe { sched_get_priority_min(SCHED_FIFO) };
+        PriorityBand((self.0 - criticality.min(i32::MAX as u32) as i32).max(min))
+    }
+
+    pub fn sched_param(self) -> sched_param {
+        sched_param {sched_priority: self.0}
+    }
+}
+
+/*
+The pod watcher runs above the other watchers, which run above
+the server and dispatcher, which run above every watchdog.
+*/
+const _: () = {
+    assert!(PriorityBand::POD_WATCHER.get() > PriorityBand::WATCHERS.get());
+    assert!(PriorityBand::WATCHERS.get() > PriorityBand::SERVER.get());
+    assert!(PriorityBand::SERVER.get() > PriorityBand::WATCHDOG_BASE.get());
+    assert!(PriorityBand::WATCHDOG_BASE.get() > PriorityBand::FIFO_MIN);
+};
+
+/*
+Current priorities of the controller threads
+*/
+pub struct ThreadPriorities {
+    pub watchers: AtomicI32,
+    pub pod_watcher: AtomicI32,
+    pub server: AtomicI32,
+    pub watchdog_base: AtomicI32,
+}
+
+pub static PRIORITIES: ThreadPriorities = ThreadPriorities {
+    watchers: AtomicI32::new(PriorityBand::WATCHERS.get()),
+    pod_watcher: AtomicI32::new(PriorityBand::POD_WATCHER.get()),
+    server: AtomicI32::new(PriorityBand::SERVER.get()),
+    watchdog_base: AtomicI32::new(PriorityBand::WATCHDOG_BASE.get()),
+};
+
+/*
+Whether the controller threads can run under SCHED_FIFO
+*/
+static REALTIME_SCHEDULING: AtomicBool = AtomicBool::new(true);
+
+/*
+This function returns whether the controller threads
+run under SCHED_FIFO (false in degraded mode).
+*/
+pub fn realtime_scheduling() -> bool {
+    REALTIME_SCHEDULING.load(Ordering::Relaxed)
+}
+
+/*
+This function probes whether SCHED_FIFO is available up to the
+highest priority of the controller threads, by switching the calling
+thread to it and back, and records the result.
+*/
+pub fn probe_realtime_scheduling() -> bool {
+    let highest = priority_of(ThreadRole::Watchers)
+        .max(priority_of(ThreadRole::PodWatcher))
+        .max(priority_of(ThreadRole::Server))
+        .max(priority_of(ThreadRole::Watchdogs));
+    let available = unsafe {
+        let thread = pthread_self();
+        let mut policy: i32 = 0;
+        let mut original = sched_param {sched_priority: 0};
+        if pthread_getschedparam(thread, &mut policy, &mut original) != 0 {
+            false
+        } else if pthread_setschedparam(thread, SCHED_FIFO, &sched_param {sched_priority: highest}) == 0 {
+            pthread_setschedparam(thread, policy, &original);
+            true
+        } else {
+            false
+        }
+    };
+    REALTIME_SCHEDULING.store(available, Ordering::Relaxed);
+    available
+}
+
+/*
+This function sets the SCHED_FIFO policy on the attributes of
+a thread to create, unless the controller runs degraded
+(then the thread inherits the default policy).
+*/
+pub fn set_realtime_policy(attr: &mut pthread_attr_t) {
+    if realtime_scheduling() {
+        unsafe {
+            pthread_attr_setschedpolicy(attr, SCHED_FIFO);
+            pthread_attr_setinheritsched(attr, PTHREAD_EXPLICIT_SCHED);
+        }
+    }
+}
+
+/*
+This function returns the current priority of a role.
+*/
+pub fn priority_of(role: ThreadRole) -> i32 {
+    match role {
+        ThreadRole::Watchers => PRIORITIES.watchers.load(Ordering::Relaxed),
+        ThreadRole::PodWatcher => PRIORITIES.pod_watcher.load(Ordering::Relaxed),
+        ThreadRole::Server => PRIORITIES.server.load(Ordering::Relaxed),
+        ThreadRole::Watchdogs => PRIORITIES.watchdog_base.load(Ordering::Relaxed),
+    }
+}
+
+/*
+This function maps the criticality of an RTResource spec
+(or of a managed pod label) onto the controller scale: it is clamped
+to [1, CRITICALITY_MAX] and, when the scale is inverted, flipped,
+so that lower values are always more critical inside the controller.
+The result is then capped to the most critical level allowed
+to the namespace of the RTResource (metadata.namespace, the tenant
+boundary, not the namespace of its pods), if any.
+Watchers, queue priorities, bands and watchdog priorities all
+use the mapped value.
+*/
+pub fn effective_criticality(config: &ControllerConfig, namespace: &str, criticality: u32) -> u32 {
+    let map = |criticality: u32| {
+        let clamped = criticality.clamp(1, config.criticality_max);
+        if config.criticality_inverted {
+            config.criticality_max + 1 - clamped
+        } else {
+            clamped
+        }
+    };
+    match config.namespace_criticality_caps.get(namespace) {
+        Some(cap) => map(criticality).max(map(*cap)),
+        None => map(criticality),
+    }
+}
+
+/*
+Roles from the highest to the lowest priority
+*/
+const ROLE_ORDER: [ThreadRole; 4] = [
+    ThreadRole::PodWatcher,
+    ThreadRole::Watchers,
+    ThreadRole::Server,
+    ThreadRole::Watchdogs
+];
+
+/*
+This function checks that a new priority of a role keeps the roles
+in the order checked at compile time for the default priorities:
+the pod watcher above the other watchers, above the server and
+dispatcher, above every watchdog.
+*/
+pub fn check_ordering(role: ThreadRole, priority: i32) -> Result<(), String> {
+    let priorities: Vec<(ThreadRole, i32)> = ROLE_ORDER.iter()
+        .map(|r| (*r, if *r == role { priority } else { priority_of(*r) }))
+        .collect();
+    for pair in priorities.windows(2) {
+        let ((higher, higher_priority), (lower, lower_priority)) = (pair[0], pair[1]);
+        if higher_priority <= lower_priority {
+            return Err(format!(
+                "priority {} for the {} would invert the {} ({}) and {} ({}) priorities",
+                priority,
+                role,
+                higher,
+                higher_priority,
+                lower,
+                lower_priority
+            ));
+        }
+    }
+    Ok(())
+}
+
+/*
+This function returns the current priority band of a role
+(the priorities set at runtime are validated when set).
+*/
+pub fn band_of(role: ThreadRole) -> PriorityBand {
+    PriorityBand(priority_of(role))
+}
+
+/*
+This function returns the priority of a watchdog handling
+an event of the given criticality, never below the
+minimum SCHED_FIFO priority.
+*/
+pub fn watchdog_priority(criticality: u32) -> PriorityBand {
+    band_of(ThreadRole::Watchdogs).watchdog(criticality)
+}
+
+fn apply(thread: pthread_t, band: PriorityBand) -> bool {
+    unsafe { pthread_setschedparam(thread, SCHED_FIFO, &band.sched_param()) == 0 }
+}
+
+/*
+This function changes the priority of a role and applies it
+to its running threads. Busy watchdogs are moved to the priority
+matching the criticality of the event they are handling (and their
+priority decay), idle ones to the new base. New watchdogs are created
+with the new base.
+A priority inverting the order of the roles is rejected; the order is
+checked under the shared mutex, so that concurrent changes cannot
+invert it either.
+It returns the number of threads updated.
+It must be called without holding the shared mutex.
+*/
+pub fn set_priority(shared_state: &mut SharedState, role: ThreadRole, priority: i32) -> Result<usize, String> {
+    if !realtime_scheduling() {
+        return Err("real-time scheduling is unavailable (degraded mode)".to_string());
+    }
+    let band = PriorityBand::validate(priority)?;
+
+    let mut updated: usize = 0;
+    let mut failed: usize = 0;
+    unsafe {
+        pthread_mutex_lock(&mut shared_state.mutex);
+        if let Err(e) = check_ordering(role, priority) {
+            pthread_mutex_unlock(&mut shared_state.mutex);
+            return Err(e);
+        }
+        match role {
+            ThreadRole::Watchers => PRIORITIES.watchers.store(priority, Ordering::Relaxed),
+            ThreadRole::PodWatcher => PRIORITIES.pod_watcher.store(priority, Ordering::Relaxed),
+            ThreadRole::Server => PRIORITIES.server.store(priority, Ordering::Relaxed),
+            ThreadRole::Watchdogs => PRIORITIES.watchdog_base.store(priority, Ordering::Relaxed),
+        }
+        if role == ThreadRole::Watchdogs {
+            for worker in shared_state.workers.iter().filter(|w| w.active && w.id != 0) {
+                let target = match worker.criticality {
+                    Some(criticality) => watchdog_priority(criticality + worker.decay),
+                    None => band,
+                };
+                if apply(worker.id, target) { updated += 1; } else { failed += 1; }
+            }
+        } else {
+            for (_, thread) in shared_state.component_threads.iter().filter(|(r, _)| *r == role) {
+                if apply(*thread, band) { updated += 1; } else { failed += 1; }
+            }
+        }
+        pthread_mutex_unlock(&mut shared_state.mutex);
+    }
+
+    println!("Priorities - The {} priority is now {} ({} threads updated)!", role, priority, updated);
+    if failed > 0 {
+        return Err(format!("the priority of {} {} threads could not be changed", failed, role));
+    }
+    Ok(updated)
+}
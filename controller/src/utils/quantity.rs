@@ -0,0 +1,100 @@
+/*
+This file contains helpers to read Kubernetes resource quantities
+(e.g. "500m", "2", "1Gi") as plain numbers in base units
+(cores for CPU, bytes for memory and hugepages).
+*/
+
+use std::collections::BTreeMap;
+use k8s_openapi::{
+    api::core::v1::PodSpec,
+    apimachinery::pkg::api::resource::Quantity
+};
+
+
+
+/*
+This function parses a quantity, returning None if malformed.
+*/
+pub fn parse_quantity(quantity: &str) -> Option<f64> {
+    let quantity = quantity.trim();
+    let split = quantity
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '+' || c == '-'))
+        .unwrap_or(quantity.len());
+    let (number, suffix) = quantity.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let multiplier = match suffix {
+        "" => 1.0,
+        "n" => 1e-9,
+        "u" => 1e-6,
+        "m" => 1e-3,
+        "k" => 1e3,
+        "M" => 1e6,
+        "G" => 1e9,
+        "T" => 1e12,
+        "P" => 1e15,
+        "E" => 1e18,
+        "Ki" => 1024.0,
+        "Mi" => 1024.0_f64.powi(2),
+        "Gi" => 1024.0_f64.powi(3),
+        "Ti" => 1024.0_f64.powi(4),
+        "Pi" => 1024.0_f64.powi(5),
+        "Ei" => 1024.0_f64.powi(6),
+        _ => {
+            /*
+            Decimal exponent notation (e.g. "1e3").
+            */
+            let exponent: i32 = suffix.strip_prefix(['e', 'E'])?.parse().ok()?;
+            10.0_f64.powi(exponent)
+        }
+    };
+    Some(number * multiplier)
+}
+
+/*
+This function returns the amount of a resource
+in a resource list (0 if missing or malformed).
+*/
+pub fn resource_amount(resources: Option<&BTreeMap<String, Quantity>>, name: &str) -> f64 {
+    resources
+        .and_then(|r| r.get(name))
+        .and_then(|q| parse_quantity(&q.0))
+        .unwrap_or(0.0)
+}
+
+/*
+This function returns the resources requested by a pod spec:
+for each container the requests are used, falling back to the
+limits (as Kubernetes does when only limits are set).
+Init containers run before the app containers, so the pod needs
+the maximum between their largest request and the app containers sum.
+*/
+pub fn pod_requests(spec: &PodSpec) -> BTreeMap<String, f64> {
+    let mut requests: BTreeMap<String, f64> = BTreeMap::new();
+    let container_requests = |c: &k8s_openapi::api::core::v1::Container| -> BTreeMap<String, f64> {
+        let mut amounts: BTreeMap<String, f64> = BTreeMap::new();
+        if let Some(resources) = c.resources.as_ref() {
+            for source in [resources.limits.as_ref(), resources.requests.as_ref()].into_iter().flatten() {
+                for (name, quantity) in source.iter() {
+                    if let Some(amount) = parse_quantity(&quantity.0) {
+                        amounts.insert(name.clone(), amount);
+                    }
+                }
+            }
+        }
+        amounts
+    };
+    for container in spec.containers.iter() {
+        for (name, amount) in container_requests(container) {
+            *requests.entry(name).or_insert(0.0) += amount;
+        }
+    }
+    for container in spec.init_containers.iter().flatten() {
+        for (name, amount) in container_requests(container) {
+            let total = requests.entry(name).or_insert(0.0);
+            if amount > *total {
+                *total = amount;
+            }
+        }
+    }
+    requests
+}
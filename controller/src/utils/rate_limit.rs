@@ -0,0 +1,154 @@
+/*
+This file contains the client-side limits of the apiserver requests.
+The critical reconcile path (watchdog gets, creates and deletes) and
+the bulk paths (state updater lists, watchers, housekeeping) use
+separate clients, each wrapped with:
+    - a token bucket limiting its requests per second, with a burst;
+    - a per-request timeout, answered with a 504 Status so that the
+      callers handle it as any other apiserver error.
+Watch requests are long-polls and are exempt from the timeout.
+A slow apiserver therefore cannot make a reconcile wait arbitrarily,
+and bulk listing cannot take the request budget of the critical path.
+*/
+
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        Arc,
+        Mutex
+    },
+    task::{
+        Context,
+        Poll
+    },
+    time::{
+        Duration,
+        Instant
+    }
+};
+use hyper::{
+    Body,
+    Request,
+    Response,
+    StatusCode
+};
+use kube::Client;
+use tower_service::Service;
+
+
+
+/*
+Token bucket shared by the clones of a client
+*/
+struct TokenBucket {
+    qps: f64,
+    burst: f64,
+    state: Mutex<(f64, Instant)>,   // Available tokens and time of the last refill
+}
+
+impl TokenBucket {
+    /*
+    This function takes a token, returning
+    how long the caller must wait for it.
+    */
+    fn take(&self) -> Duration {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        let (tokens, last) = *state;
+        let tokens = (tokens + now.duration_since(last).as_secs_f64() * self.qps).min(self.burst) - 1.0;
+        *state = (tokens, now);
+        if tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-tokens / self.qps)
+        }
+    }
+}
+
+/*
+Apiserver client with a request rate limit and timeout
+*/
+#[derive(Clone)]
+pub struct LimitedService {
+    inner: Client,
+    name: &'static str,
+    bucket: Option<Arc<TokenBucket>>,
+    timeout: Option<Duration>,
+}
+
+/*
+This function returns whether a request is a watch.
+*/
+fn is_watch(request: &Request<Body>) -> bool {
+    request.uri().query().is_some_and(|q| q.split('&').any(|p| p == "watch=true" || p == "watch=1"))
+}
+
+impl Service<Request<Body>> for LimitedService {
+    type Response = Response<Body>;
+    type Error = kube::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Response<Body>, kube::Error>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        let inner = self.inner.clone();
+        let name = self.name;
+        let delay = self.bucket.as_ref().map_or(Duration::ZERO, |b| b.take());
+        let timeout = self.timeout.filter(|_| !is_watch(&request));
+        Box::pin(async move {
+            if !delay.is_zero() {
+                tokio::time::sleep(delay).await;
+            }
+            let Some(timeout) = timeout else {
+                return inner.send(request).await;
+            };
+            let method = request.method().clone();
+            let path = request.uri().path().to_string();
+            match tokio::time::timeout(timeout, inner.send(request)).await {
+                Ok(response) => response,
+                Err(_) => {
+                    eprintln!("Rate Limit - The {} request {} {} timed out after {:?}!", name, method, path, timeout);
+                    let status = serde_json::json!({
+                        "kind": "Status",
+                        "apiVersion": "v1",
+                        "status": "Failure",
+                        "message": format!("client-side timeout after {:?}", timeout),
+                        "reason": "Timeout",
+                        "code": 504
+                    });
+                    Ok(Response::builder()
+                        .status(StatusCode::GATEWAY_TIMEOUT)
+                        .header("content-type", "application/json")
+                        .body(Body::from(status.to_string()))
+                        .unwrap())
+                }
+            }
+        })
+    }
+}
+
+/*
+This function wraps a client so that its requests are limited
+to qps per second (with the given burst) and time out after
+timeout_ms (0 disables each limit).
+*/
+pub fn limited_client(client: Client, name: &'static str, qps: f64, burst: u32, timeout_ms: u64) -> Client {
+    if qps <= 0.0 && timeout_ms == 0 {
+        return client;
+    }
+    let bucket = (qps > 0.0).then(|| {
+        let burst = f64::from(burst.max(1));
+        Arc::new(TokenBucket {
+            qps,
+            burst,
+            state: Mutex::new((burst, Instant::now())),
+        })
+    });
+    let timeout = (timeout_ms > 0).then(|| Duration::from_millis(timeout_ms));
+    println!("Rate Limit - The {} client is limited to {} QPS (burst {}) with a {} ms timeout!", name, qps, burst, timeout_ms);
+    let default_namespace = client.default_namespace().to_string();
+    Client::new(LimitedService {inner: client, name, bucket, timeout}, default_namespace)
+}
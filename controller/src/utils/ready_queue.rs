@@ -0,0 +1,277 @@
+/*
+This file contains the ready queue of the dispatcher.
+Events drained from the event priority queue are kept here
+until a watchdog is allowed to take them.
+Events are ordered by criticality (lower value = more critical,
+consistently with the watchdog thread priorities), then by
+arrival order. They are kept in one FIFO per class, i.e. per
+criticality and Soft flag (spec.criticalityClass, as of the arrival
+of the event), so that finding the next event the dispatcher may hand
+out only depends on the number of classes, not on the backlog.
+With the weighted fair dispatch (DISPATCH_MODE=wfq), the
+criticality bands (WFQ_WEIGHTS) instead share the watchdogs in
+proportion to their weights (stride scheduling): each band advances
+a virtual pass by 1/weight per event taken, the waiting band with
+the lowest pass goes first, and the events of a band are taken in
+the above order. A band getting backlogged again starts from the
+pass of the last event taken, so it gains no credit while idle.
+*/
+
+use std::{
+    collections::{
+        BTreeMap,
+        VecDeque
+    },
+    time::Instant
+};
+
+use crate::utils::vars::QueueMessage;
+
+
+
+/*
+Event waiting to be handled
+*/
+pub struct ReadyEvent {
+    pub msg: QueueMessage,
+    pub criticality: u32,
+    /*
+    Whether the RTResource was Soft when
+    the event reached the dispatcher
+    */
+    pub soft: bool,
+    /*
+    Time the event reached the dispatcher
+    */
+    pub received_at: Instant,
+    seq: u64,
+}
+
+/*
+This function returns the index of the band of a criticality
+among the (highest criticality, weight) bands, the last band
+holding the criticalities beyond them.
+*/
+pub fn band_of(bands: &[(u32, u32)], criticality: u32) -> usize {
+    bands.iter()
+        .position(|(max, _)| criticality <= *max)
+        .unwrap_or(bands.len().saturating_sub(1))
+}
+
+/*
+Shares of the criticality bands of the weighted fair dispatch
+*/
+struct FairShare {
+    bands: Vec<(u32, u32)>,
+    passes: Vec<f64>,
+    /*
+    Number of events waiting per band
+    */
+    waiting: Vec<usize>,
+    /*
+    Pass of the last event taken
+    */
+    virtual_pass: f64,
+}
+
+/*
+Ready queue
+*/
+#[derive(Default)]
+pub struct ReadyQueue {
+    /*
+    Events per (criticality, soft) class, in arrival order
+    */
+    classes: BTreeMap<(u32, bool), VecDeque<ReadyEvent>>,
+    len: usize,
+    next_seq: u64,
+    fair: Option<FairShare>,
+}
+
+impl ReadyQueue {
+    /*
+    This function creates a ready queue sharing the watchdogs between
+    the given (highest criticality, weight) bands.
+    */
+    pub fn weighted_fair(bands: Vec<(u32, u32)>) -> Self {
+        ReadyQueue {
+            fair: Some(FairShare {
+                passes: vec![0.0; bands.len()],
+                waiting: vec![0; bands.len()],
+                bands,
+                virtual_pass: 0.0,
+            }),
+            ..Default::default()
+        }
+    }
+
+    pub fn push(&mut self, msg: QueueMessage, criticality: u32, soft: bool) {
+        if let Some(fair) = self.fair.as_mut() {
+            let band = band_of(&fair.bands, criticality);
+            if fair.waiting[band] == 0 {
+                fair.passes[band] = fair.passes[band].max(fair.virtual_pass);
+            }
+            fair.waiting[band] += 1;
+        }
+        self.classes.entry((criticality, soft)).or_default().push_back(ReadyEvent {
+            msg,
+            criticality,
+            soft,
+            received_at: Instant::now(),
+            seq: self.next_seq,
+        });
+        self.next_seq += 1;
+        self.len += 1;
+    }
+
+    /*
+    This function removes the next event among the classes whose
+    (criticality, soft) satisfy the predicate: the oldest event of the
+    most critical class or, with the weighted fair dispatch, of the
+    most critical class of the band with the lowest pass.
+    */
+    pub fn pop_first<F: Fn(u32, bool) -> bool>(&mut self, predicate: F) -> Option<ReadyEvent> {
+        let pass = |criticality: u32| self.fair.as_ref()
+            .map_or(0.0, |fair| fair.passes[band_of(&fair.bands, criticality)]);
+        let head = |events: &VecDeque<ReadyEvent>| events.front().map_or(u64::MAX, |e| e.seq);
+        let class = self.classes.iter()
+            .filter(|((criticality, soft), _)| predicate(*criticality, *soft))
+            .min_by(|((a, _), a_events), ((b, _), b_events)| pass(*a).total_cmp(&pass(*b))
+                .then_with(|| a.cmp(b))
+                .then_with(|| head(a_events).cmp(&head(b_events))))
+            .map(|(class, _)| *class)?;
+        let events = self.classes.get_mut(&class)?;
+        let event = events.pop_front()?;
+        if events.is_empty() {
+            self.classes.remove(&class);
+        }
+        self.len -= 1;
+        if let Some(fair) = self.fair.as_mut() {
+            let band = band_of(&fair.bands, event.criticality);
+            fair.waiting[band] -= 1;
+            fair.virtual_pass = fair.passes[band];
+            fair.passes[band] += 1.0 / fair.bands[band].1 as f64;
+        }
+        Some(event)
+    }
+
+    /*
+    This function returns whether an event of a class whose
+    (criticality, soft) satisfy the predicate is waiting.
+    */
+    pub fn any<F: Fn(u32, bool) -> bool>(&self, predicate: F) -> bool {
+        self.classes.keys().any(|(criticality, soft)| predicate(*criticality, *soft))
+    }
+
+    /*
+    This function iterates over the events, most critical class first.
+    */
+    pub fn iter(&self) -> impl Iterator<Item = &ReadyEvent> {
+        self.classes.values().flatten()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::vars::EventKind;
+
+    fn msg(name: &str) -> QueueMessage {
+        QueueMessage {
+            name: name.to_string(),
+            uid: name.to_string(),
+            namespace: "default".to_string(),
+            enqueued_at: 0,
+            kind: EventKind::Resync,
+            trace_id: String::new(),
+        }
+    }
+
+    fn drain<F: Fn(u32, bool) -> bool>(queue: &mut ReadyQueue, predicate: F) -> Vec<String> {
+        let mut names = Vec::new();
+        while let Some(event) = queue.pop_first(&predicate) {
+            names.push(event.msg.name);
+        }
+        names
+    }
+
+    #[test]
+    fn pops_the_most_critical_class_first() {
+        let mut queue = ReadyQueue::default();
+        queue.push(msg("a"), 5, false);
+        queue.push(msg("b"), 2, false);
+        queue.push(msg("c"), 9, false);
+        queue.push(msg("d"), 2, false);
+        assert_eq!(queue.len(), 4);
+        assert_eq!(drain(&mut queue, |_, _| true), vec!["b", "d", "a", "c"]);
+        assert_eq!(queue.len(), 0);
+    }
+
+    #[test]
+    fn keeps_the_arrival_order_within_a_criticality() {
+        let mut queue = ReadyQueue::default();
+        queue.push(msg("a"), 3, true);
+        queue.push(msg("b"), 3, false);
+        queue.push(msg("c"), 3, true);
+        queue.push(msg("d"), 3, false);
+        assert_eq!(drain(&mut queue, |_, _| true), vec!["a", "b", "c", "d"]);
+    }
+
+    #[test]
+    fn skips_the_classes_rejected_by_the_predicate() {
+        let mut queue = ReadyQueue::default();
+        queue.push(msg("soft"), 1, true);
+        queue.push(msg("hard"), 4, false);
+        queue.push(msg("late"), 12, false);
+        assert_eq!(drain(&mut queue, |criticality, soft| !soft && criticality <= 10), vec!["hard"]);
+        assert_eq!(queue.len(), 2);
+        assert!(queue.any(|_, soft| soft));
+        assert!(!queue.any(|criticality, _| criticality == 4));
+        let waiting: Vec<&str> = queue.iter().map(|e| e.msg.name.as_str()).collect();
+        assert_eq!(waiting, vec!["soft", "late"]);
+    }
+
+    /*
+    This function takes the given number of events
+    and returns how many were taken per band.
+    */
+    fn take_per_band(queue: &mut ReadyQueue, bands: &[(u32, u32)], count: usize) -> Vec<usize> {
+        let mut taken = vec![0; bands.len()];
+        for _ in 0..count {
+            let event = queue.pop_first(|_, _| true).unwrap();
+            taken[band_of(bands, event.criticality)] += 1;
+        }
+        taken
+    }
+
+    #[test]
+    fn shares_the_dispatch_in_the_weight_ratio() {
+        let bands = vec![(10, 3), (80, 1)];
+        let mut queue = ReadyQueue::weighted_fair(bands.clone());
+        for i in 0..40 {
+            queue.push(msg(&format!("critical-{}", i)), 5, false);
+            queue.push(msg(&format!("general-{}", i)), 50, false);
+        }
+        assert_eq!(take_per_band(&mut queue, &bands, 20), vec![15, 5]);
+        assert_eq!(take_per_band(&mut queue, &bands, 20), vec![15, 5]);
+    }
+
+    #[test]
+    fn gives_no_credit_to_a_band_returning_from_idle() {
+        let bands = vec![(10, 3), (80, 1)];
+        let mut queue = ReadyQueue::weighted_fair(bands.clone());
+        for i in 0..60 {
+            queue.push(msg(&format!("critical-{}", i)), 5, false);
+        }
+        assert_eq!(take_per_band(&mut queue, &bands, 30), vec![30, 0]);
+        for i in 0..20 {
+            queue.push(msg(&format!("general-{}", i)), 50, false);
+        }
+        let taken = take_per_band(&mut queue, &bands, 16);
+        assert!((4..=5).contains(&taken[1]), "the returning band took {} of 16 events", taken[1]);
+    }
+}
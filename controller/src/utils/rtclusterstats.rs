@@ -0,0 +1,64 @@
+/*
+This file contains the custom resource
+specification for the RTClusterStats resource.
+The controller publishes one RTClusterStats per namespace with
+RTResources, aggregating the queue, latency and preemption
+statistics of that namespace, so that the teams can diagnose
+their RTResources under their usual namespace RBAC, without
+access to the admin API or to the controller logs.
+*/
+
+use kube::CustomResource;
+use schemars::JsonSchema;
+use serde::{
+    Deserialize,
+    Serialize
+};
+
+
+
+/*
+RTClusterStats specification
+*/
+#[derive(CustomResource, Deserialize, Serialize, Clone, Debug, JsonSchema, Default)]
+#[kube(group = "rtgroup.critical.com", version = "v1", kind = "RTClusterStats", plural = "rtclusterstats", namespaced)]
+pub struct RTClusterStatsSpec {
+    /*
+    Time of the last update
+    */
+    #[serde(rename = "updatedAt")]
+    pub updated_at: String,
+    /*
+    RTResources in the namespace
+    */
+    pub rtresources: u32,
+    /*
+    Events of the namespace waiting for a watchdog,
+    and being handled by one
+    */
+    #[serde(rename = "pendingEvents")]
+    pub pending_events: u32,
+    #[serde(rename = "inFlightEvents")]
+    pub in_flight_events: u32,
+    /*
+    Events of the namespace handled since the controller started
+    */
+    #[serde(rename = "handledEvents")]
+    pub handled_events: u64,
+    /*
+    Mean and highest recovery latency of those events
+    (from their arrival to the end of their reconcile)
+    */
+    #[serde(rename = "meanRecoveryMs")]
+    pub mean_recovery_ms: Option<u64>,
+    #[serde(rename = "maxRecoveryMs")]
+    pub max_recovery_ms: Option<u64>,
+    /*
+    Pods of the namespace preempted by kube-scheduler, and pods
+    preempted by the pods of the namespace, since the controller started
+    */
+    #[serde(rename = "preemptionsSuffered")]
+    pub preemptions_suffered: u64,
+    #[serde(rename = "preemptionsCaused")]
+    pub preemptions_caused: u64,
+}
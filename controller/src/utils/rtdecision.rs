@@ -0,0 +1,75 @@
+/*
+This file contains the custom resource
+specification for the RTDecision diagnostic resource.
+When the controller runs in observe mode, it never mutates
+pods and records the decisions it would have taken
+in an RTDecision named after the reconciled RTResource.
+*/
+
+use std::collections::BTreeMap;
+use kube::CustomResource;
+use schemars::JsonSchema;
+use serde::{
+    Deserialize,
+    Serialize
+};
+
+
+
+/*
+Label update the controller would have applied to a pod
+*/
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema)]
+pub struct DecisionLabelUpdate {
+    pub pod: String,
+    pub labels: BTreeMap<String, String>,
+}
+
+/*
+RTDecision specification
+*/
+#[derive(CustomResource, Deserialize, Serialize, Clone, Debug, JsonSchema)]
+#[kube(group = "rtgroup.critical.com", version = "v1", kind = "RTDecision", namespaced)]
+pub struct RTDecisionSpec {
+    /*
+    Name and UID of the
+    reconciled RTResource
+    */
+    #[serde(rename = "rtresourceName")]
+    pub rtresource_name: String,
+    #[serde(rename = "rtresourceUid")]
+    pub rtresource_uid: String,
+    /*
+    RTResource generation
+    the decision refers to
+    */
+    #[serde(rename = "observedGeneration")]
+    pub observed_generation: Option<i64>,
+    /*
+    Version of the controller
+    that took the decision
+    */
+    #[serde(rename = "controllerVersion")]
+    pub controller_version: String,
+    /*
+    Time of the decision
+    */
+    #[serde(rename = "decidedAt")]
+    pub decided_at: String,
+    /*
+    Whether the RTResource was found deleted
+    */
+    pub deleted: bool,
+    /*
+    Ordinals of the replicas that would be created
+    */
+    pub creates: Vec<u32>,
+    /*
+    Pods that would be deleted
+    */
+    pub deletes: Vec<String>,
+    /*
+    Label updates that would be applied
+    */
+    pub updates: Vec<DecisionLabelUpdate>,
+}
@@ -5,6 +5,13 @@ by the Preempt-K8s controller.
 */
 
 use std::collections::BTreeMap;
+use chrono::{
+    DateTime,
+    Datelike,
+    Timelike,
+    Utc,
+    Weekday
+};
 use kube::CustomResource;
 use schemars::JsonSchema;
 use serde::{
@@ -16,11 +23,13 @@ use k8s_openapi::{
     api::core::v1::PodSpec
 };
 
+use crate::utils::owner_labels::owner_labels;
+
 
 /*
 Pod template specification
 */
-#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema)]
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default)]
 pub struct Template {
     #[schemars(skip)]
     pub metadata: Option<ObjectMeta>,
@@ -49,10 +58,246 @@ pub struct Selector {
     pub match_expressions: Option<Vec<MatchExpression>>,
 }
 
+/*
+Failover policy specification
+*/
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default)]
+pub struct FailoverPolicy {
+    /*
+    Zones preferred when placing replicas
+    */
+    #[serde(rename = "preferredZones")]
+    pub preferred_zones: Option<Vec<String>>,
+    /*
+    Whether replacements must avoid the zones
+    where a replica was lost to a node failure
+    */
+    #[serde(rename = "forbidSameZoneAsFailure")]
+    pub forbid_same_zone_as_failure: Option<bool>,
+}
+
+/*
+Node assignment of a single replica
+*/
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema)]
+pub struct PlacementOverride {
+    pub ordinal: u32,
+    #[serde(rename = "nodeName")]
+    pub node_name: String,
+}
+
+/*
+Time range during which an RTResource is active,
+e.g. days "Mon-Fri" from "08:00" to "20:00" (UTC).
+A range ending before its start spans midnight.
+*/
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema)]
+pub struct ActiveWindow {
+    /*
+    Days the window starts on: comma-separated days
+    or day ranges (e.g. "Mon-Fri,Sun"), every day if unset
+    */
+    pub days: Option<String>,
+    pub start: String,
+    pub end: String,
+}
+
+/*
+Activation schedule specification
+*/
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default)]
+pub struct Schedule {
+    #[serde(rename = "activeWindows")]
+    pub active_windows: Vec<ActiveWindow>,
+    /*
+    Replicas kept outside the active windows (0 if unset)
+    */
+    #[serde(rename = "standbyReplicas")]
+    pub standby_replicas: Option<i32>,
+}
+
+/*
+Service specification
+*/
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default)]
+pub struct ServiceSpec {
+    #[serde(default)]
+    pub enabled: bool,
+    /*
+    Port exposed by the Service and targeted on the pods
+    (a headless Service may omit it)
+    */
+    pub port: Option<i32>,
+    /*
+    Whether the Service is headless, giving each replica
+    a stable DNS name (false if unset)
+    */
+    pub headless: Option<bool>,
+}
+
+/*
+Recovery probe: HTTP endpoint of the application probed by the
+controller, so that a replica counts as Ready as soon as it answers
+instead of after the kubelet readiness period
+*/
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default)]
+pub struct RecoveryProbe {
+    pub port: u16,
+    /*
+    Path of the GET request (/ if unset)
+    */
+    pub path: Option<String>,
+    /*
+    Timeout of a probe in milliseconds (1000 if unset)
+    */
+    #[serde(rename = "timeoutMs")]
+    pub timeout_ms: Option<u64>,
+}
+
+/*
+ConfigMaps and Secrets (in the namespace of the pods) read by the
+application at startup: when they change, the pods are replaced
+one at a time
+*/
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default)]
+pub struct RestartOn {
+    #[serde(rename = "configMapRefs")]
+    pub config_map_refs: Option<Vec<String>>,
+    #[serde(rename = "secretRefs")]
+    pub secret_refs: Option<Vec<String>>,
+}
+
+/*
+Partial placement policy: whether the replicas that fit are
+placed (BestEffort) or none is placed (AllOrNothing) when
+the nodes cannot host all the replicas to create
+*/
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, JsonSchema, Default, PartialEq)]
+pub enum PartialPlacementPolicy {
+    AllOrNothing,
+    #[default]
+    BestEffort,
+}
+
+/*
+Criticality class: Hard RTResources may preempt and must pass
+the schedulability analysis, Soft ones are only ordered by
+criticality, never preempt and are the first victims
+*/
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, JsonSchema, Default, PartialEq)]
+pub enum CriticalityClass {
+    #[default]
+    Hard,
+    Soft,
+}
+
+/*
+Workload backend: whether the controller manages the pods itself
+(RawPods) or a ReplicaSet, leaving the replica maintenance to
+kube-controller-manager (for the clusters whose policy forbids
+the management of bare pods)
+*/
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, JsonSchema, Default, PartialEq)]
+pub enum WorkloadBackend {
+    #[default]
+    RawPods,
+    ReplicaSet,
+}
+
+/*
+Label carrying the criticality class of the managed pods
+*/
+pub const CRITICALITY_CLASS_LABEL: &str = "criticality_class";
+
+/*
+Operating system the pods target when spec.osTarget is not set
+*/
+pub const DEFAULT_OS_TARGET: &str = "linux";
+
+/*
+Real-time settings of the pods
+*/
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default)]
+pub struct RTSpec {
+    /*
+    Capabilities kept by the hardened pods
+    (e.g. SYS_NICE, IPC_LOCK)
+    */
+    pub capabilities: Option<Vec<String>>,
+}
+
+/*
+Image variant specification
+*/
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default)]
+pub struct ImageVariant {
+    /*
+    Container the image is set on (the first one if unset)
+    */
+    pub container: Option<String>,
+    /*
+    Architecture of the node (kubernetes.io/arch label)
+    */
+    pub arch: Option<String>,
+    /*
+    Labels the node must carry (e.g. NFD labels)
+    */
+    #[serde(rename = "nodeSelector")]
+    pub node_selector: Option<BTreeMap<String, String>>,
+    pub image: String,
+}
+
+/*
+This function parses a "HH:MM" time into minutes since midnight.
+*/
+fn parse_minutes(time: &str) -> Option<u32> {
+    let (hours, minutes) = time.trim().split_once(':')?;
+    let (hours, minutes): (u32, u32) = (hours.parse().ok()?, minutes.parse().ok()?);
+    (hours < 24 && minutes < 60).then_some(hours * 60 + minutes)
+}
+
+/*
+This function returns whether a day matches a days expression.
+*/
+fn day_matches(days: &str, day: Weekday) -> Option<bool> {
+    let mut matches = false;
+    for part in days.split(',').map(str::trim) {
+        let (first, last) = match part.split_once('-') {
+            Some((first, last)) => (first.trim().parse::<Weekday>().ok()?, last.trim().parse::<Weekday>().ok()?),
+            None => {
+                let day = part.parse::<Weekday>().ok()?;
+                (day, day)
+            }
+        };
+        let (first, last, day) = (first.num_days_from_monday(), last.num_days_from_monday(), day.num_days_from_monday());
+        matches |= if first <= last { (first..=last).contains(&day) } else { day >= first || day <= last };
+    }
+    Some(matches)
+}
+
+impl ActiveWindow {
+    /*
+    This function returns whether the window contains the given time.
+    Invalid windows never match.
+    */
+    pub fn contains(&self, now: DateTime<Utc>) -> bool {
+        let (Some(start), Some(end)) = (parse_minutes(&self.start), parse_minutes(&self.end)) else {
+            return false;
+        };
+        let starts_on = |day: Weekday| self.days.as_deref().map_or(Some(true), |d| day_matches(d, day)).unwrap_or(false);
+        let minute = now.hour() * 60 + now.minute();
+        if start <= end {
+            starts_on(now.weekday()) && minute >= start && minute < end
+        } else {
+            (starts_on(now.weekday()) && minute >= start) || (starts_on(now.weekday().pred()) && minute < end)
+        }
+    }
+}
+
 /*
 RTResource specification
 */
-#[derive(CustomResource, Deserialize, Serialize, Clone, Debug, JsonSchema)]
+#[derive(CustomResource, Deserialize, Serialize, Clone, Debug, JsonSchema, Default)]
 #[kube(group = "rtgroup.critical.com", version = "v1", kind = "RTResource", namespaced, status = "RTResourceStatus")]
 pub struct RTResourceSpec {
     /*
@@ -71,12 +316,240 @@ pub struct RTResourceSpec {
     pub selector: Option<Selector>,
     /*
     Application criticality level
+    (0 until defaulted from the namespace)
     */
+    #[serde(default)]
     pub criticality: u32,
     /*
     Pod template
     */
     pub template: Template,
+    /*
+    Placement preferences when replicas
+    are recreated after a failure
+    */
+    #[serde(rename = "failoverPolicy")]
+    pub failover_policy: Option<FailoverPolicy>,
+    /*
+    Whether unmanaged pods matching the selector
+    are adopted instead of creating new ones
+    */
+    #[serde(rename = "adoptExisting")]
+    pub adopt_existing: Option<bool>,
+    /*
+    Replicas pinned to specific nodes
+    (e.g. the node wired to a field device)
+    */
+    #[serde(rename = "placementOverrides")]
+    pub placement_overrides: Option<Vec<PlacementOverride>>,
+    /*
+    Keys of the RTResource labels and annotations stamped
+    onto its pods (a trailing "*" matches a key prefix)
+    */
+    #[serde(rename = "propagateLabels")]
+    pub propagate_labels: Option<Vec<String>>,
+    #[serde(rename = "propagateAnnotations")]
+    pub propagate_annotations: Option<Vec<String>>,
+    /*
+    Containers (e.g. sidecars) ignored
+    when computing the readiness of a replica
+    */
+    #[serde(rename = "readinessExclusions")]
+    pub readiness_exclusions: Option<Vec<String>>,
+    /*
+    Names of the RTResources (in the same namespace) whose
+    pods must never share a node with the pods of this one
+    */
+    #[serde(rename = "conflictsWith")]
+    pub conflicts_with: Option<Vec<String>>,
+    /*
+    Windows during which the replicas run,
+    the standby replicas run outside them
+    */
+    pub schedule: Option<Schedule>,
+    /*
+    Service selecting the pods, maintained by the controller
+    */
+    pub service: Option<ServiceSpec>,
+    /*
+    Images used instead of the template ones on the
+    matching nodes (the first matching variant is used)
+    */
+    #[serde(rename = "imageVariants")]
+    pub image_variants: Option<Vec<ImageVariant>>,
+    /*
+    Whether a reconcile creating replicas waits for them
+    to become Ready (bounded by the recovery deadline)
+    before deleting the pods they replace
+    */
+    #[serde(rename = "waitForReady")]
+    pub wait_for_ready: Option<bool>,
+    /*
+    Endpoint probed by the controller to declare
+    the replicas Ready without waiting for the kubelet
+    */
+    #[serde(rename = "recoveryProbe")]
+    pub recovery_probe: Option<RecoveryProbe>,
+    /*
+    ConfigMaps and Secrets whose changes trigger
+    a rolling replacement of the pods
+    */
+    #[serde(rename = "restartOn")]
+    pub restart_on: Option<RestartOn>,
+    /*
+    Highest number of pod creations and deletions
+    performed by a single reconcile of the RTResource
+    */
+    #[serde(rename = "maxConcurrentOperations")]
+    pub max_concurrent_operations: Option<u32>,
+    /*
+    Whether the replicas that fit are placed when
+    some of them do not (BestEffort by default)
+    */
+    #[serde(rename = "partialPlacementPolicy")]
+    pub partial_placement_policy: Option<PartialPlacementPolicy>,
+    /*
+    Real-time settings of the pods
+    */
+    pub rt: Option<RTSpec>,
+    /*
+    Whether the RTResource may preempt
+    (Hard by default) or not (Soft)
+    */
+    #[serde(rename = "criticalityClass")]
+    pub criticality_class: Option<CriticalityClass>,
+    /*
+    Operating system of the nodes the pods
+    may run on (kubernetes.io/os, linux by default)
+    */
+    #[serde(rename = "osTarget")]
+    pub os_target: Option<String>,
+    /*
+    Crashes of the pods within CRASH_LOOP_WINDOW_MS after which
+    the replicas are no longer recreated (until resumed)
+    */
+    #[serde(rename = "failureThreshold")]
+    pub failure_threshold: Option<u32>,
+    /*
+    Whether the pods are managed directly (RawPods by default)
+    or through a ReplicaSet
+    */
+    #[serde(rename = "workloadBackend")]
+    pub workload_backend: Option<WorkloadBackend>,
+}
+
+impl RTResourceSpec {
+    /*
+    This function returns the operating system the pods target.
+    */
+    pub fn os_target(&self) -> &str {
+        self.os_target.as_deref().unwrap_or(DEFAULT_OS_TARGET)
+    }
+
+    /*
+    This function returns whether the RTResource is Soft.
+    */
+    pub fn is_soft(&self) -> bool {
+        self.criticality_class == Some(CriticalityClass::Soft)
+    }
+
+    /*
+    This function returns whether the pods are managed through a ReplicaSet.
+    */
+    pub fn uses_replicaset(&self) -> bool {
+        self.workload_backend == Some(WorkloadBackend::ReplicaSet)
+    }
+
+    /*
+    This function returns the node a replica is pinned to, if any.
+    */
+    pub fn pinned_node(&self, ordinal: u32) -> Option<&str> {
+        self.placement_overrides.iter().flatten()
+            .find(|o| o.ordinal == ordinal)
+            .map(|o| o.node_name.as_str())
+    }
+
+    /*
+    This function returns the number of replicas desired at the given
+    time, according to the activation schedule if any.
+    */
+    pub fn replicas_at(&self, now: DateTime<Utc>) -> i32 {
+        let replicas = self.replicas.unwrap_or(0).max(0);
+        match self.schedule.as_ref() {
+            Some(schedule) if !schedule.active_windows.iter().any(|w| w.contains(now)) => {
+                schedule.standby_replicas.unwrap_or(0).clamp(0, replicas)
+            }
+            _ => replicas,
+        }
+    }
+
+    /*
+    This function returns the number of replicas desired now.
+    */
+    pub fn desired_replicas(&self) -> i32 {
+        self.replicas_at(Utc::now())
+    }
+}
+
+/*
+This function selects the entries of a metadata map
+whose key matches one of the given patterns.
+*/
+fn select_metadata(source: Option<&BTreeMap<String, String>>, patterns: Option<&Vec<String>>) -> BTreeMap<String, String> {
+    let matches = |key: &str| patterns.into_iter().flatten().any(|p| match p.strip_suffix('*') {
+        Some(prefix) => key.starts_with(prefix),
+        None => key == p,
+    });
+    source.into_iter()
+        .flatten()
+        .filter(|(key, _)| matches(key))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect()
+}
+
+impl RTResource {
+    /*
+    This function returns the controller-owned labels of the pods
+    identifying their RTResource, criticality and criticality class
+    (the replica ordinal is owned by the planner).
+    */
+    pub fn owner_labels(&self) -> BTreeMap<String, String> {
+        let labels = owner_labels();
+        BTreeMap::from([
+            (labels.name.clone(), self.metadata.name.clone().unwrap_or_default()),
+            (labels.uid.clone(), self.metadata.uid.clone().unwrap_or_default()),
+            (labels.namespace.clone(), self.metadata.namespace.clone().unwrap_or_default()),
+            (labels.criticality.clone(), self.spec.criticality.to_string()),
+            (CRITICALITY_CLASS_LABEL.to_string(), format!("{:?}", self.spec.criticality_class.unwrap_or_default())),
+        ])
+    }
+
+    /*
+    This function returns the labels propagated to the pods.
+    */
+    pub fn propagated_labels(&self) -> BTreeMap<String, String> {
+        select_metadata(self.metadata.labels.as_ref(), self.spec.propagate_labels.as_ref())
+    }
+
+    /*
+    This function returns the annotations propagated to the pods.
+    */
+    pub fn propagated_annotations(&self) -> BTreeMap<String, String> {
+        select_metadata(self.metadata.annotations.as_ref(), self.spec.propagate_annotations.as_ref())
+    }
+
+    /*
+    This function returns whether the pods of two RTResources
+    must never share a node, i.e. whether either one lists
+    the other in spec.conflictsWith.
+    */
+    pub fn conflicts(&self, other: &RTResource) -> bool {
+        let lists = |a: &RTResource, b: &RTResource| b.metadata.name.as_ref()
+            .is_some_and(|name| a.spec.conflicts_with.iter().flatten().any(|c| c == name));
+        self.metadata.namespace == other.metadata.namespace
+            && self.metadata.name != other.metadata.name
+            && (lists(self, other) || lists(other, self))
+    }
 }
 
 /*
@@ -93,6 +566,96 @@ pub struct Condition {
     pub message: Option<String>,
 }
 
+/*
+Latencies of the last handled event
+*/
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default)]
+pub struct LastReconcile {
+    /*
+    Time from the enqueue of the event to its dequeue by a watchdog
+    */
+    #[serde(rename = "queuedMs")]
+    pub queued_ms: u64,
+    /*
+    Time spent by the watchdog handling the event
+    */
+    #[serde(rename = "handledMs")]
+    pub handled_ms: u64,
+}
+
+/*
+Node discarded for a pending replica
+*/
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default, PartialEq)]
+pub struct NodeFailure {
+    pub node: String,
+    pub plugin: String,
+    pub reason: String,
+}
+
+/*
+Image variant chosen for a replica
+*/
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default, PartialEq)]
+pub struct ImageVariantPlacement {
+    pub ordinal: u32,
+    pub node: String,
+    pub image: String,
+}
+
+/*
+Replica that could not be placed
+*/
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default, PartialEq)]
+pub struct PendingPlacement {
+    pub ordinal: u32,
+    pub reason: String,
+    pub message: String,
+    /*
+    The reason each node was discarded for
+    (built-in scheduler only)
+    */
+    pub nodes: Option<Vec<NodeFailure>>,
+}
+
+/*
+Capacity requested to cluster-autoscaler
+for the replicas that could not be placed
+*/
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default, PartialEq)]
+pub struct ProvisioningStatus {
+    #[serde(rename = "requestName")]
+    pub request_name: String,
+    pub replicas: u32,
+    /*
+    Pending, Provisioned or Failed
+    */
+    pub state: String,
+}
+
+/*
+Preemption affecting an RTResource
+*/
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default, PartialEq)]
+pub struct PreemptionRecord {
+    pub time: String,
+    /*
+    Victim or Preemptor
+    */
+    pub role: String,
+    /*
+    The preempted pod (Victim)
+    or the preempting pod (Preemptor)
+    */
+    pub pod: String,
+    pub node: String,
+    /*
+    The RTResource (or the pod, if unmanaged)
+    on the other side of the preemption
+    */
+    pub counterpart: String,
+}
+
 /*
 RTResource status specification
 */
@@ -103,5 +666,59 @@ pub struct RTResourceStatus {
     #[serde(rename = "desiredReplicas")]
     pub desired_replicas: Option<i32>,
     pub replicas: Option<i32>,
+    #[serde(rename = "readyReplicas")]
+    pub ready_replicas: Option<i32>,
+    #[serde(rename = "preemptedReplicas")]
+    pub preempted_replicas: Option<i32>,
     pub conditions: Option<Vec<Condition>>,
+    #[serde(rename = "lastReconcile")]
+    pub last_reconcile: Option<LastReconcile>,
+    #[serde(rename = "pendingPlacements")]
+    pub pending_placements: Option<Vec<PendingPlacement>>,
+    pub provisioning: Option<ProvisioningStatus>,
+    #[serde(rename = "preemptionHistory")]
+    pub preemption_history: Option<Vec<PreemptionRecord>>,
+    #[serde(rename = "imageVariants")]
+    pub image_variants: Option<Vec<ImageVariantPlacement>>,
+    /*
+    Concurrency of the replicas reported by their
+    sidecars (e.g. the Knative queue-proxy)
+    */
+    #[serde(rename = "observedConcurrency")]
+    pub observed_concurrency: Option<f64>,
+}
+
+impl RTResourceStatus {
+    /*
+    This function sets a condition on the status, creating it if missing.
+    The transition time is only updated when the condition status changes.
+    It returns whether the condition changed.
+    */
+    pub fn set_condition(&mut self, condition_type: &str, status: &str, reason: &str, message: &str) -> bool {
+        let conditions = self.conditions.get_or_insert_with(Vec::new);
+        let transition_time = chrono::Utc::now().to_rfc3339();
+        match conditions.iter_mut().find(|c| c.condition_type == condition_type) {
+            Some(cond) => {
+                if cond.status == status && cond.reason.as_deref() == Some(reason) && cond.message.as_deref() == Some(message) {
+                    return false;
+                }
+                if cond.status != status {
+                    cond.last_transition_time = Some(transition_time);
+                }
+                cond.status = status.to_string();
+                cond.reason = Some(reason.to_string());
+                cond.message = Some(message.to_string());
+            }
+            None => {
+                conditions.push(Condition {
+                    condition_type: condition_type.to_string(),
+                    status: status.to_string(),
+                    last_transition_time: Some(transition_time),
+                    reason: Some(reason.to_string()),
+                    message: Some(message.to_string()),
+                });
+            }
+        }
+        true
+    }
 }
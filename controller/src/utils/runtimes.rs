@@ -0,0 +1,68 @@
+/*
+This file contains the Tokio runtimes of the controller.
+The asynchronous work is split over two runtimes with separate
+worker pools, so that a heavy housekeeping request (e.g. a list of
+the resource state updater) never queues before a critical pod
+creation:
+    - the critical-path runtime (CRITICAL_RUNTIME_WORKERS workers)
+      drives the critical client and the critical phase of the
+      reconciles run by the watchdogs;
+    - the housekeeping runtime (HOUSEKEEPING_RUNTIME_WORKERS workers,
+      one per core by default) drives the watchers, the housekeeping
+      phase of the reconciles and the Tokio tasks.
+The workers of each runtime can be pinned to a set of cores
+(CRITICAL_RUNTIME_CPUSET, HOUSEKEEPING_RUNTIME_CPUSET).
+*/
+
+use std::{
+    io,
+    mem
+};
+use libc::{
+    cpu_set_t,
+    pthread_self,
+    pthread_setaffinity_np,
+    CPU_SET,
+    CPU_ZERO
+};
+use tokio::runtime::{
+    Builder,
+    Runtime
+};
+
+
+
+/*
+This function pins the calling thread to the given cores.
+*/
+fn pin_current_thread(cpuset: &[usize]) {
+    unsafe {
+        let mut set: cpu_set_t = mem::zeroed();
+        CPU_ZERO(&mut set);
+        for cpu in cpuset.iter() {
+            CPU_SET(*cpu, &mut set);
+        }
+        let result = pthread_setaffinity_np(pthread_self(), mem::size_of::<cpu_set_t>(), &set);
+        if result != 0 {
+            eprintln!("Runtimes - An error occurred while pinning a worker to CPUs {:?}: {}", cpuset, result);
+        }
+    }
+}
+
+/*
+This function builds a multi-threaded runtime whose workers
+are named after it and pinned to the given cores, if any
+(0 workers uses one per core).
+*/
+pub fn build_runtime(name: &str, workers: usize, cpuset: Vec<usize>) -> io::Result<Runtime> {
+    let mut builder = Builder::new_multi_thread();
+    builder.enable_all().thread_name(format!("preempt-{}", name));
+    if workers > 0 {
+        builder.worker_threads(workers);
+    }
+    if !cpuset.is_empty() {
+        println!("Runtimes - The {} runtime workers will be pinned to CPUs {:?}!", name, cpuset);
+        builder.on_thread_start(move || pin_current_thread(&cpuset));
+    }
+    builder.build()
+}
@@ -0,0 +1,45 @@
+/*
+This file contains the list requests issued on the watchdog
+critical path. The response is retrieved as text and decoded
+separately, so that the transfer time, the response size and the
+deserialization time are measured and exposed as metrics.
+The responses are JSON: protobuf is not negotiated, since the
+Kubernetes API types of the client only support JSON, and they are
+not compressed, so that no decompression runs on the critical path.
+*/
+
+use std::time::Instant;
+use kube::{
+    Client,
+    Resource,
+    api::{
+        ListParams,
+        ObjectList
+    },
+    core::Request
+};
+use serde::de::DeserializeOwned;
+
+use crate::utils::metrics::record_list;
+
+
+
+/*
+This function lists the objects of a resource (in a namespace,
+or in all of them), recording the list metrics.
+*/
+pub async fn list_timed<K>(client: Client, namespace: Option<&str>, lp: &ListParams) -> Result<ObjectList<K>, kube::Error>
+where
+    K: Resource<DynamicType = ()> + Clone + DeserializeOwned,
+{
+    let request = Request::new(K::url_path(&(), namespace))
+        .list(lp)
+        .map_err(kube::Error::BuildRequest)?;
+    let start = Instant::now();
+    let text = client.request_text(request).await?;
+    let transfer = start.elapsed();
+    let start = Instant::now();
+    let list: ObjectList<K> = serde_json::from_str(&text).map_err(kube::Error::SerdeError)?;
+    record_list(text.len(), transfer, start.elapsed());
+    Ok(list)
+}
@@ -3,11 +3,26 @@ This File contains useful constants and variables used
 by the Preempt-K8s controller threads.
 */
 
-use std::ffi::CString;
+use std::{
+    fmt,
+    ffi::CString,
+    collections::{
+        HashMap,
+        HashSet
+    },
+    sync::Mutex,
+    time::{
+        Duration,
+        Instant
+    }
+};
 use libc::{
     pthread_t,
     pthread_cond_t,
-    pthread_mutex_t
+    pthread_mutex_t,
+    timespec,
+    clock_gettime,
+    CLOCK_MONOTONIC
 };
 use kube::{
     Api, Client
@@ -23,8 +38,18 @@ use bincode::{
 };
 use tokio::runtime::Handle;
 
-use crate::utils::rtresource::RTResource;
+use crate::utils::rtresource::{
+    RTResource,
+    PreemptionRecord
+};
+use crate::utils::ready_queue::ReadyQueue;
 use crate::utils::configuration::*;
+use crate::utils::priorities::ThreadRole;
+use crate::components::scheduling_policy::SchedulingPolicy;
+use crate::components::resurrection::Victim;
+use crate::components::capacity_index::CapacityIndex;
+use crate::components::slo_metrics::SloStats;
+use crate::components::config_watcher::ConfigVersions;
 
 
 
@@ -38,6 +63,11 @@ pub struct ClientContext {
     */
     pub client: Client,
     /*
+    Interface with Kubernets API Server used on the
+    critical reconcile path (mapped to a dedicated APF priority level)
+    */
+    pub critical_client: Client,
+    /*
     Interface with the custom resource
     monitored by the controller
     */
@@ -56,6 +86,25 @@ If a watchdog is processing an event, its active field is set to true
 pub struct Worker {
     pub id: pthread_t,
     pub active: bool,
+    /*
+    Criticality of the event being handled, if any
+    */
+    pub criticality: Option<u32>,
+    /*
+    Whether the event is handled on a slot
+    borrowed from the general pool
+    */
+    pub borrowed: bool,
+    /*
+    Whether the event is of a Soft RTResource
+    (handled on the general pool)
+    */
+    pub soft: bool,
+    /*
+    Criticality levels the priority of a long
+    reconcile decayed by (PRIORITY_DECAY_BUDGET_MS)
+    */
+    pub decay: u32,
 }
 
 /*
@@ -72,9 +121,10 @@ pub struct SharedState {
     */
     pub context: ClientContext,
     /*
-    The Tokio Runtime
+    The Tokio Runtimes: housekeeping and critical path
     */
     pub runtime_handle: Handle,
+    pub critical_runtime_handle: Handle,
     /*
     The Condition Variable and Mutex used for sinchronization
     on common datas
@@ -82,10 +132,19 @@ pub struct SharedState {
     pub cond: pthread_cond_t,
     pub mutex: pthread_mutex_t,
     /*
+    The Condition Variable signalled when an event
+    may be dispatched to the watchdogs
+    */
+    pub dispatch_cond: pthread_cond_t,
+    /*
     The Event Queue
     */
     pub queue: CString,
     /*
+    The Ready Queue of the dispatcher
+    */
+    pub ready: ReadyQueue,
+    /*
     Currently active Threads
     */
     pub active_threads: usize,
@@ -94,9 +153,99 @@ pub struct SharedState {
     */
     pub working_threads: usize,
     /*
+    Time of the last scaling of the watchdog pool,
+    starting the cooldown of the next scale-down
+    */
+    pub last_scaled: Option<Instant>,
+    /*
+    Nodes under maintenance, whose managed
+    pods are migrated make-before-break
+    */
+    pub maintenance_nodes: HashSet<String>,
+    /*
+    UIDs of the Soft RTResources
+    (spec.criticalityClass)
+    */
+    pub soft_resources: HashSet<String>,
+    /*
+    Highest criticality admitted by the startup
+    sequencing (None once every event is admitted)
+    */
+    pub startup_gate: Option<u32>,
+    /*
     The Workers Array
     */
     pub workers: Vec<Worker>,
+    /*
+    The event each busy watchdog is handling,
+    with the time it was handed over
+    */
+    pub handling: HashMap<pthread_t, (QueueMessage, Instant)>,
+    /*
+    The watcher and server threads, with their role
+    */
+    pub component_threads: Vec<(ThreadRole, pthread_t)>,
+    /*
+    Consecutive reconcile failures per RTResource UID
+    */
+    pub reconcile_failures: HashMap<String, u32>,
+    /*
+    End of the backoff of the RTResources
+    whose reconcile circuit is open, per UID
+    */
+    pub backoff: HashMap<String, Instant>,
+    /*
+    Nodes where replicas were lost to a node failure
+    per RTResource UID, with the time of the failure
+    */
+    pub node_failures: HashMap<String, Vec<(String, Instant)>>,
+    /*
+    Crashes of the managed pods per RTResource UID
+    (spec.failureThreshold), with the time of the crash
+    */
+    pub pod_crashes: HashMap<String, Vec<Instant>>,
+    /*
+    Last observed renewal of the Lease of each node
+    (NODE_HEARTBEAT_FRESHNESS_MS)
+    */
+    pub node_heartbeats: HashMap<String, Instant>,
+    /*
+    Pods whose owner labels were stripped per RTResource UID,
+    with their namespace and name, to be re-adopted
+    */
+    pub stripped_pods: HashMap<String, Vec<(String, String)>>,
+    /*
+    Versions of the ConfigMaps and Secrets and keys referenced
+    by the RTResources (spec.restartOn), behind their own mutex
+    */
+    pub config_versions: Mutex<ConfigVersions>,
+    /*
+    The policy used by the built-in scheduler
+    */
+    pub scheduling_policy: SchedulingPolicy,
+    /*
+    RTResources waiting for the recovery
+    of preempted replicas, per UID
+    */
+    pub preempted: HashMap<String, Victim>,
+    /*
+    Preemption records waiting to be written
+    to the RTResource status, per UID
+    */
+    pub preemption_history: HashMap<String, Vec<PreemptionRecord>>,
+    /*
+    The node capacity index of the built-in scheduler
+    */
+    pub capacity: CapacityIndex,
+    /*
+    The SLO samples per RTResource UID
+    */
+    pub slo: HashMap<String, SloStats>,
+    /*
+    The preemptions suffered and caused
+    by the pods of each namespace
+    */
+    pub namespace_preemptions: HashMap<String, (u64, u64)>,
 }
 
 /*
@@ -106,34 +255,107 @@ and initializes its fields.
 pub fn new_shared_state(
     config: ControllerConfig,
     client: Client,
+    critical_client: Client,
     runtime_handle: Handle,
+    critical_runtime_handle: Handle,
     cond: pthread_cond_t,
     mutex: pthread_mutex_t,
-    queue_path: &str,
-    workers_number: usize
+    dispatch_cond: pthread_cond_t
 ) -> Box<SharedState> {
+    let queue_path = config.event_queue_path.clone();
+    let workers_number = config.max_watchdogs;
+    let startup_gate = config.startup_sequencing.then_some(1);
+    let ready = match config.dispatch_mode {
+        DispatchMode::Strict => ReadyQueue::default(),
+        DispatchMode::WeightedFair => ReadyQueue::weighted_fair(config.wfq_bands.clone()),
+    };
     Box::new(SharedState {
-        config: config,
+        config,
         context: ClientContext {
             client: client.clone(),
+            critical_client,
             rt_resources: Api::<RTResource>::all(client.clone()),
             pods: Api::<Pod>::all(client.clone()),
         },
-        runtime_handle: runtime_handle,
-        cond: cond,
-        mutex: mutex,
+        runtime_handle,
+        critical_runtime_handle,
+        cond,
+        mutex,
+        dispatch_cond,
         queue: CString::new(queue_path).expect("Failed to create Event Queue!"),
+        ready,
         active_threads: 0,
         working_threads: 0,
+        last_scaled: None,
+        maintenance_nodes: HashSet::new(),
+        soft_resources: HashSet::new(),
+        startup_gate,
         workers: vec![Worker {
                 id: 0,
-                active: false
+                active: false,
+                criticality: None,
+                borrowed: false,
+                soft: false,
+                decay: 0
             };
             workers_number
         ],
+        handling: HashMap::new(),
+        component_threads: Vec::new(),
+        reconcile_failures: HashMap::new(),
+        backoff: HashMap::new(),
+        node_failures: HashMap::new(),
+        pod_crashes: HashMap::new(),
+        node_heartbeats: HashMap::new(),
+        stripped_pods: HashMap::new(),
+        config_versions: Mutex::new(ConfigVersions::default()),
+        scheduling_policy: SchedulingPolicy::default_policy(),
+        preempted: HashMap::new(),
+        preemption_history: HashMap::new(),
+        capacity: CapacityIndex::default(),
+        slo: HashMap::new(),
+        namespace_preemptions: HashMap::new(),
     })
 }
 
+/*
+Pointer to the shared state handed to the Tokio tasks
+(e.g. the admin API), which run outside the pthreads.
+The shared state lives for the whole controller lifetime
+and its mutable fields are protected by the shared mutex.
+*/
+#[derive(Clone, Copy)]
+pub struct SharedStatePtr(pub *mut SharedState);
+
+unsafe impl Send for SharedStatePtr {}
+unsafe impl Sync for SharedStatePtr {}
+
+/*
+What happened to the RTResource of a queued event,
+set by the component sending it
+*/
+#[derive(Clone, Copy, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub enum EventKind {
+    #[default]
+    Resync,             // Periodic, recovery or retry reconcile
+    ResourceApplied,    // RTResource spec created or modified
+    ResourceDeleted,    // RTResource deleted
+    PodChanged,         // Availability change of a managed pod
+    PodDeleted,         // Managed pod deleted
+}
+
+impl fmt::Display for EventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EventKind::Resync => write!(f, "resync"),
+            EventKind::ResourceApplied => write!(f, "resource applied"),
+            EventKind::ResourceDeleted => write!(f, "resource deleted"),
+            EventKind::PodChanged => write!(f, "pod changed"),
+            EventKind::PodDeleted => write!(f, "pod deleted"),
+        }
+    }
+}
+
 /*
 This struct represents the message in
 the event priority queue.
@@ -152,14 +374,131 @@ pub struct QueueMessage {
     The RTResource namespace
     */
     pub namespace: String,
+    /*
+    The monotonic time the message was sent to the queue
+    at (in nanoseconds), set when it is serialized
+    */
+    pub enqueued_at: u64,
+    /*
+    What happened to the RTResource
+    */
+    pub kind: EventKind,
+    /*
+    The correlation identifier of the event, generated when it is
+    first enqueued and kept by its retries, stamped on the pods
+    created by its reconcile and on its logs and pipeline events
+    */
+    pub trace_id: String,
+}
+
+/*
+Message sent before the trace identifier was added, which may
+still be persisted in the queue across an upgrade
+*/
+#[derive(Deserialize)]
+struct UntracedQueueMessage {
+    name: String,
+    uid: String,
+    namespace: String,
+    enqueued_at: u64,
+    kind: EventKind,
+}
+
+/*
+Message sent before the event kind was added, which may
+still be persisted in the queue across an upgrade
+*/
+#[derive(Deserialize)]
+struct LegacyQueueMessage {
+    name: String,
+    uid: String,
+    namespace: String,
+    enqueued_at: u64,
+}
+
+/*
+Message sent before the enqueue time was added, which may
+still be persisted in the queue across an upgrade
+*/
+#[derive(Deserialize)]
+struct BaselineQueueMessage {
+    name: String,
+    uid: String,
+    namespace: String,
+}
+
+/*
+This function returns the time of the
+system-wide monotonic clock in nanoseconds.
+*/
+pub fn monotonic_ns() -> u64 {
+    let mut now: timespec = timespec {tv_sec: 0, tv_nsec: 0};
+    unsafe {
+        clock_gettime(CLOCK_MONOTONIC, &mut now);
+    }
+    now.tv_sec as u64 * 1_000_000_000 + now.tv_nsec as u64
 }
 
 impl QueueMessage {
-    pub fn into_bytes(&self) -> Vec<u8> {
-        serialize(self).expect("Serialize QueueMessage Failed!")
+    /*
+    This function serializes the message to send it to the queue,
+    stamping it with the current monotonic time and, on its
+    first enqueue, with a new trace identifier.
+    */
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut stamped = self.clone();
+        stamped.enqueued_at = monotonic_ns();
+        if stamped.trace_id.is_empty() {
+            stamped.trace_id = format!("{:016x}", rand::random::<u64>());
+        }
+        serialize(&stamped).expect("Serialize QueueMessage Failed!")
+    }
+
+    /*
+    This function returns the time elapsed since the message was enqueued.
+    */
+    pub fn queued_for(&self) -> Duration {
+        Duration::from_nanos(monotonic_ns().saturating_sub(self.enqueued_at))
     }
 
     pub fn from_bytes(bytes: &[u8]) -> Result<Self, Box<dyn std::error::Error>> {
-        Ok(deserialize(bytes)?)
+        match deserialize(bytes) {
+            Ok(msg) => Ok(msg),
+            Err(e) => {
+                if let Ok(untraced) = deserialize::<UntracedQueueMessage>(bytes) {
+                    return Ok(QueueMessage {
+                        name: untraced.name,
+                        uid: untraced.uid,
+                        namespace: untraced.namespace,
+                        enqueued_at: untraced.enqueued_at,
+                        kind: untraced.kind,
+                        trace_id: String::new(),
+                    });
+                }
+                if let Ok(legacy) = deserialize::<LegacyQueueMessage>(bytes) {
+                    return Ok(QueueMessage {
+                        name: legacy.name,
+                        uid: legacy.uid,
+                        namespace: legacy.namespace,
+                        enqueued_at: legacy.enqueued_at,
+                        kind: EventKind::Resync,
+                        trace_id: String::new(),
+                    });
+                }
+                /*
+                Without its enqueue time, the message is
+                considered as enqueued right now.
+                */
+                let baseline: BaselineQueueMessage = deserialize(bytes).map_err(|_| e)?;
+                Ok(QueueMessage {
+                    name: baseline.name,
+                    uid: baseline.uid,
+                    namespace: baseline.namespace,
+                    enqueued_at: monotonic_ns(),
+                    kind: EventKind::Resync,
+                    trace_id: String::new(),
+                })
+            }
+        }
     }
 }